@@ -1,3 +1,14 @@
+use std::{env, path::PathBuf};
+
 fn main() {
-    tonic_build::compile_protos("src/at2.proto").expect("failed to compile protobufs");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR is set by cargo"));
+
+    // only the nodes serve, sparing the clients, such as in wasm, the code
+    let server = env::var_os("CARGO_FEATURE_SERVER").is_some();
+
+    tonic_build::configure()
+        .build_server(server)
+        .file_descriptor_set_path(out_dir.join("at2_descriptor.bin"))
+        .compile(&["src/at2.proto"], &["src"])
+        .expect("failed to compile protobufs");
 }
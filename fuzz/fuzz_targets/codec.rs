@@ -0,0 +1,14 @@
+#![no_main]
+
+use at2_node::codec::{Bincode, Cbor, Codec};
+use drop::crypto::sign;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Bincode.decode::<sign::PublicKey>(data);
+    let _ = Bincode.decode::<sign::Signature>(data);
+    let _ = Bincode.decode::<at2_node::SignedTransaction>(data);
+
+    let _ = Cbor.decode::<sign::PublicKey>(data);
+    let _ = Cbor.decode::<sign::Signature>(data);
+});
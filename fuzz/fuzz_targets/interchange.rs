@@ -0,0 +1,8 @@
+#![no_main]
+
+use at2_node::interchange::Document;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Document::from_reader(data);
+});
@@ -0,0 +1,24 @@
+#![no_main]
+
+use at2_node::{
+    codec::{Codec, Encoding},
+    proto,
+};
+use drop::crypto::sign;
+use libfuzzer_sys::fuzz_target;
+use prost::Message;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(request) = proto::SendAssetRequest::decode(data) {
+        let encoding = Encoding::default();
+        let _ = encoding.decode::<sign::PublicKey>(&request.sender);
+        let _ = encoding.decode::<sign::PublicKey>(&request.recipient);
+        let _ = encoding.decode::<sign::Signature>(&request.signature);
+        let _ = request.sender_address.parse::<at2_node::address::Address>();
+    }
+
+    let _ = proto::GetBalanceRequest::decode(data);
+    let _ = proto::GetLastSequenceRequest::decode(data);
+    let _ = proto::RegisterNameRequest::decode(data);
+    let _ = proto::BridgeDepositRequest::decode(data);
+});
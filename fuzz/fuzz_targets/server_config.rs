@@ -0,0 +1,8 @@
+#![no_main]
+
+use at2_node::server::config;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = config::from_reader(data);
+});
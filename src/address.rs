@@ -0,0 +1,122 @@
+//! Human-readable encoding of the users' public keys
+//!
+//! An [`Address`] is a bech32m string, prefixed by the network it belongs to,
+//! such as `at21yq…`. The checksum catches most typos before anything is sent.
+
+use std::{fmt, str::FromStr};
+
+use bech32::{FromBase32, ToBase32, Variant};
+use drop::crypto::sign;
+use snafu::{ensure, ResultExt, Snafu};
+
+/// Prefix used when none is specified
+pub const DEFAULT_PREFIX: &str = "at2";
+
+/// Error generated when handling an [`Address`]
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// Encoding or decoding the bech32 string
+    #[snafu(display("bech32: {}", source))]
+    Bech32 {
+        /// Source of the error
+        source: bech32::Error,
+    },
+    /// Address was encoded with the older bech32 checksum
+    #[snafu(display("not using bech32m"))]
+    WrongVariant,
+    /// Converting the public key to and from bytes
+    #[snafu(display("public key: {}", source))]
+    PublicKey {
+        /// Source of the error
+        source: bincode::Error,
+    },
+    /// Address belongs to another network
+    #[snafu(display("expected prefix {}, got {}", expected, got))]
+    WrongPrefix {
+        /// Prefix expected
+        expected: String,
+        /// Prefix found in the address
+        got: String,
+    },
+}
+
+/// Public key of a user, along with the network prefix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    prefix: String,
+    public_key: sign::PublicKey,
+}
+
+impl Address {
+    /// Create an address for the given key, on the network with this `prefix`
+    pub fn new(prefix: impl Into<String>, public_key: sign::PublicKey) -> Self {
+        Self {
+            prefix: prefix.into(),
+            public_key,
+        }
+    }
+
+    /// Network prefix of this address
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Public key behind this address
+    pub fn public_key(&self) -> &sign::PublicKey {
+        &self.public_key
+    }
+
+    /// Parse an address, failing if it isn't on the network with this `prefix`
+    pub fn parse_with_prefix(prefix: &str, address: &str) -> Result<Self, Error> {
+        let parsed = address.parse::<Self>()?;
+
+        ensure!(
+            parsed.prefix == prefix,
+            WrongPrefix {
+                expected: prefix,
+                got: parsed.prefix,
+            }
+        );
+
+        Ok(parsed)
+    }
+
+    /// Encode as a string, same as [`ToString`] but without hiding errors
+    pub fn encode(&self) -> Result<String, Error> {
+        let bytes = bincode::serialize(&self.public_key).context(PublicKey)?;
+
+        bech32::encode(&self.prefix, bytes.to_base32(), Variant::Bech32m).context(Bech32)
+    }
+}
+
+impl From<sign::PublicKey> for Address {
+    fn from(public_key: sign::PublicKey) -> Self {
+        Self::new(DEFAULT_PREFIX, public_key)
+    }
+}
+
+impl From<Address> for sign::PublicKey {
+    fn from(address: Address) -> Self {
+        address.public_key
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.encode().map_err(|_| fmt::Error)?)
+    }
+}
+
+impl FromStr for Address {
+    type Err = Error;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        let (prefix, data, variant) = bech32::decode(address).context(Bech32)?;
+        ensure!(variant == Variant::Bech32m, WrongVariant);
+
+        let bytes = Vec::<u8>::from_base32(&data).context(Bech32)?;
+        let public_key = bincode::deserialize(&bytes).context(PublicKey)?;
+
+        Ok(Self { prefix, public_key })
+    }
+}
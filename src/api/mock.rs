@@ -0,0 +1,284 @@
+//! In-memory node, for testing code using [`At2Api`]
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use drop::crypto::sign;
+
+use super::At2Api;
+use crate::{
+    client::Error,
+    is_valid_memo, is_valid_name,
+    proto::{self, account_error::Kind},
+    proto_convert::{account_status, proto_amount},
+    Amount, FullTransaction, SignedTransaction, OUTPUTS_MAX_LEN,
+};
+
+/// Balance of an account never seen before, as on a real node
+pub const INITIAL_BALANCE: Amount = 10;
+
+#[derive(Clone, Copy)]
+struct Account {
+    last_sequence: sieve::Sequence,
+    balance: Amount,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Self {
+            last_sequence: sieve::Sequence::MIN,
+            balance: INITIAL_BALANCE,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    accounts: HashMap<sign::PublicKey, Account>,
+    names: HashMap<String, sign::PublicKey>,
+    transactions: Vec<FullTransaction>,
+}
+
+impl State {
+    fn account(&self, user: &sign::PublicKey) -> Account {
+        self.accounts.get(user).copied().unwrap_or_default()
+    }
+
+    /// Use the next sequence of the `user`, as any operation does
+    fn use_sequence(
+        &mut self,
+        user: sign::PublicKey,
+        sequence: sieve::Sequence,
+    ) -> Result<Account, Error> {
+        let account = self.account(&user);
+        let last = account.last_sequence;
+        if sequence != last + 1 {
+            let (code, kind, message) = if sequence <= last {
+                (
+                    tonic::Code::AlreadyExists,
+                    Kind::SequenceAlreadyUsed,
+                    "already used",
+                )
+            } else {
+                (
+                    tonic::Code::FailedPrecondition,
+                    Kind::SequenceGap,
+                    "skips some",
+                )
+            };
+
+            return Err(Error::Rpc {
+                source: account_status(
+                    code,
+                    format!("sequence {} {}, last is {}", sequence, message, last),
+                    proto::AccountError {
+                        kind: kind as i32,
+                        sequence,
+                        last_sequence: last,
+                        ..Default::default()
+                    },
+                ),
+            });
+        }
+
+        Ok(Account {
+            last_sequence: sequence,
+            ..account
+        })
+    }
+}
+
+fn rejected(message: &str) -> Error {
+    Error::Rpc {
+        source: tonic::Status::invalid_argument(message),
+    }
+}
+
+/// Node processing every operation immediately, without any network
+///
+/// Signatures are not checked. Clones share the same state, so one can be
+/// given to the code under test while another inspects the result.
+#[derive(Clone, Default)]
+pub struct MockNode {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockNode {
+    /// Create an empty node
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<State> {
+        self.state.lock().expect("mock state poisoned")
+    }
+}
+
+#[cfg_attr(target_family = "wasm", async_trait(?Send))]
+#[cfg_attr(not(target_family = "wasm"), async_trait)]
+impl At2Api for MockNode {
+    async fn send_signed(&mut self, transaction: &SignedTransaction) -> Result<(), Error> {
+        if matches!(&transaction.memo, Some(memo) if !is_valid_memo(memo)) {
+            return Err(rejected("invalid memo"));
+        }
+        if transaction.outputs.len() > OUTPUTS_MAX_LEN {
+            return Err(rejected("too many outputs"));
+        }
+        if transaction.fee != 0 {
+            return Err(Error::Rpc {
+                source: tonic::Status::failed_precondition("fee paid to a network without fees"),
+            });
+        }
+        // no asset is ever registered
+        if let Some(asset) = &transaction.asset {
+            return Err(Error::Rpc {
+                source: account_status(
+                    tonic::Code::NotFound,
+                    format!("unknown asset: {}", asset),
+                    proto::AccountError {
+                        kind: Kind::UnknownAsset as i32,
+                        ..Default::default()
+                    },
+                ),
+            });
+        }
+
+        let thin = transaction.thin();
+        let outputs = thin.all_outputs();
+        let total = outputs
+            .iter()
+            .try_fold(0, |total: Amount, output| total.checked_add(output.amount))
+            .ok_or_else(|| Error::Rpc {
+                source: account_status(
+                    tonic::Code::InvalidArgument,
+                    "transfer overflow",
+                    proto::AccountError {
+                        kind: Kind::TransferOverflow as i32,
+                        ..Default::default()
+                    },
+                ),
+            })?;
+
+        let mut state = self.state();
+
+        let mut sender = state.use_sequence(transaction.sender, transaction.sequence)?;
+        sender.balance = sender.balance.checked_sub(total).ok_or_else(|| {
+            let (have, have_high) = proto_amount(sender.balance);
+            let (need, need_high) = proto_amount(total);
+
+            Error::Rpc {
+                source: account_status(
+                    tonic::Code::FailedPrecondition,
+                    format!(
+                        "insufficient balance: have {}, need {}",
+                        sender.balance, total
+                    ),
+                    proto::AccountError {
+                        kind: Kind::InsufficientBalance as i32,
+                        have,
+                        need,
+                        have_high,
+                        need_high,
+                        ..Default::default()
+                    },
+                ),
+            }
+        })?;
+
+        let mut recipients = HashMap::new();
+        for output in &outputs {
+            let recipient = recipients.entry(output.recipient).or_insert_with(|| {
+                if output.recipient == transaction.sender {
+                    sender
+                } else {
+                    state.account(&output.recipient)
+                }
+            });
+            recipient.balance = recipient
+                .balance
+                .checked_add(output.amount)
+                .ok_or_else(|| Error::Rpc {
+                    source: account_status(
+                        tonic::Code::OutOfRange,
+                        "balance overflow",
+                        proto::AccountError {
+                            kind: Kind::BalanceOverflow as i32,
+                            ..Default::default()
+                        },
+                    ),
+                })?;
+        }
+
+        state.accounts.insert(transaction.sender, sender);
+        state.accounts.extend(recipients);
+
+        state.transactions.extend(FullTransaction::all_with_thin(
+            transaction.sender,
+            transaction.sequence,
+            thin,
+        ));
+
+        Ok(())
+    }
+
+    async fn get_balance(&mut self, user: &sign::PublicKey) -> Result<Amount, Error> {
+        Ok(self.state().account(user).balance)
+    }
+
+    async fn get_last_sequence(
+        &mut self,
+        user: &sign::PublicKey,
+    ) -> Result<sieve::Sequence, Error> {
+        Ok(self.state().account(user).last_sequence)
+    }
+
+    async fn get_latest_transactions(&mut self) -> Result<Vec<FullTransaction>, Error> {
+        Ok(self.state().transactions.clone())
+    }
+
+    async fn register_name(
+        &mut self,
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        name: String,
+    ) -> Result<(), Error> {
+        if !is_valid_name(&name) {
+            return Err(rejected("invalid name"));
+        }
+
+        let mut state = self.state();
+        let public = user.public();
+
+        let account = state.use_sequence(public, sequence)?;
+        state.accounts.insert(public, account);
+
+        if state.names.contains_key(&name) {
+            return Err(Error::Rpc {
+                source: account_status(
+                    tonic::Code::AlreadyExists,
+                    format!("name already registered: {}", name),
+                    proto::AccountError {
+                        kind: Kind::NameTaken as i32,
+                        ..Default::default()
+                    },
+                ),
+            });
+        }
+        state.names.insert(name, public);
+
+        Ok(())
+    }
+
+    async fn resolve_name(&mut self, name: String) -> Result<sign::PublicKey, Error> {
+        self.state()
+            .names
+            .get(&name)
+            .copied()
+            .ok_or_else(|| Error::Rpc {
+                source: tonic::Status::not_found("no such name"),
+            })
+    }
+}
@@ -0,0 +1,95 @@
+//! Operations offered by an AT2 node, independently of how it is reached
+//!
+//! Code written against [`At2Api`] can run on a [`Client`](crate::client::Client)
+//! connected to a node, or on a [`mock::MockNode`] in unit tests.
+
+use async_trait::async_trait;
+use drop::crypto::sign;
+
+use crate::{client::Error, Amount, FullTransaction, SignedTransaction};
+
+pub mod mock;
+
+/// Operations on the ledger
+#[cfg_attr(target_family = "wasm", async_trait(?Send))]
+#[cfg_attr(not(target_family = "wasm"), async_trait)]
+pub trait At2Api {
+    /// Send a given number of asset to the given user.
+    ///
+    /// `sequence` is counter used by the sender.
+    /// You should increase it by one for each new transaction you want to send.
+    async fn send_asset(
+        &mut self,
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        recipient: sign::PublicKey,
+        amount: Amount,
+    ) -> Result<(), Error> {
+        self.send_signed(&SignedTransaction::new(user, sequence, recipient, amount))
+            .await
+    }
+
+    /// Same as [`At2Api::send_asset`], with a memo signed along
+    async fn send_asset_with_memo(
+        &mut self,
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        recipient: sign::PublicKey,
+        amount: Amount,
+        memo: String,
+    ) -> Result<(), Error> {
+        self.send_signed(&SignedTransaction::with_memo(
+            user,
+            sequence,
+            recipient,
+            amount,
+            Some(memo),
+        ))
+        .await
+    }
+
+    /// Same as [`At2Api::send_asset`], paying a `fee` to the network
+    async fn send_asset_with_fee(
+        &mut self,
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        recipient: sign::PublicKey,
+        amount: Amount,
+        fee: Amount,
+    ) -> Result<(), Error> {
+        self.send_signed(&SignedTransaction::with_fee(
+            user,
+            sequence,
+            recipient,
+            amount,
+            vec![],
+            None,
+            fee,
+        ))
+        .await
+    }
+
+    /// Send an already signed transaction, such as one prepared offline
+    async fn send_signed(&mut self, transaction: &SignedTransaction) -> Result<(), Error>;
+
+    /// Return the balance of the user
+    async fn get_balance(&mut self, user: &sign::PublicKey) -> Result<Amount, Error>;
+
+    /// Get the latest used sequence
+    async fn get_last_sequence(&mut self, user: &sign::PublicKey)
+        -> Result<sieve::Sequence, Error>;
+
+    /// Get the number of recently processed transactions
+    async fn get_latest_transactions(&mut self) -> Result<Vec<FullTransaction>, Error>;
+
+    /// Register a name for the user, first come first served
+    async fn register_name(
+        &mut self,
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        name: String,
+    ) -> Result<(), Error>;
+
+    /// Get the user owning the given name
+    async fn resolve_name(&mut self, name: String) -> Result<sign::PublicKey, Error>;
+}
@@ -0,0 +1,143 @@
+//! Proving to a node the ownership of an account, to read it
+//!
+//! A node can require its callers to prove they own the account they read.
+//! The caller asks for a nonce with `GetChallenge`, signs it as a
+//! [`Challenge`], and sends it back in the metadata of its reads, see
+//! [`client::Client::authenticate`](crate::client::Client::authenticate).
+
+use drop::crypto::sign;
+use hex::FromHex;
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+
+/// Size of the nonces given by the nodes
+pub const NONCE_SIZE: usize = 32;
+
+/// Metadata holding the hex encoded public key of the caller
+pub const METADATA_ACCOUNT: &str = "at2-auth-account";
+/// Metadata holding the hex encoded nonce given by the node
+pub const METADATA_NONCE: &str = "at2-auth-nonce";
+/// Metadata holding the hex encoded signature of the [`Challenge`]
+pub const METADATA_SIGNATURE: &str = "at2-auth-signature";
+
+/// What the caller signs, never valid as another message
+#[drop::message]
+pub struct Challenge {
+    /// Given by the node, of [`NONCE_SIZE`]
+    pub nonce: Vec<u8>,
+}
+
+/// Error reading the metadata of a caller
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// Some metadata is absent
+    #[snafu(display("missing {}", key))]
+    Missing {
+        /// Name of the metadata
+        key: &'static str,
+    },
+    /// Some metadata isn't hex
+    #[snafu(display("{}: {}", key, source))]
+    Hex {
+        /// Name of the metadata
+        key: &'static str,
+        /// Source of the error
+        source: hex::FromHexError,
+    },
+    /// The signature doesn't decode
+    #[snafu(display("signature: {}", source))]
+    DecodeSignature {
+        /// Source of the error
+        source: bincode::Error,
+    },
+    /// The public key doesn't decode
+    InvalidAccount,
+    /// The nonce is of the wrong size
+    InvalidNonce,
+    /// The signature wasn't made by the caller
+    InvalidSignature,
+}
+
+/// Caller having signed a nonce
+#[derive(Debug, Clone)]
+pub struct Proof {
+    /// Account of the caller
+    pub account: sign::PublicKey,
+    /// Nonce signed by the caller
+    pub nonce: Vec<u8>,
+    /// Signature of the [`Challenge`]
+    pub signature: sign::Signature,
+}
+
+impl Proof {
+    /// Sign the `nonce` given by a node
+    pub fn new(keypair: &sign::KeyPair, nonce: Vec<u8>) -> Result<Self, Error> {
+        ensure!(nonce.len() == NONCE_SIZE, InvalidNonce);
+
+        let signature = keypair
+            .sign(&Challenge {
+                nonce: nonce.clone(),
+            })
+            .expect("sign failed");
+
+        Ok(Self {
+            account: keypair.public(),
+            nonce,
+            signature,
+        })
+    }
+
+    /// Add the proof to the `metadata` of a request
+    pub fn insert(&self, metadata: &mut tonic::metadata::MetadataMap) {
+        let signature =
+            bincode::serialize(&self.signature).expect("signature serializes in memory");
+
+        for (key, value) in [
+            (METADATA_ACCOUNT, self.account.to_string()),
+            (METADATA_NONCE, hex::encode(&self.nonce)),
+            (METADATA_SIGNATURE, hex::encode(signature)),
+        ] {
+            metadata.insert(key, value.parse().expect("hex is valid metadata"));
+        }
+    }
+
+    /// Read a proof from the `metadata` of a request, checking its signature
+    pub fn from_metadata(metadata: &tonic::metadata::MetadataMap) -> Result<Self, Error> {
+        let get = |key| {
+            metadata
+                .get(key)
+                .and_then(|value| value.to_str().ok())
+                .context(Missing { key })
+        };
+
+        let account =
+            sign::PublicKey::from_hex(get(METADATA_ACCOUNT)?).map_err(|_| Error::InvalidAccount)?;
+        let nonce = hex::decode(get(METADATA_NONCE)?).context(Hex {
+            key: METADATA_NONCE,
+        })?;
+        ensure!(nonce.len() == NONCE_SIZE, InvalidNonce);
+        let signature = hex::decode(get(METADATA_SIGNATURE)?).context(Hex {
+            key: METADATA_SIGNATURE,
+        })?;
+        let signature = bincode::deserialize(&signature).context(DecodeSignature)?;
+
+        let proof = Self {
+            account,
+            nonce,
+            signature,
+        };
+        ensure!(
+            proof
+                .signature
+                .verify(
+                    &Challenge {
+                        nonce: proof.nonce.clone()
+                    },
+                    &proof.account
+                )
+                .is_ok(),
+            InvalidSignature
+        );
+
+        Ok(proof)
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    collections::HashMap,
+    io::{self, stdout, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use at2_node::{
+    api::At2Api,
+    client::{self, Client, TransactionStatus},
+    Amount,
+};
+use drop::crypto::sign;
+use futures::StreamExt;
+use http::Uri;
+use serde::Serialize;
+use snafu::{ensure, ResultExt, Snafu};
+use structopt::StructOpt;
+use tokio::{
+    sync::oneshot,
+    time::{sleep_until, timeout, Instant},
+};
+
+const AMOUNT: Amount = 1;
+
+/// Transfers waiting to be seen delivered, by sender and sequence
+type Pending = Arc<Mutex<HashMap<(sign::PublicKey, sieve::Sequence), oneshot::Sender<Instant>>>>;
+
+/// Drive transfers at a target rate and report throughput, latency and errors
+///
+/// Wallets send to the next one in a ring, so that funds keep circulating
+/// however long it runs. Each wallet only has one transaction in flight, as
+/// its sequences have to be processed in order; concurrency comes from the
+/// number of wallets, spread over the given nodes. A transfer is delivered
+/// once the node it was sent to streams it back.
+#[derive(Debug, StructOpt)]
+struct Options {
+    #[structopt(required = true)]
+    rpc_addresses: Vec<Uri>,
+    #[structopt(long, default_value = "10")]
+    wallets: usize,
+    /// Transfers per second, over every wallet
+    #[structopt(long, default_value = "100")]
+    rate: f64,
+    /// How long to send for, in seconds
+    #[structopt(long, default_value = "30")]
+    duration: u64,
+    /// How long a transfer has to be delivered, in seconds
+    #[structopt(long, default_value = "10")]
+    timeout: u64,
+    /// Print the report as JSON
+    #[structopt(long)]
+    json: bool,
+}
+
+#[derive(Debug, Snafu)]
+enum Error {
+    #[snafu(display("need at least two wallets"))]
+    TooFewWallets,
+    #[snafu(display("rate has to be positive"))]
+    InvalidRate,
+    #[snafu(display("client: {}", source))]
+    ClientError { source: client::Error },
+    #[snafu(display("write report: {}", source))]
+    WriteReport { source: io::Error },
+    #[snafu(display("encode report: {}", source))]
+    Encode { source: serde_json::Error },
+}
+
+/// What happened to the transfers of a wallet
+#[derive(Default)]
+struct Outcomes {
+    sent: usize,
+    delivered: usize,
+    rejected: usize,
+    timeouts: usize,
+    send_errors: usize,
+    /// Of the transfers seen delivered
+    latencies: Vec<Duration>,
+}
+
+impl Outcomes {
+    fn merge(&mut self, other: Self) {
+        self.sent += other.sent;
+        self.delivered += other.delivered;
+        self.rejected += other.rejected;
+        self.timeouts += other.timeouts;
+        self.send_errors += other.send_errors;
+        self.latencies.extend(other.latencies);
+    }
+}
+
+#[derive(Serialize)]
+struct Latencies {
+    p50_ms: u128,
+    p90_ms: u128,
+    p99_ms: u128,
+    max_ms: u128,
+}
+
+#[derive(Serialize)]
+struct Report {
+    wallets: usize,
+    target_rate: f64,
+    elapsed_s: f64,
+    sent: usize,
+    delivered: usize,
+    rejected: usize,
+    timeouts: usize,
+    send_errors: usize,
+    /// Delivered transfers per second
+    throughput: f64,
+    /// Share of the attempts which weren't delivered
+    error_rate: f64,
+    latency: Latencies,
+}
+
+impl Report {
+    fn new(options: &Options, mut outcomes: Outcomes, elapsed: Duration) -> Self {
+        outcomes.latencies.sort_unstable();
+        let latencies = &outcomes.latencies;
+        let percentile = |p: usize| {
+            latencies
+                .get((latencies.len() * p / 100).min(latencies.len().saturating_sub(1)))
+                .map(Duration::as_millis)
+                .unwrap_or_default()
+        };
+
+        let attempts = outcomes.sent + outcomes.send_errors;
+        let failed = attempts - outcomes.delivered;
+
+        Self {
+            wallets: options.wallets,
+            target_rate: options.rate,
+            elapsed_s: elapsed.as_secs_f64(),
+            sent: outcomes.sent,
+            delivered: outcomes.delivered,
+            rejected: outcomes.rejected,
+            timeouts: outcomes.timeouts,
+            send_errors: outcomes.send_errors,
+            throughput: outcomes.delivered as f64 / elapsed.as_secs_f64(),
+            error_rate: if attempts == 0 {
+                0.0
+            } else {
+                failed as f64 / attempts as f64
+            },
+            latency: Latencies {
+                p50_ms: percentile(50),
+                p90_ms: percentile(90),
+                p99_ms: percentile(99),
+                max_ms: latencies
+                    .last()
+                    .map(Duration::as_millis)
+                    .unwrap_or_default(),
+            },
+        }
+    }
+
+    fn write(&self, json: bool, mut writer: impl Write) -> Result<(), Error> {
+        if json {
+            serde_json::to_writer(&mut writer, self).context(Encode)?;
+            return writeln!(writer).context(WriteReport);
+        }
+
+        writeln!(
+            writer,
+            "{} wallets for {:.1}s, target {:.1} tx/s",
+            self.wallets, self.elapsed_s, self.target_rate
+        )
+        .context(WriteReport)?;
+        writeln!(
+            writer,
+            "sent {}, delivered {} ({:.1} tx/s)",
+            self.sent, self.delivered, self.throughput
+        )
+        .context(WriteReport)?;
+        writeln!(
+            writer,
+            "rejected {}, timed out {}, failed to send {} ({:.2}% errors)",
+            self.rejected,
+            self.timeouts,
+            self.send_errors,
+            self.error_rate * 100.0
+        )
+        .context(WriteReport)?;
+        writeln!(
+            writer,
+            "latency p50 {}ms p90 {}ms p99 {}ms max {}ms",
+            self.latency.p50_ms, self.latency.p90_ms, self.latency.p99_ms, self.latency.max_ms
+        )
+        .context(WriteReport)
+    }
+}
+
+/// Resolve the pending transfers as the node delivers them
+async fn watch_deliveries(mut client: Client, pending: Pending) -> Result<(), Error> {
+    let mut delivered = Box::pin(client.subscribe_transactions().await.context(ClientError)?);
+
+    while let Some(tx) = delivered.next().await {
+        let tx = tx.context(ClientError)?;
+
+        let waiting = pending
+            .lock()
+            .expect("pending poisoned")
+            .remove(&(tx.sender, tx.sequence));
+        if let Some(waiting) = waiting {
+            let _ = waiting.send(Instant::now());
+        }
+    }
+
+    Ok(())
+}
+
+struct Wallet {
+    client: Client,
+    keypair: sign::KeyPair,
+    recipient: sign::PublicKey,
+    pending: Pending,
+}
+
+impl Wallet {
+    /// Send a transfer every `period`, starting at `start`, until `end`
+    async fn run(
+        mut self,
+        start: Instant,
+        period: Duration,
+        end: Instant,
+        delivery_timeout: Duration,
+    ) -> Result<Outcomes, Error> {
+        let sender = self.keypair.public();
+        let mut outcomes = Outcomes::default();
+
+        let mut sequence = self
+            .client
+            .get_last_sequence(&sender)
+            .await
+            .context(ClientError)?
+            + 1;
+
+        let mut next = start;
+        while next < end {
+            sleep_until(next).await;
+            next += period;
+
+            let (tx, rx) = oneshot::channel();
+            self.pending
+                .lock()
+                .expect("pending poisoned")
+                .insert((sender, sequence), tx);
+
+            let sent = Instant::now();
+            if self
+                .client
+                .send_asset(&self.keypair, sequence, self.recipient, AMOUNT)
+                .await
+                .is_err()
+            {
+                self.forget(sender, sequence);
+                outcomes.send_errors += 1;
+                continue;
+            }
+            outcomes.sent += 1;
+
+            if let Ok(Ok(delivered)) = timeout(delivery_timeout, rx).await {
+                outcomes.delivered += 1;
+                outcomes.latencies.push(delivered - sent);
+                sequence += 1;
+                continue;
+            }
+            self.forget(sender, sequence);
+
+            // the subscription might have skipped it
+            match self
+                .client
+                .get_transaction_status(&sender, sequence)
+                .await
+                .context(ClientError)?
+            {
+                TransactionStatus::Delivered => {
+                    outcomes.delivered += 1;
+                    sequence += 1;
+                }
+                // the sequence wasn't used
+                TransactionStatus::Rejected(_) => outcomes.rejected += 1,
+                // it might still be delivered, so the next sequence can't be known
+                TransactionStatus::Unknown | TransactionStatus::Pending => {
+                    outcomes.timeouts += 1;
+                    break;
+                }
+            }
+        }
+
+        Ok(outcomes)
+    }
+
+    fn forget(&self, sender: sign::PublicKey, sequence: sieve::Sequence) {
+        self.pending
+            .lock()
+            .expect("pending poisoned")
+            .remove(&(sender, sequence));
+    }
+}
+
+async fn run(options: Options) -> Result<(), Error> {
+    ensure!(options.wallets >= 2, TooFewWallets);
+    ensure!(options.rate > 0.0, InvalidRate);
+
+    let nodes = options
+        .rpc_addresses
+        .iter()
+        .map(|uri| {
+            let client = Client::new(uri.clone()).context(ClientError)?;
+            Ok((client, Pending::default()))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    for (client, pending) in &nodes {
+        tokio::spawn(watch_deliveries(client.clone(), pending.clone()));
+    }
+
+    let keypairs = (0..options.wallets)
+        .map(|_| sign::KeyPair::random())
+        .collect::<Vec<_>>();
+    let publics = keypairs
+        .iter()
+        .map(|keypair| keypair.public())
+        .collect::<Vec<_>>();
+
+    // each wallet sends in turn, together at the target rate
+    let period = Duration::from_secs_f64(options.wallets as f64 / options.rate);
+    let start = Instant::now();
+    let end = start + Duration::from_secs(options.duration);
+
+    let handles = keypairs
+        .into_iter()
+        .enumerate()
+        .map(|(index, keypair)| {
+            let (client, pending) = &nodes[index % nodes.len()];
+            let wallet = Wallet {
+                client: client.clone(),
+                keypair,
+                recipient: publics[(index + 1) % publics.len()],
+                pending: pending.clone(),
+            };
+            let offset = period.mul_f64(index as f64 / options.wallets as f64);
+
+            tokio::spawn(wallet.run(
+                start + offset,
+                period,
+                end,
+                Duration::from_secs(options.timeout),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let mut outcomes = Outcomes::default();
+    for handle in handles {
+        outcomes.merge(handle.await.expect("wallet panicked")?);
+    }
+
+    Report::new(&options, outcomes, start.elapsed()).write(options.json, stdout())
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    if let Err(err) = run(Options::from_args()).await {
+        eprintln!("error running bench: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -1,5 +1,10 @@
-use std::io;
+use std::{fs, io, path::PathBuf};
 
+use at2_node::{
+    client::{self, Client},
+    codec::Encoding,
+    interchange,
+};
 use drop::crypto::sign;
 use http::Uri;
 use snafu::{ResultExt, Snafu};
@@ -10,6 +15,33 @@ pub struct Config {
     pub rpc_address: Uri,
     #[serde(with = "hex")]
     pub private_key: sign::PrivateKey,
+    #[serde(default)]
+    pub encoding: Encoding,
+    #[serde(default = "default_chain_id")]
+    pub chain_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<ConfigTls>,
+}
+
+/// TLS towards the node, for `https` addresses, with PEM encoded files
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ConfigTls {
+    /// Authority signing the node's certificate, if not trusted by the system
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca: Option<PathBuf>,
+    /// Certificate and key to authenticate with, for nodes requiring it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<ConfigIdentity>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ConfigIdentity {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+fn default_chain_id() -> String {
+    interchange::DEFAULT_CHAIN_ID.to_owned()
 }
 
 #[derive(Debug, Snafu)]
@@ -23,6 +55,11 @@ pub enum Error {
     Read { source: io::Error },
     #[snafu(display("decode: {}", source))]
     Decode { source: toml::de::Error },
+
+    #[snafu(display("tls: read {}: {}", path.display(), source))]
+    ReadTls { path: PathBuf, source: io::Error },
+    #[snafu(display("client: {}", source))]
+    NewClient { source: client::Error },
 }
 
 pub fn from_reader(mut reader: impl io::Read) -> Result<Config, Error> {
@@ -35,6 +72,23 @@ pub fn from_reader(mut reader: impl io::Read) -> Result<Config, Error> {
 }
 
 impl Config {
+    /// Client for the configured node
+    pub fn client(&self) -> Result<Client, Error> {
+        let read = |path: &PathBuf| fs::read(path).context(ReadTls { path: path.clone() });
+
+        let mut builder = Client::builder().encoding(self.encoding);
+        if let Some(tls) = &self.tls {
+            if let Some(ca) = &tls.ca {
+                builder = builder.ca_certificate(read(ca)?);
+            }
+            if let Some(identity) = &tls.identity {
+                builder = builder.identity(read(&identity.cert)?, read(&identity.key)?);
+            }
+        }
+
+        builder.build(self.rpc_address.clone()).context(NewClient)
+    }
+
     pub fn to_writer(&self, mut writer: impl io::Write) -> Result<(), Error> {
         let encoded = toml::to_vec(&self).context(Encode)?;
 
@@ -0,0 +1,102 @@
+use std::{collections::BTreeMap, fs, io, path::Path, sync::Arc};
+
+use arrow::{
+    array::{StringArray, TimestampMillisecondArray},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use at2_node::FullTransaction;
+use chrono::NaiveDate;
+use parquet::{arrow::ArrowWriter, errors::ParquetError};
+use snafu::{ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("create directory: {}", source))]
+    CreateDirectory { source: io::Error },
+    #[snafu(display("create file: {}", source))]
+    CreateFile { source: io::Error },
+    #[snafu(display("build batch: {}", source))]
+    BuildBatch { source: ArrowError },
+    #[snafu(display("write parquet: {}", source))]
+    WriteParquet { source: ParquetError },
+}
+
+fn schema() -> Schema {
+    Schema::new(vec![
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".to_owned())),
+            false,
+        ),
+        Field::new("sender", DataType::Utf8, false),
+        Field::new("recipient", DataType::Utf8, false),
+        // in decimal, as no integer column holds every amount
+        Field::new("amount", DataType::Utf8, false),
+        Field::new("memo", DataType::Utf8, true),
+    ])
+}
+
+/// Write the transactions in `directory`, as one Parquet file per day
+///
+/// Files are laid out as `date=<YYYY-MM-DD>/<name>.parquet`, which is
+/// understood as a partition by most query engines.
+pub fn to_parquet(
+    directory: &Path,
+    name: &str,
+    transactions: &[FullTransaction],
+) -> Result<(), Error> {
+    let mut by_day = BTreeMap::<NaiveDate, Vec<&FullTransaction>>::new();
+    for tx in transactions {
+        by_day
+            .entry(tx.timestamp.date().naive_utc())
+            .or_default()
+            .push(tx);
+    }
+
+    let schema = Arc::new(schema());
+
+    for (day, transactions) in by_day {
+        let partition = directory.join(format!("date={}", day));
+        fs::create_dir_all(&partition).context(CreateDirectory)?;
+
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(TimestampMillisecondArray::from_vec(
+                    transactions
+                        .iter()
+                        .map(|tx| tx.timestamp.timestamp_millis())
+                        .collect(),
+                    Some("UTC".to_owned()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    transactions.iter().map(|tx| tx.sender.to_string()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    transactions.iter().map(|tx| tx.recipient.to_string()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    transactions.iter().map(|tx| tx.amount.to_string()),
+                )),
+                Arc::new(
+                    transactions
+                        .iter()
+                        .map(|tx| tx.memo.as_deref())
+                        .collect::<StringArray>(),
+                ),
+            ],
+        )
+        .context(BuildBatch)?;
+
+        let file =
+            fs::File::create(partition.join(format!("{}.parquet", name))).context(CreateFile)?;
+
+        let mut writer = ArrowWriter::try_new(file, schema.clone(), None).context(WriteParquet)?;
+        writer.write(&batch).context(WriteParquet)?;
+        writer.close().context(WriteParquet)?;
+    }
+
+    Ok(())
+}
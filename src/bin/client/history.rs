@@ -0,0 +1,124 @@
+//! Write the exported history, for accounting and analysis tools
+//!
+//! Keys are hex encoded and amounts written in decimal, as most JSON parsers
+//! lose the precision of larger numbers.
+
+use std::{
+    io::{self, Write},
+    str::FromStr,
+};
+
+use at2_node::FullTransaction;
+use hex::ToHex;
+use serde::Serialize;
+use snafu::{ResultExt, Snafu};
+
+const CSV_HEADER: &str = "timestamp,sender,sequence,recipient,amount,fee,memo";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("write: {}", source))]
+    Write { source: io::Error },
+    #[snafu(display("serialize: {}", source))]
+    Serialize { source: serde_json::Error },
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(display("unknown format: {}, expected csv or json", format))]
+pub struct UnknownFormat {
+    format: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    /// With a header, one transaction per line
+    Csv,
+    /// One object per line
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = UnknownFormat;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => UnknownFormat { format: src }.fail(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: String,
+    sender: String,
+    sequence: sieve::Sequence,
+    recipient: String,
+    amount: String,
+    fee: String,
+    memo: Option<&'a str>,
+}
+
+impl<'a> From<&'a FullTransaction> for Record<'a> {
+    fn from(tx: &'a FullTransaction) -> Self {
+        Self {
+            timestamp: tx.timestamp.to_rfc3339(),
+            sender: tx.sender.encode_hex(),
+            sequence: tx.sequence,
+            recipient: tx.recipient.encode_hex(),
+            amount: tx.amount.to_string(),
+            fee: tx.fee.to_string(),
+            memo: tx.memo.as_deref(),
+        }
+    }
+}
+
+/// Write transactions one after the other
+pub struct Writer<W> {
+    format: Format,
+    out: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Start writing to `out`, such as the header
+    pub fn new(format: Format, mut out: W) -> Result<Self, Error> {
+        if let Format::Csv = format {
+            writeln!(out, "{}", CSV_HEADER).context(Write)?;
+        }
+
+        Ok(Self { format, out })
+    }
+
+    pub fn write(&mut self, tx: &FullTransaction) -> Result<(), Error> {
+        let record = Record::from(tx);
+
+        match self.format {
+            Format::Csv => writeln!(
+                self.out,
+                "{},{},{},{},{},{},{}",
+                record.timestamp,
+                record.sender,
+                record.sequence,
+                record.recipient,
+                record.amount,
+                record.fee,
+                record.memo.map(csv_field).unwrap_or_default(),
+            )
+            .context(Write),
+            Format::Json => {
+                serde_json::to_writer(&mut self.out, &record).context(Serialize)?;
+                writeln!(self.out).context(Write)
+            }
+        }
+    }
+}
+
+/// Quote `field` if it holds a separator, a quote or a newline
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
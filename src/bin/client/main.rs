@@ -1,36 +1,218 @@
-use std::io::{stdin, stdout};
+use std::{
+    fs,
+    io::{self, stdin, stdout},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
 
-use at2_node::client::{self, Client};
+use at2_node::{
+    address::{self, Address},
+    api::At2Api,
+    client::{self, Client, TransactionStatus},
+    interchange, is_valid_name, Amount, Asset, AssetId, FullTransaction, SignedTransaction,
+    ThinTransaction,
+};
 use drop::crypto::sign;
+use futures::StreamExt;
 use hex::FromHex;
 use http::Uri;
-use snafu::{ResultExt, Snafu};
+use snafu::{ensure, ResultExt, Snafu};
 use structopt::StructOpt;
 
 mod config;
+#[cfg(feature = "parquet-export")]
+mod export;
+mod history;
 
-fn hex_decode<T: FromHex>(src: &str) -> Result<T, T::Error> {
-    T::from_hex(src)
+const IMPORT_TICK: Duration = Duration::from_millis(100);
+const IMPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Parse a public key, either hex encoded or as an [`Address`]
+fn parse_public_key(src: &str) -> Result<sign::PublicKey, address::Error> {
+    if let Ok(public_key) = sign::PublicKey::from_hex(src) {
+        return Ok(public_key);
+    }
+
+    src.parse::<Address>().map(Into::into)
+}
+
+/// Account to send to, given as for [`parse_public_key`] or by a registered name
+#[derive(Debug)]
+enum Recipient {
+    Key(sign::PublicKey),
+    /// Never a key nor an address, as these are longer
+    Name(String),
+}
+
+impl FromStr for Recipient {
+    type Err = address::Error;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        if is_valid_name(src) {
+            return Ok(Self::Name(src.to_owned()));
+        }
+
+        parse_public_key(src).map(Self::Key)
+    }
+}
+
+impl Recipient {
+    /// Ask the node for the owner of the name, if given one
+    async fn resolve(self, client: &mut Client) -> Result<sign::PublicKey, client::Error> {
+        match self {
+            Self::Key(public_key) => Ok(public_key),
+            Self::Name(name) => client.resolve_name(name).await,
+        }
+    }
 }
 
 #[derive(Debug, StructOpt)]
 enum Commands {
     Config(CommandsConfig),
+    /// Print a config holding a new account, same as `config new`
+    Keygen {
+        rpc_address: Uri,
+    },
+    /// Send asset with the next sequence of the configured account, printing it
+    Send {
+        /// Hex encoded key, address or registered name
+        recipient: Recipient,
+        amount: Amount,
+        /// Free text for the recipient, signed along
+        #[structopt(long)]
+        memo: Option<String>,
+        /// Paid to the network, at least its minimum, see get-node-info
+        #[structopt(long, default_value = "0")]
+        fee: Amount,
+        /// Registered asset to send, the network's own by default
+        #[structopt(long)]
+        asset: Option<AssetId>,
+    },
     SendAsset {
         sequence: sieve::Sequence,
-        #[structopt(parse(try_from_str = hex_decode))]
+        /// Hex encoded key, address or registered name
+        recipient: Recipient,
+        amount: Amount,
+        /// Free text for the recipient, signed along
+        #[structopt(long)]
+        memo: Option<String>,
+        /// Paid to the network, at least its minimum, see get-node-info
+        #[structopt(long, default_value = "0")]
+        fee: Amount,
+        /// Registered asset to send, the network's own by default
+        #[structopt(long)]
+        asset: Option<AssetId>,
+    },
+    SignAsset {
+        sequence: sieve::Sequence,
+        #[structopt(parse(try_from_str = parse_public_key))]
         recipient: sign::PublicKey,
-        amount: u64,
+        amount: Amount,
+        /// Free text for the recipient, signed along
+        #[structopt(long)]
+        memo: Option<String>,
+        /// Paid to the network, at least its minimum, see get-node-info
+        #[structopt(long, default_value = "0")]
+        fee: Amount,
+        /// Registered asset to send, the network's own by default
+        #[structopt(long)]
+        asset: Option<AssetId>,
+    },
+    Import {
+        files: Vec<PathBuf>,
+    },
+    /// Print the balance of an account, the configured one by default
+    #[structopt(visible_alias = "balance")]
+    GetBalance {
+        #[structopt(parse(try_from_str = parse_public_key))]
+        account: Option<sign::PublicKey>,
+        /// Registered asset, the network's own by default
+        #[structopt(long)]
+        asset: Option<AssetId>,
     },
-    GetBalance,
     GetLastSequence,
     GetLatestTransactions,
+    /// Print the transactions of the configured account, newest first
+    #[structopt(visible_alias = "history")]
+    GetAccountHistory {
+        /// Where the previous page ended
+        #[structopt(long)]
+        cursor: Option<u64>,
+        /// Most transactions to print, the node's maximum by default
+        #[structopt(long, default_value = "0")]
+        limit: u32,
+    },
+    /// Print every transaction delivered by the node, oldest first
+    Export {
+        /// Either `csv` or `json`, as one object per line
+        #[structopt(long, default_value = "csv")]
+        format: history::Format,
+    },
+    GetTransactionStatus {
+        sequence: sieve::Sequence,
+    },
+    /// Print the transfers of the configured account the node refused, and why
+    GetRejectedTransactions,
+    /// Print transactions as they are processed, until interrupted
+    #[structopt(visible_alias = "watch")]
+    SubscribeTransactions,
+    RegisterName {
+        sequence: sieve::Sequence,
+        name: String,
+    },
+    ResolveName {
+        name: String,
+    },
+    /// Let the configured key use its next sequences in any order
+    SetSequenceWindow {
+        sequence: sieve::Sequence,
+        /// How many sequences following the last one can be used
+        size: sieve::Sequence,
+    },
+    /// Create asset, the configured key being an admin of the network
+    Mint {
+        sequence: sieve::Sequence,
+        #[structopt(parse(try_from_str = parse_public_key))]
+        recipient: sign::PublicKey,
+        amount: Amount,
+        /// Registered asset to create, the network's own by default
+        #[structopt(long)]
+        asset: Option<AssetId>,
+    },
+    /// Add an asset to the registry, the configured key being an admin of the network
+    RegisterAsset {
+        sequence: sieve::Sequence,
+        /// Uppercase letters and digits, such as `EUR`
+        id: AssetId,
+        /// Free text for the users, such as `Euro`
+        name: String,
+    },
+    /// Print every registered asset
+    GetAssets,
+    /// Change what the node logs, the configured key being the node's or an admin's
+    SetLogLevel {
+        /// Such as `debug`
+        level: String,
+        /// Only change the logs of this target, such as `contagion`
+        target: Option<String>,
+    },
+    GetNodeInfo,
+    /// Print the peers of the node, and whether they answer it
+    GetNetworkInfo,
+    /// Print the round trips and traffic of the node's checks of its peers
+    GetPeerStats,
+    #[cfg(feature = "parquet-export")]
+    ExportParquet {
+        directory: PathBuf,
+    },
 }
 
 #[derive(Debug, StructOpt)]
 enum CommandsConfig {
     New { rpc_address: Uri },
     GetPublicKey,
+    GetAddress,
 }
 
 #[derive(Debug, Snafu)]
@@ -41,20 +223,88 @@ enum CommandError {
     Serialize { source: bincode::Error },
     #[snafu(display("client: {}", source))]
     ClientError { source: client::Error },
+    #[snafu(display("new client: {}", source))]
+    NewClient { source: config::Error },
+    #[snafu(display("open transactions: {}", source))]
+    OpenTransactions { source: io::Error },
+    #[snafu(display("transactions: {}", source))]
+    TransactionsError { source: interchange::Error },
+    #[snafu(display("transactions for chain {}, expected {}", got, expected))]
+    WrongChain { expected: String, got: String },
+    #[snafu(display(
+        "transaction of {} has sequence {}, expected {}",
+        sender,
+        got,
+        expected
+    ))]
+    InconsecutiveSequence {
+        sender: String,
+        expected: sieve::Sequence,
+        got: sieve::Sequence,
+    },
+    #[snafu(display("transaction of {} with sequence {} not processed", sender, sequence))]
+    NotProcessed {
+        sender: String,
+        sequence: sieve::Sequence,
+    },
+    #[cfg(feature = "parquet-export")]
+    #[snafu(display("export: {}", source))]
+    Export { source: export::Error },
+    #[snafu(display("write history: {}", source))]
+    WriteHistory { source: history::Error },
 }
 
 #[derive(Debug, Snafu)]
 enum CommandsError {
     #[snafu(display("config: {}", source))]
     Config { source: config::Error },
+    #[snafu(display("send: {}", source))]
+    Send { source: CommandError },
     #[snafu(display("send asset: {}", source))]
     SendAsset { source: CommandError },
+    #[snafu(display("sign asset: {}", source))]
+    SignAsset { source: CommandError },
+    #[snafu(display("import: {}", source))]
+    Import { source: CommandError },
     #[snafu(display("get asset: {}", source))]
     GetBalance { source: CommandError },
     #[snafu(display("get last sequence: {}", source))]
     GetLastSequence { source: CommandError },
     #[snafu(display("get latest transactions: {}", source))]
     GetLatestTransactions { source: CommandError },
+    #[snafu(display("get account history: {}", source))]
+    GetAccountHistory { source: CommandError },
+    #[snafu(display("export history: {}", source))]
+    ExportHistory { source: CommandError },
+    #[snafu(display("get transaction status: {}", source))]
+    GetTransactionStatus { source: CommandError },
+    #[snafu(display("get rejected transactions: {}", source))]
+    GetRejectedTransactions { source: CommandError },
+    #[snafu(display("subscribe transactions: {}", source))]
+    SubscribeTransactions { source: CommandError },
+    #[snafu(display("register name: {}", source))]
+    RegisterName { source: CommandError },
+    #[snafu(display("resolve name: {}", source))]
+    ResolveName { source: CommandError },
+    #[snafu(display("set sequence window: {}", source))]
+    SetSequenceWindow { source: CommandError },
+    #[snafu(display("mint: {}", source))]
+    Mint { source: CommandError },
+    #[snafu(display("register asset: {}", source))]
+    RegisterAsset { source: CommandError },
+    #[snafu(display("get assets: {}", source))]
+    GetAssets { source: CommandError },
+    #[snafu(display("set log level: {}", source))]
+    SetLogLevel { source: CommandError },
+    #[snafu(display("get node info: {}", source))]
+    GetNodeInfo { source: CommandError },
+    #[snafu(display("get network info: {}", source))]
+    GetNetworkInfo { source: CommandError },
+    #[snafu(display("get peer stats: {}", source))]
+    GetPeerStats { source: CommandError },
+    #[cfg(feature = "parquet-export")]
+    #[snafu(display("export parquet: {}", source))]
+    ExportParquet { source: CommandError },
 }
 
 fn config(cmd: CommandsConfig) -> Result<(), config::Error> {
@@ -62,6 +312,9 @@ fn config(cmd: CommandsConfig) -> Result<(), config::Error> {
         CommandsConfig::New { rpc_address } => config::Config {
             rpc_address,
             private_key: sign::KeyPair::random().private(),
+            encoding: Default::default(),
+            chain_id: interchange::DEFAULT_CHAIN_ID.to_owned(),
+            tls: None,
         }
         .to_writer(stdout()),
         CommandsConfig::GetPublicKey => {
@@ -69,6 +322,16 @@ fn config(cmd: CommandsConfig) -> Result<(), config::Error> {
 
             println!("{}", sign::KeyPair::from(config.private_key).public());
 
+            Ok(())
+        }
+        CommandsConfig::GetAddress => {
+            let config = config::from_reader(stdin())?;
+
+            println!(
+                "{}",
+                Address::from(sign::KeyPair::from(config.private_key).public())
+            );
+
             Ok(())
         }
     }
@@ -76,34 +339,186 @@ fn config(cmd: CommandsConfig) -> Result<(), config::Error> {
 
 async fn send_asset(
     sequence: sieve::Sequence,
-    recipient: sign::PublicKey,
-    amount: u64,
+    recipient: Recipient,
+    amount: Amount,
+    memo: Option<String>,
+    fee: Amount,
+    asset: Option<AssetId>,
 ) -> Result<(), CommandError> {
     let config = config::from_reader(stdin()).context(ReadConfig)?;
+    let mut client = config.client().context(NewClient)?;
+    let recipient = recipient.resolve(&mut client).await.context(ClientError)?;
 
-    Client::new(config.rpc_address)
-        .context(ClientError)?
-        .send_asset(
+    client
+        .send_signed(&SignedTransaction::sign(
             &sign::KeyPair::from(config.private_key),
             sequence,
-            recipient,
-            amount,
-        )
+            transfer(recipient, amount, memo, fee, asset),
+        ))
         .await
         .context(ClientError)?;
 
     Ok(())
 }
 
-async fn get_balance() -> Result<(), CommandError> {
+async fn send(
+    recipient: Recipient,
+    amount: Amount,
+    memo: Option<String>,
+    fee: Amount,
+    asset: Option<AssetId>,
+) -> Result<(), CommandError> {
     let config = config::from_reader(stdin()).context(ReadConfig)?;
+    let mut client = config.client().context(NewClient)?;
+    let keypair = sign::KeyPair::from(config.private_key);
+    let recipient = recipient.resolve(&mut client).await.context(ClientError)?;
 
-    let amount = Client::new(config.rpc_address)
+    let sequence = client
+        .get_last_sequence(&keypair.public())
+        .await
         .context(ClientError)?
-        .get_balance(&sign::KeyPair::from(config.private_key).public())
+        + 1;
+    client
+        .send_signed(&SignedTransaction::sign(
+            &keypair,
+            sequence,
+            transfer(recipient, amount, memo, fee, asset),
+        ))
         .await
         .context(ClientError)?;
 
+    println!("{}", sequence);
+
+    Ok(())
+}
+
+fn sign_asset(
+    sequence: sieve::Sequence,
+    recipient: sign::PublicKey,
+    amount: Amount,
+    memo: Option<String>,
+    fee: Amount,
+    asset: Option<AssetId>,
+) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    interchange::Document::new(
+        config.chain_id,
+        vec![SignedTransaction::sign(
+            &sign::KeyPair::from(config.private_key),
+            sequence,
+            transfer(recipient, amount, memo, fee, asset),
+        )],
+    )
+    .to_writer(stdout())
+    .context(TransactionsError)
+}
+
+/// Transfer to a single recipient, as signed by the send commands
+fn transfer(
+    recipient: sign::PublicKey,
+    amount: Amount,
+    memo: Option<String>,
+    fee: Amount,
+    asset: Option<AssetId>,
+) -> ThinTransaction {
+    ThinTransaction {
+        recipient,
+        amount,
+        memo,
+        outputs: vec![],
+        fee,
+        asset,
+    }
+}
+
+/// Send every transaction of the files, in order, waiting for each to be processed
+///
+/// Transactions already processed by the network are skipped, so an
+/// interrupted import can be restarted.
+async fn import(files: Vec<PathBuf>) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let mut transactions = Vec::new();
+    for file in files {
+        let document =
+            interchange::Document::from_reader(fs::File::open(file).context(OpenTransactions)?)
+                .context(TransactionsError)?;
+        ensure!(
+            document.chain_id == config.chain_id,
+            WrongChain {
+                expected: &config.chain_id,
+                got: document.chain_id,
+            }
+        );
+
+        transactions.extend(document.transactions);
+    }
+
+    let mut client = config.client().context(NewClient)?;
+
+    for tx in transactions {
+        let last_sequence = client
+            .get_last_sequence(&tx.sender)
+            .await
+            .context(ClientError)?;
+
+        if tx.sequence <= last_sequence {
+            eprintln!(
+                "skipping transaction of {} with sequence {}: already processed",
+                tx.sender, tx.sequence
+            );
+            continue;
+        }
+        ensure!(
+            tx.sequence == last_sequence + 1,
+            InconsecutiveSequence {
+                sender: tx.sender.to_string(),
+                expected: last_sequence + 1,
+                got: tx.sequence,
+            }
+        );
+
+        client.send_signed(&tx).await.context(ClientError)?;
+
+        let timeout = tokio::time::Instant::now() + IMPORT_TIMEOUT;
+        while client
+            .get_last_sequence(&tx.sender)
+            .await
+            .context(ClientError)?
+            < tx.sequence
+        {
+            ensure!(
+                tokio::time::Instant::now() < timeout,
+                NotProcessed {
+                    sender: tx.sender.to_string(),
+                    sequence: tx.sequence,
+                }
+            );
+            tokio::time::sleep(IMPORT_TICK).await;
+        }
+    }
+
+    Ok(())
+}
+
+async fn get_balance(
+    account: Option<sign::PublicKey>,
+    asset: Option<AssetId>,
+) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+    let mut client = config.client().context(NewClient)?;
+    let account = match account {
+        Some(account) => account,
+        None => sign::KeyPair::from(config.private_key).public(),
+    };
+
+    let amount = match asset {
+        None => client.get_balance(&account).await,
+        Some(asset) => client.get_asset_balance(&account, &asset).await,
+    }
+    .context(ClientError)?;
+
     println!("{}", amount);
 
     Ok(())
@@ -112,8 +527,9 @@ async fn get_balance() -> Result<(), CommandError> {
 async fn get_last_sequence() -> Result<(), CommandError> {
     let config = config::from_reader(stdin()).context(ReadConfig)?;
 
-    let sequence = Client::new(config.rpc_address)
-        .context(ClientError)?
+    let sequence = config
+        .client()
+        .context(NewClient)?
         .get_last_sequence(&sign::KeyPair::from(config.private_key).public())
         .await
         .context(ClientError)?;
@@ -126,38 +542,454 @@ async fn get_last_sequence() -> Result<(), CommandError> {
 async fn get_latest_transactions() -> Result<(), CommandError> {
     let config = config::from_reader(stdin()).context(ReadConfig)?;
 
-    Client::new(config.rpc_address)
-        .context(ClientError)?
+    config
+        .client()
+        .context(NewClient)?
         .get_latest_transactions()
         .await
         .context(ClientError)?
         .iter()
-        .for_each(|tx| {
+        .for_each(print_transaction);
+
+    Ok(())
+}
+
+async fn get_account_history(cursor: Option<u64>, limit: u32) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let page = config
+        .client()
+        .context(NewClient)?
+        .get_account_history(
+            &sign::KeyPair::from(config.private_key).public(),
+            cursor,
+            limit,
+        )
+        .await
+        .context(ClientError)?;
+
+    page.transactions.iter().for_each(print_transaction);
+    if let Some(next) = page.next {
+        println!("next cursor: {}", next);
+    }
+
+    Ok(())
+}
+
+async fn export_history(format: history::Format) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let mut transactions = Box::pin(
+        config
+            .client()
+            .context(NewClient)?
+            .export_history()
+            .await
+            .context(ClientError)?,
+    );
+
+    let stdout = stdout();
+    let mut writer = history::Writer::new(format, stdout.lock()).context(WriteHistory)?;
+    while let Some(tx) = transactions.next().await {
+        writer
+            .write(&tx.context(ClientError)?)
+            .context(WriteHistory)?;
+    }
+
+    Ok(())
+}
+
+async fn get_transaction_status(sequence: sieve::Sequence) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let status = config
+        .client()
+        .context(NewClient)?
+        .get_transaction_status(&sign::KeyPair::from(config.private_key).public(), sequence)
+        .await
+        .context(ClientError)?;
+
+    match status {
+        TransactionStatus::Unknown => println!("unknown"),
+        TransactionStatus::Pending => println!("pending"),
+        TransactionStatus::Delivered => println!("delivered"),
+        TransactionStatus::Rejected(reason) => println!("rejected: {}", reason),
+    }
+
+    Ok(())
+}
+
+async fn get_rejected_transactions() -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let rejections = config
+        .client()
+        .context(NewClient)?
+        .get_rejected_transactions(&sign::KeyPair::from(config.private_key).public())
+        .await
+        .context(ClientError)?;
+
+    for tx in rejections {
+        let recipients = tx
+            .outputs
+            .iter()
+            .map(|output| format!("{}¤ to {}", output.amount, output.recipient))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        println!(
+            "{}: {} sequence {} send {}: {}",
+            tx.timestamp, tx.sender, tx.sequence, recipients, tx.message,
+        );
+    }
+
+    Ok(())
+}
+
+async fn subscribe_transactions() -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let mut transactions = Box::pin(
+        config
+            .client()
+            .context(NewClient)?
+            .subscribe_transactions()
+            .await
+            .context(ClientError)?,
+    );
+
+    while let Some(tx) = transactions.next().await {
+        print_transaction(&tx.context(ClientError)?);
+    }
+
+    Ok(())
+}
+
+fn print_transaction(tx: &FullTransaction) {
+    match &tx.memo {
+        None => println!(
+            "{}: {} send {}¤ to {}",
+            tx.timestamp, tx.sender, tx.amount, tx.recipient,
+        ),
+        Some(memo) => println!(
+            "{}: {} send {}¤ to {}: {}",
+            tx.timestamp, tx.sender, tx.amount, tx.recipient, memo,
+        ),
+    }
+}
+
+async fn register_name(sequence: sieve::Sequence, name: String) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    config
+        .client()
+        .context(NewClient)?
+        .register_name(&sign::KeyPair::from(config.private_key), sequence, name)
+        .await
+        .context(ClientError)
+}
+
+async fn resolve_name(name: String) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let public_key = config
+        .client()
+        .context(NewClient)?
+        .resolve_name(name)
+        .await
+        .context(ClientError)?;
+
+    println!("{}", public_key);
+
+    Ok(())
+}
+
+async fn set_sequence_window(
+    sequence: sieve::Sequence,
+    size: sieve::Sequence,
+) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    config
+        .client()
+        .context(NewClient)?
+        .set_sequence_window(&sign::KeyPair::from(config.private_key), sequence, size)
+        .await
+        .context(ClientError)
+}
+
+async fn mint(
+    sequence: sieve::Sequence,
+    recipient: sign::PublicKey,
+    amount: Amount,
+    asset: Option<AssetId>,
+) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    config
+        .client()
+        .context(NewClient)?
+        .mint_asset(
+            &sign::KeyPair::from(config.private_key),
+            sequence,
+            recipient,
+            amount,
+            asset,
+        )
+        .await
+        .context(ClientError)
+}
+
+async fn register_asset(
+    sequence: sieve::Sequence,
+    id: AssetId,
+    name: String,
+) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    config
+        .client()
+        .context(NewClient)?
+        .register_asset(
+            &sign::KeyPair::from(config.private_key),
+            sequence,
+            Asset { id, name },
+        )
+        .await
+        .context(ClientError)
+}
+
+async fn get_assets() -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let assets = config
+        .client()
+        .context(NewClient)?
+        .get_assets()
+        .await
+        .context(ClientError)?;
+
+    for asset in assets {
+        println!("{}: {}", asset.id, asset.name);
+    }
+
+    Ok(())
+}
+
+async fn set_log_level(level: String, target: Option<String>) -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    config
+        .client()
+        .context(NewClient)?
+        .set_log_level(
+            &sign::KeyPair::from(config.private_key),
+            &level,
+            target.as_deref(),
+        )
+        .await
+        .context(ClientError)
+}
+
+async fn get_node_info() -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let info = config
+        .client()
+        .context(NewClient)?
+        .get_node_info()
+        .await
+        .context(ClientError)?;
+
+    println!("version: {}", info.version);
+    println!("chain id: {}", info.chain_id);
+    println!("protocol: {} to {}", info.protocol.min, info.protocol.max);
+    match info.fees {
+        None => println!("fees: none"),
+        Some(fees) => println!(
+            "fees: {}¤ + {} basis points of the amount",
+            fees.flat, fees.basis_points
+        ),
+    }
+    if info.follower {
+        println!("follower: refuses writes");
+    }
+
+    Ok(())
+}
+
+async fn get_network_info() -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let info = config
+        .client()
+        .context(NewClient)?
+        .get_network_info()
+        .await
+        .context(ClientError)?;
+
+    println!("node: {} ({})", info.node, info.network_public_key);
+    for peer in &info.peers {
+        let status = match peer.answering {
+            None => "unknown",
+            Some(true) => "answering",
+            Some(false) => "unreachable",
+        };
+        match peer.last_seen {
+            None => println!("peer {}: {}", peer.address, status),
+            Some(last_seen) => {
+                println!("peer {}: {}, last seen {}", peer.address, status, last_seen)
+            }
+        }
+    }
+    println!(
+        "echo threshold: {} of {}",
+        info.broadcast.echo_threshold, info.broadcast.echo_sample_size
+    );
+    println!(
+        "ready threshold: {} of {}",
+        info.broadcast.ready_threshold, info.broadcast.ready_sample_size
+    );
+
+    Ok(())
+}
+
+async fn get_peer_stats() -> Result<(), CommandError> {
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    let peers = config
+        .client()
+        .context(NewClient)?
+        .get_peer_stats()
+        .await
+        .context(ClientError)?;
+
+    for peer in peers {
+        println!(
+            "peer {}: {} of {} checks answered, {} bytes sent, {} received",
+            peer.address,
+            peer.messages_received,
+            peer.messages_sent,
+            peer.bytes_sent,
+            peer.bytes_received,
+        );
+        if let (Some(last), Some(min), Some(mean), Some(max)) =
+            (peer.rtt_last, peer.rtt_min, peer.rtt_mean, peer.rtt_max)
+        {
             println!(
-                "{}: {} send {}¤ to {}",
-                tx.timestamp, tx.sender, tx.amount, tx.recipient,
-            )
-        });
+                "  round trip: last {:?}, min {:?}, mean {:?}, max {:?}",
+                last, min, mean, max
+            );
+        }
+    }
 
     Ok(())
 }
 
+#[cfg(feature = "parquet-export")]
+async fn export_parquet(directory: PathBuf) -> Result<(), CommandError> {
+    use futures::TryStreamExt;
+
+    let config = config::from_reader(stdin()).context(ReadConfig)?;
+
+    // the whole history, not only the node's latest transactions
+    let transactions = config
+        .client()
+        .context(NewClient)?
+        .export_history()
+        .await
+        .context(ClientError)?
+        .try_collect::<Vec<_>>()
+        .await
+        .context(ClientError)?;
+
+    let name = chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f").to_string();
+
+    export::to_parquet(&directory, &name, &transactions).context(Export)
+}
+
 #[tokio::main(flavor = "multi_thread")]
 async fn main() {
     let ret = match Commands::from_args() {
         Commands::Config(cmd) => config(cmd).context(Config),
+        Commands::Keygen { rpc_address } => {
+            config(CommandsConfig::New { rpc_address }).context(Config)
+        }
+        Commands::Send {
+            recipient,
+            amount,
+            memo,
+            fee,
+            asset,
+        } => send(recipient, amount, memo, fee, asset)
+            .await
+            .context(Send),
         Commands::SendAsset {
             sequence,
             recipient,
             amount,
-        } => send_asset(sequence, recipient, amount)
+            memo,
+            fee,
+            asset,
+        } => send_asset(sequence, recipient, amount, memo, fee, asset)
             .await
             .context(SendAsset),
-        Commands::GetBalance => get_balance().await.context(GetBalance),
+        Commands::SignAsset {
+            sequence,
+            recipient,
+            amount,
+            memo,
+            fee,
+            asset,
+        } => sign_asset(sequence, recipient, amount, memo, fee, asset).context(SignAsset),
+        Commands::Import { files } => import(files).await.context(Import),
+        Commands::GetBalance { account, asset } => {
+            get_balance(account, asset).await.context(GetBalance)
+        }
         Commands::GetLastSequence => get_last_sequence().await.context(GetLastSequence),
         Commands::GetLatestTransactions => get_latest_transactions()
             .await
             .context(GetLatestTransactions),
+        Commands::GetAccountHistory { cursor, limit } => get_account_history(cursor, limit)
+            .await
+            .context(GetAccountHistory),
+        Commands::Export { format } => export_history(format).await.context(ExportHistory),
+        Commands::GetTransactionStatus { sequence } => get_transaction_status(sequence)
+            .await
+            .context(GetTransactionStatus),
+        Commands::GetRejectedTransactions => get_rejected_transactions()
+            .await
+            .context(GetRejectedTransactions),
+        Commands::SubscribeTransactions => subscribe_transactions()
+            .await
+            .context(SubscribeTransactions),
+        Commands::RegisterName { sequence, name } => {
+            register_name(sequence, name).await.context(RegisterName)
+        }
+        Commands::ResolveName { name } => resolve_name(name).await.context(ResolveName),
+        Commands::SetSequenceWindow { sequence, size } => set_sequence_window(sequence, size)
+            .await
+            .context(SetSequenceWindow),
+        Commands::Mint {
+            sequence,
+            recipient,
+            amount,
+            asset,
+        } => mint(sequence, recipient, amount, asset).await.context(Mint),
+        Commands::RegisterAsset { sequence, id, name } => register_asset(sequence, id, name)
+            .await
+            .context(RegisterAsset),
+        Commands::GetAssets => get_assets().await.context(GetAssets),
+        Commands::SetLogLevel { level, target } => {
+            set_log_level(level, target).await.context(SetLogLevel)
+        }
+        Commands::GetNodeInfo => get_node_info().await.context(GetNodeInfo),
+        Commands::GetNetworkInfo => get_network_info().await.context(GetNetworkInfo),
+        Commands::GetPeerStats => get_peer_stats().await.context(GetPeerStats),
+        #[cfg(feature = "parquet-export")]
+        Commands::ExportParquet { directory } => {
+            export_parquet(directory).await.context(ExportParquet)
+        }
     };
 
     if let Err(err) = ret {
@@ -0,0 +1,353 @@
+use std::{
+    io::{self, stdout, Write},
+    str::FromStr,
+    time::Duration,
+};
+
+use at2_node::{
+    api::At2Api,
+    client::{self, Client},
+    Amount,
+};
+use drop::crypto::sign;
+use http::Uri;
+use serde::Serialize;
+use snafu::{ensure, ResultExt, Snafu};
+use structopt::StructOpt;
+use tokio::time::{sleep, sleep_until, Instant};
+
+/// Balance of a new account, the only funds used by the wallets
+const INITIAL_BALANCE: Amount = 10;
+const AMOUNT: Amount = 1;
+/// Share of the transfers going to the hot account, in percent
+const HOT_SHARE: u64 = 90;
+
+const CONFIRM_TICK: Duration = Duration::from_millis(10);
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy)]
+enum Profile {
+    /// Random senders and recipients, at a steady rate
+    Uniform,
+    /// Most transfers go to the first wallet, at a steady rate
+    HotAccount,
+    /// Random senders and recipients, sent by bursts
+    Bursty,
+}
+
+impl FromStr for Profile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Self::Uniform),
+            "hot-account" => Ok(Self::HotAccount),
+            "bursty" => Ok(Self::Bursty),
+            _ => UnknownProfile { name: s }.fail(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Output {
+    Csv,
+    Json,
+}
+
+impl FromStr for Output {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => UnknownOutput { name: s }.fail(),
+        }
+    }
+}
+
+/// Send transfers between wallets and report how long each took to be processed
+///
+/// Each wallet only has one transaction in flight, so that its sequences are
+/// always processed in order; concurrency comes from the number of wallets.
+#[derive(Debug, StructOpt)]
+struct Options {
+    rpc_address: Uri,
+    #[structopt(long, default_value = "10")]
+    wallets: usize,
+    /// Total number of transfers
+    #[structopt(long, default_value = "50")]
+    count: usize,
+    /// One of uniform, hot-account or bursty
+    #[structopt(long, default_value = "uniform")]
+    profile: Profile,
+    /// Transfers per second, when not bursty
+    #[structopt(long, default_value = "50")]
+    rate: u64,
+    #[structopt(long, default_value = "20")]
+    burst_size: usize,
+    /// Delay between bursts, in milliseconds
+    #[structopt(long, default_value = "1000")]
+    burst_delay: u64,
+    /// One of csv or json
+    #[structopt(long, default_value = "csv")]
+    output: Output,
+    /// Seed of the workload, the wallets are always new
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+}
+
+#[derive(Debug, Snafu)]
+enum Error {
+    #[snafu(display("unknown profile: {}", name))]
+    UnknownProfile { name: String },
+    #[snafu(display("unknown output: {}", name))]
+    UnknownOutput { name: String },
+    #[snafu(display("need at least two wallets"))]
+    TooFewWallets,
+    #[snafu(display("wallets can only send {} transfers, add some", max))]
+    NotEnoughFunds { max: Amount },
+    #[snafu(display("client: {}", source))]
+    ClientError { source: client::Error },
+    #[snafu(display("write results: {}", source))]
+    WriteResults { source: io::Error },
+    #[snafu(display("encode results: {}", source))]
+    Encode { source: serde_json::Error },
+}
+
+/// Transfer to send, at a given time after the start
+struct Planned {
+    recipient: usize,
+    at: Duration,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Ok,
+    Timeout,
+}
+
+#[derive(Serialize)]
+struct Record {
+    wallet: usize,
+    sequence: sieve::Sequence,
+    scheduled_ms: u128,
+    latency_ms: u128,
+    status: Status,
+}
+
+/// [xorshift64*](https://en.wikipedia.org/wiki/Xorshift#xorshift*), enough to shape a workload
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // state must not be zero
+        Self(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Queue of transfers for each wallet
+///
+/// A wallet only spends its initial balance, so that no transfer depends on
+/// another wallet's one to be processed first.
+fn plan(options: &Options) -> Result<Vec<Vec<Planned>>, Error> {
+    ensure!(options.wallets >= 2, TooFewWallets);
+    let max = options.wallets as Amount * (INITIAL_BALANCE / AMOUNT);
+    ensure!(options.count as Amount <= max, NotEnoughFunds { max });
+
+    let mut rng = Rng::new(options.seed);
+    let mut funds = vec![INITIAL_BALANCE; options.wallets];
+    let mut queues = (0..options.wallets).map(|_| Vec::new()).collect::<Vec<_>>();
+
+    for index in 0..options.count {
+        let funded = (0..options.wallets)
+            .filter(|wallet| funds[*wallet] >= AMOUNT)
+            .collect::<Vec<_>>();
+        let sender = funded[rng.below(funded.len())];
+
+        let recipient = match options.profile {
+            Profile::HotAccount if sender != 0 && rng.next_u64() % 100 < HOT_SHARE => 0,
+            _ => (sender + 1 + rng.below(options.wallets - 1)) % options.wallets,
+        };
+
+        let at = match options.profile {
+            Profile::Uniform | Profile::HotAccount => {
+                Duration::from_secs_f64(index as f64 / options.rate.max(1) as f64)
+            }
+            Profile::Bursty => Duration::from_millis(
+                (index / options.burst_size.max(1)) as u64 * options.burst_delay,
+            ),
+        };
+
+        funds[sender] -= AMOUNT;
+        queues[sender].push(Planned { recipient, at });
+    }
+
+    Ok(queues)
+}
+
+async fn run_wallet(
+    mut client: Client,
+    wallet: usize,
+    keypair: sign::KeyPair,
+    recipients: Vec<sign::PublicKey>,
+    queue: Vec<Planned>,
+    start: Instant,
+) -> Result<Vec<Record>, Error> {
+    let mut records = Vec::with_capacity(queue.len());
+
+    for (sequence, planned) in (1..).zip(queue) {
+        sleep_until(start + planned.at).await;
+        let sent = Instant::now();
+
+        client
+            .send_asset(&keypair, sequence, recipients[planned.recipient], AMOUNT)
+            .await
+            .context(ClientError)?;
+
+        let status = loop {
+            let last_sequence = client
+                .get_last_sequence(&keypair.public())
+                .await
+                .context(ClientError)?;
+
+            if last_sequence >= sequence {
+                break Status::Ok;
+            }
+            if sent.elapsed() > CONFIRM_TIMEOUT {
+                break Status::Timeout;
+            }
+            sleep(CONFIRM_TICK).await;
+        };
+
+        records.push(Record {
+            wallet,
+            sequence,
+            scheduled_ms: planned.at.as_millis(),
+            latency_ms: sent.elapsed().as_millis(),
+            status,
+        });
+
+        // following sequences would never be processed
+        if status != Status::Ok {
+            break;
+        }
+    }
+
+    Ok(records)
+}
+
+fn write_results(output: Output, records: &[Record], mut writer: impl Write) -> Result<(), Error> {
+    match output {
+        Output::Json => serde_json::to_writer(&mut writer, records).context(Encode)?,
+        Output::Csv => {
+            writeln!(writer, "wallet,sequence,scheduled_ms,latency_ms,status")
+                .context(WriteResults)?;
+            for record in records {
+                writeln!(
+                    writer,
+                    "{},{},{},{},{}",
+                    record.wallet,
+                    record.sequence,
+                    record.scheduled_ms,
+                    record.latency_ms,
+                    match record.status {
+                        Status::Ok => "ok",
+                        Status::Timeout => "timeout",
+                    }
+                )
+                .context(WriteResults)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn summarize(planned: usize, records: &[Record], elapsed: Duration) {
+    let mut latencies = records
+        .iter()
+        .filter(|record| record.status == Status::Ok)
+        .map(|record| record.latency_ms)
+        .collect::<Vec<_>>();
+    latencies.sort_unstable();
+
+    let percentile = |p: usize| {
+        latencies
+            .get((latencies.len() * p / 100).min(latencies.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or_default()
+    };
+
+    eprintln!(
+        "{} planned, {} processed in {:.1}s ({:.1} tx/s), latency p50 {}ms p99 {}ms",
+        planned,
+        latencies.len(),
+        elapsed.as_secs_f64(),
+        latencies.len() as f64 / elapsed.as_secs_f64(),
+        percentile(50),
+        percentile(99),
+    );
+}
+
+async fn run(options: Options) -> Result<(), Error> {
+    let queues = plan(&options)?;
+
+    let client = Client::new(options.rpc_address).context(ClientError)?;
+    let keypairs = (0..options.wallets)
+        .map(|_| sign::KeyPair::random())
+        .collect::<Vec<_>>();
+    let recipients = keypairs
+        .iter()
+        .map(|keypair| keypair.public())
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let handles = keypairs
+        .into_iter()
+        .zip(queues)
+        .enumerate()
+        .map(|(wallet, (keypair, queue))| {
+            tokio::spawn(run_wallet(
+                client.clone(),
+                wallet,
+                keypair,
+                recipients.clone(),
+                queue,
+                start,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let mut records = Vec::with_capacity(options.count);
+    for handle in handles {
+        records.extend(handle.await.expect("wallet panicked")?);
+    }
+    let elapsed = start.elapsed();
+    records.sort_by_key(|record| (record.scheduled_ms, record.wallet));
+
+    write_results(options.output, &records, stdout())?;
+    summarize(options.count, &records, elapsed);
+
+    Ok(())
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    if let Err(err) = run(Options::from_args()).await {
+        eprintln!("error running loadgen: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -1,23 +1,107 @@
-use std::{io, process};
+use std::{
+    env, fs,
+    future::Future,
+    io,
+    net::SocketAddr,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
-use at2_node::proto;
+use at2_node::{
+    address::{self, Address},
+    client::Client,
+    proto,
+    server::{
+        config, dashboard, fingerprint, health, json, jsonrpc, keystore, request_id::RequestIds,
+        rpc, vault, websocket,
+    },
+    telemetry,
+    version::ProtocolRange,
+};
 use drop::crypto::{key::exchange, sign};
-use snafu::{OptionExt, ResultExt, Snafu};
+use futures::FutureExt;
+use hex::{FromHex, ToHex};
+use opentelemetry::{sdk, trace::TraceError, KeyValue};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
 use structopt::StructOpt;
-use tokio::net;
-use tonic::transport::Server;
-use tracing::{subscriber, Level};
-use tracing_fmt::FmtSubscriber;
+use tokio::{net, runtime, signal, time::sleep};
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tracing::{info, subscriber, warn, Level, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, reload, EnvFilter};
 
-mod accounts;
-mod config;
-mod recent_transactions;
-mod rpc;
+/// How long in-flight requests have to finish once asked to stop
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+/// How long a peer has to tell its version before being considered down
+const PEER_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Environment variable holding the passphrase of encrypted keys
+const PASSPHRASE_ENV: &str = "AT2_KEYS_PASSPHRASE";
+/// Environment variables replacing the `addresses` of the config, if set
+const NODE_ADDRESS_ENV: &str = "AT2_NODE_ADDRESS";
+const ADVERTISE_ADDRESS_ENV: &str = "AT2_ADVERTISE_ADDRESS";
+const RPC_ADDRESS_ENV: &str = "AT2_RPC_ADDRESS";
+const JSON_ADDRESS_ENV: &str = "AT2_JSON_ADDRESS";
+const JSONRPC_ADDRESS_ENV: &str = "AT2_JSONRPC_ADDRESS";
+const DASHBOARD_ADDRESS_ENV: &str = "AT2_DASHBOARD_ADDRESS";
+const WEBSOCKET_ADDRESS_ENV: &str = "AT2_WEBSOCKET_ADDRESS";
 
 #[derive(Debug, StructOpt)]
 enum Commands {
     Config(CommandsConfig),
-    Run,
+    Run {
+        /// File holding the config, `-` for stdin
+        #[structopt(long = "config", default_value = "-")]
+        config_path: PathBuf,
+        /// How to write logs, either `text` or `json`
+        #[structopt(long, env = "AT2_LOG_FORMAT", default_value = "text")]
+        log_format: LogFormat,
+        /// Most verbose level to log, such as `info` or `debug`
+        #[structopt(long, env = "AT2_LOG_LEVEL", default_value = "debug")]
+        log_level: Level,
+        /// File holding the passphrase of encrypted keys, instead of $AT2_KEYS_PASSPHRASE
+        #[structopt(long)]
+        passphrase_file: Option<PathBuf>,
+        /// Threads running the tasks, instead of `runtime.worker_threads`
+        #[structopt(long)]
+        worker_threads: Option<NonZeroUsize>,
+        /// Threads running blocking calls, instead of `runtime.max_blocking_threads`
+        #[structopt(long)]
+        max_blocking_threads: Option<NonZeroUsize>,
+        /// Workers of the broadcast processor, instead of `protocol.processor_workers`
+        #[structopt(long)]
+        processor_workers: Option<NonZeroUsize>,
+        /// Only serve reads, refusing writes with the RPC of a node accepting them
+        #[structopt(long)]
+        follower: bool,
+        /// RPC given to refused writers, instead of the first peer with one
+        #[structopt(long, requires = "follower")]
+        validator: Option<String>,
+    },
+}
+
+/// How logs are written
+#[derive(Debug, Clone, Copy)]
+enum LogFormat {
+    /// One human readable line per event
+    Text,
+    /// One JSON object per line, with the fields of the enclosing spans
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown log format: {}", s)),
+        }
+    }
 }
 
 #[derive(Debug, StructOpt)]
@@ -25,8 +109,73 @@ enum CommandsConfig {
     New {
         node_address: String,
         rpc_address: String,
+        /// Address given to the peers, if not the one listened on, such as behind a NAT
+        #[structopt(long)]
+        advertise_address: Option<String>,
+        #[structopt(long)]
+        json_address: Option<String>,
+        #[structopt(long)]
+        jsonrpc_address: Option<String>,
+        #[structopt(long)]
+        dashboard_address: Option<String>,
+        #[structopt(long)]
+        websocket_address: Option<String>,
+    },
+    GetNode {
+        /// File holding the config, `-` for stdin
+        #[structopt(long = "config", default_value = "-")]
+        config_path: PathBuf,
+    },
+    /// Give an account a starting balance, editing the config from stdin
+    AddGenesis {
+        #[structopt(parse(try_from_str = parse_public_key))]
+        public_key: sign::PublicKey,
+        amount: u64,
+    },
+    /// Print the public keys of the node, with their fingerprints
+    ShowIdentity,
+    /// Add a peer, editing the config from stdin
+    AddNode {
+        address: String,
+        /// Other addresses of the peer, tried in order if the first is unreachable
+        #[structopt(long = "fallback-address")]
+        fallback_addresses: Vec<String>,
+        /// Hex encoded network public key of the peer
+        #[structopt(parse(try_from_str = parse_network_key))]
+        public_key: exchange::PublicKey,
+        /// Where the peer serves its RPC, to check its version
+        #[structopt(long)]
+        rpc: Option<String>,
     },
-    GetNode,
+    /// Check that the addresses resolve and that the keys parse
+    Validate,
+    /// Upgrade the config to the format of this binary, editing it from stdin
+    Migrate,
+    /// Encrypt the inline keys with a passphrase, editing the config from stdin
+    Encrypt {
+        /// File holding the passphrase, instead of $AT2_KEYS_PASSPHRASE
+        #[structopt(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// Decrypt the keys back inline, editing the config from stdin
+    Decrypt {
+        /// File holding the passphrase, instead of $AT2_KEYS_PASSPHRASE
+        #[structopt(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+}
+
+fn parse_network_key(src: &str) -> Result<exchange::PublicKey, String> {
+    exchange::PublicKey::from_hex(src).map_err(|_| format!("invalid network key: {}", src))
+}
+
+/// Parse a public key, either hex encoded or as an [`Address`]
+fn parse_public_key(src: &str) -> Result<sign::PublicKey, address::Error> {
+    if let Ok(public_key) = sign::PublicKey::from_hex(src) {
+        return Ok(public_key);
+    }
+
+    src.parse::<Address>().map(Into::into)
 }
 
 #[derive(Debug, Snafu)]
@@ -35,10 +184,30 @@ enum RunError {
     Logging {
         source: tracing::dispatcher::SetGlobalDefaultError,
     },
+    #[snafu(display("telemetry: {}", source))]
+    Telemetry { source: TraceError },
     #[snafu(display("service: {}", source))]
     Service { source: rpc::Error },
     #[snafu(display("rpc: {}", source))]
     Rpc { source: tonic::transport::Error },
+    #[snafu(display("tls: read {}: {}", path.display(), source))]
+    ReadTls { path: PathBuf, source: io::Error },
+    #[snafu(display("tls: {}", source))]
+    Tls { source: tonic::transport::Error },
+    #[snafu(display("reflection: {}", source))]
+    Reflection {
+        source: tonic_reflection::server::Error,
+    },
+    #[snafu(display("json gateway: {}", source))]
+    Json { source: json::Error },
+    #[snafu(display("json-rpc: {}", source))]
+    JsonRpc { source: jsonrpc::Error },
+    #[snafu(display("dashboard: {}", source))]
+    Dashboard { source: dashboard::Error },
+    #[snafu(display("websocket: {}", source))]
+    WebSocket { source: websocket::Error },
+    #[snafu(display("vault: {}", source))]
+    WatchVault { source: vault::Error },
 }
 
 #[derive(Debug, Snafu)]
@@ -49,88 +218,792 @@ enum Error {
     UnknownHost { source: io::Error },
     #[snafu(display("config: no host resolved"))]
     NoHost,
+    #[snafu(display("config: fetch keys: {}", source))]
+    FetchKeys { source: vault::Error },
+    #[snafu(display("config: keys: {}", source))]
+    Keystore { source: keystore::Error },
+    #[snafu(display("config: only inline keys can be encrypted"))]
+    NotInline,
+    #[snafu(display("config: keys aren't encrypted"))]
+    NotEncrypted,
+    #[snafu(display("passphrase: read {}: {}", path.display(), source))]
+    ReadPassphrase { path: PathBuf, source: io::Error },
+    #[snafu(display("passphrase: none given, by file or in ${}", PASSPHRASE_ENV))]
+    NoPassphrase,
+    #[snafu(display(
+        "peer {} speaks protocol {} to {}, we speak {} to {}",
+        address,
+        min,
+        max,
+        ProtocolRange::CURRENT.min,
+        ProtocolRange::CURRENT.max
+    ))]
+    IncompatiblePeer { address: String, min: u32, max: u32 },
+    #[snafu(display("config: {} problems found", count))]
+    Invalid { count: usize },
+    #[snafu(display(
+        "config: {} address: authenticated_reads only covers the gRPC endpoint",
+        name
+    ))]
+    UnauthenticatedGateway { name: &'static str },
+    #[snafu(display("config: sync: peers refuse snapshots when authenticated_reads is set"))]
+    AuthenticatedSync,
+    #[snafu(display("runtime: {}", source))]
+    Runtime { source: io::Error },
     #[snafu(display("run server: {}", source))]
     Run { source: RunError },
 }
 
-fn config(cmd: CommandsConfig) -> Result<(), Error> {
+/// Read the passphrase from `file`, or from the environment if not given
+fn passphrase(file: Option<&Path>) -> Result<Vec<u8>, Error> {
+    let mut passphrase = match file {
+        Some(path) => fs::read(path).context(ReadPassphrase { path })?,
+        None => env::var(PASSPHRASE_ENV)
+            .ok()
+            .context(NoPassphrase)?
+            .into_bytes(),
+    };
+
+    // as left by most editors
+    if passphrase.ends_with(b"\n") {
+        passphrase.pop();
+    }
+
+    Ok(passphrase)
+}
+
+/// Resolve the keys, and the Vault to watch if they come from there
+///
+/// Encrypted keys are decrypted with the passphrase in `passphrase_file`, or
+/// in the environment.
+async fn keys(
+    source: config::ConfigKeysSource,
+    passphrase_file: Option<&Path>,
+) -> Result<
+    (
+        config::ConfigKeys,
+        Option<(config::ConfigVault, serde_json::Value)>,
+    ),
+    Error,
+> {
+    match source {
+        config::ConfigKeysSource::Inline(keys) => Ok((keys, None)),
+        config::ConfigKeysSource::Vault { vault } => {
+            let fetched = vault::fetch(&vault).await.context(FetchKeys)?;
+
+            Ok((fetched.keys, Some((vault, fetched.raw))))
+        }
+        config::ConfigKeysSource::Encrypted { encrypted } => {
+            let keys =
+                keystore::decrypt(&encrypted, &passphrase(passphrase_file)?).context(Keystore)?;
+
+            Ok((keys, None))
+        }
+    }
+}
+
+async fn config(cmd: CommandsConfig) -> Result<(), Error> {
     match cmd {
         CommandsConfig::New {
             node_address,
             rpc_address,
+            advertise_address,
+            json_address,
+            jsonrpc_address,
+            dashboard_address,
+            websocket_address,
         } => config::Config {
+            version: config::CONFIG_VERSION,
+            chain_id: at2_node::interchange::DEFAULT_CHAIN_ID.to_owned(),
             addresses: config::ConfigAddresses {
-                node: node_address,
                 rpc: rpc_address,
+                json: json_address,
+                jsonrpc: jsonrpc_address,
+                dashboard: dashboard_address,
+                websocket: websocket_address,
+                node: match advertise_address {
+                    None => config::ConfigNodeAddress::Same(node_address),
+                    Some(advertise) => config::ConfigNodeAddress::Split {
+                        bind: node_address,
+                        advertise,
+                    },
+                },
             },
-            keys: config::ConfigKeys {
+            tls: None,
+            keys: config::ConfigKeysSource::Inline(config::ConfigKeys {
                 sign: sign::KeyPair::random().private(),
                 network: exchange::KeyPair::random().secret().to_owned(),
-            },
+            }),
+            protocol: Default::default(),
+            runtime: Default::default(),
+            recent_transactions: Default::default(),
+            checkpoints: Default::default(),
+            sync: None,
+            events: None,
+            telemetry: None,
+            bridge: None,
+            rate_limit: None,
+            fees: None,
+            validation: Default::default(),
+            storage_path: None,
+            admins: vec![],
+            reflection: false,
+            authenticated_reads: false,
+            open_network: false,
+            max_body_size: config::DEFAULT_MAX_BODY_SIZE,
+            genesis: Default::default(),
             nodes: vec![],
         }
         .to_writer(io::stdout())
         .context(Config),
-        CommandsConfig::GetNode => {
-            let config = config::from_reader(io::stdin()).context(Config)?;
+        CommandsConfig::GetNode { config_path } => {
+            let mut config = config::from_path(&config_path).context(Config)?;
+            override_addresses(&mut config.addresses);
+            let (keys, _) = keys(config.keys, None).await?;
 
             config::Nodes {
                 nodes: vec![config::Node {
-                    address: config.addresses.node,
-                    public_key: exchange::KeyPair::new(config.keys.network)
-                        .public()
-                        .to_owned(),
+                    address: config.addresses.node.advertise().to_owned(),
+                    fallback_addresses: Vec::new(),
+                    public_key: exchange::KeyPair::new(keys.network).public().to_owned(),
+                    rpc: Some(config.addresses.rpc),
                 }],
             }
             .to_writer(io::stdout())
             .context(Config)
         }
+        CommandsConfig::AddGenesis { public_key, amount } => {
+            let mut config = config::from_reader(io::stdin()).context(Config)?;
+
+            config.genesis.insert(public_key.to_string(), amount);
+
+            config.to_writer(io::stdout()).context(Config)
+        }
+        CommandsConfig::ShowIdentity => {
+            let config = config::from_reader(io::stdin()).context(Config)?;
+            let (keys, _) = keys(config.keys, None).await?;
+
+            let sign = sign::KeyPair::from(keys.sign).public();
+            let network = exchange::KeyPair::new(keys.network)
+                .public()
+                .encode_hex::<String>();
+
+            println!("sign key: {}", sign);
+            println!("sign fingerprint: {}", fingerprint(&sign.to_string()));
+            println!("address: {}", Address::from(sign));
+            println!("network key: {}", network);
+            println!("network fingerprint: {}", fingerprint(&network));
+
+            Ok(())
+        }
+        CommandsConfig::AddNode {
+            address,
+            fallback_addresses,
+            public_key,
+            rpc,
+        } => {
+            let mut config = config::from_reader(io::stdin()).context(Config)?;
+
+            // a peer with the same key was moved
+            config.nodes.retain(|node| node.public_key != public_key);
+            config.nodes.push(config::Node {
+                address,
+                fallback_addresses,
+                public_key,
+                rpc,
+            });
+
+            config.to_writer(io::stdout()).context(Config)
+        }
+        CommandsConfig::Validate => validate().await,
+        CommandsConfig::Migrate => config::from_reader(io::stdin())
+            .context(Config)?
+            .to_writer(io::stdout())
+            .context(Config),
+        CommandsConfig::Encrypt { passphrase_file } => {
+            let mut config = config::from_reader(io::stdin()).context(Config)?;
+
+            let keys = match config.keys {
+                config::ConfigKeysSource::Inline(keys) => keys,
+                _ => return NotInline.fail(),
+            };
+            let encrypted = keystore::encrypt(&keys, &passphrase(passphrase_file.as_deref())?)
+                .context(Keystore)?;
+            config.keys = config::ConfigKeysSource::Encrypted { encrypted };
+
+            config.to_writer(io::stdout()).context(Config)
+        }
+        CommandsConfig::Decrypt { passphrase_file } => {
+            let mut config = config::from_reader(io::stdin()).context(Config)?;
+
+            ensure!(
+                matches!(config.keys, config::ConfigKeysSource::Encrypted { .. }),
+                NotEncrypted
+            );
+            let (keys, _) = keys(config.keys, passphrase_file.as_deref()).await?;
+            config.keys = config::ConfigKeysSource::Inline(keys);
+
+            config.to_writer(io::stdout()).context(Config)
+        }
+    }
+}
+
+/// Replace the `addresses` by the ones set in the environment
+///
+/// Only advertising another node address splits it from the one listened on.
+fn override_addresses(addresses: &mut config::ConfigAddresses) {
+    let var = |name| env::var(name).ok();
+
+    if let Some(address) = var(NODE_ADDRESS_ENV) {
+        addresses.node = config::ConfigNodeAddress::Same(address);
+    }
+    if let Some(advertise) = var(ADVERTISE_ADDRESS_ENV) {
+        addresses.node = config::ConfigNodeAddress::Split {
+            bind: addresses.node.bind().to_owned(),
+            advertise,
+        };
+    }
+    if let Some(address) = var(RPC_ADDRESS_ENV) {
+        addresses.rpc = address;
+    }
+    for (name, address) in [
+        (JSON_ADDRESS_ENV, &mut addresses.json),
+        (JSONRPC_ADDRESS_ENV, &mut addresses.jsonrpc),
+        (DASHBOARD_ADDRESS_ENV, &mut addresses.dashboard),
+        (WEBSOCKET_ADDRESS_ENV, &mut addresses.websocket),
+    ] {
+        if let Some(value) = var(name) {
+            *address = Some(value);
+        }
     }
 }
 
-async fn run() -> Result<(), Error> {
+/// Print every problem found in the config from stdin
+async fn validate() -> Result<(), Error> {
     let config = config::from_reader(io::stdin()).context(Config)?;
+    let mut problems = Vec::new();
+
+    if let Err(err) = keys(config.keys, None).await {
+        problems.push(err.to_string());
+    }
+
+    let addresses = &config.addresses;
+    let mut to_resolve = vec![
+        ("node", addresses.node.bind().to_owned()),
+        ("rpc", addresses.rpc.clone()),
+    ];
+    if let config::ConfigNodeAddress::Split { advertise, .. } = &addresses.node {
+        to_resolve.push(("node advertise", advertise.clone()));
+    }
+    to_resolve.extend(addresses.json.clone().map(|addr| ("json", addr)));
+    to_resolve.extend(addresses.jsonrpc.clone().map(|addr| ("jsonrpc", addr)));
+    to_resolve.extend(addresses.dashboard.clone().map(|addr| ("dashboard", addr)));
+    to_resolve.extend(addresses.websocket.clone().map(|addr| ("websocket", addr)));
+    for node in &config.nodes {
+        to_resolve.extend(node.addresses().map(|addr| ("peer", addr.clone())));
+        to_resolve.extend(node.rpc.as_ref().map(|addr| {
+            let authority = addr.rsplit("://").next().unwrap_or(addr);
+            ("peer rpc", authority.trim_end_matches('/').to_owned())
+        }));
+    }
+    for (name, addr) in to_resolve {
+        if let Err(err) = lookup_host(addr.clone()).await {
+            problems.push(format!("{} address {}: {}", name, addr, err));
+        }
+    }
+
+    for name in unauthenticated_gateways(&config) {
+        problems.push(Error::UnauthenticatedGateway { name }.to_string());
+    }
+    if config.authenticated_reads && config.sync.is_some() {
+        problems.push(Error::AuthenticatedSync.to_string());
+    }
+
+    if let Some(sync) = &config.sync {
+        let with_rpc = config
+            .nodes
+            .iter()
+            .filter(|node| node.rpc.is_some())
+            .count();
+        if sync.quorum.get() > with_rpc {
+            problems.push(format!(
+                "sync: quorum of {} but only {} peers have an rpc address",
+                sync.quorum, with_rpc
+            ));
+        }
+    }
+
+    if let Some(tls) = config.tls {
+        if let Err(err) = server_tls(tls) {
+            problems.push(err.to_string());
+        }
+    }
+
+    let mut to_parse = config
+        .genesis
+        .keys()
+        .map(|key| ("genesis", key.clone()))
+        .chain(config.admins.iter().map(|key| ("admin", key.clone())))
+        .collect::<Vec<_>>();
+    if let Some(fees) = &config.fees {
+        to_parse.push(("fees account", fees.account.clone()));
+    }
+    if let Some(bridge) = &config.bridge {
+        to_parse.push(("bridge lock", bridge.lock.clone()));
+        to_parse.extend(
+            bridge
+                .validators
+                .iter()
+                .map(|key| ("bridge validator", key.clone())),
+        );
+    }
+    for (name, key) in to_parse {
+        if sign::PublicKey::from_hex(&key).is_err() {
+            problems.push(format!("{} key {}: invalid", name, key));
+        }
+    }
+
+    problems.iter().for_each(|problem| eprintln!("{}", problem));
+    ensure!(
+        problems.is_empty(),
+        Invalid {
+            count: problems.len()
+        }
+    );
+
+    println!("config is valid");
+
+    Ok(())
+}
 
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::DEBUG)
-        .finish();
-    subscriber::set_global_default(subscriber)
-        .context(Logging)
-        .context(Run)?;
+/// Gateways configured along `authenticated_reads`, which they don't check
+fn unauthenticated_gateways(config: &config::Config) -> Vec<&'static str> {
+    if !config.authenticated_reads {
+        return Vec::new();
+    }
+
+    let addresses = &config.addresses;
+    vec![
+        ("json", &addresses.json),
+        ("jsonrpc", &addresses.jsonrpc),
+        ("dashboard", &addresses.dashboard),
+        ("websocket", &addresses.websocket),
+    ]
+    .into_iter()
+    .filter(|(_, addr)| addr.is_some())
+    .map(|(name, _)| name)
+    .collect()
+}
+
+/// Refuse to start alongside peers which can't understand us
+///
+/// Unreachable peers are only warned about, as they might not be started yet.
+async fn check_peers(nodes: &[config::Node]) -> Result<(), Error> {
+    for node in nodes {
+        let rpc = match node.rpc_uri() {
+            None => continue,
+            Some(rpc) => rpc,
+        };
+
+        let info = match rpc.parse() {
+            Err(err) => Err(err.to_string()),
+            Ok(uri) => match Client::builder()
+                .overall_timeout(PEER_CHECK_TIMEOUT)
+                .build(uri)
+            {
+                Err(err) => Err(err.to_string()),
+                Ok(mut client) => client.get_node_info().await.map_err(|err| err.to_string()),
+            },
+        };
 
+        match info {
+            Err(err) => warn!("unable to check version of peer {}: {}", node.address, err),
+            Ok(info) => {
+                if ProtocolRange::CURRENT.negotiate(&info.protocol).is_none() {
+                    return IncompatiblePeer {
+                        address: node.address.clone(),
+                        min: info.protocol.min,
+                        max: info.protocol.max,
+                    }
+                    .fail();
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve on the first Ctrl-C, or SIGTERM on unix and Ctrl-Break on windows
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(windows)]
+    let terminate = async {
+        signal::windows::ctrl_break()
+            .expect("install Ctrl-Break handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(any(unix, windows)))]
+    let terminate = futures::future::pending::<()>();
+
+    tokio::select! {
+        _ = signal::ctrl_c() => {},
+        _ = terminate => {},
+    }
+}
+
+/// Start logging from `level` on, returning how to change it later
+///
+/// The spans are also exported to the `telemetry` collector, if any.
+fn logging(
+    format: LogFormat,
+    level: Level,
+    telemetry: Option<config::ConfigTelemetry>,
+) -> Result<rpc::SetLogLevel, RunError> {
+    let builder = tracing_subscriber::fmt().with_env_filter(EnvFilter::new(level.to_string()));
+    let tracer = telemetry.map(tracer).transpose()?;
+
+    match format {
+        LogFormat::Text => {
+            let builder = builder.with_filter_reloading();
+            let handle = builder.reload_handle();
+            let exporter = tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
+            subscriber::set_global_default(builder.finish().with(exporter)).context(Logging)?;
+            Ok(set_log_level(level, handle))
+        }
+        LogFormat::Json => {
+            let builder = builder.json().with_span_list(true).with_filter_reloading();
+            let handle = builder.reload_handle();
+            let exporter = tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
+            subscriber::set_global_default(builder.finish().with(exporter)).context(Logging)?;
+            Ok(set_log_level(level, handle))
+        }
+    }
+}
+
+/// Export spans in batches to the collector of `config`
+fn tracer(config: config::ConfigTelemetry) -> Result<sdk::trace::Tracer, RunError> {
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(config.endpoint),
+        )
+        .with_trace_config(sdk::trace::config().with_resource(sdk::Resource::new(vec![
+            KeyValue::new("service.name", config.service_name),
+        ])))
+        .install_batch(opentelemetry::runtime::Tokio)
+        .context(Telemetry)
+}
+
+/// Replace the filter behind `handle`, other targets staying at `initial`
+fn set_log_level<S>(initial: Level, handle: reload::Handle<EnvFilter, S>) -> rpc::SetLogLevel
+where
+    S: Subscriber + Send + Sync + 'static,
+{
+    Arc::new(move |level, target| {
+        let directives = match target {
+            None => level.to_string(),
+            Some(target) => format!("{},{}={}", initial, target, level),
+        };
+        let filter = EnvFilter::try_new(directives).map_err(|err| err.to_string())?;
+
+        handle.reload(filter).map_err(|err| err.to_string())
+    })
+}
+
+/// Read the files of the TLS configuration
+fn server_tls(config: config::ConfigTls) -> Result<ServerTlsConfig, RunError> {
+    let read = |path: PathBuf| fs::read(&path).context(ReadTls { path });
+
+    let mut tls =
+        ServerTlsConfig::new().identity(Identity::from_pem(read(config.cert)?, read(config.key)?));
+    if let Some(client_ca) = config.client_ca {
+        tls = tls.client_ca_root(Certificate::from_pem(read(client_ca)?));
+    }
+
+    Ok(tls)
+}
+
+async fn run(
+    config: config::Config,
+    log_format: LogFormat,
+    log_level: Level,
+    passphrase_file: Option<PathBuf>,
+    follower: bool,
+    validator: Option<String>,
+) -> Result<(), Error> {
+    if let Some(name) = unauthenticated_gateways(&config).into_iter().next() {
+        return UnauthenticatedGateway { name }.fail();
+    }
+    ensure!(
+        !(config.authenticated_reads && config.sync.is_some()),
+        AuthenticatedSync
+    );
+
+    let (keys, watched) = keys(config.keys, passphrase_file.as_deref()).await?;
+
+    let set_log_level = logging(log_format, log_level, config.telemetry).context(Run)?;
+
+    check_peers(&config.nodes).await?;
+
+    let batch_delay = Duration::from_millis(config.protocol.batch_delay);
     let service = rpc::Service::new(
-        config.addresses.node,
-        exchange::KeyPair::new(config.keys.network),
-        config.nodes,
+        config.addresses.node.bind().to_owned(),
+        exchange::KeyPair::new(keys.network),
+        sign::KeyPair::from(keys.sign),
+        rpc::Options {
+            chain_id: config.chain_id,
+            advertised_addr: Some(config.addresses.node.advertise().to_owned()),
+            network: config.nodes,
+            protocol: config.protocol,
+            recent_transactions: config.recent_transactions,
+            checkpoints: config.checkpoints,
+            sync: config.sync,
+            events: config.events,
+            bridge: config.bridge,
+            rate_limit: config.rate_limit,
+            fees: config.fees,
+            validation: config.validation,
+            storage_path: config.storage_path,
+            admins: config.admins,
+            genesis: config.genesis,
+            authenticated_reads: config.authenticated_reads,
+            open_network: config.open_network,
+        },
     )
     .await
     .context(Service)
-    .context(Run)?;
+    .context(Run)?
+    .with_set_log_level(set_log_level);
+    let service = if follower {
+        info!("following, refusing writes");
+        service.as_follower(validator)
+    } else {
+        service
+    };
+
+    let shutdown = shutdown_signal().shared();
+
+    let json_server = match config.addresses.json {
+        None => None,
+        Some(addr) => Some(json::serve(
+            lookup_host(addr).await?,
+            service.clone(),
+            config.max_body_size,
+            shutdown.clone(),
+        )),
+    };
+    let jsonrpc_server = match config.addresses.jsonrpc {
+        None => None,
+        Some(addr) => Some(jsonrpc::serve(
+            lookup_host(addr).await?,
+            service.clone(),
+            config.max_body_size,
+            shutdown.clone(),
+        )),
+    };
+    let dashboard_server = match config.addresses.dashboard {
+        None => None,
+        Some(addr) => Some(dashboard::serve(
+            lookup_host(addr).await?,
+            service.clone(),
+            shutdown.clone(),
+        )),
+    };
+    let websocket_server = match config.addresses.websocket {
+        None => None,
+        Some(addr) => Some(websocket::serve(
+            lookup_host(addr).await?,
+            service.clone(),
+            shutdown.clone(),
+        )),
+    };
+
+    let (health_reporter, health_service) = health::service().await;
+    let watched_service = service.clone();
+    let health_watcher = until(shutdown.clone(), async move {
+        health::watch(health_reporter, watched_service).await;
+        Ok::<_, RunError>(())
+    });
+
+    let reflection_service = if config.reflection {
+        Some(
+            tonic_reflection::server::Builder::configure()
+                .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
+                .build()
+                .context(Reflection)
+                .context(Run)?,
+        )
+    } else {
+        None
+    };
 
     let web_config = tonic_web::config().allow_all_origins();
 
-    Server::builder()
+    let mut rpc_builder = Server::builder();
+    if let Some(tls) = config.tls {
+        rpc_builder = rpc_builder
+            .tls_config(server_tls(tls).context(Run)?)
+            .context(Tls)
+            .context(Run)?;
+    }
+
+    let rpc_server = rpc_builder
+        .trace_fn(|request| {
+            let span = tracing::info_span!("rpc", method = request.uri().path());
+            span.set_parent(telemetry::extract(request.headers()));
+            span
+        })
         .accept_http1(true)
-        .add_service(web_config.enable(proto::at2_server::At2Server::new(service)))
-        .serve(
-            net::lookup_host(config.addresses.rpc)
-                .await
-                .context(UnknownHost)?
-                .next()
-                .context(NoHost)?,
+        .add_service(health_service)
+        .add_optional_service(reflection_service)
+        .add_service(web_config.enable(RequestIds::new(proto::at2_server::At2Server::new(service))))
+        .serve_with_shutdown(lookup_host(config.addresses.rpc).await?, shutdown.clone());
+
+    let rpc_server = async { rpc_server.await.context(Rpc) };
+    let json_server = async {
+        match json_server {
+            None => Ok(()),
+            Some(server) => server.await.context(Json),
+        }
+    };
+    let jsonrpc_server = async {
+        match jsonrpc_server {
+            None => Ok(()),
+            Some(server) => server.await.context(JsonRpc),
+        }
+    };
+    let dashboard_server = async {
+        match dashboard_server {
+            None => Ok(()),
+            Some(server) => server.await.context(Dashboard),
+        }
+    };
+    let websocket_server = async {
+        match websocket_server {
+            None => Ok(()),
+            Some(server) => server.await.context(WebSocket),
+        }
+    };
+
+    let vault_watcher = until(shutdown.clone(), async {
+        match watched {
+            None => Ok(()),
+            Some((vault, fetched)) => vault::watch(vault, fetched).await.context(WatchVault),
+        }
+    });
+
+    let servers = async {
+        tokio::try_join!(
+            rpc_server,
+            json_server,
+            jsonrpc_server,
+            dashboard_server,
+            websocket_server,
+            vault_watcher,
+            health_watcher
         )
-        .await
-        .context(Rpc)
-        .context(Run)?;
+    };
+
+    // streams, such as subscriptions, never finish by themselves
+    let grace = async {
+        shutdown.clone().await;
+        sleep(SHUTDOWN_GRACE).await
+    };
+
+    tokio::select! {
+        ret = servers => {
+            ret.context(Run)?;
+        }
+        _ = grace => warn!("shutdown: closing remaining connections"),
+    }
+
+    // the last transactions might still wait in a batch
+    info!("shutdown: flushing pending batches");
+    sleep(batch_delay).await;
+
+    // the same for the spans, a no-op without telemetry
+    opentelemetry::global::shutdown_tracer_provider();
 
     Ok(())
 }
 
-#[tokio::main(flavor = "multi_thread")]
-async fn main() {
+/// Run `fut`, stopping early with success once `shutdown` resolves
+async fn until<T>(
+    shutdown: impl Future<Output = ()>,
+    fut: impl Future<Output = Result<(), T>>,
+) -> Result<(), T> {
+    tokio::select! {
+        ret = fut => ret,
+        _ = shutdown => Ok(()),
+    }
+}
+
+async fn lookup_host(addr: String) -> Result<SocketAddr, Error> {
+    net::lookup_host(addr)
+        .await
+        .context(UnknownHost)?
+        .next()
+        .context(NoHost)
+}
+
+/// Multi-threaded runtime, tuned by `config`
+fn runtime(config: &config::ConfigRuntime) -> Result<runtime::Runtime, Error> {
+    let mut builder = runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(threads) = config.worker_threads {
+        builder.worker_threads(threads.get());
+    }
+    if let Some(threads) = config.max_blocking_threads {
+        builder.max_blocking_threads(threads.get());
+    }
+
+    builder.build().context(Runtime)
+}
+
+fn main() {
     let ret = match Commands::from_args() {
-        Commands::Config(cmd) => config(cmd),
-        Commands::Run => run().await,
+        Commands::Config(cmd) => {
+            runtime(&Default::default()).and_then(|runtime| runtime.block_on(config(cmd)))
+        }
+        Commands::Run {
+            config_path,
+            log_format,
+            log_level,
+            passphrase_file,
+            worker_threads,
+            max_blocking_threads,
+            processor_workers,
+            follower,
+            validator,
+        } => config::from_path(&config_path)
+            .context(Config)
+            .and_then(|mut config| {
+                override_addresses(&mut config.addresses);
+                config.runtime.worker_threads = worker_threads.or(config.runtime.worker_threads);
+                config.runtime.max_blocking_threads =
+                    max_blocking_threads.or(config.runtime.max_blocking_threads);
+                config.protocol.processor_workers =
+                    processor_workers.or(config.protocol.processor_workers);
+
+                runtime(&config.runtime)?.block_on(run(
+                    config,
+                    log_format,
+                    log_level,
+                    passphrase_file,
+                    follower,
+                    validator,
+                ))
+            }),
     };
 
     if let Err(err) = ret {
@@ -1,12 +1,34 @@
 //! Client for connecting to an AT2 node
 
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
 use drop::crypto::sign;
+use futures::{
+    future::{self, Either},
+    Stream, StreamExt,
+};
 use http::Uri;
-use snafu::{ResultExt, Snafu};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
 
 use crate::{
-    proto::{at2_client::At2Client, *},
-    FullTransaction, ThinTransaction,
+    api::At2Api,
+    auth,
+    codec::{self, Codec, Encoding},
+    proto::{self, at2_client::At2Client, *},
+    proto_convert::{self, proto_amount, proto_public_key, proto_signature},
+    version::ProtocolRange,
+    Amount, Asset, AssetId, BalanceAttestation, BalanceProof, BalanceStatement, BridgeDeposit,
+    Evidence, Fees, FullTransaction, LogLevel, Mint, Operation, Output, Receipt, ReceiptStatement,
+    SignedOperation, SignedTransaction,
 };
 
 /// Error generated by this client
@@ -21,7 +43,7 @@ pub enum Error {
     /// Deserialize the server's reply
     Deserialize {
         /// Source of the error
-        source: bincode::Error,
+        source: codec::Error,
     },
     /// Deserializing the timestamp
     DeserializeTimestamp {
@@ -31,27 +53,512 @@ pub enum Error {
     /// Serializing the server's query
     Serialize {
         /// Source of the error
-        source: bincode::Error,
+        source: codec::Error,
+    },
+    /// Converting to or from the typed keys and signatures
+    Convert {
+        /// Source of the error
+        source: proto_convert::Error,
     },
     /// Communicating with the server
     Rpc {
         /// Source of the error
         source: tonic::Status,
     },
+    /// Call still unanswered after the overall timeout
+    Timeout,
+    /// Sending a transfer without any output
+    NoOutput,
+    /// Asking for a receipt, to a node not giving any
+    NoReceipt,
+    /// Creating a client without any node
+    NoNode,
+}
+
+impl Error {
+    /// Why the node's accounts refused the call, if they did
+    pub fn account_error(&self) -> Option<AccountError> {
+        match self {
+            Self::Rpc { source } => proto_convert::account_error(source),
+            _ => None,
+        }
+    }
+
+    /// ID the node gave to the failed call, also found in its logs
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            Self::Rpc { source } => source
+                .metadata()
+                .get(crate::REQUEST_ID_METADATA_KEY)
+                .and_then(|id| id.to_str().ok()),
+            _ => None,
+        }
+    }
+
+    /// RPC of a node accepting the write refused by a read-only follower,
+    /// if the follower knows one
+    pub fn validator(&self) -> Option<&str> {
+        match self {
+            Self::Rpc { source } => source
+                .metadata()
+                .get(crate::VALIDATOR_METADATA_KEY)
+                .and_then(|validator| validator.to_str().ok())
+                .filter(|validator| !validator.is_empty()),
+            _ => None,
+        }
+    }
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
-/// gRPC web client for the node
+/// What a node runs, see [`Client::get_node_info`]
+#[derive(Debug, Clone)]
+pub struct NodeInfo {
+    /// Version of the node's crate
+    pub version: String,
+    /// Protocol versions spoken by the node
+    pub protocol: ProtocolRange,
+    /// Network the node belongs to
+    pub chain_id: String,
+    /// Fee asked for each transfer, `None` if free
+    pub fees: Option<Fees>,
+    /// Whether the node refuses writes, only serving reads
+    pub follower: bool,
+}
+
+/// How a node sees the network, see [`Client::get_network_info`]
+#[derive(Debug, Clone)]
+pub struct NetworkInfo {
+    /// Address of the node in the network
+    pub node: String,
+    /// Hex encoded network key of the node
+    pub network_public_key: String,
+    /// Configured peers of the node
+    pub peers: Vec<PeerInfo>,
+    /// Tuning of the broadcast
+    pub broadcast: BroadcastInfo,
+}
+
+/// Peer of a node, see [`NetworkInfo`]
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    /// Address of the peer in the network
+    pub address: String,
+    /// Hex encoded network key of the peer
+    pub network_public_key: String,
+    /// Where the peer serves its RPC, if known to the node
+    pub rpc: Option<String>,
+    /// Whether it answered the node's last check, `None` if never checked
+    pub answering: Option<bool>,
+    /// When it last answered the node
+    pub last_seen: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Round trips and traffic of the checks a node sends a peer, see
+/// [`Client::get_peer_stats`]
+#[derive(Debug, Clone)]
+pub struct PeerStats {
+    /// Address of the peer in the network
+    pub address: String,
+    /// Checks sent
+    pub messages_sent: u64,
+    /// Checks answered
+    pub messages_received: u64,
+    /// Encoded size of the checks
+    pub bytes_sent: u64,
+    /// Encoded size of the answers
+    pub bytes_received: u64,
+    /// Round trip of the last answered check, `None` if never answered
+    pub rtt_last: Option<Duration>,
+    /// Shortest round trip
+    pub rtt_min: Option<Duration>,
+    /// Average round trip
+    pub rtt_mean: Option<Duration>,
+    /// Longest round trip
+    pub rtt_max: Option<Duration>,
+}
+
+/// Tuning of the broadcast of a node, see [`NetworkInfo`]
+#[derive(Debug, Clone, Copy)]
+pub struct BroadcastInfo {
+    /// Transactions gathered before sending a batch
+    pub batch_size: u32,
+    /// How long to wait before flushing an incomplete batch
+    pub batch_delay: Duration,
+    /// Peers murmur gossips each batch to
+    pub gossip_size: u32,
+    /// Peers sieve asks to echo a batch
+    pub echo_sample_size: u32,
+    /// Echoes sieve waits for before delivering
+    pub echo_threshold: u32,
+    /// Peers contagion asks to be ready for a batch
+    pub ready_sample_size: u32,
+    /// Ready peers contagion waits for before delivering
+    pub ready_threshold: u32,
+}
+
+/// What happened to a transaction, see [`Client::get_transaction_status`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionStatus {
+    /// Never seen by the node, or forgotten since
+    Unknown,
+    /// Broadcasted by the node, not yet delivered
+    Pending,
+    /// Applied to the accounts
+    Delivered,
+    /// Delivered but refused, with the reason
+    Rejected(String),
+}
+
+/// Transfer refused by a node, see [`Client::get_rejected_transactions`]
+#[derive(Debug, Clone)]
+pub struct RejectedTransaction {
+    /// When the node refused it
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub sender: sign::PublicKey,
+    pub sequence: sieve::Sequence,
+    /// Every recipient with its amount, the first one included
+    pub outputs: Vec<Output>,
+    pub fee: Amount,
+    /// Registered one sent, the network's own if `None`
+    pub asset: Option<AssetId>,
+    pub reason: rejected_transaction::Reason,
+    /// As logged by the node
+    pub message: String,
+}
+
+#[cfg(target_family = "wasm")]
+type Inner = At2Client<grpc_web_client::Client>;
+#[cfg(not(target_family = "wasm"))]
+type Inner = At2Client<tonic::transport::Channel>;
+
+/// Hash of a node's accounts, see [`Client::get_checkpoint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Number of the checkpoint, starting at one
+    pub epoch: u64,
+    /// Operations applied to the accounts when taken
+    pub applied: u64,
+    /// SHA-256 of the node's accounts, not to be compared across nodes, which
+    /// might have applied other operations by the same count
+    pub hash: Vec<u8>,
+    /// When the node took it
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Hash of a node's accounts as they are now, see [`Client::get_state_digest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDigest {
+    /// Hash of the accounts, equal on nodes having applied the same operations
+    pub digest: Vec<u8>,
+    /// Operations applied to the accounts
+    pub applied: u64,
+}
+
+/// Every account of a node, see [`Client::get_snapshot`]
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Account, balance and last sequence, in any order
+    pub accounts: Vec<(sign::PublicKey, Amount, sieve::Sequence)>,
+    /// Registered assets, in any order
+    pub assets: Vec<Asset>,
+    /// Non-zero balances in the registered assets, in any order
+    pub asset_balances: Vec<(AssetId, sign::PublicKey, Amount)>,
+    /// SHA-256 of the accounts and assets, as in [`Checkpoint::hash`]
+    pub hash: Vec<u8>,
+    /// Transactions the node keeps as recent, newest last
+    pub recent_transactions: Vec<FullTransaction>,
+}
+
+/// Accounts known to a node, see [`Client::list_accounts`]
+#[derive(Debug, Clone)]
+pub struct AccountsPage {
+    /// Account, balance and last sequence, ordered by account
+    pub accounts: Vec<(sign::PublicKey, Amount, sieve::Sequence)>,
+    /// Cursor to get the next page, if accounts remain
+    pub next: Option<sign::PublicKey>,
+    /// How many accounts the node knows
+    pub total: u64,
+}
+
+/// Transactions of an account, see [`Client::get_account_history`]
+#[derive(Debug, Clone)]
+pub struct HistoryPage {
+    /// Sent or received by the account, newest first
+    pub transactions: Vec<FullTransaction>,
+    /// Cursor to get the next page, if older transactions remain
+    pub next: Option<u64>,
+}
+
+/// State of an account along its version, see [`Client::get_balance_if_newer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Versioned<T> {
+    /// As the node sees it now
+    pub value: T,
+    /// To give back, so that the state is only sent once it changed
+    pub version: u64,
+}
+
+/// gRPC web client for the node, or for several ones
+///
+/// Clones share the connections to the nodes, so a service should clone a
+/// single client rather than create one per task.
 #[derive(Clone)]
-pub struct Client(
-    #[cfg(target_family = "wasm")] At2Client<grpc_web_client::Client>,
-    #[cfg(not(target_family = "wasm"))] At2Client<tonic::transport::Channel>,
-);
+pub struct Client {
+    nodes: Arc<Nodes>,
+    encoding: Encoding,
+    policy: Policy,
+    auth: Option<Arc<Auth>>,
+}
 
-impl Client {
-    /// Create a new client connecting to the given [`Uri`]
-    pub fn new(uri: Uri) -> Result<Self> {
+/// Proves owning an account to the nodes, see [`Client::authenticate`]
+struct Auth {
+    keypair: sign::KeyPair,
+    /// Last nonce signed for each node
+    proofs: Mutex<Vec<Option<auth::Proof>>>,
+}
+
+impl Auth {
+    /// Add the proof for the node at `index` to the `request`, if any
+    fn insert<T>(&self, index: usize, request: &mut tonic::Request<T>) {
+        let proofs = self.proofs.lock().expect("proofs poisoned");
+        if let Some(proof) = &proofs[index] {
+            proof.insert(request.metadata_mut());
+        }
+    }
+
+    /// Sign a new nonce of the node at `index`, returning whether it worked
+    async fn challenge(&self, index: usize, mut inner: Inner) -> bool {
+        let request = GetChallengeRequest {
+            account: Some(proto_public_key(&self.keypair.public())),
+        };
+
+        let proof = match inner.get_challenge(request).await {
+            Err(_) => return false,
+            Ok(reply) => match auth::Proof::new(&self.keypair, reply.into_inner().nonce) {
+                Err(_) => return false,
+                Ok(proof) => proof,
+            },
+        };
+
+        self.proofs.lock().expect("proofs poisoned")[index] = Some(proof);
+        true
+    }
+}
+
+/// How a [`Client`] of several nodes picks the one to read from
+///
+/// Whatever the node picked, a call fails over to the next ones if it is
+/// [`tonic::Code::Unavailable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// Each node in turn
+    RoundRobin,
+    /// The node which answered the fastest lately, trying unmeasured ones first
+    ///
+    /// Time can't be measured on wasm, where it behaves as
+    /// [`Selection::RoundRobin`].
+    LowestLatency,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self::RoundRobin
+    }
+}
+
+/// Weight of the last call in the latency of a node, in percent
+const LATENCY_WEIGHT: u32 = 20;
+
+/// Nodes of a [`Client`], in the given order
+struct Nodes {
+    inners: Vec<Inner>,
+    /// Moving average of the calls to each node
+    latencies: Mutex<Vec<Option<Duration>>>,
+    selection: Selection,
+    next: AtomicUsize,
+}
+
+impl Nodes {
+    /// Index of the node to read from
+    fn select(&self) -> usize {
+        let round_robin = self.next.fetch_add(1, Ordering::Relaxed) % self.inners.len();
+        if self.selection == Selection::RoundRobin || cfg!(target_family = "wasm") {
+            return round_robin;
+        }
+
+        let latencies = self.latencies.lock().expect("latencies poisoned");
+        latencies
+            .iter()
+            .position(Option::is_none)
+            .or_else(|| {
+                latencies
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, latency)| *latency)
+                    .map(|(index, _)| index)
+            })
+            .unwrap_or(round_robin)
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn record(&self, index: usize, elapsed: Duration) {
+        let mut latencies = self.latencies.lock().expect("latencies poisoned");
+        let latency = &mut latencies[index];
+
+        *latency = Some(match *latency {
+            None => elapsed,
+            Some(previous) => (previous * (100 - LATENCY_WEIGHT) + elapsed * LATENCY_WEIGHT) / 100,
+        });
+    }
+}
+
+/// How calls handle slow or unavailable nodes, see [`ClientBuilder`]
+#[derive(Debug, Clone, Copy, Default)]
+struct Policy {
+    timeout: Option<Duration>,
+    overall_timeout: Option<Duration>,
+    retries: u32,
+    backoff: Duration,
+}
+
+/// HTTP/2 tuning of the connection to each node, see [`ClientBuilder`]
+#[cfg(not(target_family = "wasm"))]
+#[derive(Debug, Clone, Copy, Default)]
+struct Connection {
+    keepalive: Option<(Duration, Duration)>,
+    stream_window: Option<u32>,
+    connection_window: Option<u32>,
+    max_concurrent_streams: Option<usize>,
+}
+
+/// Configure a [`Client`] before creating it
+///
+/// By default, calls are tried once and wait as long as needed.
+#[derive(Debug, Clone, Default)]
+pub struct ClientBuilder {
+    encoding: Encoding,
+    policy: Policy,
+    selection: Selection,
+    #[cfg(not(target_family = "wasm"))]
+    tls: Option<tonic::transport::ClientTlsConfig>,
+    #[cfg(not(target_family = "wasm"))]
+    connection: Connection,
+}
+
+impl ClientBuilder {
+    /// Use the given [`Encoding`] for the payloads, instead of the default one
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Give up on each try of a call after `timeout`, the node doing the same
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.policy.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry calls failing with [`tonic::Code::Unavailable`] up to `retries` times
+    ///
+    /// It waits `backoff` before the first retry, doubling it every time.
+    pub fn retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.policy.retries = retries;
+        self.policy.backoff = backoff;
+        self
+    }
+
+    /// Give up on a call after `timeout`, connecting and retrying included
+    pub fn overall_timeout(mut self, timeout: Duration) -> Self {
+        self.policy.overall_timeout = Some(timeout);
+        self
+    }
+
+    /// Trust the PEM encoded `ca` certificate, for nodes serving TLS
+    ///
+    /// `https` nodes are otherwise checked against the system's authorities.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn ca_certificate(mut self, ca: impl AsRef<[u8]>) -> Self {
+        let tls = self.tls.take().unwrap_or_default();
+        self.tls = Some(tls.ca_certificate(tonic::transport::Certificate::from_pem(ca)));
+        self
+    }
+
+    /// Authenticate with the PEM encoded `cert` and `key`, for nodes requiring mTLS
+    #[cfg(not(target_family = "wasm"))]
+    pub fn identity(mut self, cert: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Self {
+        let tls = self.tls.take().unwrap_or_default();
+        self.tls = Some(tls.identity(tonic::transport::Identity::from_pem(cert, key)));
+        self
+    }
+
+    /// Ping each node every `interval`, even while no call is running, and
+    /// drop the connection if not answered within `timeout`
+    ///
+    /// Without it, a connection broken without notice, such as by a NAT or a
+    /// load balancer forgetting it, is only found out by the next calls.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.connection.keepalive = Some((interval, timeout));
+        self
+    }
+
+    /// Let each node send up to `stream` bytes of a call, and `connection`
+    /// bytes of every call, before waiting for the client to read them
+    ///
+    /// Both default to 64 KiB, HTTP/2's own default.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn window_sizes(mut self, stream: u32, connection: u32) -> Self {
+        self.connection.stream_window = Some(stream);
+        self.connection.connection_window = Some(connection);
+        self
+    }
+
+    /// Run at most `max` calls at once on the connection to each node, the
+    /// others waiting for one to end
+    #[cfg(not(target_family = "wasm"))]
+    pub fn max_concurrent_streams(mut self, max: usize) -> Self {
+        self.connection.max_concurrent_streams = Some(max);
+        self
+    }
+
+    /// Pick the node to read from with `selection`, for clients of several nodes
+    pub fn selection(self, selection: Selection) -> Self {
+        Self { selection, ..self }
+    }
+
+    /// Create a client connecting to the given [`Uri`], on its first call
+    pub fn build(self, uri: Uri) -> Result<Client> {
+        self.build_multi(vec![uri])
+    }
+
+    /// Create a client of the nodes at the given [`Uri`]s, connecting on their first call
+    ///
+    /// Reads are spread over the nodes following the [`Selection`]. Writes
+    /// go to the first node, so that the transactions of a sender are
+    /// broadcast in order, and only fail over to the next ones if it is
+    /// unavailable.
+    pub fn build_multi(self, uris: Vec<Uri>) -> Result<Client> {
+        ensure!(!uris.is_empty(), NoNode);
+
+        let inners = uris
+            .into_iter()
+            .map(|uri| self.connect(uri))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Client {
+            nodes: Arc::new(Nodes {
+                latencies: Mutex::new(vec![None; inners.len()]),
+                inners,
+                selection: self.selection,
+                next: AtomicUsize::new(0),
+            }),
+            encoding: self.encoding,
+            policy: self.policy,
+            auth: None,
+        })
+    }
+
+    fn connect(&self, uri: Uri) -> Result<Inner> {
         let mut url_string = uri.to_string();
         if uri.path() == "/" {
             // TODO fix upstream handling
@@ -61,81 +568,1447 @@ impl Client {
         #[cfg(target_family = "wasm")]
         let connection = grpc_web_client::Client::new(url_string);
         #[cfg(not(target_family = "wasm"))]
-        let connection = tonic::transport::Channel::builder(uri)
-            .connect_lazy()
-            .context(Transport)?;
+        let connection = {
+            let tls = match self.tls.clone() {
+                Some(tls) => Some(tls),
+                None if uri.scheme() == Some(&http::uri::Scheme::HTTPS) => Some(Default::default()),
+                None => None,
+            };
+
+            let mut endpoint = tonic::transport::Channel::builder(uri);
+            if let Some(timeout) = self.policy.timeout {
+                endpoint = endpoint.timeout(timeout);
+            }
+            if let Some(tls) = tls {
+                endpoint = endpoint.tls_config(tls).context(Transport)?;
+            }
+            if let Some((interval, timeout)) = self.connection.keepalive {
+                endpoint = endpoint
+                    .http2_keep_alive_interval(interval)
+                    .keep_alive_timeout(timeout)
+                    .keep_alive_while_idle(true);
+            }
+            if let Some(max) = self.connection.max_concurrent_streams {
+                endpoint = endpoint.concurrency_limit(max);
+            }
+            endpoint = endpoint
+                .initial_stream_window_size(self.connection.stream_window)
+                .initial_connection_window_size(self.connection.connection_window);
+
+            endpoint.connect_lazy().context(Transport)?
+        };
+
+        Ok(At2Client::new(connection))
+    }
+}
+
+impl Policy {
+    /// Run `attempt` until it succeeds or fails for good
+    async fn retry<T, F, Fut>(self, mut attempt: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = std::result::Result<T, tonic::Status>>,
+    {
+        let attempts = async move {
+            let mut backoff = self.backoff;
+            for _ in 0..self.retries {
+                match attempt().await {
+                    Err(status) if status.code() == tonic::Code::Unavailable => {
+                        sleep(backoff).await;
+                        backoff = backoff.saturating_mul(2);
+                    }
+                    ret => return ret.context(Rpc),
+                }
+            }
+
+            attempt().await.context(Rpc)
+        };
+
+        match self.overall_timeout {
+            None => attempts.await,
+            Some(timeout) => {
+                futures::pin_mut!(attempts);
+                let timer = sleep(timeout);
+                futures::pin_mut!(timer);
+
+                match future::select(attempts, timer).await {
+                    Either::Left((ret, _)) => ret,
+                    Either::Right(_) => Timeout.fail(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_family = "wasm")]
+async fn sleep(duration: Duration) {
+    use std::convert::TryFrom;
+
+    let millis = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+    gloo_timers::future::TimeoutFuture::new(millis).await
+}
+
+#[cfg(not(target_family = "wasm"))]
+async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+impl Client {
+    /// Create a new client connecting to the given [`Uri`]
+    pub fn new(uri: Uri) -> Result<Self> {
+        Self::builder().build(uri)
+    }
+
+    /// Create a new client of the nodes at the given [`Uri`]s, see [`ClientBuilder::build_multi`]
+    pub fn new_multi(uris: Vec<Uri>) -> Result<Self> {
+        Self::builder().build_multi(uris)
+    }
+
+    /// Configure a client, with timeouts and retries
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Use the given [`Encoding`] for the payloads, instead of the default one
+    pub fn with_encoding(self, encoding: Encoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Prove owning the account of `keypair` to nodes requiring it to read
+    ///
+    /// The nonce of each node is only asked for and signed once it refuses
+    /// a call as [`tonic::Code::Unauthenticated`], which is then tried again.
+    pub fn authenticate(&mut self, keypair: &sign::KeyPair) {
+        self.auth = Some(Arc::new(Auth {
+            keypair: sign::KeyPair::from(keypair.private()),
+            proofs: Mutex::new(vec![None; self.nodes.inners.len()]),
+        }));
+    }
+
+    /// Wrap a message, announcing the used [`Encoding`]
+    fn request<T>(encoding: Encoding, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        request.metadata_mut().insert(
+            codec::METADATA_KEY,
+            encoding
+                .name()
+                .parse()
+                .expect("encoding name is valid metadata"),
+        );
+        #[cfg(feature = "telemetry")]
+        crate::telemetry::inject(request.metadata_mut());
+
+        request
+    }
+
+    /// Read with `message` and `rpc`, following the timeouts and retries
+    async fn call<M, T, F, Fut>(&mut self, message: M, rpc: F) -> Result<T>
+    where
+        M: Clone,
+        F: Fn(Inner, tonic::Request<M>) -> Fut + Clone,
+        Fut: Future<Output = std::result::Result<tonic::Response<T>, tonic::Status>>,
+    {
+        let first = self.nodes.select();
+        self.call_from(first, message, rpc).await
+    }
+
+    /// Write with `message` and `rpc`, on the first available node
+    async fn call_write<M, T, F, Fut>(&mut self, message: M, rpc: F) -> Result<T>
+    where
+        M: Clone,
+        F: Fn(Inner, tonic::Request<M>) -> Fut + Clone,
+        Fut: Future<Output = std::result::Result<tonic::Response<T>, tonic::Status>>,
+    {
+        self.call_from(0, message, rpc).await
+    }
+
+    /// Call every node from the `first` one, until one is available and
+    /// isn't a follower refusing the call
+    async fn call_from<M, T, F, Fut>(&mut self, first: usize, message: M, rpc: F) -> Result<T>
+    where
+        M: Clone,
+        F: Fn(Inner, tonic::Request<M>) -> Fut + Clone,
+        Fut: Future<Output = std::result::Result<tonic::Response<T>, tonic::Status>>,
+    {
+        let nodes = self.nodes.clone();
+        let encoding = self.encoding;
+        let timeout = self.policy.timeout;
+        let auth = self.auth.clone();
+
+        self.policy
+            .retry(move || {
+                let nodes = nodes.clone();
+                let message = message.clone();
+                let rpc = rpc.clone();
+                let auth = auth.clone();
+
+                async move {
+                    let request = |index| {
+                        let mut request = Self::request(encoding, message.clone());
+                        if let Some(timeout) = timeout {
+                            request.set_timeout(timeout);
+                        }
+                        if let Some(auth) = &auth {
+                            auth.insert(index, &mut request);
+                        }
+                        request
+                    };
+
+                    let count = nodes.inners.len();
+                    for offset in 0..count {
+                        let index = (first + offset) % count;
+                        let inner = &nodes.inners[index];
+
+                        #[cfg(not(target_family = "wasm"))]
+                        let start = std::time::Instant::now();
+                        let mut ret = rpc(inner.clone(), request(index)).await;
+                        if let (Err(status), Some(auth)) = (&ret, &auth) {
+                            if status.code() == tonic::Code::Unauthenticated
+                                && auth.challenge(index, inner.clone()).await
+                            {
+                                ret = rpc(inner.clone(), request(index)).await;
+                            }
+                        }
+                        #[cfg(not(target_family = "wasm"))]
+                        if ret.is_ok() {
+                            nodes.record(index, start.elapsed());
+                        }
+
+                        // a read-only follower refuses writes, the next node might not
+                        match ret {
+                            Err(status)
+                                if (status.code() == tonic::Code::Unavailable
+                                    || status
+                                        .metadata()
+                                        .contains_key(crate::VALIDATOR_METADATA_KEY))
+                                    && offset + 1 < count => {}
+                            ret => return ret,
+                        }
+                    }
+
+                    unreachable!("at least one node is called")
+                }
+            })
+            .await
+            .map(tonic::Response::into_inner)
+    }
+
+    /// Receive every transaction processed by the node from now on
+    ///
+    /// The stream ends with an error if the connection is lost, and might
+    /// skip transactions if not consumed fast enough.
+    pub async fn subscribe_transactions(
+        &mut self,
+    ) -> Result<impl Stream<Item = Result<FullTransaction>>> {
+        let inner = self.nodes.inners[self.nodes.select()].clone();
+        let encoding = self.encoding;
+
+        // the stream lives as long as the subscription, so no timeout per try
+        let stream = self
+            .policy
+            .retry(|| {
+                let mut inner = inner.clone();
+                let request = Self::request(encoding, SubscribeTransactionsRequest {});
+
+                async move { inner.subscribe_transactions(request).await }
+            })
+            .await?
+            .into_inner();
+
+        Ok(stream.map(move |reply| full_transaction(encoding, &reply.context(Rpc)?)))
+    }
+
+    /// Get what happened to the transaction of `sender` at `sequence`
+    pub async fn get_transaction_status(
+        &mut self,
+        sender: &sign::PublicKey,
+        sequence: sieve::Sequence,
+    ) -> Result<TransactionStatus> {
+        use get_transaction_status_reply::Status;
+
+        let message = GetTransactionStatusRequest {
+            sender: self.encoding.encode(sender).context(Serialize)?,
+            typed_sender: Some(proto_public_key(sender)),
+            sequence,
+            ..Default::default()
+        };
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_transaction_status(request).await
+            })
+            .await?;
+
+        Ok(match reply.status() {
+            Status::Unknown => TransactionStatus::Unknown,
+            Status::Pending => TransactionStatus::Pending,
+            Status::Delivered => TransactionStatus::Delivered,
+            Status::Rejected => TransactionStatus::Rejected(reply.reason),
+        })
+    }
+
+    /// Get the transfers sent or received by `account` which the node refused,
+    /// newest first, as far as it remembers
+    pub async fn get_rejected_transactions(
+        &mut self,
+        account: &sign::PublicKey,
+    ) -> Result<Vec<RejectedTransaction>> {
+        let message = GetRejectedTransactionsRequest {
+            account: Some(proto_public_key(account)),
+        };
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_rejected_transactions(request).await
+            })
+            .await?;
+
+        reply
+            .transactions
+            .iter()
+            .map(rejected_transaction)
+            .collect()
+    }
+
+    /// Get up to `limit` transactions sent or received by `account`, newest first
+    ///
+    /// It starts from the newest one, or from the `cursor` of a previous
+    /// [`HistoryPage`]. Cursors are only understood by the node returning them.
+    /// A `limit` of zero leaves it to the node.
+    pub async fn get_account_history(
+        &mut self,
+        account: &sign::PublicKey,
+        cursor: Option<u64>,
+        limit: u32,
+    ) -> Result<HistoryPage> {
+        let message = GetAccountHistoryRequest {
+            account: self.encoding.encode(account).context(Serialize)?,
+            typed_account: Some(proto_public_key(account)),
+            cursor: cursor.unwrap_or_default(),
+            limit,
+            ..Default::default()
+        };
+        let encoding = self.encoding;
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_account_history(request).await
+            })
+            .await?;
+
+        Ok(HistoryPage {
+            transactions: reply
+                .transactions
+                .iter()
+                .map(|tx| full_transaction(encoding, tx))
+                .collect::<Result<_>>()?,
+            next: Some(reply.next_cursor).filter(|cursor| *cursor != 0),
+        })
+    }
+
+    /// Get the balance of `user`, unless it didn't change since `version`
+    ///
+    /// `version` is the one of a previous call, zero to always get it.
+    /// Versions count the changes a node applied to the account, so they only
+    /// agree between nodes which delivered the same transactions.
+    pub async fn get_balance_if_newer(
+        &mut self,
+        user: &sign::PublicKey,
+        version: u64,
+    ) -> Result<Option<Versioned<Amount>>> {
+        let message = GetBalanceRequest {
+            sender: self.encoding.encode(user).context(Serialize)?,
+            typed_sender: Some(proto_public_key(user)),
+            if_version_newer_than: version,
+            ..Default::default()
+        };
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_balance(request).await
+            })
+            .await?;
+
+        Ok(Some(Versioned {
+            value: proto_convert::amount(reply.amount, reply.amount_high),
+            version: reply.version,
+        })
+        .filter(|_| !reply.not_modified))
+    }
+
+    /// Same as [`Client::get_balance_if_newer`], for the last sequence
+    pub async fn get_last_sequence_if_newer(
+        &mut self,
+        user: &sign::PublicKey,
+        version: u64,
+    ) -> Result<Option<Versioned<sieve::Sequence>>> {
+        let message = GetLastSequenceRequest {
+            sender: self.encoding.encode(user).context(Serialize)?,
+            typed_sender: Some(proto_public_key(user)),
+            if_version_newer_than: version,
+            ..Default::default()
+        };
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_last_sequence(request).await
+            })
+            .await?;
+
+        Ok(Some(Versioned {
+            value: reply.sequence,
+            version: reply.version,
+        })
+        .filter(|_| !reply.not_modified))
+    }
+
+    /// Receive every transaction delivered by the node, oldest first
+    ///
+    /// The stream ends once the latest one is received. The history only
+    /// survives a restart of the node if it has a `storage_path`.
+    pub async fn export_history(&mut self) -> Result<impl Stream<Item = Result<FullTransaction>>> {
+        let inner = self.nodes.inners[self.nodes.select()].clone();
+        let encoding = self.encoding;
+
+        // the stream lives as long as the export, so no timeout per try
+        let stream = self
+            .policy
+            .retry(|| {
+                let mut inner = inner.clone();
+                let request = Self::request(encoding, ExportHistoryRequest {});
+
+                async move { inner.export_history(request).await }
+            })
+            .await?
+            .into_inner();
+
+        Ok(stream.map(move |reply| full_transaction(encoding, &reply.context(Rpc)?)))
+    }
+
+    /// Send to every recipient of the `outputs` at once, all being paid or none
+    pub async fn send_multi_asset(
+        &mut self,
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        outputs: Vec<Output>,
+    ) -> Result<()> {
+        let mut outputs = outputs.into_iter();
+        let first = outputs.next().context(NoOutput)?;
 
-        Ok(Self(At2Client::new(connection)))
+        self.send_signed(&SignedTransaction::with_outputs(
+            user,
+            sequence,
+            first.recipient,
+            first.amount,
+            outputs.collect(),
+            None,
+        ))
+        .await
     }
 
-    /// Send a given number of asset to the given user.
+    /// Get up to `limit` of the latest transactions processed by the node
     ///
-    /// `sequence` is counter used by the sender.
-    /// You should increase it by one for each new transaction you want to send.
-    pub async fn send_asset(
+    /// A `limit` of zero leaves it to the node, as does
+    /// [`At2Api::get_latest_transactions`].
+    pub async fn get_latest_transactions_up_to(
+        &mut self,
+        limit: u32,
+    ) -> Result<Vec<FullTransaction>> {
+        let message = GetLatestTransactionsRequest { limit };
+        let encoding = self.encoding;
+
+        self.call(message, |mut inner, request| async move {
+            inner.get_latest_transactions(request).await
+        })
+        .await?
+        .transactions
+        .iter()
+        .map(|tx| full_transaction(encoding, tx))
+        .collect()
+    }
+
+    /// Get the balance and last sequence of each of the `accounts`, in a single call
+    pub async fn get_balances(
+        &mut self,
+        accounts: &[sign::PublicKey],
+    ) -> Result<HashMap<sign::PublicKey, (Amount, sieve::Sequence)>> {
+        let message = GetBalancesRequest {
+            accounts: accounts.iter().map(proto_public_key).collect(),
+        };
+
+        self.call(message, |mut inner, request| async move {
+            inner.get_balances(request).await
+        })
+        .await?
+        .balances
+        .into_iter()
+        .map(|balance| {
+            Ok((
+                proto_convert::public_key(&balance.account.unwrap_or_default()).context(Convert)?,
+                (
+                    proto_convert::amount(balance.balance, balance.balance_high),
+                    balance.last_sequence,
+                ),
+            ))
+        })
+        .collect()
+    }
+
+    /// Get up to `limit` of the accounts known by the node, with their count
+    ///
+    /// It starts from the first account, or from the `cursor` of a previous
+    /// [`AccountsPage`]. Accounts being ordered the same on every node, the
+    /// cursor can be given to another one. A `limit` of zero leaves it to the
+    /// node.
+    pub async fn list_accounts(
+        &mut self,
+        cursor: Option<&sign::PublicKey>,
+        limit: u32,
+    ) -> Result<AccountsPage> {
+        let message = ListAccountsRequest {
+            cursor: cursor.map(proto_public_key),
+            limit,
+        };
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.list_accounts(request).await
+            })
+            .await?;
+
+        Ok(AccountsPage {
+            accounts: reply
+                .accounts
+                .into_iter()
+                .map(|balance| {
+                    Ok((
+                        proto_convert::public_key(&balance.account.unwrap_or_default())
+                            .context(Convert)?,
+                        proto_convert::amount(balance.balance, balance.balance_high),
+                        balance.last_sequence,
+                    ))
+                })
+                .collect::<Result<_>>()?,
+            next: reply
+                .next_cursor
+                .map(|cursor| proto_convert::public_key(&cursor).context(Convert))
+                .transpose()?,
+            total: reply.total,
+        })
+    }
+
+    /// Request sending the already signed `transaction`
+    fn send_asset_request(&self, transaction: &SignedTransaction) -> Result<SendAssetRequest> {
+        let (amount, amount_high) = proto_amount(transaction.amount);
+        let (fee, fee_high) = proto_amount(transaction.fee);
+
+        Ok(SendAssetRequest {
+            sender: self
+                .encoding
+                .encode(&transaction.sender)
+                .context(Serialize)?,
+            sequence: transaction.sequence,
+            recipient: self
+                .encoding
+                .encode(&transaction.recipient)
+                .context(Serialize)?,
+            amount,
+            signature: self
+                .encoding
+                .encode(&transaction.signature)
+                .context(Serialize)?,
+            memo: transaction.memo.clone().unwrap_or_default(),
+            typed_sender: Some(proto_public_key(&transaction.sender)),
+            typed_recipient: Some(proto_public_key(&transaction.recipient)),
+            typed_signature: Some(proto_signature(&transaction.signature).context(Convert)?),
+            outputs: transaction
+                .outputs
+                .iter()
+                .map(|output| {
+                    let (amount, amount_high) = proto_amount(output.amount);
+
+                    TransferOutput {
+                        recipient: Some(proto_public_key(&output.recipient)),
+                        amount,
+                        amount_high,
+                        ..Default::default()
+                    }
+                })
+                .collect(),
+            fee,
+            amount_high,
+            fee_high,
+            asset: transaction.asset.clone().unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+
+    /// Same as [`At2Api::send_asset`], returning the receipt signed by the
+    /// node accepting the transfer
+    ///
+    /// The receipt should then be checked with [`verify_receipt`].
+    pub async fn send_asset_with_receipt(
         &mut self,
         user: &sign::KeyPair,
         sequence: sieve::Sequence,
         recipient: sign::PublicKey,
-        amount: u64,
+        amount: Amount,
+    ) -> Result<Receipt> {
+        let transaction = SignedTransaction::new(user, sequence, recipient, amount);
+        let message = SendAssetRequest {
+            with_receipt: true,
+            ..self.send_asset_request(&transaction)?
+        };
+
+        let receipt = self
+            .call_write(message, |mut inner, request| async move {
+                inner.send_asset(request).await
+            })
+            .await?
+            .receipt
+            .context(NoReceipt)?;
+
+        Ok(Receipt {
+            statement: ReceiptStatement {
+                sender: proto_convert::public_key(&receipt.sender.unwrap_or_default())
+                    .context(Convert)?,
+                sequence: receipt.sequence,
+                recipient: proto_convert::public_key(&receipt.recipient.unwrap_or_default())
+                    .context(Convert)?,
+                amount: proto_convert::amount(receipt.amount, receipt.amount_high),
+                received_at: receipt.received_at,
+                asset: Some(receipt.asset).filter(|asset| !asset.is_empty()),
+            },
+            node: proto_convert::public_key(&receipt.node.unwrap_or_default()).context(Convert)?,
+            signature: proto_convert::signature(&receipt.signature.unwrap_or_default())
+                .context(Convert)?,
+        })
+    }
+
+    /// Get the balance of `account`, signed by the node
+    ///
+    /// With `aggregate`, the node also asks its peers to sign it. The proof
+    /// should then be checked with [`verify_balance_proof`].
+    pub async fn get_balance_proof(
+        &mut self,
+        account: &sign::PublicKey,
+        aggregate: bool,
+    ) -> Result<BalanceProof> {
+        let message = GetBalanceProofRequest {
+            account: Some(proto_public_key(account)),
+            aggregate,
+            ..Default::default()
+        };
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_balance_proof(request).await
+            })
+            .await?;
+
+        Ok(BalanceProof {
+            statement: BalanceStatement {
+                account: proto_convert::public_key(&reply.account.unwrap_or_default())
+                    .context(Convert)?,
+                balance: proto_convert::amount(reply.balance, reply.balance_high),
+                last_sequence: reply.last_sequence,
+                epoch: reply.epoch,
+            },
+            attestations: reply
+                .attestations
+                .into_iter()
+                .map(|attestation| {
+                    Ok(BalanceAttestation {
+                        node: proto_convert::public_key(&attestation.node.unwrap_or_default())
+                            .context(Convert)?,
+                        signature: proto_convert::signature(
+                            &attestation.signature.unwrap_or_default(),
+                        )
+                        .context(Convert)?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Get what the node runs
+    pub async fn get_node_info(&mut self) -> Result<NodeInfo> {
+        let message = GetNodeInfoRequest {};
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_node_info(request).await
+            })
+            .await?;
+
+        Ok(NodeInfo {
+            version: reply.version,
+            protocol: ProtocolRange {
+                min: reply.min_protocol_version,
+                max: reply.protocol_version,
+            },
+            chain_id: reply.chain_id,
+            fees: reply.fees.map(|fees| Fees {
+                flat: proto_convert::amount(fees.flat, fees.flat_high),
+                basis_points: fees.basis_points,
+            }),
+            follower: reply.follower,
+        })
+    }
+
+    /// Get how the node sees the network: its peers, whether they answer, and
+    /// how transactions are broadcasted
+    pub async fn get_network_info(&mut self) -> Result<NetworkInfo> {
+        use get_network_info_reply::PeerStatus;
+
+        let message = GetNetworkInfoRequest {};
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_network_info(request).await
+            })
+            .await?;
+
+        let peers = reply
+            .peers
+            .into_iter()
+            .map(|peer| {
+                Ok(PeerInfo {
+                    answering: match peer.status() {
+                        PeerStatus::Unknown => None,
+                        PeerStatus::Answering => Some(true),
+                        PeerStatus::Unreachable => Some(false),
+                    },
+                    last_seen: match peer.last_seen.as_str() {
+                        "" => None,
+                        last_seen => Some(
+                            chrono::DateTime::parse_from_rfc3339(last_seen)
+                                .context(DeserializeTimestamp)?
+                                .into(),
+                        ),
+                    },
+                    address: peer.address,
+                    network_public_key: peer.network_public_key,
+                    rpc: Some(peer.rpc).filter(|rpc| !rpc.is_empty()),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let broadcast = reply.broadcast.unwrap_or_default();
+
+        Ok(NetworkInfo {
+            node: reply.node,
+            network_public_key: reply.network_public_key,
+            peers,
+            broadcast: BroadcastInfo {
+                batch_size: broadcast.batch_size,
+                batch_delay: Duration::from_millis(broadcast.batch_delay),
+                gossip_size: broadcast.gossip_size,
+                echo_sample_size: broadcast.echo_sample_size,
+                echo_threshold: broadcast.echo_threshold,
+                ready_sample_size: broadcast.ready_sample_size,
+                ready_threshold: broadcast.ready_threshold,
+            },
+        })
+    }
+
+    /// Get how the node's checks of its peers went, since it started
+    ///
+    /// Only peers with an `rpc` address are checked.
+    pub async fn get_peer_stats(&mut self) -> Result<Vec<PeerStats>> {
+        let message = GetPeerStatsRequest {};
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_peer_stats(request).await
+            })
+            .await?;
+
+        let rtt = |micros| Some(Duration::from_micros(micros)).filter(|_| micros != 0);
+
+        Ok(reply
+            .peers
+            .into_iter()
+            .map(|peer| PeerStats {
+                address: peer.address,
+                messages_sent: peer.messages_sent,
+                messages_received: peer.messages_received,
+                bytes_sent: peer.bytes_sent,
+                bytes_received: peer.bytes_received,
+                rtt_last: rtt(peer.rtt_last),
+                rtt_min: rtt(peer.rtt_min),
+                rtt_mean: rtt(peer.rtt_mean),
+                rtt_max: rtt(peer.rtt_max),
+            })
+            .collect())
+    }
+
+    /// Get the evidences of senders signing conflicting operations, oldest first
+    ///
+    /// They come from the node, so should be checked with [`Evidence::verify`]
+    /// before being acted upon.
+    pub async fn get_evidence(&mut self) -> Result<Vec<Evidence>> {
+        let message = GetEvidenceRequest {};
+        let encoding = self.encoding;
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_evidence(request).await
+            })
+            .await?;
+
+        let signed = |signed: Option<get_evidence_reply::SignedOperation>| {
+            let signed = signed.unwrap_or_default();
+
+            Ok::<_, Error>(SignedOperation {
+                operation: encoding.decode(&signed.operation).context(Deserialize)?,
+                signature: signature(encoding, signed.typed_signature.as_ref(), &signed.signature)?,
+            })
+        };
+
+        reply
+            .evidence
+            .into_iter()
+            .map(|evidence| {
+                Ok(Evidence {
+                    timestamp: chrono::DateTime::parse_from_rfc3339(&evidence.timestamp)
+                        .context(DeserializeTimestamp)?
+                        .into(),
+                    sender: public_key(encoding, evidence.typed_sender.as_ref(), &evidence.sender)?,
+                    sequence: evidence.sequence,
+                    first: signed(evidence.first)?,
+                    second: signed(evidence.second)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Get the checkpoint the node took at `epoch`, or its latest one if `None`
+    pub async fn get_checkpoint(&mut self, epoch: Option<u64>) -> Result<Checkpoint> {
+        let message = GetCheckpointRequest {
+            epoch: epoch.unwrap_or_default(),
+        };
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_checkpoint(request).await
+            })
+            .await?;
+
+        Ok(Checkpoint {
+            epoch: reply.epoch,
+            applied: reply.applied,
+            hash: reply.hash,
+            timestamp: chrono::DateTime::parse_from_rfc3339(&reply.timestamp)
+                .context(DeserializeTimestamp)?
+                .into(),
+        })
+    }
+
+    /// Get the hash of the accounts as they are now, updated on each operation
+    ///
+    /// Unlike checkpoints, it is always up to date, so that monitoring can
+    /// compare the nodes at any time; nodes having applied the same number of
+    /// operations but not yet the same ones differ for a while.
+    pub async fn get_state_digest(&mut self) -> Result<StateDigest> {
+        let reply = self
+            .call(GetStateDigestRequest {}, |mut inner, request| async move {
+                inner.get_state_digest(request).await
+            })
+            .await?;
+
+        Ok(StateDigest {
+            digest: reply.digest,
+            applied: reply.applied,
+        })
+    }
+
+    /// Get every account of the node, as used by peers to catch up
+    pub async fn get_snapshot(&mut self) -> Result<Snapshot> {
+        let encoding = self.encoding;
+
+        let reply = self
+            .call(GetSnapshotRequest {}, |mut inner, request| async move {
+                inner.get_snapshot(request).await
+            })
+            .await?;
+
+        Ok(Snapshot {
+            accounts: reply
+                .accounts
+                .into_iter()
+                .map(|balance| {
+                    Ok((
+                        proto_convert::public_key(&balance.account.unwrap_or_default())
+                            .context(Convert)?,
+                        proto_convert::amount(balance.balance, balance.balance_high),
+                        balance.last_sequence,
+                    ))
+                })
+                .collect::<Result<_>>()?,
+            assets: reply
+                .assets
+                .into_iter()
+                .map(|asset| Asset {
+                    id: asset.id,
+                    name: asset.name,
+                })
+                .collect(),
+            asset_balances: reply
+                .asset_balances
+                .into_iter()
+                .map(|balance| {
+                    Ok((
+                        balance.asset,
+                        proto_convert::public_key(&balance.account.unwrap_or_default())
+                            .context(Convert)?,
+                        proto_convert::amount(balance.balance, balance.balance_high),
+                    ))
+                })
+                .collect::<Result<_>>()?,
+            hash: reply.hash,
+            recent_transactions: reply
+                .recent_transactions
+                .iter()
+                .map(|tx| full_transaction(encoding, tx))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Attest, as a bridge validator, that a deposit was seen on its source network
+    ///
+    /// It uses the `sequence` of the validator, as a transfer would. Once
+    /// enough validators attested it, the deposit is minted when delivered;
+    /// see [`Self::get_transaction_status`].
+    pub async fn bridge_deposit(
+        &mut self,
+        validator: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        deposit: &BridgeDeposit,
     ) -> Result<()> {
-        let message = ThinTransaction { recipient, amount };
-        let signature = user.sign(&message).expect("sign failed");
-
-        self.0
-            .send_asset(tonic::Request::new(SendAssetRequest {
-                sender: bincode::serialize(&user.public()).context(Serialize)?,
-                sequence,
-                recipient: bincode::serialize(&recipient).context(Serialize)?,
-                amount,
-                signature: bincode::serialize(&signature).context(Serialize)?,
-            }))
-            .await
-            .context(Rpc)
-            .map(|_| ())
+        let signature = validator
+            .sign(&Operation::BridgeDeposit(deposit.clone()))
+            .expect("sign failed");
+        let (amount, amount_high) = proto_amount(deposit.amount);
+
+        let message = BridgeDepositRequest {
+            validator: self
+                .encoding
+                .encode(&validator.public())
+                .context(Serialize)?,
+            source_network: deposit.source_network.clone(),
+            source_sequence: deposit.source_sequence,
+            user: self.encoding.encode(&deposit.user).context(Serialize)?,
+            amount,
+            signature: self.encoding.encode(&signature).context(Serialize)?,
+            typed_validator: Some(proto_public_key(&validator.public())),
+            typed_user: Some(proto_public_key(&deposit.user)),
+            typed_signature: Some(proto_signature(&signature).context(Convert)?),
+            amount_high,
+            sequence,
+        };
+
+        self.call_write(message, |mut inner, request| async move {
+            inner.bridge_deposit(request).await
+        })
+        .await
+        .map(|_| ())
     }
 
-    /// Return the balance of the user
-    pub async fn get_balance(&mut self, user: &sign::PublicKey) -> Result<u64> {
-        self.0
-            .get_balance(tonic::Request::new(GetBalanceRequest {
-                sender: bincode::serialize(user).context(Serialize)?,
-            }))
+    /// Create `amount` for the `recipient`, as an admin of the network
+    ///
+    /// It uses the `sequence` of the admin, as a transfer would.
+    pub async fn mint(
+        &mut self,
+        admin: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        recipient: sign::PublicKey,
+        amount: Amount,
+    ) -> Result<()> {
+        self.mint_asset(admin, sequence, recipient, amount, None)
             .await
-            .context(Rpc)
-            .map(|reply| reply.get_ref().amount)
     }
 
-    /// Get the latest used sequence
-    pub async fn get_last_sequence(&mut self, user: &sign::PublicKey) -> Result<sieve::Sequence> {
-        self.0
-            .get_last_sequence(tonic::Request::new(GetLastSequenceRequest {
-                sender: bincode::serialize(user).context(Serialize)?,
+    /// Same as [`Client::mint`], in a registered `asset` if given
+    pub async fn mint_asset(
+        &mut self,
+        admin: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        recipient: sign::PublicKey,
+        amount: Amount,
+        asset: Option<AssetId>,
+    ) -> Result<()> {
+        let signature = admin
+            .sign(&Operation::Mint(Mint {
+                recipient,
+                amount,
+                asset: asset.clone(),
             }))
-            .await
-            .context(Rpc)
-            .map(|reply| reply.get_ref().sequence)
+            .expect("sign failed");
+        let (amount, amount_high) = proto_amount(amount);
+
+        let message = MintRequest {
+            admin: self.encoding.encode(&admin.public()).context(Serialize)?,
+            sequence,
+            recipient: self.encoding.encode(&recipient).context(Serialize)?,
+            amount,
+            signature: self.encoding.encode(&signature).context(Serialize)?,
+            typed_admin: Some(proto_public_key(&admin.public())),
+            typed_recipient: Some(proto_public_key(&recipient)),
+            typed_signature: Some(proto_signature(&signature).context(Convert)?),
+            amount_high,
+            asset: asset.unwrap_or_default(),
+            ..Default::default()
+        };
+
+        self.call_write(message, |mut inner, request| async move {
+            inner.mint(request).await
+        })
+        .await
+        .map(|_| ())
     }
 
-    /// Get the number of recently processed transactions
-    pub async fn get_latest_transactions(&mut self) -> Result<Vec<FullTransaction>> {
-        self.0
-            .get_latest_transactions(tonic::Request::new(GetLatestTransactionsRequest {}))
-            .await
-            .context(Rpc)?
-            .into_inner()
-            .transactions
+    /// Add the `asset` to the registry of the network, as an admin of it
+    ///
+    /// It uses the `sequence` of the admin, even if the identifier is taken.
+    pub async fn register_asset(
+        &mut self,
+        admin: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        asset: Asset,
+    ) -> Result<()> {
+        let signature = admin
+            .sign(&Operation::RegisterAsset(asset.clone()))
+            .expect("sign failed");
+
+        let message = RegisterAssetRequest {
+            admin: Some(proto_public_key(&admin.public())),
+            sequence,
+            asset: Some(proto::Asset {
+                id: asset.id,
+                name: asset.name,
+            }),
+            signature: Some(proto_signature(&signature).context(Convert)?),
+        };
+
+        self.call_write(message, |mut inner, request| async move {
+            inner.register_asset(request).await
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Get every asset registered on the network, ordered by identifier
+    pub async fn get_assets(&mut self) -> Result<Vec<Asset>> {
+        let reply = self
+            .call(GetAssetsRequest {}, |mut inner, request| async move {
+                inner.get_assets(request).await
+            })
+            .await?;
+
+        Ok(reply
+            .assets
+            .into_iter()
+            .map(|asset| Asset {
+                id: asset.id,
+                name: asset.name,
+            })
+            .collect())
+    }
+
+    /// Get the balance of `user` in a registered `asset`
+    ///
+    /// [`At2Api::get_balance`] gets the one in the network's own asset.
+    pub async fn get_asset_balance(
+        &mut self,
+        user: &sign::PublicKey,
+        asset: &str,
+    ) -> Result<Amount> {
+        let message = GetBalanceRequest {
+            sender: self.encoding.encode(user).context(Serialize)?,
+            typed_sender: Some(proto_public_key(user)),
+            asset: asset.to_owned(),
+            ..Default::default()
+        };
+
+        self.call(message, |mut inner, request| async move {
+            inner.get_balance(request).await
+        })
+        .await
+        .map(|reply| proto_convert::amount(reply.amount, reply.amount_high))
+    }
+
+    /// Let the `user` use its next `size` sequences in any order, up to
+    /// [`crate::SEQUENCE_WINDOW_MAX_SIZE`]
+    ///
+    /// It uses the `sequence` of the user, within its previous window.
+    pub async fn set_sequence_window(
+        &mut self,
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        size: sieve::Sequence,
+    ) -> Result<()> {
+        let signature = user
+            .sign(&Operation::SetSequenceWindow(size))
+            .expect("sign failed");
+
+        let message = SetSequenceWindowRequest {
+            sender: Some(proto_public_key(&user.public())),
+            sequence,
+            size,
+            signature: Some(proto_signature(&signature).context(Convert)?),
+        };
+
+        self.call_write(message, |mut inner, request| async move {
+            inner.set_sequence_window(request).await
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Make the node log from `level` on, such as `debug`, only for `target` if given
+    ///
+    /// The `admin` is either the node itself or one of its admins. Only the
+    /// first node is changed.
+    pub async fn set_log_level(
+        &mut self,
+        admin: &sign::KeyPair,
+        level: &str,
+        target: Option<&str>,
+    ) -> Result<()> {
+        let change = LogLevel {
+            level: level.to_owned(),
+            target: target.unwrap_or_default().to_owned(),
+            timestamp: chrono::Utc::now().timestamp() as u64,
+        };
+        let signature = admin.sign(&change).expect("sign failed");
+
+        let message = SetLogLevelRequest {
+            level: change.level,
+            target: change.target,
+            timestamp: change.timestamp,
+            admin: Some(proto_public_key(&admin.public())),
+            signature: Some(proto_signature(&signature).context(Convert)?),
+        };
+
+        self.call_write(message, |mut inner, request| async move {
+            inner.set_log_level(request).await
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Get every transaction locking assets into the bridge, newest first
+    pub async fn get_bridge_withdrawals(&mut self) -> Result<Vec<FullTransaction>> {
+        let mut withdrawals = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let page = self.get_bridge_withdrawals_page(cursor, 0).await?;
+            withdrawals.extend(page.transactions);
+
+            cursor = match page.next {
+                None => return Ok(withdrawals),
+                next => next,
+            };
+        }
+    }
+
+    /// Get the transactions locking assets into the bridge among up to `limit`
+    /// of the lock account, newest first
+    ///
+    /// It starts from the newest one, or from the `cursor` of a previous
+    /// [`HistoryPage`], as [`Self::get_account_history`] does.
+    pub async fn get_bridge_withdrawals_page(
+        &mut self,
+        cursor: Option<u64>,
+        limit: u32,
+    ) -> Result<HistoryPage> {
+        let message = GetBridgeWithdrawalsRequest {
+            cursor: cursor.unwrap_or_default(),
+            limit,
+        };
+        let encoding = self.encoding;
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.get_bridge_withdrawals(request).await
+            })
+            .await?;
+
+        Ok(HistoryPage {
+            transactions: reply
+                .transactions
+                .iter()
+                .map(|tx| full_transaction(encoding, tx))
+                .collect::<Result<_>>()?,
+            next: Some(reply.next_cursor).filter(|cursor| *cursor != 0),
+        })
+    }
+}
+
+#[cfg_attr(target_family = "wasm", async_trait(?Send))]
+#[cfg_attr(not(target_family = "wasm"), async_trait)]
+impl At2Api for Client {
+    async fn send_signed(&mut self, transaction: &SignedTransaction) -> Result<()> {
+        let message = self.send_asset_request(transaction)?;
+
+        self.call_write(message, |mut inner, request| async move {
+            inner.send_asset(request).await
+        })
+        .await
+        .map(|_| ())
+    }
+
+    async fn get_balance(&mut self, user: &sign::PublicKey) -> Result<Amount> {
+        let message = GetBalanceRequest {
+            sender: self.encoding.encode(user).context(Serialize)?,
+            typed_sender: Some(proto_public_key(user)),
+            ..Default::default()
+        };
+
+        self.call(message, |mut inner, request| async move {
+            inner.get_balance(request).await
+        })
+        .await
+        .map(|reply| proto_convert::amount(reply.amount, reply.amount_high))
+    }
+
+    async fn get_last_sequence(&mut self, user: &sign::PublicKey) -> Result<sieve::Sequence> {
+        let message = GetLastSequenceRequest {
+            sender: self.encoding.encode(user).context(Serialize)?,
+            typed_sender: Some(proto_public_key(user)),
+            ..Default::default()
+        };
+
+        self.call(message, |mut inner, request| async move {
+            inner.get_last_sequence(request).await
+        })
+        .await
+        .map(|reply| reply.sequence)
+    }
+
+    async fn get_latest_transactions(&mut self) -> Result<Vec<FullTransaction>> {
+        self.get_latest_transactions_up_to(0).await
+    }
+
+    async fn register_name(
+        &mut self,
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        name: String,
+    ) -> Result<()> {
+        let signature = user
+            .sign(&Operation::RegisterName(name.clone()))
+            .expect("sign failed");
+
+        let message = RegisterNameRequest {
+            sender: self.encoding.encode(&user.public()).context(Serialize)?,
+            sequence,
+            name,
+            signature: self.encoding.encode(&signature).context(Serialize)?,
+            typed_sender: Some(proto_public_key(&user.public())),
+            typed_signature: Some(proto_signature(&signature).context(Convert)?),
+            ..Default::default()
+        };
+
+        self.call_write(message, |mut inner, request| async move {
+            inner.register_name(request).await
+        })
+        .await
+        .map(|_| ())
+    }
+
+    async fn resolve_name(&mut self, name: String) -> Result<sign::PublicKey> {
+        let message = ResolveNameRequest { name };
+
+        let reply = self
+            .call(message, |mut inner, request| async move {
+                inner.resolve_name(request).await
+            })
+            .await?;
+
+        public_key(
+            self.encoding,
+            reply.typed_public_key.as_ref(),
+            &reply.public_key,
+        )
+    }
+}
+
+/// Whether the `receipt` is of the `transaction`, signed by one of the
+/// `trusted` nodes
+pub fn verify_receipt(
+    receipt: &Receipt,
+    transaction: &SignedTransaction,
+    trusted: &[sign::PublicKey],
+) -> bool {
+    receipt.statement.is_for(transaction) && trusted.contains(&receipt.node) && receipt.verify()
+}
+
+/// Whether at least `threshold` of the `trusted` nodes attest the proof
+///
+/// The caller still has to check that the [`BalanceStatement::epoch`] is
+/// recent enough, such as against [`crate::current_epoch`].
+pub fn verify_balance_proof(
+    proof: &BalanceProof,
+    trusted: &[sign::PublicKey],
+    threshold: usize,
+) -> bool {
+    let mut attesting = proof
+        .attestations
+        .iter()
+        .filter(|attestation| trusted.contains(&attestation.node))
+        .filter(|attestation| attestation.verify(&proof.statement))
+        .map(|attestation| attestation.node)
+        .collect::<Vec<_>>();
+    // a node attesting twice only counts once
+    attesting.sort_by_key(|node| node.to_string());
+    attesting.dedup();
+
+    attesting.len() >= threshold
+}
+
+/// Account sending transfers, keeping track of its own sequence
+///
+/// The last sequence is asked to the node on first use, then incremented
+/// locally for each transfer. As nodes only check sequences on delivery, a
+/// transfer rejected afterwards, such as for lack of asset, leaves the wallet
+/// ahead of the node; [`Wallet::resync`] then asks the node again, which also
+/// happens by itself when sending fails.
+pub struct Wallet<A = Client> {
+    api: A,
+    keypair: sign::KeyPair,
+    last_sequence: Option<sieve::Sequence>,
+}
+
+impl<A: At2Api> Wallet<A> {
+    /// Create a wallet for `keypair`, reaching the node via `api`
+    pub fn new(api: A, keypair: sign::KeyPair) -> Self {
+        Self {
+            api,
+            keypair,
+            last_sequence: None,
+        }
+    }
+
+    /// Public key of the account
+    pub fn public(&self) -> sign::PublicKey {
+        self.keypair.public()
+    }
+
+    /// Send `amount` to `recipient`, returning the sequence used
+    pub async fn transfer(
+        &mut self,
+        recipient: sign::PublicKey,
+        amount: Amount,
+    ) -> Result<sieve::Sequence> {
+        self.transfer_with_memo(recipient, amount, None).await
+    }
+
+    /// Same as [`Wallet::transfer`], with a memo signed along
+    pub async fn transfer_with_memo(
+        &mut self,
+        recipient: sign::PublicKey,
+        amount: Amount,
+        memo: Option<String>,
+    ) -> Result<sieve::Sequence> {
+        let sequence = match self.last_sequence {
+            Some(last_sequence) => last_sequence,
+            None => self.resync().await?,
+        } + 1;
+
+        let transaction =
+            SignedTransaction::with_memo(&self.keypair, sequence, recipient, amount, memo);
+        if let Err(err) = self.api.send_signed(&transaction).await {
+            self.last_sequence = None;
+            return Err(err);
+        }
+        self.last_sequence = Some(sequence);
+
+        Ok(sequence)
+    }
+
+    /// Forget the local sequence, using the one of the node instead
+    pub async fn resync(&mut self) -> Result<sieve::Sequence> {
+        self.last_sequence = None;
+
+        let last_sequence = self.api.get_last_sequence(&self.keypair.public()).await?;
+        self.last_sequence = Some(last_sequence);
+
+        Ok(last_sequence)
+    }
+
+    /// Node used by the wallet, for other calls
+    pub fn api(&mut self) -> &mut A {
+        &mut self.api
+    }
+}
+
+fn full_transaction(encoding: Encoding, tx: &ProcessedTransaction) -> Result<FullTransaction> {
+    Ok(FullTransaction {
+        timestamp: chrono::DateTime::parse_from_rfc3339(&tx.timestamp)
+            .context(DeserializeTimestamp)?
+            .into(),
+        sender: public_key(encoding, tx.typed_sender.as_ref(), &tx.sender)?,
+        sequence: tx.sequence,
+        recipient: public_key(encoding, tx.typed_recipient.as_ref(), &tx.recipient)?,
+        amount: proto_convert::amount(tx.amount, tx.amount_high),
+        memo: Some(tx.memo.clone()).filter(|memo| !memo.is_empty()),
+        fee: proto_convert::amount(tx.fee, tx.fee_high),
+        asset: Some(tx.asset.clone()).filter(|asset| !asset.is_empty()),
+    })
+}
+
+fn rejected_transaction(tx: &proto::RejectedTransaction) -> Result<RejectedTransaction> {
+    Ok(RejectedTransaction {
+        timestamp: chrono::DateTime::parse_from_rfc3339(&tx.timestamp)
+            .context(DeserializeTimestamp)?
+            .into(),
+        sender: proto_convert::public_key(&tx.sender.clone().unwrap_or_default())
+            .context(Convert)?,
+        sequence: tx.sequence,
+        outputs: tx
+            .outputs
             .iter()
-            .map(|tx| {
-                Ok(FullTransaction {
-                    timestamp: chrono::DateTime::parse_from_rfc3339(&tx.timestamp)
-                        .context(DeserializeTimestamp)?
-                        .into(),
-                    sender: bincode::deserialize(&tx.sender).context(Deserialize)?,
-                    recipient: bincode::deserialize(&tx.recipient).context(Deserialize)?,
-                    amount: tx.amount,
+            .map(|output| {
+                Ok(Output {
+                    recipient: proto_convert::public_key(
+                        &output.recipient.clone().unwrap_or_default(),
+                    )
+                    .context(Convert)?,
+                    amount: proto_convert::amount(output.amount, output.amount_high),
                 })
             })
-            .collect()
+            .collect::<Result<_>>()?,
+        fee: proto_convert::amount(tx.fee, tx.fee_high),
+        asset: Some(tx.asset.clone()).filter(|asset| !asset.is_empty()),
+        reason: tx.reason(),
+        message: tx.message.clone(),
+    })
+}
+
+/// Read a key sent both typed and encoded, preferring the former, as older
+/// nodes only send the latter
+fn public_key(
+    encoding: Encoding,
+    typed: Option<&proto::PublicKey>,
+    bytes: &[u8],
+) -> Result<sign::PublicKey> {
+    match typed {
+        Some(typed) => proto_convert::public_key(typed).context(Convert),
+        None => encoding.decode(bytes).context(Deserialize),
+    }
+}
+
+/// Same as [`public_key`], for signatures
+fn signature(
+    encoding: Encoding,
+    typed: Option<&proto::Signature>,
+    bytes: &[u8],
+) -> Result<sign::Signature> {
+    match typed {
+        Some(typed) => proto_convert::signature(typed).context(Convert),
+        None => encoding.decode(bytes).context(Deserialize),
     }
 }
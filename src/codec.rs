@@ -0,0 +1,168 @@
+//! Serialization of the payloads carried in the RPC messages
+//!
+//! Keys, signatures and such are sent as opaque `bytes`; the [`Codec`] used to
+//! produce them is announced by the client via the [`METADATA_KEY`] metadata.
+
+use std::{fmt, str::FromStr};
+
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use snafu::{ensure, ResultExt, Snafu};
+
+/// gRPC metadata key used to announce the [`Encoding`] of a request
+pub const METADATA_KEY: &str = "at2-encoding";
+
+/// Largest payload accepted by [`Codec::decode`], way above any key or signature
+///
+/// It also bounds what a decoder allocates when trusting a length read from
+/// the payload.
+pub const MAX_DECODED_SIZE: usize = 4096;
+
+/// Error generated by a [`Codec`]
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// Using bincode
+    #[snafu(display("bincode: {}", source))]
+    UsingBincode {
+        /// Source of the error
+        source: bincode::Error,
+    },
+    /// Using CBOR
+    #[cfg(feature = "cbor")]
+    #[snafu(display("cbor: {}", source))]
+    UsingCbor {
+        /// Source of the error
+        source: serde_cbor::Error,
+    },
+    /// Payload is bigger than [`MAX_DECODED_SIZE`]
+    #[snafu(display("payload too large: {} bytes", size))]
+    TooLarge {
+        /// Size of the payload
+        size: usize,
+    },
+    /// Parsing an unknown encoding name
+    #[snafu(display("unknown encoding: {}", name))]
+    UnknownEncoding {
+        /// Name which was given
+        name: String,
+    },
+}
+
+/// Way to convert values to and from bytes
+pub trait Codec {
+    /// Encode the given value
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, Error>;
+    /// Decode a value from the given bytes
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, Error>;
+}
+
+/// Compact binary format, used by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bincode;
+
+impl Codec for Bincode {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, Error> {
+        bincode::serialize(value).context(UsingBincode)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, Error> {
+        ensure!(
+            bytes.len() <= MAX_DECODED_SIZE,
+            TooLarge { size: bytes.len() }
+        );
+
+        // same as bincode::deserialize, but bounded
+        bincode::options()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(MAX_DECODED_SIZE as u64)
+            .deserialize(bytes)
+            .context(UsingBincode)
+    }
+}
+
+/// Self-describing format, easier to implement outside of Rust
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, Error> {
+        serde_cbor::to_vec(value).context(UsingCbor)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, Error> {
+        ensure!(
+            bytes.len() <= MAX_DECODED_SIZE,
+            TooLarge { size: bytes.len() }
+        );
+
+        serde_cbor::from_slice(bytes).context(UsingCbor)
+    }
+}
+
+/// [`Codec`] chosen at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// See [`Bincode`]
+    Bincode,
+    /// See [`Cbor`]
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self::Bincode
+    }
+}
+
+impl Codec for Encoding {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Bincode => Bincode.encode(value),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => Cbor.encode(value),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, Error> {
+        match self {
+            Self::Bincode => Bincode.decode(bytes),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => Cbor.decode(bytes),
+        }
+    }
+}
+
+impl Encoding {
+    /// Name of the encoding, as sent in the metadata
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bincode => "bincode",
+            #[cfg(feature = "cbor")]
+            Self::Cbor => "cbor",
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "bincode" => Ok(Self::Bincode),
+            #[cfg(feature = "cbor")]
+            "cbor" => Ok(Self::Cbor),
+            _ => UnknownEncoding { name }.fail(),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Deterministic values for examples, benches and tests
+//!
+//! The same seed always gives the same keys, and so the same signed
+//! transactions, which makes failures reproducible.
+
+use drop::crypto::sign;
+use hex::FromHex;
+
+use crate::{interchange, Amount, SignedTransaction};
+
+/// Amount of every transaction in [`signed_transactions`]
+pub const AMOUNT: Amount = 1;
+
+/// [SplitMix64](https://prng.di.unimi.it/splitmix64.c), enough to spread a seed
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Signing keys derived from the `seed`
+pub fn keypair(seed: u64) -> sign::KeyPair {
+    let mut state = seed;
+    let bytes = (0..4)
+        .flat_map(|_| splitmix64(&mut state).to_le_bytes())
+        .collect::<Vec<_>>();
+
+    sign::KeyPair::from(
+        sign::PrivateKey::from_hex(hex::encode(bytes)).expect("32 bytes is a valid private key"),
+    )
+}
+
+/// `count` different signing keys, derived from consecutive seeds
+pub fn keypairs(seed: u64, count: usize) -> Vec<sign::KeyPair> {
+    (0..count as u64)
+        .map(|offset| keypair(seed.wrapping_add(offset)))
+        .collect()
+}
+
+/// `count` consecutive transactions of [`AMOUNT`], from the user of `seed`
+/// to the one of `seed + 1`, starting at sequence one
+pub fn signed_transactions(seed: u64, count: usize) -> Vec<SignedTransaction> {
+    let sender = keypair(seed);
+    let recipient = keypair(seed.wrapping_add(1)).public();
+
+    (1..=count as sieve::Sequence)
+        .map(|sequence| SignedTransaction::new(&sender, sequence, recipient, AMOUNT))
+        .collect()
+}
+
+/// [`signed_transactions`], ready to be written for the default chain
+pub fn document(seed: u64, count: usize) -> interchange::Document {
+    interchange::Document::new(
+        interchange::DEFAULT_CHAIN_ID,
+        signed_transactions(seed, count),
+    )
+}
+
+/// Configuration of a node listening on the given addresses, without peers
+///
+/// The signing key is derived from the `seed`, but drop offers no way to
+/// build the network key from bytes, so that one is random.
+#[cfg(feature = "server")]
+pub fn server_config(
+    seed: u64,
+    node: std::net::SocketAddr,
+    rpc: std::net::SocketAddr,
+) -> crate::server::config::Config {
+    use drop::crypto::key::exchange;
+
+    use crate::server::config;
+
+    config::Config {
+        version: config::CONFIG_VERSION,
+        chain_id: interchange::DEFAULT_CHAIN_ID.to_owned(),
+        addresses: config::ConfigAddresses {
+            rpc: rpc.to_string(),
+            json: None,
+            jsonrpc: None,
+            dashboard: None,
+            websocket: None,
+            node: config::ConfigNodeAddress::Same(node.to_string()),
+        },
+        tls: None,
+        keys: config::ConfigKeysSource::Inline(config::ConfigKeys {
+            sign: keypair(seed).private(),
+            network: exchange::KeyPair::random().secret().to_owned(),
+        }),
+        protocol: Default::default(),
+        runtime: Default::default(),
+        recent_transactions: Default::default(),
+        checkpoints: Default::default(),
+        sync: None,
+        events: None,
+        telemetry: None,
+        bridge: None,
+        rate_limit: None,
+        fees: None,
+        validation: Default::default(),
+        storage_path: None,
+        admins: vec![],
+        reflection: false,
+        authenticated_reads: false,
+        open_network: false,
+        max_body_size: config::DEFAULT_MAX_BODY_SIZE,
+        genesis: Default::default(),
+        nodes: vec![],
+    }
+}
@@ -0,0 +1,248 @@
+//! Portable file format for signed transactions, not yet submitted
+//!
+//! A [`Document`] is stored as JSON, tagged with its format and version so
+//! that readers can reject what they do not understand:
+//!
+//! ```json
+//! {
+//!   "format": "at2-signed-transactions",
+//!   "version": 6,
+//!   "chain_id": "at2",
+//!   "transactions": [
+//!     {
+//!       "sender": "at21…",
+//!       "sequence": 1,
+//!       "recipient": "at21…",
+//!       "amount": 10,
+//!       "memo": "invoice 42",
+//!       "outputs": [{ "recipient": "at21…", "amount": 5 }],
+//!       "fee": 1,
+//!       "asset": "EUR",
+//!       "signature": "<hex>"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Users are written as [`Address`]es and signatures as hex encoded bincode.
+//! The `memo` is optional, and was added in the second version; the further
+//! `outputs` as well, added in the third one, and the `fee`, zero if absent,
+//! added in the fourth one. Since the fifth one, amounts are 128 bits; as
+//! signatures cover their size, older documents are refused. The same goes for
+//! the `asset`, absent for the network's own, added in the sixth one.
+
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, ResultExt, Snafu};
+
+use crate::{address::Address, is_valid_memo, Amount, AssetId, Output, SignedTransaction};
+
+/// Name of the format, as written in every document
+pub const FORMAT: &str = "at2-signed-transactions";
+/// Version of the format written by this library
+pub const VERSION: u32 = 6;
+/// Oldest version read by this library
+pub const MIN_VERSION: u32 = 6;
+/// Chain used when none is configured
+pub const DEFAULT_CHAIN_ID: &str = "at2";
+
+/// Error generated when reading or writing a [`Document`]
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// Writing the JSON
+    #[snafu(display("encode: {}", source))]
+    Encode {
+        /// Source of the error
+        source: serde_json::Error,
+    },
+    /// Reading the JSON
+    #[snafu(display("decode: {}", source))]
+    Decode {
+        /// Source of the error
+        source: serde_json::Error,
+    },
+    /// Document is not of this format
+    #[snafu(display("unknown format: {}", format))]
+    UnknownFormat {
+        /// Format found in the document
+        format: String,
+    },
+    /// Document was written by a newer version, or a too old one
+    #[snafu(display("unsupported version: {}", version))]
+    UnsupportedVersion {
+        /// Version found in the document
+        version: u32,
+    },
+    /// Memo is too long or empty
+    #[snafu(display("invalid memo"))]
+    InvalidMemo,
+    /// User is not a valid address
+    #[snafu(display("address: {}", source))]
+    InvalidAddress {
+        /// Source of the error
+        source: crate::address::Error,
+    },
+    /// Signature is not valid hex
+    #[snafu(display("signature: {}", source))]
+    SignatureHex {
+        /// Source of the error
+        source: hex::FromHexError,
+    },
+    /// Converting the signature to and from bytes
+    #[snafu(display("signature: {}", source))]
+    SignatureBytes {
+        /// Source of the error
+        source: bincode::Error,
+    },
+}
+
+/// Signed transactions for a given chain
+#[derive(Debug, Clone)]
+pub struct Document {
+    /// Network the transactions are meant for
+    pub chain_id: String,
+    /// Transactions, in submission order
+    pub transactions: Vec<SignedTransaction>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RawDocument {
+    format: String,
+    version: u32,
+    chain_id: String,
+    transactions: Vec<RawTransaction>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RawTransaction {
+    sender: String,
+    sequence: sieve::Sequence,
+    recipient: String,
+    amount: Amount,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    outputs: Vec<RawOutput>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    fee: Amount,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    asset: Option<AssetId>,
+    signature: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RawOutput {
+    recipient: String,
+    amount: Amount,
+}
+
+fn is_zero(fee: &Amount) -> bool {
+    *fee == 0
+}
+
+impl RawTransaction {
+    fn new(tx: &SignedTransaction) -> Result<Self, Error> {
+        Ok(Self {
+            sender: Address::from(tx.sender).to_string(),
+            sequence: tx.sequence,
+            recipient: Address::from(tx.recipient).to_string(),
+            amount: tx.amount,
+            memo: tx.memo.clone(),
+            outputs: tx
+                .outputs
+                .iter()
+                .map(|output| RawOutput {
+                    recipient: Address::from(output.recipient).to_string(),
+                    amount: output.amount,
+                })
+                .collect(),
+            fee: tx.fee,
+            asset: tx.asset.clone(),
+            signature: hex::encode(bincode::serialize(&tx.signature).context(SignatureBytes)?),
+        })
+    }
+
+    fn parse(self) -> Result<SignedTransaction, Error> {
+        let parse_address = |address: String| {
+            address
+                .parse::<Address>()
+                .map(Into::into)
+                .context(InvalidAddress)
+        };
+
+        if let Some(memo) = &self.memo {
+            ensure!(is_valid_memo(memo), InvalidMemo);
+        }
+
+        Ok(SignedTransaction {
+            sender: parse_address(self.sender)?,
+            sequence: self.sequence,
+            recipient: parse_address(self.recipient)?,
+            amount: self.amount,
+            memo: self.memo,
+            outputs: self
+                .outputs
+                .into_iter()
+                .map(|output| {
+                    Ok(Output {
+                        recipient: parse_address(output.recipient)?,
+                        amount: output.amount,
+                    })
+                })
+                .collect::<Result<_, Error>>()?,
+            fee: self.fee,
+            asset: self.asset,
+            signature: bincode::deserialize(&hex::decode(self.signature).context(SignatureHex)?)
+                .context(SignatureBytes)?,
+        })
+    }
+}
+
+impl Document {
+    /// Create a document for the given chain
+    pub fn new(chain_id: impl Into<String>, transactions: Vec<SignedTransaction>) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+            transactions,
+        }
+    }
+
+    /// Read a document, checking its format and version
+    pub fn from_reader(reader: impl io::Read) -> Result<Self, Error> {
+        let raw: RawDocument = serde_json::from_reader(reader).context(Decode)?;
+
+        ensure!(raw.format == FORMAT, UnknownFormat { format: raw.format });
+        ensure!(
+            (MIN_VERSION..=VERSION).contains(&raw.version),
+            UnsupportedVersion {
+                version: raw.version
+            }
+        );
+
+        Ok(Self {
+            chain_id: raw.chain_id,
+            transactions: raw
+                .transactions
+                .into_iter()
+                .map(RawTransaction::parse)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Write the document, in the current version
+    pub fn to_writer(&self, writer: impl io::Write) -> Result<(), Error> {
+        let raw = RawDocument {
+            format: FORMAT.to_owned(),
+            version: VERSION,
+            chain_id: self.chain_id.clone(),
+            transactions: self
+                .transactions
+                .iter()
+                .map(RawTransaction::new)
+                .collect::<Result<_, _>>()?,
+        };
+
+        serde_json::to_writer_pretty(writer, &raw).context(Encode)
+    }
+}
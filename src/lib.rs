@@ -5,42 +5,576 @@
 use drop::crypto::sign;
 use serde::{Deserialize, Serialize};
 
+pub mod address;
+pub mod api;
+pub mod auth;
 pub mod client;
+pub mod codec;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+pub mod interchange;
+
+#[cfg(feature = "server")]
+#[allow(missing_docs)]
+pub mod server;
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+pub mod version;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 /// `tonic-build` generated files
 #[allow(missing_docs)]
 pub mod proto;
+pub mod proto_convert;
+
+/// Quantity of asset, as held by an account or sent in a transfer
+///
+/// Every computation on it is checked, refusing what would overflow.
+pub type Amount = u128;
+
+/// Asset registered on a network, see [`Operation::RegisterAsset`]
+///
+/// Where an asset is optional, `None` stands for the network's own asset, the
+/// one every account starts with.
+pub type AssetId = String;
 
 /// Type of message sent via sieve
 #[drop::message]
+pub enum Operation {
+    /// Send asset to another user
+    Transfer(ThinTransaction),
+    /// Register a name for the sender, see [`is_valid_name`]
+    RegisterName(String),
+    /// Create asset, only accepted from an admin of the network
+    Mint(Mint),
+    /// Let the sender use this many sequences following its last one in any
+    /// order, each at most once
+    SetSequenceWindow(sieve::Sequence),
+    /// Add an asset to the registry, only accepted from an admin of the network
+    RegisterAsset(Asset),
+    /// Attest a deposit, only accepted from a validator of the bridge
+    BridgeDeposit(BridgeDeposit),
+}
+
+/// Entry of the registry of assets, see [`Operation::RegisterAsset`]
+#[drop::message]
+pub struct Asset {
+    /// Unique on the network, see [`is_valid_asset_id`]
+    pub id: AssetId,
+    /// Free text for the users, such as "Euro"
+    pub name: String,
+}
+
+/// Creation of asset, as sent via sieve
+#[drop::message]
+pub struct Mint {
+    /// User receiving the amount
+    pub recipient: sign::PublicKey,
+    /// How many asset to create
+    pub amount: Amount,
+    /// Registered one to create, the network's own if `None`
+    pub asset: Option<AssetId>,
+}
+
+/// Transfer of asset, as sent via sieve
+#[drop::message]
 pub struct ThinTransaction {
     /// User receiving the amount
     pub recipient: sign::PublicKey,
     /// How many asset to send
-    pub amount: u64,
+    pub amount: Amount,
+    /// Free text for the recipient, such as a payment reference
+    pub memo: Option<String>,
+    /// Further recipients, paid atomically along the first one
+    pub outputs: Vec<Output>,
+    /// Paid on top of the outputs to the network's fee account, see [`Fees`]
+    pub fee: Amount,
+    /// Registered one sent, the fee included, the network's own if `None`
+    pub asset: Option<AssetId>,
+}
+
+impl ThinTransaction {
+    /// Every recipient with its amount, the first one included
+    pub fn all_outputs(&self) -> Vec<Output> {
+        let first = Output {
+            recipient: self.recipient,
+            amount: self.amount,
+        };
+
+        std::iter::once(first)
+            .chain(self.outputs.iter().cloned())
+            .collect()
+    }
+
+    /// Sum of the amounts of every output, without the fee, if it fits
+    pub fn total(&self) -> Option<Amount> {
+        self.outputs.iter().try_fold(self.amount, |total, output| {
+            total.checked_add(output.amount)
+        })
+    }
+}
+
+/// Fee asked by a network for each transfer
+///
+/// Nodes refuse transfers declaring a lower fee than [`Fees::minimum`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fees {
+    /// Paid whatever the amount
+    pub flat: Amount,
+    /// Paid per ten thousand of the total amount, rounded down
+    pub basis_points: u64,
+}
+
+impl Fees {
+    /// Lowest fee accepted for transferring `total`
+    pub fn minimum(&self, total: Amount) -> Amount {
+        let basis_points = Amount::from(self.basis_points);
+        // split to only overflow if the result does
+        let proportional = (total / 10_000)
+            .saturating_mul(basis_points)
+            .saturating_add(total % 10_000 * basis_points / 10_000);
+
+        self.flat.saturating_add(proportional)
+    }
+}
+
+/// Recipient of a transfer, see [`ThinTransaction::outputs`]
+#[drop::message]
+pub struct Output {
+    /// User receiving the amount
+    pub recipient: sign::PublicKey,
+    /// How many asset to send
+    pub amount: Amount,
+}
+
+/// Maximum number of further outputs of a transfer
+pub const OUTPUTS_MAX_LEN: usize = 16;
+
+/// Maximum length of a memo, in bytes
+pub const MEMO_MAX_LEN: usize = 128;
+
+/// Whether the memo can be attached to a transfer
+///
+/// An empty memo is sent as no memo at all, so it is refused to keep the
+/// signature valid; see [`SignedTransaction::with_memo`].
+pub fn is_valid_memo(memo: &str) -> bool {
+    !memo.is_empty() && memo.len() <= MEMO_MAX_LEN
+}
+
+/// Metadata carrying the ID of a call, echoed back by the node and found in
+/// its logs
+pub const REQUEST_ID_METADATA_KEY: &str = "at2-request-id";
+
+/// Maximum length of a call ID given by the client
+pub const REQUEST_ID_MAX_LEN: usize = 64;
+
+/// Metadata of a write refused by a read-only follower, carrying the RPC of a
+/// node accepting it, empty if unknown to the follower
+pub const VALIDATOR_METADATA_KEY: &str = "at2-validator";
+
+/// Maximum length of a registered name
+pub const NAME_MAX_LEN: usize = 32;
+
+/// Most sequences a sender can use out of order, see
+/// [`Operation::SetSequenceWindow`]
+pub const SEQUENCE_WINDOW_MAX_SIZE: sieve::Sequence = 64;
+
+/// Maximum length of an asset identifier
+pub const ASSET_ID_MAX_LEN: usize = 16;
+
+/// Whether the asset identifier can be registered
+///
+/// Identifiers are made of uppercase ASCII letters and digits, up to
+/// [`ASSET_ID_MAX_LEN`] characters, such as `EUR`.
+pub fn is_valid_asset_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= ASSET_ID_MAX_LEN
+        && id
+            .bytes()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/// Whether the name can be registered
+///
+/// Names are made of lowercase ASCII letters, digits and dashes, up to
+/// [`NAME_MAX_LEN`] characters.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= NAME_MAX_LEN
+        && name
+            .bytes()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-')
+}
+
+/// Attestation by a bridge validator that assets were locked on another network
+///
+/// The locked amount is to be minted for the same user on this network. It is
+/// signed and broadcasted as an [`Operation::BridgeDeposit`], using a sequence
+/// of the validator.
+#[drop::message]
+pub struct BridgeDeposit {
+    /// Network where the assets were locked
+    pub source_network: String,
+    /// Sequence of the locking transaction, unique per user on its network
+    pub source_sequence: sieve::Sequence,
+    /// User who locked the assets, and who receives them
+    pub user: sign::PublicKey,
+    /// How many asset were locked
+    pub amount: Amount,
+}
+
+/// Transaction signed by its sender, ready to be broadcasted
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SignedTransaction {
+    /// User sending it
+    pub sender: sign::PublicKey,
+    /// Sequence of the sender
+    pub sequence: sieve::Sequence,
+    /// User receiving it
+    pub recipient: sign::PublicKey,
+    /// How many asset to send
+    pub amount: Amount,
+    /// Free text for the recipient, see [`is_valid_memo`]
+    pub memo: Option<String>,
+    /// Further recipients, see [`ThinTransaction::outputs`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<Output>,
+    /// Paid to the network, see [`ThinTransaction::fee`]
+    #[serde(default)]
+    pub fee: Amount,
+    /// Asset sent, see [`ThinTransaction::asset`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<AssetId>,
+    /// Signature of the [`Operation::Transfer`] by the sender
+    pub signature: sign::Signature,
+}
+
+impl SignedTransaction {
+    /// Sign a new transaction with the user's keys
+    pub fn new(
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        recipient: sign::PublicKey,
+        amount: Amount,
+    ) -> Self {
+        Self::with_memo(user, sequence, recipient, amount, None)
+    }
+
+    /// Sign a new transaction carrying a memo, an empty one being dropped
+    pub fn with_memo(
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        recipient: sign::PublicKey,
+        amount: Amount,
+        memo: Option<String>,
+    ) -> Self {
+        Self::with_outputs(user, sequence, recipient, amount, vec![], memo)
+    }
+
+    /// Sign a new transaction paying further `outputs` along the `recipient`
+    pub fn with_outputs(
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        recipient: sign::PublicKey,
+        amount: Amount,
+        outputs: Vec<Output>,
+        memo: Option<String>,
+    ) -> Self {
+        Self::with_fee(user, sequence, recipient, amount, outputs, memo, 0)
+    }
+
+    /// Same as [`SignedTransaction::with_outputs`], paying a `fee` to the network
+    pub fn with_fee(
+        user: &sign::KeyPair,
+        sequence: sieve::Sequence,
+        recipient: sign::PublicKey,
+        amount: Amount,
+        outputs: Vec<Output>,
+        memo: Option<String>,
+        fee: Amount,
+    ) -> Self {
+        Self::sign(
+            user,
+            sequence,
+            ThinTransaction {
+                recipient,
+                amount,
+                memo,
+                outputs,
+                fee,
+                asset: None,
+            },
+        )
+    }
+
+    /// Sign `thin` as it is, such as to send another asset than the network's
+    /// own, an empty memo being dropped
+    pub fn sign(user: &sign::KeyPair, sequence: sieve::Sequence, thin: ThinTransaction) -> Self {
+        let thin = ThinTransaction {
+            memo: thin.memo.filter(|memo| !memo.is_empty()),
+            ..thin
+        };
+
+        let signature = user
+            .sign(&Operation::Transfer(thin.clone()))
+            .expect("sign failed");
+
+        Self {
+            sender: user.public(),
+            sequence,
+            recipient: thin.recipient,
+            amount: thin.amount,
+            memo: thin.memo,
+            outputs: thin.outputs,
+            fee: thin.fee,
+            asset: thin.asset,
+            signature,
+        }
+    }
+
+    /// Whether the signature was made by the sender
+    pub fn verify(&self) -> bool {
+        self.signature
+            .verify(&Operation::Transfer(self.thin()), &self.sender)
+            .is_ok()
+    }
+
+    /// Operation signed by the sender
+    pub fn thin(&self) -> ThinTransaction {
+        ThinTransaction {
+            recipient: self.recipient,
+            amount: self.amount,
+            memo: self.memo.clone(),
+            outputs: self.outputs.clone(),
+            fee: self.fee,
+            asset: self.asset.clone(),
+        }
+    }
 }
 
 /// Transaction when committed to memory
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FullTransaction {
     /// When the transaction was stored
     pub timestamp: chrono::DateTime<chrono::Utc>,
     /// User sending it
     pub sender: sign::PublicKey,
+    /// Sequence of the sender
+    pub sequence: sieve::Sequence,
     /// User receiving it
     pub recipient: sign::PublicKey,
     /// How many asset to send
-    pub amount: u64,
+    pub amount: Amount,
+    /// Free text for the recipient
+    pub memo: Option<String>,
+    /// Paid by the sender to the network, only set on the first output
+    #[serde(default)]
+    pub fee: Amount,
+    /// Asset sent, the network's own if `None`
+    #[serde(default)]
+    pub asset: Option<AssetId>,
 }
 
 impl FullTransaction {
-    /// Expend a [`ThinTransaction`] to a full one
-    pub fn with_thin(sender: sign::PublicKey, thin: ThinTransaction) -> Self {
+    /// Expend a [`ThinTransaction`] to a full one, keeping only its first output
+    pub fn with_thin(
+        sender: sign::PublicKey,
+        sequence: sieve::Sequence,
+        thin: ThinTransaction,
+    ) -> Self {
         Self {
             timestamp: chrono::Utc::now(),
             sender,
+            sequence,
             recipient: thin.recipient,
             amount: thin.amount,
+            memo: thin.memo,
+            fee: thin.fee,
+            asset: thin.asset,
         }
     }
+
+    /// Expend a [`ThinTransaction`] to a full one per output, in order
+    pub fn all_with_thin(
+        sender: sign::PublicKey,
+        sequence: sieve::Sequence,
+        thin: ThinTransaction,
+    ) -> Vec<Self> {
+        let timestamp = chrono::Utc::now();
+
+        thin.all_outputs()
+            .into_iter()
+            .enumerate()
+            .map(|(index, output)| Self {
+                timestamp,
+                sender,
+                sequence,
+                recipient: output.recipient,
+                amount: output.amount,
+                memo: thin.memo.clone(),
+                fee: if index == 0 { thin.fee } else { 0 },
+                asset: thin.asset.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Operation with the signature of its sender, see [`Evidence`]
+#[derive(Debug, Clone)]
+pub struct SignedOperation {
+    /// What was signed
+    pub operation: Operation,
+    /// Signature of the sender
+    pub signature: sign::Signature,
+}
+
+/// Two different operations signed by a sender for the same sequence
+///
+/// Honest senders never do so, as only one of them can be applied; this is
+/// proof that the sender tried to double spend.
+#[derive(Debug, Clone)]
+pub struct Evidence {
+    /// When the node noticed it
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// User signing both
+    pub sender: sign::PublicKey,
+    /// Sequence used by both
+    pub sequence: sieve::Sequence,
+    /// Delivered first
+    pub first: SignedOperation,
+    /// Delivered afterwards
+    pub second: SignedOperation,
+}
+
+impl Evidence {
+    /// Whether both operations differ and are signed by the sender
+    pub fn verify(&self) -> bool {
+        let encoded = |signed: &SignedOperation| bincode::serialize(&signed.operation).ok();
+
+        encoded(&self.first) != encoded(&self.second)
+            && [&self.first, &self.second].iter().all(|signed| {
+                signed
+                    .signature
+                    .verify(&signed.operation, &self.sender)
+                    .is_ok()
+            })
+    }
+}
+
+/// Change of what a node logs, signed by one of its admins
+#[drop::message]
+pub struct LogLevel {
+    /// Such as `debug`
+    pub level: String,
+    /// Only change the logs of this target, every target if empty
+    pub target: String,
+    /// When it was signed, in seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// How far from the node's time a [`LogLevel`] can be signed
+pub const LOG_LEVEL_MAX_SKEW: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How long an epoch of a [`BalanceStatement`] lasts
+pub const EPOCH_DURATION: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Epoch of the current time, see [`EPOCH_DURATION`]
+pub fn current_epoch() -> u64 {
+    chrono::Utc::now().timestamp() as u64 / EPOCH_DURATION.as_secs()
+}
+
+/// State of an account as seen by a node, see [`BalanceProof`]
+#[drop::message]
+pub struct BalanceStatement {
+    /// User owning the account
+    pub account: sign::PublicKey,
+    /// How many asset it holds
+    pub balance: Amount,
+    /// Last sequence used by the user
+    pub last_sequence: sieve::Sequence,
+    /// When it was seen, see [`current_epoch`]
+    pub epoch: u64,
+}
+
+/// Signature of a [`BalanceStatement`] by a node
+#[derive(Debug, Clone)]
+pub struct BalanceAttestation {
+    /// Signing key of the node
+    pub node: sign::PublicKey,
+    /// Signature of the statement by the node
+    pub signature: sign::Signature,
+}
+
+impl BalanceAttestation {
+    /// Whether the node signed this `statement`
+    pub fn verify(&self, statement: &BalanceStatement) -> bool {
+        self.signature.verify(statement, &self.node).is_ok()
+    }
+}
+
+/// State of an account, attested by one or more nodes
+///
+/// A single node can lie, so light clients should only believe statements
+/// attested by enough of the nodes they trust, see
+/// [`client::verify_balance_proof`].
+#[derive(Debug, Clone)]
+pub struct BalanceProof {
+    /// What the nodes attest
+    pub statement: BalanceStatement,
+    /// Attestations of the statement, the answering node first
+    pub attestations: Vec<BalanceAttestation>,
+}
+
+/// Transfer as accepted by a node, see [`Receipt`]
+///
+/// Only the first output of a transfer is stated.
+#[drop::message]
+pub struct ReceiptStatement {
+    /// User sending it
+    pub sender: sign::PublicKey,
+    /// Sequence of the sender
+    pub sequence: sieve::Sequence,
+    /// User receiving it
+    pub recipient: sign::PublicKey,
+    /// How many asset to send
+    pub amount: Amount,
+    /// When the node received it, in milliseconds since the unix epoch
+    pub received_at: u64,
+    /// Asset sent, see [`ThinTransaction::asset`]
+    pub asset: Option<AssetId>,
+}
+
+impl ReceiptStatement {
+    /// Whether it states the given `transaction`
+    pub fn is_for(&self, transaction: &SignedTransaction) -> bool {
+        self.sender == transaction.sender
+            && self.sequence == transaction.sequence
+            && self.recipient == transaction.recipient
+            && self.amount == transaction.amount
+            && self.asset == transaction.asset
+    }
+}
+
+/// Proof that a node accepted a transfer, to be kept by its sender
+///
+/// It doesn't tell that the transfer was delivered, only that the node took
+/// it to broadcast, see [`client::verify_receipt`].
+#[derive(Debug, Clone)]
+pub struct Receipt {
+    /// What the node accepted
+    pub statement: ReceiptStatement,
+    /// Signing key of the node
+    pub node: sign::PublicKey,
+    /// Signature of the statement by the node
+    pub signature: sign::Signature,
+}
+
+impl Receipt {
+    /// Whether the node signed the statement
+    pub fn verify(&self) -> bool {
+        self.signature.verify(&self.statement, &self.node).is_ok()
+    }
 }
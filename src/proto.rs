@@ -1,5 +1,9 @@
 tonic::include_proto!("at2");
 
+/// Encoded descriptors of the schema, served by the reflection service
+#[cfg(feature = "server")]
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("at2_descriptor");
+
 #[cfg(feature = "server")]
 impl<T: at2_server::At2> tonic::transport::NamedService for at2_server::At2Server<T> {
     const NAME: &'static str = "at2.AT2";
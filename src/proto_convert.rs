@@ -0,0 +1,102 @@
+//! Conversions between the typed messages of the RPC schema and the crypto types
+//!
+//! Contrary to the deprecated `bytes` fields, which follow the
+//! [`Encoding`](crate::codec::Encoding) announced by the client, the typed
+//! messages have a single canonical encoding: a [`proto::PublicKey`] holds the
+//! 32 raw bytes of the key and a [`proto::Signature`] the signature as
+//! serialized by bincode.
+//!
+//! An [`Amount`] is split in two `uint64` fields, its lower half keeping the
+//! original name and its upper half suffixed by `_high`, see [`amount`].
+//!
+//! Refused account operations carry a [`proto::AccountError`] in the details
+//! of their [`tonic::Status`], see [`account_status`].
+
+use drop::crypto::sign;
+use hex::FromHex;
+use prost::Message;
+use snafu::{ensure, ResultExt, Snafu};
+
+use crate::{
+    codec::{self, Bincode, Codec},
+    proto, Amount,
+};
+
+/// Size of a [`proto::PublicKey`]
+pub const PUBLIC_KEY_SIZE: usize = 32;
+
+/// Error generated when converting a typed message
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// Public key doesn't have [`PUBLIC_KEY_SIZE`] bytes
+    #[snafu(display("public key: expected {} bytes, got {}", PUBLIC_KEY_SIZE, size))]
+    PublicKeySize {
+        /// Size of the given key
+        size: usize,
+    },
+    /// Bytes aren't a valid public key
+    #[snafu(display("public key: invalid"))]
+    InvalidPublicKey,
+    /// Converting the signature to and from bytes
+    #[snafu(display("signature: {}", source))]
+    SignatureBytes {
+        /// Source of the error
+        source: codec::Error,
+    },
+}
+
+/// Convert a key to its typed message
+pub fn proto_public_key(public_key: &sign::PublicKey) -> proto::PublicKey {
+    proto::PublicKey {
+        // displayed as hex of its raw bytes
+        bytes: hex::decode(public_key.to_string()).expect("public key is displayed as hex"),
+    }
+}
+
+/// Read a key from its typed message
+pub fn public_key(public_key: &proto::PublicKey) -> Result<sign::PublicKey, Error> {
+    let size = public_key.bytes.len();
+    ensure!(size == PUBLIC_KEY_SIZE, PublicKeySize { size });
+
+    sign::PublicKey::from_hex(hex::encode(&public_key.bytes)).map_err(|_| Error::InvalidPublicKey)
+}
+
+/// Convert a signature to its typed message
+pub fn proto_signature(signature: &sign::Signature) -> Result<proto::Signature, Error> {
+    Ok(proto::Signature {
+        bytes: Bincode.encode(signature).context(SignatureBytes)?,
+    })
+}
+
+/// Read a signature from its typed message
+pub fn signature(signature: &proto::Signature) -> Result<sign::Signature, Error> {
+    Bincode.decode(&signature.bytes).context(SignatureBytes)
+}
+
+/// Join the halves of an amount
+pub fn amount(low: u64, high: u64) -> Amount {
+    Amount::from(high) << 64 | Amount::from(low)
+}
+
+/// Split an amount in its lower and upper halves
+pub fn proto_amount(amount: Amount) -> (u64, u64) {
+    (amount as u64, (amount >> 64) as u64)
+}
+
+/// Status refusing an account operation, with `error` in its details
+pub fn account_status(
+    code: tonic::Code,
+    message: impl Into<String>,
+    error: proto::AccountError,
+) -> tonic::Status {
+    tonic::Status::with_details(code, message, error.encode_to_vec().into())
+}
+
+/// Read the [`proto::AccountError`] in the details of `status`, if any
+pub fn account_error(status: &tonic::Status) -> Option<proto::AccountError> {
+    if status.details().is_empty() {
+        return None;
+    }
+
+    proto::AccountError::decode(status.details()).ok()
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, OptionExt};
+
+use super::{BalanceOverflow, Error, InsufficientBalance, SequenceAlreadyUsed, SequenceGap};
+use crate::Amount;
+
+/// Contains the balance for a user
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct Account {
+    last_sequence: sieve::Sequence,
+    balance: Amount,
+}
+
+impl Account {
+    /// Create a new account
+    pub fn new() -> Self {
+        Self::with_balance(10) // TODO create faucet
+    }
+
+    /// Create a new account owning `balance`
+    pub fn with_balance(balance: Amount) -> Self {
+        Self {
+            last_sequence: sieve::Sequence::MIN,
+            balance,
+        }
+    }
+
+    /// Account as copied from a peer, see [`super::Snapshot`]
+    pub fn restored(balance: Amount, last_sequence: sieve::Sequence) -> Self {
+        Self {
+            last_sequence,
+            balance,
+        }
+    }
+
+    /// Add some amount to this account
+    pub fn credit(&self, amount: Amount) -> Result<Self, Error> {
+        Ok(Self {
+            last_sequence: self.last_sequence,
+            balance: self.balance.checked_add(amount).context(BalanceOverflow)?,
+        })
+    }
+
+    /// Remove some amount from this account, iff the `sequence` is unused and
+    /// within the `window` following the last one
+    ///
+    /// The last sequence then moves over the used ones following it.
+    pub fn debit(
+        &self,
+        window: &Window,
+        sequence: sieve::Sequence,
+        amount: Amount,
+    ) -> Result<(Self, Window), Error> {
+        let last = self.last_sequence;
+        ensure!(
+            sequence > last && !window.used.contains(&sequence),
+            SequenceAlreadyUsed { sequence, last }
+        );
+        ensure!(
+            sequence - last <= window.size(),
+            SequenceGap { sequence, last }
+        );
+
+        let balance = self
+            .balance
+            .checked_sub(amount)
+            .context(InsufficientBalance {
+                have: self.balance,
+                need: amount,
+            })?;
+
+        let mut used = window.used.clone();
+        used.insert(sequence);
+        let mut last_sequence = last;
+        while used.remove(&(last_sequence + 1)) {
+            last_sequence += 1;
+        }
+
+        Ok((
+            Self {
+                last_sequence,
+                balance,
+            },
+            Window {
+                size: window.size,
+                used,
+            },
+        ))
+    }
+
+    /// Return the last used sequence
+    pub fn last_sequence(&self) -> sieve::Sequence {
+        self.last_sequence
+    }
+
+    /// Return the owned amount
+    pub fn balance(&self) -> Amount {
+        self.balance
+    }
+}
+
+/// Sequences following the last one that a sender can use out of order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Window {
+    /// As set by the sender, zero if never set
+    size: sieve::Sequence,
+    /// Used ones, past the last sequence
+    used: BTreeSet<sieve::Sequence>,
+}
+
+impl Window {
+    /// Same window, but of the given `size`
+    pub fn resized(self, size: sieve::Sequence) -> Self {
+        Self { size, ..self }
+    }
+
+    /// Same window, forgetting the used sequences
+    pub fn cleared(self) -> Self {
+        Self {
+            size: self.size,
+            used: BTreeSet::new(),
+        }
+    }
+
+    /// How many sequences past the last one can be used, only the next one
+    /// by default
+    pub fn size(&self) -> sieve::Sequence {
+        self.size.max(1)
+    }
+
+    /// Whether `sequence` was used, past the last one
+    pub fn is_used(&self, sequence: sieve::Sequence) -> bool {
+        self.used.contains(&sequence)
+    }
+
+    /// How many sequences were used, past the last one
+    pub fn used(&self) -> usize {
+        self.used.len()
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+
+use drop::crypto::sign;
+use serde::{Deserialize, Serialize};
+use snafu::ensure;
+
+use crate::{Amount, BridgeDeposit};
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("deposit already attested with other content"))]
+    ConflictingDeposit,
+}
+
+/// Identify a locking transaction on another network
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct DepositId {
+    source_network: String,
+    user: sign::PublicKey,
+    source_sequence: sieve::Sequence,
+}
+
+/// Validators who saw a deposit, waiting for enough of them
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Attestations {
+    amount: Amount,
+    validators: HashSet<sign::PublicKey>,
+}
+
+impl DepositId {
+    pub fn new(deposit: &BridgeDeposit) -> Self {
+        Self {
+            source_network: deposit.source_network.clone(),
+            user: deposit.user,
+            source_sequence: deposit.source_sequence,
+        }
+    }
+}
+
+impl Attestations {
+    /// No validator saw the `deposit` yet
+    pub fn new(deposit: &BridgeDeposit) -> Self {
+        Self {
+            amount: deposit.amount,
+            validators: HashSet::new(),
+        }
+    }
+
+    /// Record the attestation of a `validator`
+    ///
+    /// Return true if the deposit reached the `threshold` of validators,
+    /// meaning that it should now be minted.
+    pub fn attest(
+        &mut self,
+        validator: sign::PublicKey,
+        deposit: &BridgeDeposit,
+        threshold: usize,
+    ) -> Result<bool, Error> {
+        ensure!(self.amount == deposit.amount, ConflictingDeposit);
+
+        self.validators.insert(validator);
+
+        Ok(self.validators.len() >= threshold)
+    }
+}
@@ -0,0 +1,84 @@
+//! Hashes of the accounts, taken every given number of applied operations
+//!
+//! Each operation using a sequence, that is transfers, name registrations and
+//! mints, adds one to the last sequence of its sender; their sum thus counts
+//! the applied operations, the same on every node and across restarts.
+//! Checkpoint `n` hashes the accounts once `n * interval` were applied.
+//!
+//! As contagion only orders the operations of each sender, two correct nodes
+//! might reach the same count having applied different operations, so a
+//! checkpoint only records the accounts of the node taking it, and isn't to be
+//! compared with the ones of its peers; state digests are, see [`super::digest`].
+
+use drop::crypto::sign;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{account::Account, store, Error};
+use crate::{Amount, Asset, AssetId};
+
+/// Size of [`Checkpoint::hash`]
+pub const HASH_SIZE: usize = 32;
+
+/// Hash of the accounts at an epoch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Number of the checkpoint, starting at one
+    pub epoch: u64,
+    /// Operations applied to the accounts when taken
+    pub applied: u64,
+    /// SHA-256 of every account ever modified, sorted by key, then of the
+    /// registered assets and of their non-zero balances, see [`hash`]
+    pub hash: [u8; HASH_SIZE],
+    /// When this node took it
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Hash the `accounts`, the registered `assets` and the `asset_balances`,
+/// each in any order
+///
+/// Zero balances are left out, as they can't be told from missing ones.
+/// Without any asset, it is the hash of the accounts alone.
+pub fn hash(
+    accounts: Vec<(sign::PublicKey, Account)>,
+    assets: &[Asset],
+    asset_balances: &[(AssetId, sign::PublicKey, Amount)],
+) -> Result<[u8; HASH_SIZE], Error> {
+    let encode = |source| Error::Storage {
+        source: store::Error::Encode { source },
+    };
+
+    let mut encoded = accounts
+        .iter()
+        .map(|(user, account)| Ok((bincode::serialize(user)?, bincode::serialize(account)?)))
+        .collect::<Result<Vec<_>, bincode::Error>>()
+        .map_err(encode)?;
+    encoded.sort_unstable();
+
+    let mut registry = assets
+        .iter()
+        .map(bincode::serialize)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(encode)?;
+    registry.sort_unstable();
+
+    let mut balances = asset_balances
+        .iter()
+        .filter(|(_, _, balance)| *balance != 0)
+        .map(bincode::serialize)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(encode)?;
+    balances.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    for (user, account) in encoded {
+        hasher.update(user);
+        hasher.update(account);
+    }
+    registry
+        .into_iter()
+        .chain(balances)
+        .for_each(|encoded| hasher.update(encoded));
+
+    Ok(hasher.finalize().into())
+}
@@ -0,0 +1,156 @@
+//! Hash of every account, kept up to date as they change
+//!
+//! Each account is hashed on its own, and these hashes are summed modulo
+//! 2^256. Updating an account thus only takes its previous hash away and adds
+//! its new one, without reading the others, and the result doesn't depend on
+//! the order in which the accounts changed.
+//!
+//! Registered assets and the non-zero balances in them are summed the same
+//! way, each hash being prefixed by what it is of, so that it can't be taken
+//! for another.
+
+use drop::crypto::sign;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::{account::Account, store, Error};
+use crate::{Amount, Asset, AssetId};
+
+/// Size of [`StateDigest::to_bytes`]
+pub const HASH_SIZE: usize = 32;
+
+const LIMBS: usize = HASH_SIZE / 8;
+
+/// Sum of the hashes of the accounts, as little endian limbs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateDigest([u64; LIMBS]);
+
+impl StateDigest {
+    /// Digest of the `accounts`, the registered `assets` and the
+    /// `asset_balances`, in any order
+    pub fn of(
+        accounts: &[(sign::PublicKey, Account)],
+        assets: &[Asset],
+        asset_balances: &[(AssetId, sign::PublicKey, Amount)],
+    ) -> Result<Self, Error> {
+        let mut digest = Self::default();
+        for (user, account) in accounts {
+            digest.add(user, account)?;
+        }
+        for asset in assets {
+            digest.add_asset(asset)?;
+        }
+        for (asset, user, balance) in asset_balances {
+            digest.add_asset_balance(asset, user, *balance)?;
+        }
+
+        Ok(digest)
+    }
+
+    /// Account for a new `account`
+    pub fn add(&mut self, user: &sign::PublicKey, account: &Account) -> Result<(), Error> {
+        self.add_leaf(leaf(&[], &(user, account))?);
+
+        Ok(())
+    }
+
+    /// Forget an `account` previously added
+    pub fn remove(&mut self, user: &sign::PublicKey, account: &Account) -> Result<(), Error> {
+        self.remove_leaf(leaf(&[], &(user, account))?);
+
+        Ok(())
+    }
+
+    /// Account for a newly registered `asset`
+    pub fn add_asset(&mut self, asset: &Asset) -> Result<(), Error> {
+        self.add_leaf(leaf(ASSET, asset)?);
+
+        Ok(())
+    }
+
+    /// Account for the `balance` of `user` in `asset`, unless zero
+    pub fn add_asset_balance(
+        &mut self,
+        asset: &str,
+        user: &sign::PublicKey,
+        balance: Amount,
+    ) -> Result<(), Error> {
+        if balance != 0 {
+            self.add_leaf(leaf(ASSET_BALANCE, &(asset, user, balance))?);
+        }
+
+        Ok(())
+    }
+
+    /// Forget a `balance` previously added
+    pub fn remove_asset_balance(
+        &mut self,
+        asset: &str,
+        user: &sign::PublicKey,
+        balance: Amount,
+    ) -> Result<(), Error> {
+        if balance != 0 {
+            self.remove_leaf(leaf(ASSET_BALANCE, &(asset, user, balance))?);
+        }
+
+        Ok(())
+    }
+
+    fn add_leaf(&mut self, leaf: [u64; LIMBS]) {
+        let mut carry = false;
+        for (limb, other) in self.0.iter_mut().zip(leaf.iter()) {
+            let (sum, first) = limb.overflowing_add(*other);
+            let (sum, second) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = first || second;
+        }
+    }
+
+    fn remove_leaf(&mut self, leaf: [u64; LIMBS]) {
+        let mut borrow = false;
+        for (limb, other) in self.0.iter_mut().zip(leaf.iter()) {
+            let (diff, first) = limb.overflowing_sub(*other);
+            let (diff, second) = diff.overflowing_sub(borrow as u64);
+            *limb = diff;
+            borrow = first || second;
+        }
+    }
+
+    /// Little endian encoding
+    pub fn to_bytes(&self) -> [u8; HASH_SIZE] {
+        let mut bytes = [0; HASH_SIZE];
+        for (chunk, limb) in bytes.chunks_exact_mut(8).zip(self.0.iter()) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+
+        bytes
+    }
+}
+
+/// Prefix of the hash of a registered asset
+const ASSET: &[u8] = b"asset";
+/// Prefix of the hash of a balance in a registered asset
+const ASSET_BALANCE: &[u8] = b"asset balance";
+
+/// SHA-256 of the `prefix` then of the encoded `value`, as little endian limbs
+///
+/// Accounts have no prefix, keeping the digest of a network without assets.
+fn leaf(prefix: &[u8], value: &impl Serialize) -> Result<[u64; LIMBS], Error> {
+    let encoded = bincode::serialize(value).map_err(|source| Error::Storage {
+        source: store::Error::Encode { source },
+    })?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(prefix);
+    hasher.update(encoded);
+    let hash = hasher.finalize();
+
+    let mut limbs = [0; LIMBS];
+    for (limb, chunk) in limbs.iter_mut().zip(hash.chunks_exact(8)) {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(chunk);
+        *limb = u64::from_le_bytes(bytes);
+    }
+
+    Ok(limbs)
+}
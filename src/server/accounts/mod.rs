@@ -0,0 +1,1372 @@
+use std::collections::HashMap;
+
+use drop::crypto::sign;
+use sha2::{Digest, Sha256};
+use snafu::{ensure, OptionExt, ResultExt};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{is_valid_asset_id, Amount, Asset, AssetId, BridgeDeposit, SEQUENCE_WINDOW_MAX_SIZE};
+
+mod account;
+use account::{Account, Window};
+mod bridge;
+pub mod checkpoint;
+pub use checkpoint::Checkpoint;
+pub mod digest;
+pub use digest::StateDigest;
+pub mod store;
+use store::Store;
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("unknown account: {}", pubkey))]
+    UnknownAccount { pubkey: Box<sign::PublicKey> },
+    #[snafu(display("insufficient balance: have {}, need {}", have, need))]
+    InsufficientBalance { have: Amount, need: Amount },
+    #[snafu(display("sequence {} already used, last is {}", sequence, last))]
+    SequenceAlreadyUsed {
+        sequence: sieve::Sequence,
+        last: sieve::Sequence,
+    },
+    #[snafu(display("sequence {} already applied, by the same transfer", sequence))]
+    AlreadyApplied { sequence: sieve::Sequence },
+    #[snafu(display("sequence {} skips some, last is {}", sequence, last))]
+    SequenceGap {
+        sequence: sieve::Sequence,
+        last: sieve::Sequence,
+    },
+    #[snafu(display("balance overflow"))]
+    BalanceOverflow,
+    #[snafu(display("bridge: {}", source))]
+    Bridge { source: bridge::Error },
+    #[snafu(display("storage: {}", source))]
+    Storage { source: store::Error },
+    #[snafu(display("transfer overflow"))]
+    TransferOverflow,
+    #[snafu(display("name already registered: {}", name))]
+    NameTaken { name: String },
+    #[snafu(display(
+        "invalid window size {}, expected 1 to {}",
+        size,
+        SEQUENCE_WINDOW_MAX_SIZE
+    ))]
+    InvalidWindow { size: sieve::Sequence },
+    #[snafu(display("unknown asset: {}", asset))]
+    UnknownAsset { asset: AssetId },
+    #[snafu(display("asset already registered: {}", asset))]
+    AssetTaken { asset: AssetId },
+    #[snafu(display("invalid asset identifier: {}", asset))]
+    InvalidAsset { asset: AssetId },
+
+    #[snafu(display("gone on send"))]
+    GoneOnSend,
+    #[snafu(display("gone on recv"))]
+    GoneOnRecv,
+}
+
+type Response<T> = oneshot::Sender<Result<T, Error>>;
+
+/// Starting balance of some users, the others having the default one
+pub type Genesis = HashMap<sign::PublicKey, Amount>;
+
+/// Most accounts returned in a single [`Page`]
+pub const PAGE_MAX_SIZE: usize = 100;
+
+/// Operations applied between two [`Checkpoint`]s, if not configured
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 1_000;
+
+#[derive(Debug)]
+enum Commands {
+    GetBalance {
+        user: Box<sign::PublicKey>,
+        resp: Response<Amount>,
+    },
+    GetLastSequence {
+        user: Box<sign::PublicKey>,
+        resp: Response<sieve::Sequence>,
+    },
+    GetBalanceAndSequence {
+        user: Box<sign::PublicKey>,
+        resp: Response<(Amount, sieve::Sequence)>,
+    },
+    GetVersioned {
+        user: Box<sign::PublicKey>,
+        resp: Response<Versioned>,
+    },
+    Transfer {
+        sender: Box<sign::PublicKey>,
+        sender_sequence: sieve::Sequence,
+        asset: Option<AssetId>,
+        outputs: Vec<(sign::PublicKey, Amount)>,
+        resp: Response<()>,
+    },
+    RegisterName {
+        user: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        name: String,
+        resp: Response<()>,
+    },
+    SetWindow {
+        user: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        size: sieve::Sequence,
+        resp: Response<()>,
+    },
+    ResolveName {
+        name: String,
+        resp: Response<Option<sign::PublicKey>>,
+    },
+    BridgeDeposit {
+        validator: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        deposit: Box<BridgeDeposit>,
+        threshold: usize,
+        resp: Response<bool>,
+    },
+    Mint {
+        admin: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        recipient: Box<sign::PublicKey>,
+        amount: Amount,
+        asset: Option<AssetId>,
+        resp: Response<()>,
+    },
+    RegisterAsset {
+        admin: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        asset: Box<Asset>,
+        resp: Response<()>,
+    },
+    GetAssetBalance {
+        user: Box<sign::PublicKey>,
+        asset: AssetId,
+        resp: Response<Amount>,
+    },
+    GetAssets {
+        resp: Response<Vec<Asset>>,
+    },
+    GetCheckpoint {
+        epoch: Option<u64>,
+        resp: Response<Option<Checkpoint>>,
+    },
+    GetSnapshot {
+        resp: Response<Snapshot>,
+    },
+    GetStateDigest {
+        resp: Response<(StateDigest, u64)>,
+    },
+    List {
+        after: Option<Box<sign::PublicKey>>,
+        limit: usize,
+        resp: Response<Page>,
+    },
+    Restore {
+        snapshot: Snapshot,
+        resp: Response<()>,
+    },
+}
+
+/// Every account ever modified, along the registered assets, as copied
+/// between nodes
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Account, balance and last sequence, in any order
+    pub accounts: Vec<(sign::PublicKey, Amount, sieve::Sequence)>,
+    /// Registered assets, in any order
+    pub assets: Vec<Asset>,
+    /// Non-zero balances in the registered assets, in any order
+    pub asset_balances: Vec<(AssetId, sign::PublicKey, Amount)>,
+    /// Same as [`Checkpoint::hash`], for the accounts as they are now
+    pub hash: [u8; checkpoint::HASH_SIZE],
+}
+
+impl Snapshot {
+    /// Hash the given `accounts`, `assets` and `asset_balances`
+    pub fn new(
+        accounts: Vec<(sign::PublicKey, Amount, sieve::Sequence)>,
+        assets: Vec<Asset>,
+        asset_balances: Vec<(AssetId, sign::PublicKey, Amount)>,
+    ) -> Result<Self, Error> {
+        let hash = checkpoint::hash(
+            accounts
+                .iter()
+                .map(|(user, balance, sequence)| (*user, Account::restored(*balance, *sequence)))
+                .collect(),
+            &assets,
+            &asset_balances,
+        )?;
+
+        Ok(Self {
+            accounts,
+            assets,
+            asset_balances,
+            hash,
+        })
+    }
+}
+
+/// Account as seen at a given version, see [`Accounts::get_versioned`]
+#[derive(Debug, Clone, Copy)]
+pub struct Versioned {
+    pub balance: Amount,
+    pub last_sequence: sieve::Sequence,
+    /// One more than the times the account changed on this node
+    pub version: u64,
+}
+
+/// Accounts ever modified, ordered by key, see [`Accounts::list`]
+#[derive(Debug, Default)]
+pub struct Page {
+    /// Account, balance and last sequence
+    pub accounts: Vec<(sign::PublicKey, Amount, sieve::Sequence)>,
+    /// Where to continue from, if accounts remain
+    pub next: Option<sign::PublicKey>,
+    /// How many accounts were ever modified
+    pub total: u64,
+}
+
+#[derive(Clone)]
+pub struct Accounts {
+    agent: mpsc::Sender<Commands>,
+}
+
+/// Own the accounts themselves
+struct AccountsHandler {
+    store: Box<dyn Store>,
+    genesis: Genesis,
+    checkpoint_interval: u64,
+    /// Operations applied so far, counted from the store on first use
+    applied: Option<u64>,
+    /// Of the accounts in the store, computed on first use
+    digest: Option<StateDigest>,
+    /// Accounts in the store, counted on first use
+    count: Option<u64>,
+}
+
+impl Accounts {
+    /// Accounts kept in memory, starting from `genesis`
+    pub fn new(genesis: Genesis) -> Self {
+        Self::with_store(Box::new(store::Memory::default()), genesis)
+    }
+
+    /// Accounts kept in the given store, such as [`store::Sled`]
+    ///
+    /// Users missing from the store start with their `genesis` balance.
+    pub fn with_store(store: Box<dyn Store>, genesis: Genesis) -> Self {
+        Self::with_checkpoint_interval(store, genesis, DEFAULT_CHECKPOINT_INTERVAL)
+    }
+
+    /// Accounts kept in the given store, checkpointed every `interval` operations
+    ///
+    /// No checkpoint is taken if `interval` is zero.
+    pub fn with_checkpoint_interval(
+        store: Box<dyn Store>,
+        genesis: Genesis,
+        interval: u64,
+    ) -> Self {
+        Self {
+            agent: AccountsHandler::new(store, genesis, interval).spawn(),
+        }
+    }
+
+    /// Return the balance for the given user
+    pub async fn get_balance(&self, user: Box<sign::PublicKey>) -> Result<Amount, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetBalance { user, resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Transfer an `amount` from the `sender` account to the `receiver`
+    ///
+    /// It fails if the `sender_sequence` is already used or not within the
+    /// window of the sender, by default the one following the last.
+    pub async fn transfer(
+        &self,
+        sender: Box<sign::PublicKey>,
+        sender_sequence: sieve::Sequence,
+        receiver: Box<sign::PublicKey>,
+        amount: Amount,
+    ) -> Result<(), Error> {
+        self.transfer_multi(sender, sender_sequence, vec![(*receiver, amount)])
+            .await
+    }
+
+    /// Transfer from the `sender` account to every receiver of the `outputs`
+    ///
+    /// Either every output is applied, or none of them.
+    pub async fn transfer_multi(
+        &self,
+        sender: Box<sign::PublicKey>,
+        sender_sequence: sieve::Sequence,
+        outputs: Vec<(sign::PublicKey, Amount)>,
+    ) -> Result<(), Error> {
+        self.transfer_asset(sender, sender_sequence, None, outputs)
+            .await
+    }
+
+    /// Same as [`Accounts::transfer_multi`], in a registered `asset` if given
+    ///
+    /// The `sender_sequence` is the one of the sender, whatever the asset.
+    pub async fn transfer_asset(
+        &self,
+        sender: Box<sign::PublicKey>,
+        sender_sequence: sieve::Sequence,
+        asset: Option<AssetId>,
+        outputs: Vec<(sign::PublicKey, Amount)>,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Transfer {
+                sender,
+                sender_sequence,
+                asset,
+                outputs,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return the last sequence used for this user.
+    pub async fn get_last_sequence(
+        &self,
+        user: Box<sign::PublicKey>,
+    ) -> Result<sieve::Sequence, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetLastSequence { user, resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return both the balance and the last sequence of this user, as seen at
+    /// the same time
+    pub async fn get_balance_and_sequence(
+        &self,
+        user: Box<sign::PublicKey>,
+    ) -> Result<(Amount, sieve::Sequence), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetBalanceAndSequence { user, resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return the balance and the last sequence of this user, along their
+    /// version, which is bumped whenever they change
+    pub async fn get_versioned(&self, user: Box<sign::PublicKey>) -> Result<Versioned, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetVersioned { user, resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Register `name` for the `user`, if not already taken
+    ///
+    /// It uses the `sequence` of the user, even if the name was taken.
+    pub async fn register_name(
+        &self,
+        user: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        name: String,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::RegisterName {
+                user,
+                sequence,
+                name,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Let the `user` use the `size` sequences following its last one in any
+    /// order, each at most once
+    ///
+    /// It uses the `sequence` of the user, within the previous window.
+    pub async fn set_window(
+        &self,
+        user: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        size: sieve::Sequence,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::SetWindow {
+                user,
+                sequence,
+                size,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return the user owning this name
+    pub async fn resolve_name(&self, name: String) -> Result<Option<sign::PublicKey>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::ResolveName { name, resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Record that a bridge `validator` saw the `deposit` on its source network,
+    /// using the `sequence` of the `validator`
+    ///
+    /// Once `threshold` validators attested it, the amount is credited to the
+    /// user, which is signaled by returning true. Whether the `validator` is
+    /// one of the bridge is for the caller to check.
+    pub async fn bridge_deposit(
+        &self,
+        validator: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        deposit: Box<BridgeDeposit>,
+        threshold: usize,
+    ) -> Result<bool, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::BridgeDeposit {
+                validator,
+                sequence,
+                deposit,
+                threshold,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Create an `amount` for the `recipient`, using the `sequence` of the `admin`,
+    /// in a registered `asset` if given
+    ///
+    /// Whether the `admin` is allowed to do so is for the caller to check.
+    pub async fn mint(
+        &self,
+        admin: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        recipient: Box<sign::PublicKey>,
+        amount: Amount,
+        asset: Option<AssetId>,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Mint {
+                admin,
+                sequence,
+                recipient,
+                amount,
+                asset,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Add the `asset` to the registry, using the `sequence` of the `admin`
+    ///
+    /// It uses the `sequence` even if the identifier was taken. Whether the
+    /// `admin` is allowed to do so is for the caller to check.
+    pub async fn register_asset(
+        &self,
+        admin: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        asset: Box<Asset>,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::RegisterAsset {
+                admin,
+                sequence,
+                asset,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return the balance of the user in a registered asset
+    pub async fn get_asset_balance(
+        &self,
+        user: Box<sign::PublicKey>,
+        asset: AssetId,
+    ) -> Result<Amount, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetAssetBalance {
+                user,
+                asset,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return every registered asset, ordered by identifier
+    pub async fn get_assets(&self) -> Result<Vec<Asset>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetAssets { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return the checkpoint taken at `epoch`, or the latest one if `None`
+    pub async fn get_checkpoint(&self, epoch: Option<u64>) -> Result<Option<Checkpoint>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetCheckpoint { epoch, resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return every account ever modified
+    pub async fn get_snapshot(&self) -> Result<Snapshot, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetSnapshot { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Get the [`StateDigest`] of the accounts, along the operations applied to them
+    pub async fn get_state_digest(&self) -> Result<(StateDigest, u64), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetStateDigest { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return up to `limit` accounts ever modified, with their total count
+    ///
+    /// Accounts are ordered by key, starting from the first one or right
+    /// after `after`, as returned by a previous [`Page`]. `limit` is capped to
+    /// [`PAGE_MAX_SIZE`].
+    pub async fn list(
+        &self,
+        after: Option<Box<sign::PublicKey>>,
+        limit: usize,
+    ) -> Result<Page, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::List {
+                after,
+                limit: limit.min(PAGE_MAX_SIZE),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Overwrite the accounts in the `snapshot`, leaving the others as is
+    pub async fn restore(&self, snapshot: Snapshot) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Restore { snapshot, resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+}
+
+impl AccountsHandler {
+    fn new(store: Box<dyn Store>, genesis: Genesis, checkpoint_interval: u64) -> Self {
+        Self {
+            store,
+            genesis,
+            checkpoint_interval,
+            applied: None,
+            digest: None,
+            count: None,
+        }
+    }
+
+    fn spawn(mut self) -> mpsc::Sender<Commands> {
+        let (tx, mut rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            while let Some(cmd) = rx.recv().await {
+                match cmd {
+                    Commands::GetBalance { user, resp } => {
+                        let _ = resp.send(self.get_balance(&user));
+                    }
+                    Commands::Transfer {
+                        sender,
+                        sender_sequence,
+                        asset,
+                        outputs,
+                        resp,
+                    } => {
+                        let _ = resp.send(self.transfer(*sender, sender_sequence, asset, outputs));
+                    }
+                    Commands::GetLastSequence { user, resp } => {
+                        let _ = resp.send(self.get_last_sequence(*user));
+                    }
+                    Commands::GetBalanceAndSequence { user, resp } => {
+                        let _ = resp.send(
+                            self.account(&user)
+                                .map(|account| (account.balance(), account.last_sequence())),
+                        );
+                    }
+                    Commands::GetVersioned { user, resp } => {
+                        let _ = resp.send(self.get_versioned(&user));
+                    }
+                    Commands::RegisterName {
+                        user,
+                        sequence,
+                        name,
+                        resp,
+                    } => {
+                        let _ = resp.send(self.register_name(*user, sequence, name));
+                    }
+                    Commands::SetWindow {
+                        user,
+                        sequence,
+                        size,
+                        resp,
+                    } => {
+                        let _ = resp.send(self.set_window(*user, sequence, size));
+                    }
+                    Commands::ResolveName { name, resp } => {
+                        let _ = resp.send(self.store.name(&name).context(Storage));
+                    }
+                    Commands::BridgeDeposit {
+                        validator,
+                        sequence,
+                        deposit,
+                        threshold,
+                        resp,
+                    } => {
+                        let _ = resp
+                            .send(self.bridge_deposit(*validator, sequence, *deposit, threshold));
+                    }
+                    Commands::Mint {
+                        admin,
+                        sequence,
+                        recipient,
+                        amount,
+                        asset,
+                        resp,
+                    } => {
+                        let _ = resp.send(self.mint(*admin, sequence, *recipient, amount, asset));
+                    }
+                    Commands::RegisterAsset {
+                        admin,
+                        sequence,
+                        asset,
+                        resp,
+                    } => {
+                        let _ = resp.send(self.register_asset(*admin, sequence, *asset));
+                    }
+                    Commands::GetAssetBalance { user, asset, resp } => {
+                        let _ = resp.send(self.get_asset_balance(&user, &asset));
+                    }
+                    Commands::GetAssets { resp } => {
+                        let _ = resp.send(self.store.assets().context(Storage));
+                    }
+                    Commands::GetCheckpoint { epoch, resp } => {
+                        let _ = resp.send(self.get_checkpoint(epoch));
+                    }
+                    Commands::GetSnapshot { resp } => {
+                        let _ = resp.send(self.get_snapshot());
+                    }
+                    Commands::GetStateDigest { resp } => {
+                        let _ = resp.send(self.get_state_digest());
+                    }
+                    Commands::List { after, limit, resp } => {
+                        let _ = resp.send(self.list(after.as_deref(), limit));
+                    }
+                    Commands::Restore { snapshot, resp } => {
+                        let _ = resp.send(self.restore(snapshot));
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+
+    /// Account of the user, a new one if never seen
+    fn account(&self, user: &sign::PublicKey) -> Result<Account, Error> {
+        // TODO remove me when create_account is done
+        Ok(self
+            .store
+            .account(user)
+            .context(Storage)?
+            .unwrap_or_else(|| match self.genesis.get(user) {
+                Some(balance) => Account::with_balance(*balance),
+                None => Account::new(),
+            }))
+    }
+
+    /// Debit the `user` at `sequence`, along its window if changed
+    fn debit(
+        &self,
+        user: &sign::PublicKey,
+        sequence: sieve::Sequence,
+        amount: Amount,
+    ) -> Result<(Account, Option<Window>), Error> {
+        let window = self.store.window(user).context(Storage)?;
+        let (account, new_window) = self.account(user)?.debit(&window, sequence, amount)?;
+
+        Ok((account, Some(new_window).filter(|new| *new != window)))
+    }
+
+    fn get_balance(&self, user: &sign::PublicKey) -> Result<Amount, Error> {
+        Ok(self.account(user)?.balance())
+    }
+
+    fn get_versioned(&self, user: &sign::PublicKey) -> Result<Versioned, Error> {
+        let account = self.account(user)?;
+
+        Ok(Versioned {
+            balance: account.balance(),
+            last_sequence: account.last_sequence(),
+            version: self.store.changes(user).context(Storage)? + 1,
+        })
+    }
+
+    fn get_asset_balance(&self, user: &sign::PublicKey, asset: &str) -> Result<Amount, Error> {
+        self.check_asset(asset)?;
+
+        self.store.asset_balance(asset, user).context(Storage)
+    }
+
+    /// Refuse an unregistered asset
+    fn check_asset(&self, asset: &str) -> Result<(), Error> {
+        ensure!(
+            self.store.asset(asset).context(Storage)?.is_some(),
+            UnknownAsset { asset }
+        );
+
+        Ok(())
+    }
+
+    fn transfer(
+        &mut self,
+        sender: sign::PublicKey,
+        sender_sequence: sieve::Sequence,
+        asset: Option<AssetId>,
+        outputs: Vec<(sign::PublicKey, Amount)>,
+    ) -> Result<(), Error> {
+        let total = outputs
+            .iter()
+            .try_fold(0, |total: Amount, (_, amount)| total.checked_add(*amount))
+            .context(TransferOverflow)?;
+        if let Some(asset) = &asset {
+            self.check_asset(asset)?;
+        }
+
+        // delivered again, such as after a restart
+        let digest = transfer_digest(asset.as_deref(), &outputs)?;
+        let used = sender_sequence <= self.account(&sender)?.last_sequence()
+            || self
+                .store
+                .window(&sender)
+                .context(Storage)?
+                .is_used(sender_sequence);
+        if used
+            && self
+                .store
+                .applied(&sender, sender_sequence)
+                .context(Storage)?
+                == Some(digest)
+        {
+            return AlreadyApplied {
+                sequence: sender_sequence,
+            }
+            .fail();
+        }
+
+        let update = match asset {
+            None => self.own_transfer(sender, sender_sequence, total, outputs)?,
+            Some(asset) => self.asset_transfer(sender, sender_sequence, asset, total, outputs)?,
+        };
+        self.update(store::Update {
+            applied: Some(store::Applied {
+                sender,
+                sequence: sender_sequence,
+                digest,
+            }),
+            ..update
+        })?;
+
+        self.applied_one()
+    }
+
+    /// Changes of a transfer of the network's own asset
+    fn own_transfer(
+        &self,
+        sender: sign::PublicKey,
+        sender_sequence: sieve::Sequence,
+        total: Amount,
+        outputs: Vec<(sign::PublicKey, Amount)>,
+    ) -> Result<store::Update, Error> {
+        let (sender_account, window) = self.debit(&sender, sender_sequence, total)?;
+        let mut updated = HashMap::new();
+        updated.insert(sender, sender_account);
+
+        // sending to oneself, or twice to someone, still has to add up
+        for (receiver, amount) in outputs {
+            let receiver_account = match updated.get(&receiver) {
+                Some(account) => *account,
+                None => self.account(&receiver)?,
+            };
+            updated.insert(receiver, receiver_account.credit(amount)?);
+        }
+
+        Ok(store::Update {
+            accounts: updated.into_iter().collect(),
+            windows: window.map(|window| (sender, window)).into_iter().collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Changes of a transfer of a registered asset, the sequence still being
+    /// the one of the sender's account
+    fn asset_transfer(
+        &self,
+        sender: sign::PublicKey,
+        sender_sequence: sieve::Sequence,
+        asset: AssetId,
+        total: Amount,
+        outputs: Vec<(sign::PublicKey, Amount)>,
+    ) -> Result<store::Update, Error> {
+        let (sender_account, window) = self.debit(&sender, sender_sequence, 0)?;
+
+        let have = self.store.asset_balance(&asset, &sender).context(Storage)?;
+        ensure!(have >= total, InsufficientBalance { have, need: total });
+        let mut balances = HashMap::new();
+        balances.insert(sender, have - total);
+
+        for (receiver, amount) in outputs {
+            let balance = match balances.get(&receiver) {
+                Some(balance) => *balance,
+                None => self
+                    .store
+                    .asset_balance(&asset, &receiver)
+                    .context(Storage)?,
+            };
+            balances.insert(
+                receiver,
+                balance.checked_add(amount).context(BalanceOverflow)?,
+            );
+        }
+
+        Ok(store::Update {
+            accounts: vec![(sender, sender_account)],
+            windows: window.map(|window| (sender, window)).into_iter().collect(),
+            asset_balances: balances
+                .into_iter()
+                .map(|(user, balance)| (asset.clone(), user, balance))
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    fn register_name(
+        &mut self,
+        user: sign::PublicKey,
+        sequence: sieve::Sequence,
+        name: String,
+    ) -> Result<(), Error> {
+        let (new_user_account, window) = self.debit(&user, sequence, 0)?;
+
+        let taken = self.store.name(&name).context(Storage)?.is_some();
+
+        self.update(store::Update {
+            accounts: vec![(user, new_user_account)],
+            name: Some((name.clone(), user)).filter(|_| !taken),
+            windows: window.map(|window| (user, window)).into_iter().collect(),
+            ..Default::default()
+        })?;
+        self.applied_one()?;
+
+        if taken {
+            NameTaken { name }.fail()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn set_window(
+        &mut self,
+        user: sign::PublicKey,
+        sequence: sieve::Sequence,
+        size: sieve::Sequence,
+    ) -> Result<(), Error> {
+        ensure!(
+            (1..=SEQUENCE_WINDOW_MAX_SIZE).contains(&size),
+            InvalidWindow { size }
+        );
+
+        let (new_user_account, window) = self.debit(&user, sequence, 0)?;
+        let window = match window {
+            Some(window) => window,
+            None => self.store.window(&user).context(Storage)?,
+        };
+
+        self.update(store::Update {
+            accounts: vec![(user, new_user_account)],
+            windows: vec![(user, window.resized(size))],
+            ..Default::default()
+        })?;
+
+        self.applied_one()
+    }
+
+    fn bridge_deposit(
+        &mut self,
+        validator: sign::PublicKey,
+        sequence: sieve::Sequence,
+        deposit: BridgeDeposit,
+        threshold: usize,
+    ) -> Result<bool, Error> {
+        let user = deposit.user;
+        let amount = deposit.amount;
+        let id = bridge::DepositId::new(&deposit);
+
+        let (new_validator_account, window) = self.debit(&validator, sequence, 0)?;
+        let windows = window
+            .map(|window| (validator, window))
+            .into_iter()
+            .collect();
+
+        if self.store.is_minted(&id).context(Storage)? {
+            self.update(store::Update {
+                accounts: vec![(validator, new_validator_account)],
+                windows,
+                ..Default::default()
+            })?;
+            self.applied_one()?;
+
+            return Ok(false);
+        }
+
+        let user_account = if user == validator {
+            new_validator_account.clone()
+        } else {
+            self.account(&user)?
+        };
+        let new_user_account = user_account.credit(amount)?;
+
+        let mut attestations = self
+            .store
+            .attestations(&id)
+            .context(Storage)?
+            .unwrap_or_else(|| bridge::Attestations::new(&deposit));
+        let to_mint = attestations
+            .attest(validator, &deposit, threshold)
+            .context(Bridge)?;
+
+        let mut accounts = vec![(validator, new_validator_account)];
+        if to_mint {
+            // replacing the validator's one if the same
+            accounts.retain(|(key, _)| *key != user);
+            accounts.push((user, new_user_account));
+        }
+        self.update(store::Update {
+            accounts,
+            windows,
+            attestations: (!to_mint).then(|| (id.clone(), attestations)),
+            minted: to_mint.then(|| id),
+            ..Default::default()
+        })?;
+        self.applied_one()?;
+
+        Ok(to_mint)
+    }
+
+    fn mint(
+        &mut self,
+        admin: sign::PublicKey,
+        sequence: sieve::Sequence,
+        recipient: sign::PublicKey,
+        amount: Amount,
+        asset: Option<AssetId>,
+    ) -> Result<(), Error> {
+        if let Some(asset) = &asset {
+            self.check_asset(asset)?;
+        }
+
+        let (new_admin_account, window) = self.debit(&admin, sequence, 0)?;
+        let windows = window.map(|window| (admin, window)).into_iter().collect();
+
+        let update = match asset {
+            None => {
+                let recipient_account = if recipient == admin {
+                    new_admin_account
+                } else {
+                    self.account(&recipient)?
+                };
+                let new_recipient_account = recipient_account.credit(amount)?;
+
+                store::Update {
+                    accounts: vec![
+                        (admin, new_admin_account),
+                        (recipient, new_recipient_account),
+                    ],
+                    windows,
+                    ..Default::default()
+                }
+            }
+            Some(asset) => {
+                let balance = self
+                    .store
+                    .asset_balance(&asset, &recipient)
+                    .context(Storage)?
+                    .checked_add(amount)
+                    .context(BalanceOverflow)?;
+
+                store::Update {
+                    accounts: vec![(admin, new_admin_account)],
+                    windows,
+                    asset_balances: vec![(asset, recipient, balance)],
+                    ..Default::default()
+                }
+            }
+        };
+        self.update(update)?;
+
+        self.applied_one()
+    }
+
+    fn register_asset(
+        &mut self,
+        admin: sign::PublicKey,
+        sequence: sieve::Sequence,
+        asset: Asset,
+    ) -> Result<(), Error> {
+        ensure!(
+            is_valid_asset_id(&asset.id),
+            InvalidAsset {
+                asset: asset.id.clone()
+            }
+        );
+
+        let (new_admin_account, window) = self.debit(&admin, sequence, 0)?;
+
+        let id = asset.id.clone();
+        let taken = self.store.asset(&id).context(Storage)?.is_some();
+
+        self.update(store::Update {
+            accounts: vec![(admin, new_admin_account)],
+            assets: Some(asset).filter(|_| !taken).into_iter().collect(),
+            windows: window.map(|window| (admin, window)).into_iter().collect(),
+            ..Default::default()
+        })?;
+        self.applied_one()?;
+
+        if taken {
+            AssetTaken { asset: id }.fail()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Operations applied so far, as the sum of the last sequences and of the
+    /// ones used past them
+    fn applied(&mut self) -> Result<u64, Error> {
+        if let Some(applied) = self.applied {
+            return Ok(applied);
+        }
+
+        let sequences = self
+            .store
+            .accounts()
+            .context(Storage)?
+            .iter()
+            .map(|(_, account)| u64::from(account.last_sequence()))
+            .sum::<u64>();
+        let past = self
+            .store
+            .windows()
+            .context(Storage)?
+            .iter()
+            .map(|(_, window)| window.used() as u64)
+            .sum::<u64>();
+        let applied = sequences + past;
+        self.applied = Some(applied);
+
+        Ok(applied)
+    }
+
+    /// Count an applied operation, taking a checkpoint if it ends an epoch
+    ///
+    /// If not yet known, the count is read from the store, which already
+    /// includes the operation.
+    fn applied_one(&mut self) -> Result<(), Error> {
+        let applied = match self.applied {
+            Some(applied) => applied + 1,
+            None => self.applied()?,
+        };
+        self.applied = Some(applied);
+
+        if self.checkpoint_interval == 0 || applied % self.checkpoint_interval != 0 {
+            return Ok(());
+        }
+
+        let checkpoint = Checkpoint {
+            epoch: applied / self.checkpoint_interval,
+            applied,
+            hash: checkpoint::hash(
+                self.store.accounts().context(Storage)?,
+                &self.store.assets().context(Storage)?,
+                &self.store.asset_balances().context(Storage)?,
+            )?,
+            timestamp: chrono::Utc::now(),
+        };
+        self.update(store::Update {
+            checkpoint: Some(checkpoint),
+            ..Default::default()
+        })
+    }
+
+    fn get_checkpoint(&mut self, epoch: Option<u64>) -> Result<Option<Checkpoint>, Error> {
+        let epoch = match epoch {
+            Some(epoch) => epoch,
+            None if self.checkpoint_interval == 0 => return Ok(None),
+            None => self.applied()? / self.checkpoint_interval,
+        };
+
+        self.store.checkpoint(epoch).context(Storage)
+    }
+
+    fn get_snapshot(&self) -> Result<Snapshot, Error> {
+        Snapshot::new(
+            self.store
+                .accounts()
+                .context(Storage)?
+                .into_iter()
+                .map(|(user, account)| (user, account.balance(), account.last_sequence()))
+                .collect(),
+            self.store.assets().context(Storage)?,
+            self.store
+                .asset_balances()
+                .context(Storage)?
+                .into_iter()
+                .filter(|(_, _, balance)| *balance != 0)
+                .collect(),
+        )
+    }
+
+    fn get_state_digest(&mut self) -> Result<(StateDigest, u64), Error> {
+        let digest = match self.digest {
+            Some(digest) => digest,
+            None => {
+                let digest = StateDigest::of(
+                    &self.store.accounts().context(Storage)?,
+                    &self.store.assets().context(Storage)?,
+                    &self.store.asset_balances().context(Storage)?,
+                )?;
+                self.digest = Some(digest);
+                digest
+            }
+        };
+
+        Ok((digest, self.applied()?))
+    }
+
+    fn list(&mut self, after: Option<&sign::PublicKey>, limit: usize) -> Result<Page, Error> {
+        let mut accounts = self
+            .store
+            .accounts_after(after, limit + 1)
+            .context(Storage)?;
+        let next = if accounts.len() > limit {
+            accounts.truncate(limit);
+            accounts.last().map(|(user, _)| *user)
+        } else {
+            None
+        };
+
+        Ok(Page {
+            accounts: accounts
+                .into_iter()
+                .map(|(user, account)| (user, account.balance(), account.last_sequence()))
+                .collect(),
+            next,
+            total: self.count()?,
+        })
+    }
+
+    /// Accounts in the store
+    fn count(&mut self) -> Result<u64, Error> {
+        if let Some(count) = self.count {
+            return Ok(count);
+        }
+
+        let count = self.store.accounts().context(Storage)?.len() as u64;
+        self.count = Some(count);
+
+        Ok(count)
+    }
+
+    /// Apply the `update` to the store, along to the digest and the count if
+    /// already computed
+    fn update(&mut self, update: store::Update) -> Result<(), Error> {
+        // the last change of an account is the one kept
+        let changed = update.accounts.iter().copied().collect::<HashMap<_, _>>();
+        let previous = if self.digest.is_some() || self.count.is_some() {
+            changed
+                .keys()
+                .map(|user| Ok((*user, self.store.account(user).context(Storage)?)))
+                .collect::<Result<HashMap<_, _>, Error>>()?
+        } else {
+            HashMap::new()
+        };
+
+        // only the digest needs the previous balances in assets
+        let mut changed_balances = HashMap::new();
+        let mut registered = Vec::new();
+        if self.digest.is_some() {
+            for (asset, user, balance) in &update.asset_balances {
+                let previous = self.store.asset_balance(asset, user).context(Storage)?;
+                changed_balances.insert((asset.clone(), *user), (previous, *balance));
+            }
+            for asset in &update.assets {
+                if self.store.asset(&asset.id).context(Storage)?.is_none() {
+                    registered.push(asset.clone());
+                }
+            }
+        }
+
+        self.store.update(update).context(Storage)?;
+
+        if let Some(digest) = &mut self.digest {
+            for asset in &registered {
+                digest.add_asset(asset)?;
+            }
+            for ((asset, user), (previous, balance)) in &changed_balances {
+                digest.remove_asset_balance(asset, user, *previous)?;
+                digest.add_asset_balance(asset, user, *balance)?;
+            }
+        }
+
+        for (user, account) in &changed {
+            let previous = previous.get(user).copied().flatten();
+
+            if let Some(digest) = &mut self.digest {
+                if let Some(previous) = &previous {
+                    digest.remove(user, previous)?;
+                }
+                digest.add(user, account)?;
+            }
+            if let (Some(count), None) = (&mut self.count, previous) {
+                *count += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) -> Result<(), Error> {
+        // the peer's sequences used out of order aren't copied
+        let windows = snapshot
+            .accounts
+            .iter()
+            .map(|(user, _, _)| Ok((*user, self.store.window(user).context(Storage)?.cleared())))
+            .collect::<Result<_, Error>>()?;
+
+        self.update(store::Update {
+            accounts: snapshot
+                .accounts
+                .into_iter()
+                .map(|(user, balance, sequence)| (user, Account::restored(balance, sequence)))
+                .collect(),
+            windows,
+            assets: snapshot.assets,
+            asset_balances: snapshot.asset_balances,
+            ..Default::default()
+        })?;
+
+        // counted again from the store
+        self.applied = None;
+
+        Ok(())
+    }
+
+    fn get_last_sequence(&self, sender: sign::PublicKey) -> Result<sieve::Sequence, Error> {
+        Ok(self
+            .store
+            .account(&sender)
+            .context(Storage)?
+            .map(|account| account.last_sequence())
+            .unwrap_or_default())
+    }
+}
+
+/// Hash the `outputs` of a transfer, along its asset unless the network's
+/// own, to recognize it once applied
+fn transfer_digest(
+    asset: Option<&str>,
+    outputs: &[(sign::PublicKey, Amount)],
+) -> Result<[u8; store::DIGEST_SIZE], Error> {
+    let encoded = match asset {
+        None => bincode::serialize(outputs),
+        Some(asset) => bincode::serialize(&(asset, outputs)),
+    }
+    .map_err(|source| Error::Storage {
+        source: store::Error::Encode { source },
+    })?;
+
+    Ok(Sha256::digest(&encoded).into())
+}
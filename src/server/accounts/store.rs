@@ -0,0 +1,660 @@
+//! Where the accounts are kept, in memory or on disk, along the registered
+//! assets
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryInto,
+    ops::Bound,
+    path::Path,
+};
+
+use drop::crypto::sign;
+use sled::{transaction::TransactionError, Transactional};
+use snafu::{ResultExt, Snafu};
+
+use super::{
+    account::{Account, Window},
+    bridge::{Attestations, DepositId},
+    checkpoint::Checkpoint,
+};
+use crate::{Amount, Asset, AssetId};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("database: {}", source))]
+    Database { source: sled::Error },
+    #[snafu(display("encode: {}", source))]
+    Encode { source: bincode::Error },
+    #[snafu(display("decode: {}", source))]
+    Decode { source: bincode::Error },
+}
+
+/// Size of [`Applied::digest`]
+pub const DIGEST_SIZE: usize = 32;
+
+/// Sequences of each sender whose transfers are remembered, see [`Applied`]
+///
+/// It covers the largest sequence window, so that a transfer applied out of
+/// order is remembered until its sequence is the last one.
+pub const DEDUP_WINDOW: sieve::Sequence = crate::SEQUENCE_WINDOW_MAX_SIZE;
+
+/// Transfer applied by a sender, to recognize it if delivered again
+///
+/// Only the last [`DEDUP_WINDOW`] ones of each sender are kept.
+pub struct Applied {
+    pub sender: sign::PublicKey,
+    pub sequence: sieve::Sequence,
+    /// Hash of what was transferred
+    pub digest: [u8; DIGEST_SIZE],
+}
+
+/// Changes to apply at once
+#[derive(Default)]
+pub struct Update {
+    pub accounts: Vec<(sign::PublicKey, Account)>,
+    pub name: Option<(String, sign::PublicKey)>,
+    pub minted: Option<DepositId>,
+    /// Of a deposit not minted yet, forgotten once it is
+    pub attestations: Option<(DepositId, Attestations)>,
+    pub checkpoint: Option<Checkpoint>,
+    pub applied: Option<Applied>,
+    /// Windows of the users, forgotten when back to the default one
+    pub windows: Vec<(sign::PublicKey, Window)>,
+    /// Newly registered
+    pub assets: Vec<Asset>,
+    /// Balances of the users in registered assets
+    pub asset_balances: Vec<(AssetId, sign::PublicKey, Amount)>,
+}
+
+/// Storage of the accounts and registered names
+pub trait Store: Send {
+    /// Account of the user, if it was ever modified
+    fn account(&self, user: &sign::PublicKey) -> Result<Option<Account>, Error>;
+
+    /// Every account ever modified, in any order
+    fn accounts(&self) -> Result<Vec<(sign::PublicKey, Account)>, Error>;
+
+    /// Up to `limit` accounts following `after`, or from the first one,
+    /// ordered by their encoded key
+    fn accounts_after(
+        &self,
+        after: Option<&sign::PublicKey>,
+        limit: usize,
+    ) -> Result<Vec<(sign::PublicKey, Account)>, Error>;
+
+    /// User owning the name
+    fn name(&self, name: &str) -> Result<Option<sign::PublicKey>, Error>;
+
+    /// Whether the bridge deposit was already minted
+    fn is_minted(&self, deposit: &DepositId) -> Result<bool, Error>;
+
+    /// Validators who saw the bridge deposit, if not minted yet
+    fn attestations(&self, deposit: &DepositId) -> Result<Option<Attestations>, Error>;
+
+    /// Checkpoint taken at `epoch`
+    fn checkpoint(&self, epoch: u64) -> Result<Option<Checkpoint>, Error>;
+
+    /// Digest of the transfer applied at `sequence`, if still remembered
+    fn applied(
+        &self,
+        sender: &sign::PublicKey,
+        sequence: sieve::Sequence,
+    ) -> Result<Option<[u8; DIGEST_SIZE]>, Error>;
+
+    /// How many times the account of the user was updated
+    fn changes(&self, user: &sign::PublicKey) -> Result<u64, Error>;
+
+    /// Sequence window of the user, the default one if never set
+    fn window(&self, user: &sign::PublicKey) -> Result<Window, Error>;
+
+    /// Every window differing from the default one, in any order
+    fn windows(&self) -> Result<Vec<(sign::PublicKey, Window)>, Error>;
+
+    /// Registered asset
+    fn asset(&self, id: &str) -> Result<Option<Asset>, Error>;
+
+    /// Every registered asset, ordered by identifier
+    fn assets(&self) -> Result<Vec<Asset>, Error>;
+
+    /// Balance of the user in a registered asset, zero if never held
+    fn asset_balance(&self, asset: &str, user: &sign::PublicKey) -> Result<Amount, Error>;
+
+    /// Every balance in the registered assets, in any order
+    fn asset_balances(&self) -> Result<Vec<(AssetId, sign::PublicKey, Amount)>, Error>;
+
+    /// Apply every change, or none of them
+    fn update(&mut self, update: Update) -> Result<(), Error>;
+}
+
+/// Lost on restart
+#[derive(Default)]
+pub struct Memory {
+    accounts: HashMap<sign::PublicKey, Account>,
+    /// Users of `accounts`, by encoded key, as ordered on disk
+    order: BTreeMap<Vec<u8>, sign::PublicKey>,
+    names: HashMap<String, sign::PublicKey>,
+    minted: HashSet<DepositId>,
+    attestations: HashMap<DepositId, Attestations>,
+    checkpoints: HashMap<u64, Checkpoint>,
+    applied: HashMap<(sign::PublicKey, sieve::Sequence), [u8; DIGEST_SIZE]>,
+    changes: HashMap<sign::PublicKey, u64>,
+    windows: HashMap<sign::PublicKey, Window>,
+    assets: BTreeMap<AssetId, Asset>,
+    asset_balances: HashMap<(AssetId, sign::PublicKey), Amount>,
+}
+
+impl Store for Memory {
+    fn account(&self, user: &sign::PublicKey) -> Result<Option<Account>, Error> {
+        Ok(self.accounts.get(user).copied())
+    }
+
+    fn accounts(&self) -> Result<Vec<(sign::PublicKey, Account)>, Error> {
+        Ok(self
+            .accounts
+            .iter()
+            .map(|(user, account)| (*user, *account))
+            .collect())
+    }
+
+    fn accounts_after(
+        &self,
+        after: Option<&sign::PublicKey>,
+        limit: usize,
+    ) -> Result<Vec<(sign::PublicKey, Account)>, Error> {
+        let start = match after {
+            None => Bound::Unbounded,
+            Some(after) => Bound::Excluded(bincode::serialize(after).context(Encode)?),
+        };
+
+        Ok(self
+            .order
+            .range((start, Bound::Unbounded))
+            .take(limit)
+            .filter_map(|(_, user)| Some((*user, *self.accounts.get(user)?)))
+            .collect())
+    }
+
+    fn name(&self, name: &str) -> Result<Option<sign::PublicKey>, Error> {
+        Ok(self.names.get(name).copied())
+    }
+
+    fn is_minted(&self, deposit: &DepositId) -> Result<bool, Error> {
+        Ok(self.minted.contains(deposit))
+    }
+
+    fn attestations(&self, deposit: &DepositId) -> Result<Option<Attestations>, Error> {
+        Ok(self.attestations.get(deposit).cloned())
+    }
+
+    fn checkpoint(&self, epoch: u64) -> Result<Option<Checkpoint>, Error> {
+        Ok(self.checkpoints.get(&epoch).cloned())
+    }
+
+    fn applied(
+        &self,
+        sender: &sign::PublicKey,
+        sequence: sieve::Sequence,
+    ) -> Result<Option<[u8; DIGEST_SIZE]>, Error> {
+        Ok(self.applied.get(&(*sender, sequence)).copied())
+    }
+
+    fn changes(&self, user: &sign::PublicKey) -> Result<u64, Error> {
+        Ok(self.changes.get(user).copied().unwrap_or_default())
+    }
+
+    fn window(&self, user: &sign::PublicKey) -> Result<Window, Error> {
+        Ok(self.windows.get(user).cloned().unwrap_or_default())
+    }
+
+    fn windows(&self) -> Result<Vec<(sign::PublicKey, Window)>, Error> {
+        Ok(self
+            .windows
+            .iter()
+            .map(|(user, window)| (*user, window.clone()))
+            .collect())
+    }
+
+    fn asset(&self, id: &str) -> Result<Option<Asset>, Error> {
+        Ok(self.assets.get(id).cloned())
+    }
+
+    fn assets(&self) -> Result<Vec<Asset>, Error> {
+        Ok(self.assets.values().cloned().collect())
+    }
+
+    fn asset_balance(&self, asset: &str, user: &sign::PublicKey) -> Result<Amount, Error> {
+        Ok(self
+            .asset_balances
+            .get(&(asset.to_owned(), *user))
+            .copied()
+            .unwrap_or_default())
+    }
+
+    fn asset_balances(&self) -> Result<Vec<(AssetId, sign::PublicKey, Amount)>, Error> {
+        Ok(self
+            .asset_balances
+            .iter()
+            .map(|((asset, user), balance)| (asset.clone(), *user, *balance))
+            .collect())
+    }
+
+    fn update(&mut self, update: Update) -> Result<(), Error> {
+        for (user, _) in &update.accounts {
+            self.order
+                .insert(bincode::serialize(user).context(Encode)?, *user);
+            *self.changes.entry(*user).or_default() += 1;
+        }
+        self.accounts.extend(update.accounts);
+        self.names.extend(update.name);
+        if let Some(deposit) = &update.minted {
+            self.attestations.remove(deposit);
+        }
+        self.minted.extend(update.minted);
+        self.attestations.extend(update.attestations);
+        self.checkpoints.extend(
+            update
+                .checkpoint
+                .map(|checkpoint| (checkpoint.epoch, checkpoint)),
+        );
+        if let Some(applied) = update.applied {
+            if let Some(forgotten) = applied.sequence.checked_sub(DEDUP_WINDOW) {
+                self.applied.remove(&(applied.sender, forgotten));
+            }
+            self.applied
+                .insert((applied.sender, applied.sequence), applied.digest);
+        }
+        for (user, window) in update.windows {
+            if window == Window::default() {
+                self.windows.remove(&user);
+            } else {
+                self.windows.insert(user, window);
+            }
+        }
+        self.assets.extend(
+            update
+                .assets
+                .into_iter()
+                .map(|asset| (asset.id.clone(), asset)),
+        );
+        self.asset_balances.extend(
+            update
+                .asset_balances
+                .into_iter()
+                .map(|(asset, user, balance)| ((asset, user), balance)),
+        );
+
+        Ok(())
+    }
+}
+
+/// Kept in a [sled](https://sled.rs) database, surviving restarts
+pub struct Sled {
+    accounts: sled::Tree,
+    names: sled::Tree,
+    minted: sled::Tree,
+    /// Keyed by the encoded deposit, as `minted`
+    attestations: sled::Tree,
+    checkpoints: sled::Tree,
+    /// Keyed by the encoded sender followed by the big-endian sequence
+    applied: sled::Tree,
+    /// Big-endian count, keyed by the encoded user
+    changes: sled::Tree,
+    /// Keyed by the encoded user
+    windows: sled::Tree,
+    /// Keyed by the identifier
+    assets: sled::Tree,
+    /// Keyed by the encoded asset followed by the encoded user
+    asset_balances: sled::Tree,
+}
+
+impl Sled {
+    /// Open or create the database in `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::with_db(&sled::open(path).context(Database)?)
+    }
+
+    /// Use an already opened database, which can be shared with other stores
+    pub fn with_db(db: &sled::Db) -> Result<Self, Error> {
+        Ok(Self {
+            accounts: db.open_tree("accounts").context(Database)?,
+            names: db.open_tree("names").context(Database)?,
+            minted: db.open_tree("minted").context(Database)?,
+            attestations: db.open_tree("attestations").context(Database)?,
+            checkpoints: db.open_tree("checkpoints").context(Database)?,
+            applied: db.open_tree("applied").context(Database)?,
+            changes: db.open_tree("changes").context(Database)?,
+            windows: db.open_tree("windows").context(Database)?,
+            assets: db.open_tree("assets").context(Database)?,
+            asset_balances: db.open_tree("asset_balances").context(Database)?,
+        })
+    }
+}
+
+fn applied_key(sender: &sign::PublicKey, sequence: sieve::Sequence) -> Result<Vec<u8>, Error> {
+    let mut key = bincode::serialize(sender).context(Encode)?;
+    key.extend_from_slice(&sequence.to_be_bytes());
+
+    Ok(key)
+}
+
+fn asset_balance_key(asset: &str, user: &sign::PublicKey) -> Result<Vec<u8>, Error> {
+    bincode::serialize(&(asset, user)).context(Encode)
+}
+
+fn decode_changes(value: Option<sled::IVec>) -> u64 {
+    value
+        .and_then(|value| value.as_ref().try_into().ok())
+        .map(u64::from_be_bytes)
+        .unwrap_or_default()
+}
+
+fn decode_account(
+    entry: sled::Result<(sled::IVec, sled::IVec)>,
+) -> Result<(sign::PublicKey, Account), Error> {
+    let (key, value) = entry.context(Database)?;
+
+    Ok((
+        bincode::deserialize(&key).context(Decode)?,
+        bincode::deserialize(&value).context(Decode)?,
+    ))
+}
+
+impl Store for Sled {
+    fn account(&self, user: &sign::PublicKey) -> Result<Option<Account>, Error> {
+        let key = bincode::serialize(user).context(Encode)?;
+
+        self.accounts
+            .get(key)
+            .context(Database)?
+            .map(|value| bincode::deserialize(&value).context(Decode))
+            .transpose()
+    }
+
+    fn accounts(&self) -> Result<Vec<(sign::PublicKey, Account)>, Error> {
+        self.accounts.iter().map(decode_account).collect()
+    }
+
+    fn accounts_after(
+        &self,
+        after: Option<&sign::PublicKey>,
+        limit: usize,
+    ) -> Result<Vec<(sign::PublicKey, Account)>, Error> {
+        let entries = match after {
+            None => self.accounts.iter(),
+            Some(after) => self.accounts.range((
+                Bound::Excluded(bincode::serialize(after).context(Encode)?),
+                Bound::Unbounded,
+            )),
+        };
+
+        entries.take(limit).map(decode_account).collect()
+    }
+
+    fn name(&self, name: &str) -> Result<Option<sign::PublicKey>, Error> {
+        self.names
+            .get(name)
+            .context(Database)?
+            .map(|value| bincode::deserialize(&value).context(Decode))
+            .transpose()
+    }
+
+    fn is_minted(&self, deposit: &DepositId) -> Result<bool, Error> {
+        let key = bincode::serialize(deposit).context(Encode)?;
+
+        self.minted.contains_key(key).context(Database)
+    }
+
+    fn attestations(&self, deposit: &DepositId) -> Result<Option<Attestations>, Error> {
+        let key = bincode::serialize(deposit).context(Encode)?;
+
+        self.attestations
+            .get(key)
+            .context(Database)?
+            .map(|value| bincode::deserialize(&value).context(Decode))
+            .transpose()
+    }
+
+    fn checkpoint(&self, epoch: u64) -> Result<Option<Checkpoint>, Error> {
+        self.checkpoints
+            .get(epoch.to_be_bytes())
+            .context(Database)?
+            .map(|value| bincode::deserialize(&value).context(Decode))
+            .transpose()
+    }
+
+    fn applied(
+        &self,
+        sender: &sign::PublicKey,
+        sequence: sieve::Sequence,
+    ) -> Result<Option<[u8; DIGEST_SIZE]>, Error> {
+        Ok(self
+            .applied
+            .get(applied_key(sender, sequence)?)
+            .context(Database)?
+            .and_then(|value| value.as_ref().try_into().ok()))
+    }
+
+    fn changes(&self, user: &sign::PublicKey) -> Result<u64, Error> {
+        let key = bincode::serialize(user).context(Encode)?;
+
+        Ok(decode_changes(self.changes.get(key).context(Database)?))
+    }
+
+    fn window(&self, user: &sign::PublicKey) -> Result<Window, Error> {
+        let key = bincode::serialize(user).context(Encode)?;
+
+        Ok(self
+            .windows
+            .get(key)
+            .context(Database)?
+            .map(|value| bincode::deserialize(&value).context(Decode))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    fn windows(&self) -> Result<Vec<(sign::PublicKey, Window)>, Error> {
+        self.windows
+            .iter()
+            .map(|entry| {
+                let (key, value) = entry.context(Database)?;
+
+                Ok((
+                    bincode::deserialize(&key).context(Decode)?,
+                    bincode::deserialize(&value).context(Decode)?,
+                ))
+            })
+            .collect()
+    }
+
+    fn asset(&self, id: &str) -> Result<Option<Asset>, Error> {
+        self.assets
+            .get(id)
+            .context(Database)?
+            .map(|value| bincode::deserialize(&value).context(Decode))
+            .transpose()
+    }
+
+    fn assets(&self) -> Result<Vec<Asset>, Error> {
+        self.assets
+            .iter()
+            .map(|entry| bincode::deserialize(&entry.context(Database)?.1).context(Decode))
+            .collect()
+    }
+
+    fn asset_balance(&self, asset: &str, user: &sign::PublicKey) -> Result<Amount, Error> {
+        Ok(self
+            .asset_balances
+            .get(asset_balance_key(asset, user)?)
+            .context(Database)?
+            .map(|value| bincode::deserialize(&value).context(Decode))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    fn asset_balances(&self) -> Result<Vec<(AssetId, sign::PublicKey, Amount)>, Error> {
+        self.asset_balances
+            .iter()
+            .map(|entry| {
+                let (key, value) = entry.context(Database)?;
+                let (asset, user) = bincode::deserialize(&key).context(Decode)?;
+
+                Ok((asset, user, bincode::deserialize(&value).context(Decode)?))
+            })
+            .collect()
+    }
+
+    fn update(&mut self, update: Update) -> Result<(), Error> {
+        let accounts = update
+            .accounts
+            .iter()
+            .map(|(user, account)| {
+                Ok((
+                    bincode::serialize(user).context(Encode)?,
+                    bincode::serialize(account).context(Encode)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let name = update
+            .name
+            .map(|(name, user)| Ok((name, bincode::serialize(&user).context(Encode)?)))
+            .transpose()?;
+        let minted = update
+            .minted
+            .map(|deposit| bincode::serialize(&deposit).context(Encode))
+            .transpose()?;
+        let attestations = update
+            .attestations
+            .map(|(deposit, attestations)| {
+                Ok((
+                    bincode::serialize(&deposit).context(Encode)?,
+                    bincode::serialize(&attestations).context(Encode)?,
+                ))
+            })
+            .transpose()?;
+        let checkpoint = update
+            .checkpoint
+            .map(|checkpoint| {
+                Ok((
+                    checkpoint.epoch.to_be_bytes(),
+                    bincode::serialize(&checkpoint).context(Encode)?,
+                ))
+            })
+            .transpose()?;
+        let applied = update
+            .applied
+            .map(|applied| {
+                let forgotten = applied
+                    .sequence
+                    .checked_sub(DEDUP_WINDOW)
+                    .map(|sequence| applied_key(&applied.sender, sequence))
+                    .transpose()?;
+
+                Ok((
+                    applied_key(&applied.sender, applied.sequence)?,
+                    applied.digest,
+                    forgotten,
+                ))
+            })
+            .transpose()?;
+        let windows = update
+            .windows
+            .iter()
+            .map(|(user, window)| {
+                let value = if *window == Window::default() {
+                    None
+                } else {
+                    Some(bincode::serialize(window).context(Encode)?)
+                };
+
+                Ok((bincode::serialize(user).context(Encode)?, value))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let assets = update
+            .assets
+            .iter()
+            .map(|asset| Ok((asset.id.clone(), bincode::serialize(asset).context(Encode)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let asset_balances = update
+            .asset_balances
+            .iter()
+            .map(|(asset, user, balance)| {
+                Ok((
+                    asset_balance_key(asset, user)?,
+                    bincode::serialize(balance).context(Encode)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        (
+            &self.accounts,
+            &self.names,
+            &self.minted,
+            &self.attestations,
+            &self.checkpoints,
+            &self.applied,
+            &self.changes,
+            &self.windows,
+            &self.assets,
+            &self.asset_balances,
+        )
+            .transaction(
+                |(
+                    tx_accounts,
+                    tx_names,
+                    tx_minted,
+                    tx_attestations,
+                    tx_checkpoints,
+                    tx_applied,
+                    tx_changes,
+                    tx_windows,
+                    tx_assets,
+                    tx_asset_balances,
+                )| {
+                    for (key, value) in &accounts {
+                        tx_accounts.insert(key.as_slice(), value.as_slice())?;
+
+                        let changes = decode_changes(tx_changes.get(key.as_slice())?) + 1;
+                        tx_changes.insert(key.as_slice(), &changes.to_be_bytes()[..])?;
+                    }
+                    if let Some((name, user)) = &name {
+                        tx_names.insert(name.as_bytes(), user.as_slice())?;
+                    }
+                    if let Some(deposit) = &minted {
+                        tx_minted.insert(deposit.as_slice(), &[])?;
+                        tx_attestations.remove(deposit.as_slice())?;
+                    }
+                    if let Some((deposit, attestations)) = &attestations {
+                        tx_attestations.insert(deposit.as_slice(), attestations.as_slice())?;
+                    }
+                    if let Some((epoch, checkpoint)) = &checkpoint {
+                        tx_checkpoints.insert(&epoch[..], checkpoint.as_slice())?;
+                    }
+                    if let Some((key, digest, forgotten)) = &applied {
+                        if let Some(forgotten) = forgotten {
+                            tx_applied.remove(forgotten.as_slice())?;
+                        }
+                        tx_applied.insert(key.as_slice(), &digest[..])?;
+                    }
+                    for (key, value) in &windows {
+                        match value {
+                            None => tx_windows.remove(key.as_slice())?,
+                            Some(value) => tx_windows.insert(key.as_slice(), value.as_slice())?,
+                        };
+                    }
+                    for (id, asset) in &assets {
+                        tx_assets.insert(id.as_bytes(), asset.as_slice())?;
+                    }
+                    for (key, balance) in &asset_balances {
+                        tx_asset_balances.insert(key.as_slice(), balance.as_slice())?;
+                    }
+
+                    Ok::<_, sled::transaction::ConflictableTransactionError<()>>(())
+                },
+            )
+            .map_err(|err| match err {
+                TransactionError::Abort(()) => unreachable!("transaction never aborts"),
+                TransactionError::Storage(err) => err,
+            })
+            .context(Database)
+    }
+}
@@ -0,0 +1,74 @@
+//! Only let the configured peers in
+
+use std::{collections::HashSet, fmt, net::SocketAddr};
+
+use drop::{
+    crypto::key::exchange::{self, Exchanger},
+    net::{Connection, Listener, ListenerError},
+};
+use tracing::warn;
+
+/// Drop the connections of peers whose key isn't allowed, once exchanged
+pub struct AllowlistListener<L> {
+    inner: L,
+    /// Any key is allowed if absent
+    allowed: Option<HashSet<exchange::PublicKey>>,
+}
+
+impl<L> AllowlistListener<L> {
+    /// Only accept the peers of `inner` with one of the `allowed` keys
+    pub fn new(inner: L, allowed: impl IntoIterator<Item = exchange::PublicKey>) -> Self {
+        Self {
+            inner,
+            allowed: Some(allowed.into_iter().collect()),
+        }
+    }
+
+    /// Accept every peer of `inner`
+    pub fn open(inner: L) -> Self {
+        Self {
+            inner,
+            allowed: None,
+        }
+    }
+}
+
+impl<L: fmt::Display> fmt::Display for AllowlistListener<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "allowlisted {}", self.inner)
+    }
+}
+
+#[async_trait::async_trait]
+impl<L: Listener> Listener for AllowlistListener<L> {
+    type Candidate = L::Candidate;
+
+    async fn local_addr(&self) -> Option<SocketAddr> {
+        self.inner.local_addr().await
+    }
+
+    async fn accept_raw(&mut self) -> Result<Connection, ListenerError> {
+        self.inner.accept_raw().await
+    }
+
+    fn exchanger(&self) -> &Exchanger {
+        self.inner.exchanger()
+    }
+
+    async fn accept(&mut self) -> Result<Connection, ListenerError> {
+        loop {
+            let connection = self.inner.accept().await?;
+
+            let allowed = match &self.allowed {
+                None => return Ok(connection),
+                Some(allowed) => allowed,
+            };
+
+            match connection.remote_key() {
+                Some(key) if allowed.contains(&key) => return Ok(connection),
+                Some(key) => warn!("allowlist: dropping unknown peer {}", key),
+                None => warn!("allowlist: dropping peer without key"),
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Nonces given to callers proving they own an account, see [`crate::auth`]
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use drop::crypto::sign;
+use rand::{rngs::OsRng, RngCore};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+
+use crate::auth::NONCE_SIZE;
+
+/// How long a signed nonce can be used
+pub const LIFETIME: Duration = Duration::from_secs(5 * 60);
+
+/// How many nonces are kept at most, the oldest being dropped to issue more,
+/// as anyone can ask for them
+pub const MAX_ISSUED: usize = 10_000;
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("gone on send"))]
+    GoneOnSend,
+    #[snafu(display("gone on recv"))]
+    GoneOnRecv,
+}
+
+#[derive(Debug)]
+enum Commands {
+    Issue {
+        account: sign::PublicKey,
+        resp: oneshot::Sender<Vec<u8>>,
+    },
+    Check {
+        account: sign::PublicKey,
+        nonce: Vec<u8>,
+        resp: oneshot::Sender<bool>,
+    },
+}
+
+#[derive(Clone)]
+pub struct Challenges {
+    agent: mpsc::Sender<Commands>,
+}
+
+/// Tokio agent owning the issued nonces.
+/// The only way to interacte with it is to use [`Challenges`].
+struct ChallengesHandler {
+    /// Account each nonce was given for, and when it expires
+    issued: HashMap<Vec<u8>, (sign::PublicKey, Instant)>,
+    /// Issued nonces, oldest first, thus ordered by expiry
+    order: VecDeque<Vec<u8>>,
+}
+
+impl Challenges {
+    pub fn new() -> Self {
+        Self {
+            agent: ChallengesHandler {
+                issued: HashMap::new(),
+                order: VecDeque::new(),
+            }
+            .spawn(),
+        }
+    }
+
+    /// Give a new nonce for `account` to sign
+    pub async fn issue(&self, account: sign::PublicKey) -> Result<Vec<u8>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Issue { account, resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// Whether `nonce` was given for `account` and didn't expire
+    pub async fn check(&self, account: sign::PublicKey, nonce: Vec<u8>) -> Result<bool, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Check {
+                account,
+                nonce,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+}
+
+impl ChallengesHandler {
+    fn spawn(mut self) -> mpsc::Sender<Commands> {
+        let (tx, mut rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            while let Some(cmd) = rx.recv().await {
+                match cmd {
+                    Commands::Issue { account, resp } => {
+                        let now = Instant::now();
+                        self.prune(now);
+
+                        let mut nonce = vec![0; NONCE_SIZE];
+                        OsRng.fill_bytes(&mut nonce);
+                        self.issued.insert(nonce.clone(), (account, now + LIFETIME));
+                        self.order.push_back(nonce.clone());
+
+                        let _ = resp.send(nonce);
+                    }
+                    Commands::Check {
+                        account,
+                        nonce,
+                        resp,
+                    } => {
+                        let valid = matches!(
+                            self.issued.get(&nonce),
+                            Some((issued_for, expiry))
+                                if *issued_for == account && *expiry > Instant::now()
+                        );
+
+                        let _ = resp.send(valid);
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+
+    /// Drop the expired nonces, and the oldest ones to make room for another
+    fn prune(&mut self, now: Instant) {
+        while let Some(oldest) = self.order.front() {
+            let expired = self
+                .issued
+                .get(oldest)
+                .map_or(true, |(_, expiry)| *expiry <= now);
+            if !expired && self.order.len() < MAX_ISSUED {
+                break;
+            }
+
+            if let Some(oldest) = self.order.pop_front() {
+                self.issued.remove(&oldest);
+            }
+        }
+    }
+}
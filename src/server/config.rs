@@ -0,0 +1,555 @@
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs, io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use drop::crypto::{key::exchange, sign};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ConfigAddresses {
+    pub rpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jsonrpc: Option<String>,
+    /// Where to serve the web dashboard, disabled if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dashboard: Option<String>,
+    /// Where to serve JSON-RPC over WebSocket, disabled if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub websocket: Option<String>,
+    /// Either an address or a `bind` and `advertise` table, last as toml
+    /// writes the tables after the values
+    pub node: ConfigNodeAddress,
+}
+
+/// Where the node listens for its peers, and where they should connect
+///
+/// Both are the same unless the node is behind a NAT or a load balancer.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ConfigNodeAddress {
+    Same(String),
+    Split {
+        /// Local address to listen on, such as `0.0.0.0:3000`
+        bind: String,
+        /// Address given to the peers, as found in `config get-node`
+        advertise: String,
+    },
+}
+
+impl ConfigNodeAddress {
+    /// Address to listen on
+    pub fn bind(&self) -> &str {
+        match self {
+            Self::Same(address) | Self::Split { bind: address, .. } => address,
+        }
+    }
+
+    /// Address the peers should connect to
+    pub fn advertise(&self) -> &str {
+        match self {
+            Self::Same(address)
+            | Self::Split {
+                advertise: address, ..
+            } => address,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ConfigKeys {
+    #[serde(with = "hex")]
+    pub sign: sign::PrivateKey,
+    pub network: exchange::PrivateKey,
+}
+
+/// Where to find the node's keys, either inline or in a secret store
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+pub enum ConfigKeysSource {
+    Inline(ConfigKeys),
+    Vault { vault: ConfigVault },
+    Encrypted { encrypted: ConfigEncryptedKeys },
+}
+
+/// [`ConfigKeys`] encrypted with a passphrase, see [`super::keystore`]
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ConfigEncryptedKeys {
+    #[serde(with = "hex")]
+    pub salt: Vec<u8>,
+    #[serde(with = "hex")]
+    pub nonce: Vec<u8>,
+    #[serde(with = "hex")]
+    pub ciphertext: Vec<u8>,
+}
+
+/// Secret in a HashiCorp Vault KV v2 engine, holding the fields of [`ConfigKeys`]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct ConfigVault {
+    /// Base URL of the Vault server, such as `https://vault:8200`
+    pub address: String,
+    /// API path of the secret, such as `secret/data/at2/node`
+    pub path: String,
+    /// Environment variable holding the Vault token
+    #[serde(default = "default_vault_token_env")]
+    pub token_env: String,
+    /// How often to check for a rotated secret, in seconds
+    #[serde(default = "default_vault_refresh")]
+    pub refresh: u64,
+}
+
+fn default_vault_token_env() -> String {
+    "VAULT_TOKEN".to_owned()
+}
+
+fn default_vault_refresh() -> u64 {
+    60
+}
+
+/// Tuning of the broadcast stack, defaults to murmur's own defaults
+///
+/// Sample sizes default to the number of peers, or to the expected size of the
+/// [`ConfigSampling::Poisson`] samples, and thresholds to the sample sizes,
+/// less the `byzantine_fraction` of them; lowering them trades safety for
+/// liveness.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct ConfigProtocol {
+    /// How many transactions to gather before sending a batch
+    pub batch_size: usize,
+    /// How long to wait before flushing an incomplete batch, in milliseconds
+    pub batch_delay: u64,
+    /// How many peers murmur gossips each batch to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gossip_size: Option<usize>,
+    /// How many peers sieve asks to echo a batch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub echo_sample_size: Option<usize>,
+    /// How many echoes sieve waits for before delivering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub echo_threshold: Option<usize>,
+    /// How many peers contagion asks to be ready for a batch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ready_sample_size: Option<usize>,
+    /// How many ready peers contagion waits for before delivering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ready_threshold: Option<usize>,
+    /// Fraction of each sample which might be faulty, below one half, none if absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub byzantine_fraction: Option<f64>,
+    /// How many delivered transactions can wait to be applied, delivery pausing past it
+    pub delivery_capacity: usize,
+    /// How many workers the broadcast processor runs, one per core if absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processor_workers: Option<NonZeroUsize>,
+    /// Last as toml writes the tables after the values
+    pub sampling: ConfigSampling,
+}
+
+/// How the peers taking part in each step of a broadcast are drawn
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConfigSampling {
+    /// Every peer, only sensible for small networks
+    All,
+    /// Each peer independently, so that samples hold `expected` peers on average
+    Poisson {
+        expected: usize,
+        /// Fraction of `expected` a sample might lack, lowering the thresholds
+        tolerance: f64,
+    },
+}
+
+impl Default for ConfigSampling {
+    fn default() -> Self {
+        Self::All
+    }
+}
+
+impl Default for ConfigProtocol {
+    fn default() -> Self {
+        let murmur = murmur::MurmurConfig::default();
+
+        Self {
+            batch_size: murmur.batch_size,
+            batch_delay: murmur.batch_delay,
+            gossip_size: None,
+            echo_sample_size: None,
+            echo_threshold: None,
+            ready_sample_size: None,
+            ready_threshold: None,
+            byzantine_fraction: None,
+            delivery_capacity: 1024,
+            processor_workers: None,
+            sampling: ConfigSampling::default(),
+        }
+    }
+}
+
+/// Threads of the tokio runtime, defaults to tokio's own defaults
+#[derive(serde::Deserialize, serde::Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ConfigRuntime {
+    /// How many threads run the tasks, one per core if absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker_threads: Option<NonZeroUsize>,
+    /// Up to how many threads run blocking calls, such as to the storage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_blocking_threads: Option<NonZeroUsize>,
+}
+
+/// Which of the delivered transactions are kept for `GetLatestTransactions`,
+/// the dashboard and such
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct ConfigRecentTransactions {
+    /// How many transactions to keep, the oldest being dropped first
+    pub max_count: usize,
+    /// How long to keep a transaction, in seconds, unbounded if absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<u64>,
+    /// Whether to keep them in the `storage_path` database, surviving restarts
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub persistent: bool,
+}
+
+impl Default for ConfigRecentTransactions {
+    fn default() -> Self {
+        Self {
+            max_count: 10,
+            max_age: None,
+            persistent: false,
+        }
+    }
+}
+
+/// How often to hash the accounts, see [`super::accounts::Checkpoint`]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct ConfigCheckpoints {
+    /// Operations applied between two checkpoints, none being taken if zero
+    ///
+    /// Changing it renumbers the epochs of the next checkpoints.
+    pub interval: u64,
+}
+
+impl Default for ConfigCheckpoints {
+    fn default() -> Self {
+        Self {
+            interval: super::accounts::DEFAULT_CHECKPOINT_INTERVAL,
+        }
+    }
+}
+
+/// Copy the accounts of the peers when starting without any, see [`super::sync`]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct ConfigSync {
+    /// How many peers, among the ones with an `rpc` address, have to give the
+    /// same accounts
+    pub quorum: NonZeroUsize,
+}
+
+/// TLS of the RPC endpoint, with PEM encoded files
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ConfigTls {
+    /// Certificate chain of the node
+    pub cert: PathBuf,
+    /// Private key of the certificate
+    pub key: PathBuf,
+    /// Authority signing the certificates of clients, required if present
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca: Option<PathBuf>,
+}
+
+/// Where to publish the node's events
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct ConfigEvents {
+    pub nats_url: String,
+    pub subject: String,
+}
+
+/// OpenTelemetry collector receiving the spans, over OTLP
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct ConfigTelemetry {
+    /// gRPC endpoint of the collector, such as `http://localhost:4317`
+    pub endpoint: String,
+    /// Name the spans are exported under
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+fn default_service_name() -> String {
+    "at2-node".to_owned()
+}
+
+/// Limit on submitted transactions, per sender and per peer address
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct ConfigRateLimit {
+    /// How many transactions are allowed per second, on average
+    pub rate: f64,
+    /// How many transactions can be submitted at once
+    pub burst: u32,
+}
+
+/// Fee paid by every transfer, see [`crate::Fees`]
+///
+/// Every node of the network has to agree on it.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct ConfigFees {
+    /// Hex encoded account receiving the fees
+    pub account: String,
+    /// Paid whatever the amount
+    #[serde(default)]
+    pub flat: u64,
+    /// Paid per ten thousand of the total amount
+    #[serde(default)]
+    pub basis_points: u64,
+}
+
+/// What a submitted transfer is checked against, beyond its signature
+///
+/// Only checked by the node a transfer is submitted to, so nodes can differ.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ConfigValidation {
+    /// Whether an output can send nothing
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub allow_zero_amount: bool,
+    /// Whether an output can send back to the sender
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub allow_self_transfer: bool,
+}
+
+/// Bridge with another AT2 network
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ConfigBridge {
+    /// Hex encoded account where assets are sent to be withdrawn to the other network
+    pub lock: String,
+    /// Hex encoded public keys of the validators watching the other network
+    pub validators: Vec<String>,
+    /// How many validators need to attest a deposit before it is minted
+    pub threshold: usize,
+}
+
+/// Format of the configs written by this binary, see [`migrate`]
+///
+/// 0. unversioned, as written before versions
+/// 1. carries its `version`
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Upgrades of the config, the `n`th one going from version `n` to `n + 1`
+const MIGRATIONS: [fn(&mut toml::value::Table); CONFIG_VERSION as usize] = [
+    // unversioned configs are still read as is
+    |_| {},
+];
+
+fn default_chain_id() -> String {
+    crate::interchange::DEFAULT_CHAIN_ID.to_owned()
+}
+
+/// Largest request body read by the HTTP gateways, in bytes
+pub const DEFAULT_MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+fn default_max_body_size() -> usize {
+    DEFAULT_MAX_BODY_SIZE
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct Config {
+    /// Format of the config, see [`CONFIG_VERSION`]
+    #[serde(default)]
+    pub version: u32,
+    /// Network accepted in submitted [`crate::interchange::Document`]s
+    #[serde(default = "default_chain_id")]
+    pub chain_id: String,
+    /// Directory where to keep the accounts, in memory if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_path: Option<PathBuf>,
+    /// Hex encoded public keys allowed to mint asset, for test networks
+    #[serde(skip_serializing_if = "Vec::is_empty", default = "Vec::default")]
+    pub admins: Vec<String>,
+    /// Whether to serve the gRPC reflection service, for generic tooling
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reflection: bool,
+    /// Whether reading an account requires proving its ownership, see [`crate::auth`]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub authenticated_reads: bool,
+    /// Whether peers not listed in `nodes` can connect to this node
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub open_network: bool,
+    /// Largest request body the JSON and JSON-RPC gateways read, in bytes
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    pub addresses: ConfigAddresses,
+    /// Plaintext RPC if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<ConfigTls>,
+    pub keys: ConfigKeysSource,
+    #[serde(default)]
+    pub protocol: ConfigProtocol,
+    #[serde(default)]
+    pub runtime: ConfigRuntime,
+    #[serde(default)]
+    pub recent_transactions: ConfigRecentTransactions,
+    #[serde(default)]
+    pub checkpoints: ConfigCheckpoints,
+    /// Start from scratch if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync: Option<ConfigSync>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<ConfigEvents>,
+    /// Spans aren't exported if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub telemetry: Option<ConfigTelemetry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bridge: Option<ConfigBridge>,
+    /// Unlimited if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<ConfigRateLimit>,
+    /// Transfers are free if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fees: Option<ConfigFees>,
+    #[serde(default)]
+    pub validation: ConfigValidation,
+    /// Starting balances, by hex encoded public key of their owner
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub genesis: BTreeMap<String, u64>,
+    // FIXME toml fails with empty Vec alexcrichton/toml-rs#384
+    #[serde(skip_serializing_if = "Vec::is_empty", default = "Vec::default")]
+    pub nodes: Vec<Node>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct Node {
+    /// `host:port` of the peer, the host being an IP or a DNS name
+    pub address: String,
+    /// Other `host:port`s to try in order if `address` is unreachable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_addresses: Vec<String>,
+    #[serde(with = "hex")]
+    pub public_key: exchange::PublicKey,
+    /// Where the node serves its RPC, to check its version before starting
+    ///
+    /// Either `host:port`, or a URI such as `https://host:port` if serving TLS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpc: Option<String>,
+}
+
+impl Node {
+    /// Every address of the peer, the preferred one first
+    pub fn addresses(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.address).chain(&self.fallback_addresses)
+    }
+
+    /// URI of the node's RPC, plaintext if no scheme is given
+    pub fn rpc_uri(&self) -> Option<String> {
+        self.rpc.as_ref().map(|rpc| {
+            if rpc.contains("://") {
+                rpc.clone()
+            } else {
+                format!("http://{}", rpc)
+            }
+        })
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct Nodes {
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("encode: {}", source))]
+    Encode { source: toml::ser::Error },
+    #[snafu(display("write: {}", source))]
+    Write { source: io::Error },
+
+    #[snafu(display("read: {}", source))]
+    Read { source: io::Error },
+    #[snafu(display("open {}: {}", path.display(), source))]
+    Open { path: PathBuf, source: io::Error },
+    #[snafu(display("decode: {}", source))]
+    Decode { source: toml::de::Error },
+    #[snafu(display("invalid version: {}", version))]
+    InvalidVersion { version: toml::Value },
+    #[snafu(display(
+        "version {} is newer than {}, the latest this binary reads: upgrade it",
+        version,
+        CONFIG_VERSION
+    ))]
+    NewerVersion { version: u32 },
+}
+
+/// Read the config, upgrading it if written in an older format
+pub fn from_reader(mut reader: impl io::Read) -> Result<Config, Error> {
+    let mut buffer = String::new();
+    reader.read_to_string(&mut buffer).context(Read)?;
+
+    let mut table = toml::from_str(&buffer).context(Decode)?;
+    migrate(&mut table)?;
+
+    toml::Value::Table(table).try_into().context(Decode)
+}
+
+/// Upgrade the config in `table` to [`CONFIG_VERSION`], returning the version
+/// it was written in
+///
+/// A config newer than this binary is refused, as it might mean something else.
+pub fn migrate(table: &mut toml::value::Table) -> Result<u32, Error> {
+    let version = match table.get("version") {
+        None => 0,
+        Some(value) => value
+            .as_integer()
+            .and_then(|version| u32::try_from(version).ok())
+            .context(InvalidVersion {
+                version: value.clone(),
+            })?,
+    };
+    ensure!(version <= CONFIG_VERSION, NewerVersion { version });
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(table);
+    }
+    table.insert(
+        "version".to_owned(),
+        toml::Value::Integer(CONFIG_VERSION.into()),
+    );
+
+    Ok(version)
+}
+
+/// Read the config in the file at `path`, or from stdin if `-`
+pub fn from_path(path: &Path) -> Result<Config, Error> {
+    if path == Path::new("-") {
+        return from_reader(io::stdin());
+    }
+
+    from_reader(fs::File::open(path).context(Open { path })?)
+}
+
+impl Config {
+    pub fn to_writer(&self, mut writer: impl io::Write) -> Result<(), Error> {
+        let encoded = toml::to_vec(&self).context(Encode)?;
+
+        writer.write_all(&encoded).context(Write)?;
+
+        Ok(())
+    }
+}
+
+impl Nodes {
+    // TODO wrapped into a vec, get rid of it when toml with empty vec works
+    pub fn to_writer(&self, mut writer: impl io::Write) -> Result<(), Error> {
+        let encoded = toml::to_vec(&self).context(Encode)?;
+
+        writer.write_all(&encoded).context(Write)?;
+
+        Ok(())
+    }
+}
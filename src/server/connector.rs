@@ -0,0 +1,68 @@
+//! Reach a peer at any of its addresses
+
+use std::fmt;
+
+use drop::{
+    crypto::key::exchange,
+    net::{ConnectError, Connection, Connector},
+};
+use tracing::debug;
+
+/// Every `host:port` of a peer, the preferred one first, never empty
+#[derive(Debug, Clone)]
+pub struct Addresses(Vec<String>);
+
+impl Addresses {
+    /// Addresses of the `node`, as configured
+    pub fn of(node: &super::config::Node) -> Self {
+        Self(node.addresses().cloned().collect())
+    }
+}
+
+impl fmt::Display for Addresses {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join(" or "))
+    }
+}
+
+/// Try each address in order with the inner connector, until one answers
+///
+/// Names are resolved by the inner connector, anew on each try, so that
+/// retrying finds a peer whose IP changed meanwhile.
+pub struct FallbackConnector<C> {
+    inner: C,
+}
+
+impl<C> FallbackConnector<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait::async_trait]
+impl<C> Connector for FallbackConnector<C>
+where
+    C: Connector<Candidate = str>,
+{
+    type Candidate = Addresses;
+
+    async fn establish(
+        &self,
+        pkey: &exchange::PublicKey,
+        candidate: &Self::Candidate,
+    ) -> Result<Connection, ConnectError> {
+        let mut last_err = None;
+
+        for address in &candidate.0 {
+            match self.inner.establish(pkey, address).await {
+                Ok(connection) => return Ok(connection),
+                Err(err) => {
+                    debug!("unable to connect to {}: {}", address, err);
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        Err(last_err.expect("at least one address"))
+    }
+}
@@ -0,0 +1,294 @@
+//! Minimal web dashboard, embedded in the binary
+//!
+//! `GET /` serves the page, which polls the JSON served under `/api`:
+//! - `/api/status`, the node, its peers and its delivery queue
+//! - `/api/transactions`, the recent transactions
+//! - `/api/balances`, the balances of the accounts in the recent transactions
+//!
+//! `/metrics` serves the same counters in the Prometheus text format, along
+//! the transfers rejected for each reason.
+
+use std::{
+    collections::BTreeMap, convert::Infallible, fmt::Write, future::Future, net::SocketAddr,
+};
+
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, StatusCode,
+};
+use serde::Serialize;
+use snafu::{ResultExt, Snafu};
+use tracing::warn;
+
+use super::{json::json_response, recent_transactions, rejected_transactions, rpc};
+use crate::{address::Address, Amount, FullTransaction};
+
+const INDEX: &str = include_str!("index.html");
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("serve: {}", source))]
+    Serve { source: hyper::Error },
+}
+
+#[derive(Snafu, Debug)]
+enum RequestError {
+    #[snafu(display("not found"))]
+    NotFound,
+    #[snafu(display("get recent transactions: {}", source))]
+    RecentTransactions {
+        source: super::recent_transactions::Error,
+    },
+    #[snafu(display("get rejected transactions: {}", source))]
+    RejectedTransactions {
+        source: super::rejected_transactions::Error,
+    },
+    #[snafu(display("get balance: {}", source))]
+    Balance { source: super::accounts::Error },
+    #[snafu(display("encode body: {}", source))]
+    EncodeBody { source: serde_json::Error },
+}
+
+#[derive(Serialize)]
+struct Status<'a> {
+    node: &'a str,
+    peers: &'a [String],
+    delivered: u64,
+    queued: usize,
+    recent_transactions: recent_transactions::Stats,
+}
+
+#[derive(Serialize)]
+struct Transaction {
+    timestamp: String,
+    sender: String,
+    sequence: u32,
+    recipient: String,
+    amount: Amount,
+    memo: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AccountBalance {
+    address: String,
+    amount: Amount,
+}
+
+impl From<&FullTransaction> for Transaction {
+    fn from(tx: &FullTransaction) -> Self {
+        Self {
+            timestamp: tx.timestamp.to_rfc3339(),
+            sender: Address::from(tx.sender).to_string(),
+            sequence: tx.sequence,
+            recipient: Address::from(tx.recipient).to_string(),
+            amount: tx.amount,
+            memo: tx.memo.clone(),
+        }
+    }
+}
+
+/// Serve the dashboard for `service` on `addr`, until `shutdown` resolves
+pub async fn serve(
+    addr: SocketAddr,
+    service: rpc::Service,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), Error> {
+    let make_service = make_service_fn(move |_| {
+        let service = service.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let service = service.clone();
+
+                async move { Ok::<_, Infallible>(handle(service, req).await) }
+            }))
+        }
+    });
+
+    hyper::Server::bind(&addr)
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+        .context(Serve)
+}
+
+async fn handle(service: rpc::Service, req: Request<Body>) -> Response<Body> {
+    match route(service, req).await {
+        Ok(reply) => reply,
+        Err(err) => {
+            let status = match &err {
+                RequestError::NotFound => StatusCode::NOT_FOUND,
+                _ => {
+                    warn!("dashboard: {}", err);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+
+            let mut response = Response::new(Body::from(err.to_string()));
+            *response.status_mut() = status;
+            response
+        }
+    }
+}
+
+async fn route(service: rpc::Service, req: Request<Body>) -> Result<Response<Body>, RequestError> {
+    if req.method() != Method::GET {
+        return Err(RequestError::NotFound);
+    }
+
+    match req.uri().path() {
+        "/" => {
+            let mut response = Response::new(Body::from(INDEX));
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                "text/html; charset=utf-8".parse().expect("valid header"),
+            );
+            Ok(response)
+        }
+        "/api/status" => {
+            let stats = service
+                .recent_transactions()
+                .get_stats()
+                .await
+                .context(RecentTransactions)?;
+
+            encode(&Status {
+                node: service.node(),
+                peers: service.peers(),
+                delivered: stats.delivered,
+                queued: service.queued(),
+                recent_transactions: stats,
+            })
+        }
+        "/metrics" => {
+            let stats = service
+                .recent_transactions()
+                .get_stats()
+                .await
+                .context(RecentTransactions)?;
+
+            let rejected = service
+                .rejected_transactions()
+                .get_counts()
+                .await
+                .context(RejectedTransactions)?;
+
+            let mut response =
+                Response::new(Body::from(metrics(service.queued(), stats, &rejected)));
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                "text/plain; version=0.0.4".parse().expect("valid header"),
+            );
+            Ok(response)
+        }
+        "/api/transactions" => {
+            let recents = service
+                .recent_transactions()
+                .get_all()
+                .await
+                .context(RecentTransactions)?;
+
+            encode(&recents.iter().map(Transaction::from).collect::<Vec<_>>())
+        }
+        "/api/balances" => {
+            let users = service
+                .recent_transactions()
+                .get_all()
+                .await
+                .context(RecentTransactions)?
+                .iter()
+                .flat_map(|tx| vec![tx.sender, tx.recipient])
+                .map(|user| (Address::from(user).to_string(), user))
+                .collect::<BTreeMap<_, _>>();
+
+            let mut balances = Vec::with_capacity(users.len());
+            for (address, user) in users {
+                let amount = service
+                    .accounts()
+                    .get_balance(Box::new(user))
+                    .await
+                    .context(Balance)?;
+
+                balances.push(AccountBalance { address, amount });
+            }
+
+            encode(&balances)
+        }
+        _ => Err(RequestError::NotFound),
+    }
+}
+
+/// Counters in the Prometheus text format
+fn metrics(
+    queued: usize,
+    stats: recent_transactions::Stats,
+    rejected: &[(rejected_transactions::Reason, u64)],
+) -> String {
+    let metrics: [(&str, &str, &str, u64); 6] = [
+        (
+            "at2_delivered_transactions_total",
+            "counter",
+            "Transactions delivered, counted across restarts if persistent",
+            stats.delivered,
+        ),
+        (
+            "at2_delivery_queue",
+            "gauge",
+            "Delivered transactions waiting to be applied",
+            queued as u64,
+        ),
+        (
+            "at2_recent_transactions",
+            "gauge",
+            "Recent transactions kept",
+            stats.kept as u64,
+        ),
+        (
+            "at2_recent_transactions_capacity",
+            "gauge",
+            "Most recent transactions kept at once",
+            stats.capacity as u64,
+        ),
+        (
+            "at2_recent_transactions_dropped_total",
+            "counter",
+            "Recent transactions dropped to make room, since the start",
+            stats.dropped,
+        ),
+        (
+            "at2_recent_transactions_expired_total",
+            "counter",
+            "Recent transactions dropped for their age, since the start",
+            stats.expired,
+        ),
+    ];
+
+    let mut text = String::new();
+    for (name, kind, help, value) in metrics.iter() {
+        writeln!(text, "# HELP {} {}", name, help).expect("write to string");
+        writeln!(text, "# TYPE {} {}", name, kind).expect("write to string");
+        writeln!(text, "{} {}", name, value).expect("write to string");
+    }
+
+    let name = "at2_rejected_transactions_total";
+    writeln!(
+        text,
+        "# HELP {} Transfers delivered but refused, by reason, since the start",
+        name
+    )
+    .expect("write to string");
+    writeln!(text, "# TYPE {} counter", name).expect("write to string");
+    for (reason, count) in rejected {
+        writeln!(text, "{}{{reason=\"{}\"}} {}", name, reason.as_str(), count)
+            .expect("write to string");
+    }
+
+    text
+}
+
+fn encode<T: Serialize>(reply: &T) -> Result<Response<Body>, RequestError> {
+    let body = serde_json::to_vec(reply).context(EncodeBody)?;
+
+    Ok(json_response(StatusCode::OK, body))
+}
@@ -0,0 +1,147 @@
+use std::io;
+
+use serde::Serialize;
+use snafu::{ResultExt, Snafu};
+
+use super::{accounts::Checkpoint, config, rejected_transactions::Rejection};
+use crate::{Amount, AssetId, FullTransaction};
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("connect to nats: {}", source))]
+    Connect { source: io::Error },
+    #[snafu(display("encode event: {}", source))]
+    Encode { source: serde_json::Error },
+    #[snafu(display("publish event: {}", source))]
+    Publish { source: io::Error },
+}
+
+/// What is published, as JSON
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Transaction {
+        timestamp: String,
+        sender: String,
+        recipient: String,
+        amount: Amount,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        memo: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        asset: Option<AssetId>,
+    },
+    /// Taken by this node, see [`Checkpoint`]
+    Checkpoint {
+        timestamp: String,
+        epoch: u64,
+        applied: u64,
+        /// Hex encoded
+        hash: String,
+    },
+    /// Transfer refused once delivered, see [`Rejection`]
+    Rejection {
+        timestamp: String,
+        sender: String,
+        sequence: sieve::Sequence,
+        outputs: Vec<EventOutput>,
+        fee: Amount,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        asset: Option<AssetId>,
+        reason: &'static str,
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+pub struct EventOutput {
+    recipient: String,
+    amount: Amount,
+}
+
+impl From<&FullTransaction> for Event {
+    fn from(tx: &FullTransaction) -> Self {
+        Self::Transaction {
+            timestamp: tx.timestamp.to_rfc3339(),
+            sender: tx.sender.to_string(),
+            recipient: tx.recipient.to_string(),
+            amount: tx.amount,
+            memo: tx.memo.clone(),
+            asset: tx.asset.clone(),
+        }
+    }
+}
+
+impl From<&Checkpoint> for Event {
+    fn from(checkpoint: &Checkpoint) -> Self {
+        Self::Checkpoint {
+            timestamp: checkpoint.timestamp.to_rfc3339(),
+            epoch: checkpoint.epoch,
+            applied: checkpoint.applied,
+            hash: hex::encode(checkpoint.hash),
+        }
+    }
+}
+
+impl From<&Rejection> for Event {
+    fn from(rejection: &Rejection) -> Self {
+        Self::Rejection {
+            timestamp: rejection.timestamp.to_rfc3339(),
+            sender: rejection.sender.to_string(),
+            sequence: rejection.sequence,
+            outputs: rejection
+                .outputs
+                .iter()
+                .map(|output| EventOutput {
+                    recipient: output.recipient.to_string(),
+                    amount: output.amount,
+                })
+                .collect(),
+            fee: rejection.fee,
+            asset: rejection.asset.clone(),
+            reason: rejection.reason.as_str(),
+            message: rejection.message.clone(),
+        }
+    }
+}
+
+/// Publish what happens on the node to a NATS subject
+#[derive(Clone)]
+pub struct Events {
+    connection: async_nats::Connection,
+    subject: String,
+}
+
+impl Events {
+    pub async fn connect(config: config::ConfigEvents) -> Result<Self, Error> {
+        Ok(Self {
+            connection: async_nats::connect(&config.nats_url)
+                .await
+                .context(Connect)?,
+            subject: config.subject,
+        })
+    }
+
+    /// Publish a delivered transaction
+    pub async fn transaction(&self, tx: &FullTransaction) -> Result<(), Error> {
+        self.publish(&Event::from(tx)).await
+    }
+
+    /// Publish a checkpoint just taken
+    pub async fn checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), Error> {
+        self.publish(&Event::from(checkpoint)).await
+    }
+
+    /// Publish a transfer just rejected
+    pub async fn rejection(&self, rejection: &Rejection) -> Result<(), Error> {
+        self.publish(&Event::from(rejection)).await
+    }
+
+    async fn publish(&self, event: &Event) -> Result<(), Error> {
+        let encoded = serde_json::to_vec(event).context(Encode)?;
+
+        self.connection
+            .publish(&self.subject, encoded)
+            .await
+            .context(Publish)
+    }
+}
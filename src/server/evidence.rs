@@ -0,0 +1,160 @@
+//! Proofs of senders signing conflicting operations, see [`Evidence`]
+//!
+//! Contagion should never deliver two payloads for the same sender and
+//! sequence, but faulty or lowered thresholds can let it happen; the node then
+//! keeps both signed operations, for monitoring or slashing by other tools.
+
+use std::collections::{HashMap, VecDeque};
+
+use drop::crypto::sign;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{Evidence, SignedOperation};
+
+/// How many delivered operations to remember, the oldest being forgotten first
+const TRACKED_MAX_SIZE: usize = 10_000;
+/// How many evidences to keep, the oldest being forgotten first
+const EVIDENCE_MAX_SIZE: usize = 1_000;
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("gone on send"))]
+    GoneOnSend,
+    #[snafu(display("gone on recv"))]
+    GoneOnRecv,
+}
+
+type Key = (sign::PublicKey, sieve::Sequence);
+
+#[derive(Debug)]
+enum Commands {
+    Put {
+        key: Box<Key>,
+        signed: Box<SignedOperation>,
+        resp: oneshot::Sender<bool>,
+    },
+    GetAll {
+        resp: oneshot::Sender<Vec<Evidence>>,
+    },
+}
+
+#[derive(Clone)]
+pub struct Evidences {
+    agent: mpsc::Sender<Commands>,
+}
+
+/// Tokio agent owning the evidences.
+/// The only way to interacte with it is to use [`Evidences`].
+struct EvidencesHandler {
+    delivered: HashMap<Key, SignedOperation>,
+    /// Keys in order of delivery
+    order: VecDeque<Key>,
+    evidences: VecDeque<Evidence>,
+}
+
+impl Evidences {
+    pub fn new() -> Self {
+        Self {
+            agent: EvidencesHandler {
+                delivered: HashMap::new(),
+                order: VecDeque::new(),
+                evidences: VecDeque::new(),
+            }
+            .spawn(),
+        }
+    }
+
+    /// Record the operation delivered for `sender` at `sequence`
+    ///
+    /// Return true if it conflicts with a previous one, which is then kept as
+    /// evidence. The signature is expected to be already checked.
+    pub async fn put(
+        &self,
+        sender: sign::PublicKey,
+        sequence: sieve::Sequence,
+        signed: SignedOperation,
+    ) -> Result<bool, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Put {
+                key: Box::new((sender, sequence)),
+                signed: Box::new(signed),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// Return the kept evidences, oldest first
+    pub async fn get_all(&self) -> Result<Vec<Evidence>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetAll { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+}
+
+impl EvidencesHandler {
+    fn spawn(mut self) -> mpsc::Sender<Commands> {
+        let (tx, mut rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            while let Some(cmd) = rx.recv().await {
+                match cmd {
+                    Commands::Put { key, signed, resp } => {
+                        let _ = resp.send(self.put(*key, *signed));
+                    }
+                    Commands::GetAll { resp } => {
+                        let _ = resp.send(self.evidences.iter().cloned().collect());
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+
+    fn put(&mut self, key: Key, signed: SignedOperation) -> bool {
+        let first = match self.delivered.get(&key) {
+            Some(first) => first,
+            None => {
+                if self.order.len() == TRACKED_MAX_SIZE {
+                    if let Some(oldest) = self.order.pop_front() {
+                        self.delivered.remove(&oldest);
+                    }
+                }
+                self.order.push_back(key);
+                self.delivered.insert(key, signed);
+
+                return false;
+            }
+        };
+
+        let (sender, sequence) = key;
+        let evidence = Evidence {
+            timestamp: chrono::Utc::now(),
+            sender,
+            sequence,
+            first: first.clone(),
+            second: signed,
+        };
+        // a payload delivered twice is no proof of anything
+        if !evidence.verify() {
+            return false;
+        }
+
+        if self.evidences.len() == EVIDENCE_MAX_SIZE {
+            self.evidences.pop_front();
+        }
+        self.evidences.push_back(evidence);
+
+        true
+    }
+}
@@ -0,0 +1,173 @@
+//! Readiness of the node, through the standard `grpc.health.v1.Health` service
+//!
+//! drop doesn't tell which peers are connected, so the node instead asks
+//! its peers for their node info, and serves while enough of them answer to
+//! deliver transactions. Answers are kept in the [`rpc::Service::peer_statuses`],
+//! along the round trips and sizes of the checks, summarized in the logs.
+
+use std::time::{Duration, Instant};
+
+use futures::future;
+use prost::Message;
+use tokio::time::{sleep, timeout};
+use tonic::transport::{Channel, ClientTlsConfig, NamedService};
+use tonic_health::{
+    server::{health_reporter, Health, HealthReporter, HealthServer},
+    ServingStatus,
+};
+use tracing::{info, warn};
+
+use super::{config, peer_statuses::Check, rpc};
+use crate::proto::{self, at2_client::At2Client, at2_server::At2Server};
+
+/// How often the peers are asked
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// How long a peer has to answer
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often the stats of the peers are logged
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Create the health service, not serving until [`watch`] says otherwise
+pub async fn service() -> (HealthReporter, HealthServer<impl Health>) {
+    let (mut reporter, service) = health_reporter();
+    set_status(&mut reporter, ServingStatus::NotServing).await;
+
+    (reporter, service)
+}
+
+/// Report the node as serving while at least a quorum of the `service`'s peers answer
+///
+/// Peers without an `rpc` address can't be asked, so they are counted as
+/// answering.
+pub async fn watch(mut reporter: HealthReporter, service: rpc::Service) {
+    let peers = service.nodes();
+    let quorum = service.quorum();
+    let mut serving = None;
+    let mut last_summary = Instant::now();
+
+    // kept across checks, so that only the first one waits for a connection
+    let clients = peers
+        .iter()
+        .map(|peer| peer.rpc_uri().map(|uri| connect(&uri)))
+        .collect::<Vec<_>>();
+
+    loop {
+        let checks = future::join_all(clients.iter().map(check)).await;
+
+        for (peer, check) in peers.iter().zip(&checks) {
+            if let Some(check) = check {
+                if let Err(err) = service
+                    .peer_statuses()
+                    .put(peer.address.clone(), check.clone())
+                    .await
+                {
+                    warn!("health: record peer status: {}", err);
+                }
+            }
+        }
+
+        let answering = checks
+            .iter()
+            .filter(|check| check.as_ref().map_or(true, |check| check.rtt.is_some()))
+            .count();
+
+        let ready = answering >= quorum;
+        if serving != Some(ready) {
+            info!(
+                "health: {} peers answering, {} needed, {}",
+                answering,
+                quorum,
+                if ready { "serving" } else { "not serving" }
+            );
+
+            let status = if ready {
+                ServingStatus::Serving
+            } else {
+                ServingStatus::NotServing
+            };
+            set_status(&mut reporter, status).await;
+            serving = Some(ready);
+        }
+
+        if last_summary.elapsed() >= SUMMARY_INTERVAL {
+            summarize(&service).await;
+            last_summary = Instant::now();
+        }
+
+        sleep(CHECK_INTERVAL).await;
+    }
+}
+
+/// Set the status of the whole node, as well as of the AT2 service
+async fn set_status(reporter: &mut HealthReporter, status: ServingStatus) {
+    reporter.set_service_status("", status).await;
+    reporter
+        .set_service_status(<At2Server<rpc::Service> as NamedService>::NAME, status)
+        .await;
+}
+
+/// Client of the peer at `uri`, `None` if invalid
+fn connect(uri: &str) -> Option<At2Client<Channel>> {
+    let uri = uri.parse::<http::Uri>().ok()?;
+
+    let mut endpoint = Channel::builder(uri.clone());
+    if uri.scheme() == Some(&http::uri::Scheme::HTTPS) {
+        endpoint = endpoint.tls_config(ClientTlsConfig::new()).ok()?;
+    }
+
+    Some(At2Client::new(endpoint.connect_lazy().ok()?))
+}
+
+/// Ask a peer for its node info, `None` if it can't be asked
+///
+/// A peer with an invalid `rpc` address has no `client`, and never answers.
+async fn check(client: &Option<Option<At2Client<Channel>>>) -> Option<Check> {
+    let request = proto::GetNodeInfoRequest {};
+    let unanswered = Check {
+        bytes_sent: request.encoded_len() as u64,
+        ..Default::default()
+    };
+
+    let mut client = match client.as_ref()? {
+        None => return Some(unanswered),
+        Some(client) => client.clone(),
+    };
+
+    let start = Instant::now();
+    Some(
+        match timeout(CHECK_TIMEOUT, client.get_node_info(request)).await {
+            Ok(Ok(reply)) => Check {
+                rtt: Some(start.elapsed()),
+                bytes_received: reply.get_ref().encoded_len() as u64,
+                ..unanswered
+            },
+            _ => unanswered,
+        },
+    )
+}
+
+/// Log the stats of every checked peer
+async fn summarize(service: &rpc::Service) {
+    let statuses = match service.peer_statuses().get_all().await {
+        Ok(statuses) => statuses,
+        Err(err) => {
+            warn!("health: get peer statuses: {}", err);
+            return;
+        }
+    };
+
+    for (address, status) in statuses {
+        let stats = status.stats;
+        info!(
+            peer = %address,
+            sent = stats.messages_sent,
+            received = stats.messages_received,
+            bytes_sent = stats.bytes_sent,
+            bytes_received = stats.bytes_received,
+            rtt_last = ?stats.rtt_last,
+            rtt_mean = ?stats.rtt_mean(),
+            rtt_max = ?stats.rtt_max,
+            "health: peer stats"
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! Delivered transactions, indexed by the accounts involved
+//!
+//! Positions are given in order of delivery on this node, starting at one, so
+//! a cursor is only meaningful to the node which returned it.
+
+use std::{collections::HashMap, convert::TryInto};
+
+use drop::crypto::sign;
+use sled::{transaction::TransactionError, Transactional};
+use snafu::{ResultExt, Snafu};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::FullTransaction;
+
+/// Most transactions returned in a single [`Page`]
+pub const PAGE_MAX_SIZE: usize = 100;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("database: {}", source))]
+    Database { source: sled::Error },
+    #[snafu(display("encode: {}", source))]
+    Encode { source: bincode::Error },
+    #[snafu(display("decode: {}", source))]
+    Decode { source: bincode::Error },
+
+    #[snafu(display("gone on send"))]
+    GoneOnSend,
+    #[snafu(display("gone on recv"))]
+    GoneOnRecv,
+}
+
+/// Position of a transaction in the history
+pub type Cursor = u64;
+
+/// Transactions of an account, newest first, or of every account, oldest first
+#[derive(Debug, Default)]
+pub struct Page {
+    pub transactions: Vec<FullTransaction>,
+    /// Where to continue from, if other transactions remain
+    pub next: Option<Cursor>,
+}
+
+#[derive(Debug)]
+enum Commands {
+    Put {
+        full: Box<FullTransaction>,
+        resp: oneshot::Sender<Result<(), Error>>,
+    },
+    Page {
+        account: Box<sign::PublicKey>,
+        before: Option<Cursor>,
+        limit: usize,
+        resp: oneshot::Sender<Result<Page, Error>>,
+    },
+    Since {
+        after: Cursor,
+        limit: usize,
+        resp: oneshot::Sender<Result<Page, Error>>,
+    },
+}
+
+#[derive(Clone)]
+pub struct History {
+    agent: mpsc::Sender<Commands>,
+}
+
+/// Storage of the history, in memory or on disk
+trait Store: Send {
+    /// Append the transaction
+    fn put(&mut self, full: FullTransaction) -> Result<(), Error>;
+
+    /// Up to `limit` transactions involving `account` before `before`, newest first
+    fn page(
+        &self,
+        account: &sign::PublicKey,
+        before: Option<Cursor>,
+        limit: usize,
+    ) -> Result<Page, Error>;
+
+    /// Up to `limit` transactions of any account after `after`, oldest first
+    fn since(&self, after: Cursor, limit: usize) -> Result<Page, Error>;
+}
+
+/// Tokio agent owning the history.
+/// The only way to interacte with it is to use [`History`].
+struct HistoryHandler {
+    store: Box<dyn Store>,
+}
+
+impl History {
+    /// History kept in memory
+    pub fn new() -> Self {
+        Self {
+            agent: HistoryHandler {
+                store: Box::new(Memory::default()),
+            }
+            .spawn(),
+        }
+    }
+
+    /// History kept in the given database, surviving restarts
+    pub fn open(db: &sled::Db) -> Result<Self, Error> {
+        Ok(Self {
+            agent: HistoryHandler {
+                store: Box::new(Sled::open(db)?),
+            }
+            .spawn(),
+        })
+    }
+
+    /// Add a delivered transaction
+    pub async fn put(&self, full: FullTransaction) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Put {
+                full: Box::new(full),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return up to `limit` transactions sent or received by `account`
+    ///
+    /// It starts from the newest one, or from right before `before`, as
+    /// returned by a previous [`Page`]. `limit` is capped to [`PAGE_MAX_SIZE`].
+    pub async fn page(
+        &self,
+        account: Box<sign::PublicKey>,
+        before: Option<Cursor>,
+        limit: usize,
+    ) -> Result<Page, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Page {
+                account,
+                before,
+                limit: limit.min(PAGE_MAX_SIZE),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Return up to `limit` transactions of every account, in order of delivery
+    ///
+    /// It starts from the oldest one, or from right after `after`, as returned
+    /// by a previous [`Page`]. `limit` is capped to [`PAGE_MAX_SIZE`].
+    pub async fn since(&self, after: Option<Cursor>, limit: usize) -> Result<Page, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Since {
+                after: after.unwrap_or_default(),
+                limit: limit.min(PAGE_MAX_SIZE),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+}
+
+impl HistoryHandler {
+    fn spawn(mut self) -> mpsc::Sender<Commands> {
+        let (tx, mut rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            while let Some(cmd) = rx.recv().await {
+                match cmd {
+                    Commands::Put { full, resp } => {
+                        let _ = resp.send(self.store.put(*full));
+                    }
+                    Commands::Page {
+                        account,
+                        before,
+                        limit,
+                        resp,
+                    } => {
+                        let _ = resp.send(self.store.page(&account, before, limit));
+                    }
+                    Commands::Since { after, limit, resp } => {
+                        let _ = resp.send(self.store.since(after, limit));
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+}
+
+/// Accounts to index the transaction under
+fn involved(full: &FullTransaction) -> Vec<sign::PublicKey> {
+    if full.sender == full.recipient {
+        vec![full.sender]
+    } else {
+        vec![full.sender, full.recipient]
+    }
+}
+
+/// Build a page out of positions, in the order to return them
+///
+/// One more position than the `limit` is expected, to know if any remain.
+fn paginate(
+    mut positions: Vec<Cursor>,
+    limit: usize,
+    get: impl Fn(Cursor) -> Result<FullTransaction, Error>,
+) -> Result<Page, Error> {
+    let next = if positions.len() > limit {
+        positions.truncate(limit);
+        positions.last().copied()
+    } else {
+        None
+    };
+
+    Ok(Page {
+        transactions: positions.into_iter().map(get).collect::<Result<_, _>>()?,
+        next,
+    })
+}
+
+#[derive(Default)]
+struct Memory {
+    /// Transaction at position `n` is at index `n - 1`
+    transactions: Vec<FullTransaction>,
+    accounts: HashMap<sign::PublicKey, Vec<Cursor>>,
+}
+
+impl Store for Memory {
+    fn put(&mut self, full: FullTransaction) -> Result<(), Error> {
+        let position = self.transactions.len() as Cursor + 1;
+
+        for account in involved(&full) {
+            self.accounts.entry(account).or_default().push(position);
+        }
+        self.transactions.push(full);
+
+        Ok(())
+    }
+
+    fn page(
+        &self,
+        account: &sign::PublicKey,
+        before: Option<Cursor>,
+        limit: usize,
+    ) -> Result<Page, Error> {
+        let positions = self
+            .accounts
+            .get(account)
+            .map(|positions| {
+                positions
+                    .iter()
+                    .rev()
+                    .copied()
+                    .filter(|position| before.map_or(true, |before| *position < before))
+                    .take(limit + 1)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        paginate(positions, limit, |position| {
+            Ok(self.transactions[position as usize - 1].clone())
+        })
+    }
+
+    fn since(&self, after: Cursor, limit: usize) -> Result<Page, Error> {
+        let last = self.transactions.len() as Cursor;
+        let positions = (after + 1..=last).take(limit + 1).collect();
+
+        paginate(positions, limit, |position| {
+            Ok(self.transactions[position as usize - 1].clone())
+        })
+    }
+}
+
+/// Kept in the trees `history` and `history_accounts`, the latter keyed by
+/// the encoded account followed by the big-endian position
+struct Sled {
+    transactions: sled::Tree,
+    accounts: sled::Tree,
+    last: Cursor,
+}
+
+impl Sled {
+    fn open(db: &sled::Db) -> Result<Self, Error> {
+        let transactions = db.open_tree("history").context(Database)?;
+        let last = transactions
+            .last()
+            .context(Database)?
+            .map(|(key, _)| position(&key))
+            .unwrap_or_default();
+
+        Ok(Self {
+            transactions,
+            accounts: db.open_tree("history_accounts").context(Database)?,
+            last,
+        })
+    }
+
+    fn get(&self, position: Cursor) -> Result<FullTransaction, Error> {
+        let value = self
+            .transactions
+            .get(position.to_be_bytes())
+            .context(Database)?
+            .expect("indexed transactions are stored");
+
+        bincode::deserialize(&value).context(Decode)
+    }
+}
+
+fn position(key: &[u8]) -> Cursor {
+    let bytes = key[key.len() - 8..]
+        .try_into()
+        .expect("keys end with a position");
+
+    Cursor::from_be_bytes(bytes)
+}
+
+impl Store for Sled {
+    fn put(&mut self, full: FullTransaction) -> Result<(), Error> {
+        let position = self.last + 1;
+
+        let value = bincode::serialize(&full).context(Encode)?;
+        let keys = involved(&full)
+            .iter()
+            .map(|account| {
+                let mut key = bincode::serialize(account).context(Encode)?;
+                key.extend_from_slice(&position.to_be_bytes());
+                Ok(key)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        (&self.transactions, &self.accounts)
+            .transaction(|(tx_transactions, tx_accounts)| {
+                tx_transactions.insert(&position.to_be_bytes()[..], value.as_slice())?;
+                for key in &keys {
+                    tx_accounts.insert(key.as_slice(), &[])?;
+                }
+
+                Ok::<_, sled::transaction::ConflictableTransactionError<()>>(())
+            })
+            .map_err(|err| match err {
+                TransactionError::Abort(()) => unreachable!("transaction never aborts"),
+                TransactionError::Storage(err) => err,
+            })
+            .context(Database)?;
+
+        self.last = position;
+
+        Ok(())
+    }
+
+    fn page(
+        &self,
+        account: &sign::PublicKey,
+        before: Option<Cursor>,
+        limit: usize,
+    ) -> Result<Page, Error> {
+        let prefix = bincode::serialize(account).context(Encode)?;
+        let mut start = prefix.clone();
+        start.extend_from_slice(&Cursor::MIN.to_be_bytes());
+        let mut end = prefix;
+        end.extend_from_slice(&before.unwrap_or(Cursor::MAX).to_be_bytes());
+
+        let positions = self
+            .accounts
+            .range(start..end)
+            .rev()
+            .take(limit + 1)
+            .map(|entry| entry.map(|(key, _)| position(&key)))
+            .collect::<Result<Vec<_>, _>>()
+            .context(Database)?;
+
+        paginate(positions, limit, |position| self.get(position))
+    }
+
+    fn since(&self, after: Cursor, limit: usize) -> Result<Page, Error> {
+        let positions = self
+            .transactions
+            .range((after + 1).to_be_bytes()..)
+            .take(limit + 1)
+            .map(|entry| entry.map(|(key, _)| position(&key)))
+            .collect::<Result<Vec<_>, _>>()
+            .context(Database)?;
+
+        paginate(positions, limit, |position| self.get(position))
+    }
+}
@@ -0,0 +1,471 @@
+//! HTTP/JSON transcoding of the [`proto::at2_server::At2`] service
+//!
+//! Each RPC is exposed as `POST /v1/<method>`, taking and returning the JSON
+//! version of the proto messages; `bytes` fields are encoded in base64.
+//! Signed transactions can be submitted, as an
+//! [`interchange::Document`], on `POST /v1/submit_transactions`.
+//! Delivered transactions are also streamed as server-sent events on
+//! `GET /v1/events`.
+
+use std::{convert::Infallible, future::Future, net::SocketAddr};
+
+use futures::stream;
+use hyper::{
+    body::HttpBody,
+    header::{CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use snafu::{ensure, ResultExt, Snafu};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use super::{events::Event, rosetta, rpc};
+use crate::{
+    interchange,
+    proto::{self, at2_server::At2},
+    proto_convert, Amount,
+};
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("serve: {}", source))]
+    Serve { source: hyper::Error },
+}
+
+#[derive(Snafu, Debug)]
+enum RequestError {
+    #[snafu(display("no such method"))]
+    NotFound,
+    #[snafu(display("read body: {}", source))]
+    ReadBody { source: BodyError },
+    #[snafu(display("decode body: {}", source))]
+    DecodeBody { source: serde_json::Error },
+    #[snafu(display("encode body: {}", source))]
+    EncodeBody { source: serde_json::Error },
+    #[snafu(display("decode document: {}", source))]
+    DecodeDocument { source: interchange::Error },
+    #[snafu(display("document for chain {}, expected {}", got, expected))]
+    WrongChain { expected: String, got: String },
+    #[snafu(display("rpc: {}", source.message()))]
+    Rpc { source: tonic::Status },
+}
+
+#[derive(Snafu, Debug)]
+pub(super) enum BodyError {
+    #[snafu(display("larger than {} bytes", limit))]
+    TooLarge { limit: usize },
+    #[snafu(display("receive: {}", source))]
+    Receive { source: hyper::Error },
+}
+
+mod base64_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        base64::decode(String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SendAssetRequest {
+    #[serde(with = "base64_bytes", default)]
+    sender: Vec<u8>,
+    sequence: u32,
+    #[serde(with = "base64_bytes", default)]
+    recipient: Vec<u8>,
+    amount: Amount,
+    #[serde(with = "base64_bytes")]
+    signature: Vec<u8>,
+    #[serde(default)]
+    sender_address: String,
+    #[serde(default)]
+    recipient_address: String,
+    #[serde(default)]
+    memo: String,
+}
+
+#[derive(Serialize)]
+pub struct SendAssetReply {}
+
+#[derive(Deserialize)]
+pub struct SenderRequest {
+    #[serde(with = "base64_bytes", default)]
+    sender: Vec<u8>,
+    #[serde(default)]
+    sender_address: String,
+    #[serde(default)]
+    if_version_newer_than: u64,
+}
+
+#[derive(Serialize)]
+pub struct GetBalanceReply {
+    amount: Amount,
+    version: u64,
+    not_modified: bool,
+}
+
+#[derive(Serialize)]
+struct GetLastSequenceReply {
+    sequence: u32,
+    version: u64,
+    not_modified: bool,
+}
+
+#[derive(Deserialize)]
+struct GetLatestTransactionsRequest {
+    #[serde(default)]
+    limit: u32,
+}
+
+#[derive(Serialize)]
+struct ProcessedTransaction {
+    timestamp: String,
+    #[serde(with = "base64_bytes")]
+    sender: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    recipient: Vec<u8>,
+    amount: Amount,
+    sequence: u32,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    memo: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    asset: String,
+}
+
+#[derive(Serialize)]
+pub struct GetLatestTransactionsReply {
+    transactions: Vec<ProcessedTransaction>,
+}
+
+#[derive(Serialize)]
+struct ErrorReply {
+    message: String,
+}
+
+impl From<SendAssetRequest> for proto::SendAssetRequest {
+    fn from(req: SendAssetRequest) -> Self {
+        let (amount, amount_high) = proto_convert::proto_amount(req.amount);
+
+        Self {
+            sender: req.sender,
+            sequence: req.sequence,
+            recipient: req.recipient,
+            amount,
+            amount_high,
+            signature: req.signature,
+            sender_address: req.sender_address,
+            recipient_address: req.recipient_address,
+            memo: req.memo,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<SenderRequest> for proto::GetBalanceRequest {
+    fn from(req: SenderRequest) -> Self {
+        Self {
+            sender: req.sender,
+            sender_address: req.sender_address,
+            if_version_newer_than: req.if_version_newer_than,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<SenderRequest> for proto::GetLastSequenceRequest {
+    fn from(req: SenderRequest) -> Self {
+        Self {
+            sender: req.sender,
+            sender_address: req.sender_address,
+            if_version_newer_than: req.if_version_newer_than,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<GetLatestTransactionsRequest> for proto::GetLatestTransactionsRequest {
+    fn from(req: GetLatestTransactionsRequest) -> Self {
+        Self { limit: req.limit }
+    }
+}
+
+impl From<proto::SendAssetReply> for SendAssetReply {
+    fn from(_: proto::SendAssetReply) -> Self {
+        Self {}
+    }
+}
+
+impl From<proto::GetBalanceReply> for GetBalanceReply {
+    fn from(reply: proto::GetBalanceReply) -> Self {
+        Self {
+            amount: proto_convert::amount(reply.amount, reply.amount_high),
+            version: reply.version,
+            not_modified: reply.not_modified,
+        }
+    }
+}
+
+impl From<proto::GetLastSequenceReply> for GetLastSequenceReply {
+    fn from(reply: proto::GetLastSequenceReply) -> Self {
+        Self {
+            sequence: reply.sequence,
+            version: reply.version,
+            not_modified: reply.not_modified,
+        }
+    }
+}
+
+impl From<proto::GetLatestTransactionsReply> for GetLatestTransactionsReply {
+    fn from(reply: proto::GetLatestTransactionsReply) -> Self {
+        Self {
+            transactions: reply
+                .transactions
+                .into_iter()
+                .map(|tx| ProcessedTransaction {
+                    timestamp: tx.timestamp,
+                    sender: tx.sender,
+                    recipient: tx.recipient,
+                    amount: proto_convert::amount(tx.amount, tx.amount_high),
+                    sequence: tx.sequence,
+                    memo: tx.memo,
+                    asset: tx.asset,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Serve the JSON gateway for `service` on `addr`, until `shutdown` resolves
+///
+/// Request bodies larger than `max_body_size` bytes are refused.
+pub async fn serve(
+    addr: SocketAddr,
+    service: rpc::Service,
+    max_body_size: usize,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), Error> {
+    let make_service = make_service_fn(move |_| {
+        let service = service.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let service = service.clone();
+
+                async move { Ok::<_, Infallible>(handle(service, req, max_body_size).await) }
+            }))
+        }
+    });
+
+    hyper::Server::bind(&addr)
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+        .context(Serve)
+}
+
+async fn handle(service: rpc::Service, req: Request<Body>, max_body_size: usize) -> Response<Body> {
+    match route(service, req, max_body_size).await {
+        Ok(reply) => reply,
+        Err(err) => {
+            let status = match &err {
+                RequestError::NotFound => StatusCode::NOT_FOUND,
+                RequestError::ReadBody {
+                    source: BodyError::TooLarge { .. },
+                } => StatusCode::PAYLOAD_TOO_LARGE,
+                RequestError::ReadBody { .. }
+                | RequestError::DecodeBody { .. }
+                | RequestError::DecodeDocument { .. }
+                | RequestError::WrongChain { .. } => StatusCode::BAD_REQUEST,
+                RequestError::EncodeBody { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+                RequestError::Rpc { source } => rpc_status(source.code()),
+            };
+            if status.is_server_error() {
+                warn!("json gateway: {}", err);
+            }
+
+            let body = serde_json::to_vec(&ErrorReply {
+                message: err.to_string(),
+            })
+            .unwrap_or_default();
+
+            json_response(status, body)
+        }
+    }
+}
+
+async fn route(
+    service: rpc::Service,
+    req: Request<Body>,
+    max_body_size: usize,
+) -> Result<Response<Body>, RequestError> {
+    if req.method() == Method::GET && req.uri().path() == "/v1/events" {
+        return Ok(events(service.subscribe()));
+    }
+
+    if req.method() != Method::POST {
+        return Err(RequestError::NotFound);
+    }
+
+    let path = req.uri().path().to_owned();
+    let body = read_body(req, max_body_size).await.context(ReadBody)?;
+
+    if rosetta::PATHS.contains(&path.as_str()) {
+        return Ok(rosetta::route(service, &path, &body).await);
+    }
+
+    match path.as_str() {
+        "/v1/send_asset" => {
+            let request = decode::<SendAssetRequest, proto::SendAssetRequest>(&body)?;
+            encode::<_, SendAssetReply>(service.send_asset(request).await)
+        }
+        "/v1/get_balance" => {
+            let request = decode::<SenderRequest, proto::GetBalanceRequest>(&body)?;
+            encode::<_, GetBalanceReply>(service.get_balance(request).await)
+        }
+        "/v1/get_last_sequence" => {
+            let request = decode::<SenderRequest, proto::GetLastSequenceRequest>(&body)?;
+            encode::<_, GetLastSequenceReply>(service.get_last_sequence(request).await)
+        }
+        "/v1/get_latest_transactions" => {
+            let request =
+                decode::<GetLatestTransactionsRequest, proto::GetLatestTransactionsRequest>(&body)?;
+            encode::<_, GetLatestTransactionsReply>(service.get_latest_transactions(request).await)
+        }
+        "/v1/submit_transactions" => submit_transactions(service, &body).await,
+        _ => Err(RequestError::NotFound),
+    }
+}
+
+/// Buffer the body of `req`, refusing it once past `limit` bytes, be it
+/// announced by its `Content-Length` or not
+pub(super) async fn read_body(req: Request<Body>, limit: usize) -> Result<Vec<u8>, BodyError> {
+    let announced = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<u64>().ok());
+    ensure!(
+        announced.map_or(true, |length| length <= limit as u64),
+        TooLarge { limit }
+    );
+
+    let mut body = req.into_body();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.context(Receive)?;
+        ensure!(bytes.len() + chunk.len() <= limit, TooLarge { limit });
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
+
+/// Broadcast every transaction of the document, in order
+async fn submit_transactions(
+    service: rpc::Service,
+    body: &[u8],
+) -> Result<Response<Body>, RequestError> {
+    let document = interchange::Document::from_reader(body).context(DecodeDocument)?;
+
+    ensure!(
+        document.chain_id == service.chain_id(),
+        WrongChain {
+            expected: service.chain_id(),
+            got: document.chain_id,
+        }
+    );
+
+    for transaction in document.transactions {
+        service.submit(transaction).await.context(Rpc)?;
+    }
+
+    Ok(json_response(StatusCode::OK, b"{}".to_vec()))
+}
+
+/// Stream the delivered transactions as server-sent events
+fn events(receiver: broadcast::Receiver<crate::FullTransaction>) -> Response<Body> {
+    let stream = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(tx) => {
+                    let data = serde_json::to_string(&Event::from(&tx)).ok()?;
+                    return Some((Ok::<_, Infallible>(format!("data: {}\n\n", data)), receiver));
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("events stream lagged, skipped {} transactions", skipped)
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    let mut response = Response::new(Body::wrap_stream(stream));
+    let headers = response.headers_mut();
+    headers.insert(
+        CONTENT_TYPE,
+        "text/event-stream".parse().expect("valid header"),
+    );
+    headers.insert(CACHE_CONTROL, "no-cache".parse().expect("valid header"));
+
+    response
+}
+
+fn decode<T, P>(body: &[u8]) -> Result<tonic::Request<P>, RequestError>
+where
+    T: DeserializeOwned + Into<P>,
+{
+    let json: T = serde_json::from_slice(body).context(DecodeBody)?;
+
+    Ok(tonic::Request::new(json.into()))
+}
+
+fn encode<P, T>(
+    reply: Result<tonic::Response<P>, tonic::Status>,
+) -> Result<Response<Body>, RequestError>
+where
+    T: Serialize + From<P>,
+{
+    let json = T::from(reply.context(Rpc)?.into_inner());
+    let body = serde_json::to_vec(&json).context(EncodeBody)?;
+
+    Ok(json_response(StatusCode::OK, body))
+}
+
+pub fn json_response(status: StatusCode, body: Vec<u8>) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        "application/json".parse().expect("valid header"),
+    );
+
+    response
+}
+
+/// Map gRPC codes to their HTTP counterparts, as done by grpc-gateway
+fn rpc_status(code: tonic::Code) -> StatusCode {
+    use tonic::Code;
+
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Cancelled => StatusCode::REQUEST_TIMEOUT,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
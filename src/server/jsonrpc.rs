@@ -0,0 +1,272 @@
+//! [JSON-RPC 2.0](https://www.jsonrpc.org/specification) endpoint
+//!
+//! Methods take their parameters by name, using the same JSON messages as the
+//! [`json`](super::json) gateway:
+//! - `at2_sendAsset`, as `/v1/send_asset`
+//! - `at2_getBalance`, as `/v1/get_balance`
+//! - `at2_getHistory`, as `/v1/get_latest_transactions`
+
+use std::{convert::Infallible, future::Future, net::SocketAddr};
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use snafu::{ResultExt, Snafu};
+
+use super::{json, rpc};
+use crate::proto::{self, at2_server::At2};
+
+pub(super) const VERSION: &str = "2.0";
+
+pub(super) const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+pub(super) const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+/// Start of the implementation-defined server errors
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("serve: {}", source))]
+    Serve { source: hyper::Error },
+}
+
+#[derive(Deserialize)]
+pub(super) struct RpcRequest {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    pub id: Option<Value>,
+}
+
+#[derive(Serialize)]
+pub(super) struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Serialize)]
+pub(super) struct RpcResponse {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+    id: Value,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl RpcResponse {
+    pub fn new(id: Value, ret: Result<Value, RpcError>) -> Self {
+        let (result, error) = match ret {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        Self {
+            jsonrpc: VERSION,
+            result,
+            error,
+            id,
+        }
+    }
+}
+
+/// Serve the JSON-RPC endpoint for `service` on `addr`, until `shutdown` resolves
+///
+/// Request bodies larger than `max_body_size` bytes are refused.
+pub async fn serve(
+    addr: SocketAddr,
+    service: rpc::Service,
+    max_body_size: usize,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), Error> {
+    let make_service = make_service_fn(move |_| {
+        let service = service.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let service = service.clone();
+
+                async move { Ok::<_, Infallible>(handle(service, req, max_body_size).await) }
+            }))
+        }
+    });
+
+    hyper::Server::bind(&addr)
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+        .context(Serve)
+}
+
+async fn handle(service: rpc::Service, req: Request<Body>, max_body_size: usize) -> Response<Body> {
+    if req.method() != Method::POST {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+        return response;
+    }
+
+    let reply = match json::read_body(req, max_body_size).await {
+        Err(err @ json::BodyError::TooLarge { .. }) => {
+            return json::json_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                serde_json::to_vec(&RpcResponse::new(
+                    Value::Null,
+                    Err(RpcError::new(INVALID_REQUEST, err)),
+                ))
+                .unwrap_or_default(),
+            );
+        }
+        Err(err) => Some(serde_json::to_value(RpcResponse::new(
+            Value::Null,
+            Err(RpcError::new(PARSE_ERROR, err)),
+        ))),
+        Ok(bytes) => match serde_json::from_slice::<Value>(&bytes) {
+            Err(err) => Some(serde_json::to_value(RpcResponse::new(
+                Value::Null,
+                Err(RpcError::new(PARSE_ERROR, err)),
+            ))),
+            Ok(message) => dispatch(&service, message).await,
+        },
+    };
+
+    match reply {
+        // only notifications
+        None => {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NO_CONTENT;
+            response
+        }
+        Some(reply) => json::json_response(
+            StatusCode::OK,
+            reply
+                .and_then(|reply| serde_json::to_vec(&reply))
+                .unwrap_or_default(),
+        ),
+    }
+}
+
+/// Execute a single call or a batch, returning `None` if only made of notifications
+pub(super) async fn dispatch(
+    service: &rpc::Service,
+    message: Value,
+) -> Option<serde_json::Result<Value>> {
+    match message {
+        Value::Array(batch) if batch.is_empty() => Some(serde_json::to_value(RpcResponse::new(
+            Value::Null,
+            Err(RpcError::new(INVALID_REQUEST, "empty batch")),
+        ))),
+        Value::Array(batch) => {
+            let mut replies = Vec::with_capacity(batch.len());
+            for call in batch {
+                if let Some(reply) = call_one(service, call).await {
+                    replies.push(reply);
+                }
+            }
+
+            if replies.is_empty() {
+                None
+            } else {
+                Some(serde_json::to_value(replies))
+            }
+        }
+        call => call_one(service, call).await.map(serde_json::to_value),
+    }
+}
+
+/// Execute a single call, returning `None` for notifications
+async fn call_one(service: &rpc::Service, call: Value) -> Option<RpcResponse> {
+    let request = match serde_json::from_value::<RpcRequest>(call) {
+        Ok(request) if request.jsonrpc == VERSION => request,
+        Ok(_) => {
+            return Some(RpcResponse::new(
+                Value::Null,
+                Err(RpcError::new(INVALID_REQUEST, "unsupported version")),
+            ))
+        }
+        Err(err) => {
+            return Some(RpcResponse::new(
+                Value::Null,
+                Err(RpcError::new(INVALID_REQUEST, err)),
+            ))
+        }
+    };
+
+    let ret = match request.method.as_str() {
+        "at2_sendAsset" => {
+            call::<json::SendAssetRequest, proto::SendAssetRequest, _, json::SendAssetReply, _, _>(
+                request.params,
+                |req| service.send_asset(req),
+            )
+            .await
+        }
+        "at2_getBalance" => {
+            call::<json::SenderRequest, proto::GetBalanceRequest, _, json::GetBalanceReply, _, _>(
+                request.params,
+                |req| service.get_balance(req),
+            )
+            .await
+        }
+        "at2_getHistory" => {
+            call::<
+                Empty,
+                proto::GetLatestTransactionsRequest,
+                _,
+                json::GetLatestTransactionsReply,
+                _,
+                _,
+            >(request.params, |req| service.get_latest_transactions(req))
+            .await
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, "method not found")),
+    };
+
+    request.id.map(|id| RpcResponse::new(id, ret))
+}
+
+/// Parameters of a method without any
+#[derive(Deserialize)]
+struct Empty {}
+
+impl From<Empty> for proto::GetLatestTransactionsRequest {
+    fn from(_: Empty) -> Self {
+        Self::default()
+    }
+}
+
+/// Decode `params` as `T`, call `method` with it and encode its reply as `R`
+async fn call<T, P, Q, R, F, M>(params: Value, method: M) -> Result<Value, RpcError>
+where
+    T: DeserializeOwned + Into<P>,
+    R: From<Q> + Serialize,
+    F: Future<Output = Result<tonic::Response<Q>, tonic::Status>>,
+    M: FnOnce(tonic::Request<P>) -> F,
+{
+    let params = match params {
+        Value::Null => Value::Object(Default::default()),
+        params => params,
+    };
+    let params: T =
+        serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+
+    let reply = method(tonic::Request::new(params.into()))
+        .await
+        .map_err(|status| RpcError::new(SERVER_ERROR, status.message()))?;
+
+    serde_json::to_value(R::from(reply.into_inner()))
+        .map_err(|err| RpcError::new(INTERNAL_ERROR, err))
+}
@@ -0,0 +1,77 @@
+//! Encrypt the node's keys with a passphrase, to keep them at rest in the config
+//!
+//! The passphrase is stretched by Argon2id, with the crate's default
+//! parameters and a random salt, into the key of a ChaCha20-Poly1305
+//! encryption of the `[keys]` section, serialized as TOML.
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use snafu::{ensure, ResultExt, Snafu};
+
+use super::config::{ConfigEncryptedKeys, ConfigKeys};
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const KEY_SIZE: usize = 32;
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("derive key: {}", message))]
+    DeriveKey { message: String },
+    #[snafu(display("encrypt"))]
+    Encrypt,
+    #[snafu(display("decrypt: wrong passphrase or corrupted keys"))]
+    Decrypt,
+    #[snafu(display("encode: {}", source))]
+    Encode { source: toml::ser::Error },
+    #[snafu(display("decode: {}", source))]
+    Decode { source: toml::de::Error },
+}
+
+fn cipher(passphrase: &[u8], salt: &[u8]) -> Result<ChaCha20Poly1305, Error> {
+    let mut key = [0; KEY_SIZE];
+    Argon2::default()
+        .hash_password_into(passphrase, salt, &mut key)
+        .map_err(|err| Error::DeriveKey {
+            message: err.to_string(),
+        })?;
+
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Encrypt `keys` with `passphrase`
+pub fn encrypt(keys: &ConfigKeys, passphrase: &[u8]) -> Result<ConfigEncryptedKeys, Error> {
+    let mut salt = vec![0; SALT_SIZE];
+    OsRng.fill_bytes(&mut salt);
+    let mut nonce = vec![0; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+
+    let plaintext = toml::to_string(keys).context(Encode)?;
+    let ciphertext = cipher(passphrase, &salt)?
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|_| Error::Encrypt)?;
+
+    Ok(ConfigEncryptedKeys {
+        salt,
+        nonce,
+        ciphertext,
+    })
+}
+
+/// Decrypt `encrypted` with `passphrase`
+pub fn decrypt(encrypted: &ConfigEncryptedKeys, passphrase: &[u8]) -> Result<ConfigKeys, Error> {
+    ensure!(encrypted.nonce.len() == NONCE_SIZE, Decrypt);
+
+    let plaintext = cipher(passphrase, &encrypted.salt)?
+        .decrypt(
+            Nonce::from_slice(&encrypted.nonce),
+            encrypted.ciphertext.as_slice(),
+        )
+        .map_err(|_| Error::Decrypt)?;
+
+    toml::from_slice(&plaintext).context(Decode)
+}
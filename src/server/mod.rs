@@ -0,0 +1,33 @@
+//! AT2 node, as run by the `server` binary
+
+pub mod accounts;
+mod allowlist;
+mod challenges;
+pub mod config;
+mod connector;
+pub mod dashboard;
+mod events;
+mod evidence;
+pub mod health;
+mod history;
+pub mod json;
+pub mod jsonrpc;
+pub mod keystore;
+mod peer_statuses;
+mod rate_limit;
+mod recent_transactions;
+mod rejected_transactions;
+pub mod request_id;
+mod rosetta;
+pub mod rpc;
+mod submissions;
+mod sync;
+mod transaction_statuses;
+mod validation;
+pub mod vault;
+pub mod websocket;
+
+/// Start of a hex encoded key, enough to tell keys apart, as shown in the logs
+pub fn fingerprint(hex_key: &str) -> &str {
+    hex_key.get(..16).unwrap_or(hex_key)
+}
@@ -0,0 +1,172 @@
+//! How peers answered the checks of the [`health`](super::health) watcher
+
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::{mpsc, oneshot};
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("gone on send"))]
+    GoneOnSend,
+    #[snafu(display("gone on recv"))]
+    GoneOnRecv,
+}
+
+/// Last checks of a peer
+#[derive(Debug, Clone, Default)]
+pub struct PeerStatus {
+    /// Whether it answered the last check, `None` if never checked
+    pub answering: Option<bool>,
+    /// When it last answered
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Over every check
+    pub stats: PeerStats,
+}
+
+/// Round trips and traffic of the checks of a peer, since the node started
+#[derive(Debug, Clone, Default)]
+pub struct PeerStats {
+    /// Checks sent
+    pub messages_sent: u64,
+    /// Checks answered
+    pub messages_received: u64,
+    /// Encoded size of the checks
+    pub bytes_sent: u64,
+    /// Encoded size of the answers
+    pub bytes_received: u64,
+    /// Of the last answered check
+    pub rtt_last: Option<Duration>,
+    pub rtt_min: Option<Duration>,
+    pub rtt_max: Option<Duration>,
+    /// Sum of the round trips, see [`PeerStats::rtt_mean`]
+    rtt_total: Duration,
+}
+
+impl PeerStats {
+    /// Average round trip of the answered checks
+    pub fn rtt_mean(&self) -> Option<Duration> {
+        if self.messages_received == 0 {
+            return None;
+        }
+
+        Some(self.rtt_total / self.messages_received as u32)
+    }
+
+    fn record(&mut self, check: &Check) {
+        self.messages_sent += 1;
+        self.bytes_sent += check.bytes_sent;
+
+        if let Some(rtt) = check.rtt {
+            self.messages_received += 1;
+            self.bytes_received += check.bytes_received;
+            self.rtt_last = Some(rtt);
+            self.rtt_min = Some(self.rtt_min.map_or(rtt, |min| min.min(rtt)));
+            self.rtt_max = Some(self.rtt_max.map_or(rtt, |max| max.max(rtt)));
+            self.rtt_total += rtt;
+        }
+    }
+}
+
+/// How a single check went
+#[derive(Debug, Clone, Default)]
+pub struct Check {
+    /// Round trip, `None` if it didn't answer
+    pub rtt: Option<Duration>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[derive(Debug)]
+enum Commands {
+    Put {
+        address: String,
+        check: Check,
+        resp: oneshot::Sender<()>,
+    },
+    GetAll {
+        resp: oneshot::Sender<HashMap<String, PeerStatus>>,
+    },
+}
+
+#[derive(Clone)]
+pub struct PeerStatuses {
+    agent: mpsc::Sender<Commands>,
+}
+
+/// Tokio agent owning the statuses.
+/// The only way to interacte with it is to use [`PeerStatuses`].
+struct PeerStatusesHandler {
+    statuses: HashMap<String, PeerStatus>,
+}
+
+impl PeerStatuses {
+    pub fn new() -> Self {
+        Self {
+            agent: PeerStatusesHandler {
+                statuses: HashMap::new(),
+            }
+            .spawn(),
+        }
+    }
+
+    /// Record how the peer at `address` just answered
+    pub async fn put(&self, address: String, check: Check) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Put {
+                address,
+                check,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// Return the status of every checked peer, by address
+    pub async fn get_all(&self) -> Result<HashMap<String, PeerStatus>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetAll { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+}
+
+impl PeerStatusesHandler {
+    fn spawn(mut self) -> mpsc::Sender<Commands> {
+        let (tx, mut rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            while let Some(cmd) = rx.recv().await {
+                match cmd {
+                    Commands::Put {
+                        address,
+                        check,
+                        resp,
+                    } => {
+                        let status = self.statuses.entry(address).or_default();
+                        status.answering = Some(check.rtt.is_some());
+                        if check.rtt.is_some() {
+                            status.last_seen = Some(Utc::now());
+                        }
+                        status.stats.record(&check);
+
+                        let _ = resp.send(());
+                    }
+                    Commands::GetAll { resp } => {
+                        let _ = resp.send(self.statuses.clone());
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+}
@@ -0,0 +1,133 @@
+//! Token buckets limiting how often transactions can be submitted
+//!
+//! Each sender and each peer address gets its own bucket, holding up to
+//! `burst` tokens and refilled by `rate` tokens per second. A submission takes
+//! a token from every bucket it concerns, and is refused if one is empty.
+
+use std::{collections::HashMap, net::IpAddr, time::Instant};
+
+use drop::crypto::sign;
+use tokio::sync::{mpsc, oneshot};
+
+use super::config;
+
+/// How many buckets to keep, full ones being forgotten past it
+const TRACKED_MAX_SIZE: usize = 10_000;
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("gone on send"))]
+    GoneOnSend,
+    #[snafu(display("gone on recv"))]
+    GoneOnRecv,
+}
+
+/// What a bucket is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Sender(sign::PublicKey),
+    Peer(IpAddr),
+}
+
+#[derive(Debug)]
+enum Commands {
+    Take {
+        key: Box<Key>,
+        resp: oneshot::Sender<bool>,
+    },
+}
+
+#[derive(Clone)]
+pub struct RateLimit {
+    agent: mpsc::Sender<Commands>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Tokio agent owning the buckets.
+/// The only way to interacte with it is to use [`RateLimit`].
+struct RateLimitHandler {
+    rate: f64,
+    burst: f64,
+    buckets: HashMap<Key, Bucket>,
+}
+
+impl RateLimit {
+    pub fn new(config: config::ConfigRateLimit) -> Self {
+        Self {
+            agent: RateLimitHandler {
+                rate: config.rate,
+                burst: config.burst as f64,
+                buckets: HashMap::new(),
+            }
+            .spawn(),
+        }
+    }
+
+    /// Take a token for `key`, returning whether there was one
+    pub async fn take(&self, key: Key) -> Result<bool, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Take {
+                key: Box::new(key),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+}
+
+impl RateLimitHandler {
+    fn spawn(mut self) -> mpsc::Sender<Commands> {
+        let (tx, mut rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            while let Some(cmd) = rx.recv().await {
+                match cmd {
+                    Commands::Take { key, resp } => {
+                        let _ = resp.send(self.take(*key, Instant::now()));
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+
+    fn take(&mut self, key: Key, now: Instant) -> bool {
+        if self.buckets.len() >= TRACKED_MAX_SIZE && !self.buckets.contains_key(&key) {
+            let (rate, burst) = (self.rate, self.burst);
+            self.buckets
+                .retain(|_, bucket| refill(bucket, rate, burst, now) < burst);
+        }
+
+        let burst = self.burst;
+        let bucket = self.buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+
+        if refill(bucket, self.rate, burst, now) < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+
+        true
+    }
+}
+
+/// Add the tokens earned since the last update, returning how many there are
+fn refill(bucket: &mut Bucket, rate: f64, burst: f64, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.updated);
+
+    bucket.tokens = (bucket.tokens + rate * elapsed.as_secs_f64()).min(burst);
+    bucket.updated = now;
+
+    bucket.tokens
+}
@@ -0,0 +1,250 @@
+//! Where the recent transactions are kept, in memory or on disk
+
+use std::{collections::VecDeque, convert::TryInto};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sled::{transaction::TransactionError, Transactional};
+use snafu::{ResultExt, Snafu};
+
+use crate::FullTransaction;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("database: {}", source))]
+    Database { source: sled::Error },
+    #[snafu(display("encode: {}", source))]
+    Encode { source: bincode::Error },
+    #[snafu(display("decode: {}", source))]
+    Decode { source: bincode::Error },
+}
+
+/// Transactions in order of delivery, indexed from zero, up to a maximum count
+#[async_trait]
+pub trait TransactionLog: Send {
+    /// Add a delivered transaction, dropping the oldest ones if full, returning
+    /// how many were
+    async fn append(&mut self, full: FullTransaction) -> Result<usize, Error>;
+
+    /// Up to `limit` of the newest transactions, oldest first
+    async fn latest(&self, limit: usize) -> Result<Vec<FullTransaction>, Error>;
+
+    /// Every kept transaction, along with the index of the first one
+    async fn indexed(&self) -> Result<(usize, Vec<FullTransaction>), Error>;
+
+    /// Drop the transactions delivered before `oldest`, returning how many were
+    async fn prune(&mut self, oldest: DateTime<Utc>) -> Result<usize, Error>;
+
+    /// How many transactions are kept
+    fn kept(&self) -> usize;
+
+    /// How many transactions were ever appended
+    fn delivered(&self) -> u64;
+}
+
+/// Lost on restart
+///
+/// Allocated once, as a ring buffer of `max_count` transactions.
+pub struct Memory {
+    max_count: usize,
+    recents: VecDeque<FullTransaction>,
+    /// How many transactions were appended since the start
+    delivered: usize,
+}
+
+impl Memory {
+    pub fn new(max_count: usize) -> Self {
+        Self {
+            max_count,
+            recents: VecDeque::with_capacity(max_count),
+            delivered: 0,
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionLog for Memory {
+    async fn append(&mut self, full: FullTransaction) -> Result<usize, Error> {
+        let mut dropped = 0;
+        if self.recents.len() >= self.max_count && self.recents.pop_front().is_some() {
+            dropped += 1;
+        }
+        if self.max_count > 0 {
+            self.recents.push_back(full);
+        } else {
+            dropped += 1;
+        }
+        self.delivered += 1;
+
+        Ok(dropped)
+    }
+
+    async fn latest(&self, limit: usize) -> Result<Vec<FullTransaction>, Error> {
+        let skipped = self.recents.len().saturating_sub(limit);
+
+        Ok(self.recents.iter().skip(skipped).cloned().collect())
+    }
+
+    async fn indexed(&self) -> Result<(usize, Vec<FullTransaction>), Error> {
+        Ok((
+            self.delivered - self.recents.len(),
+            self.recents.iter().cloned().collect(),
+        ))
+    }
+
+    async fn prune(&mut self, oldest: DateTime<Utc>) -> Result<usize, Error> {
+        let kept = self.recents.len();
+        while matches!(self.recents.front(), Some(full) if full.timestamp < oldest) {
+            self.recents.pop_front();
+        }
+
+        Ok(kept - self.recents.len())
+    }
+
+    fn kept(&self) -> usize {
+        self.recents.len()
+    }
+
+    fn delivered(&self) -> u64 {
+        self.delivered as u64
+    }
+}
+
+/// Key of the delivered count in the `recent_transactions_meta` tree
+const DELIVERED_KEY: &[u8] = b"delivered";
+
+/// Kept in the tree `recent_transactions`, keyed by big-endian index, and the
+/// count of delivered transactions in `recent_transactions_meta`, so that
+/// indexes carry on after a restart
+pub struct Sled {
+    max_count: usize,
+    transactions: sled::Tree,
+    meta: sled::Tree,
+    delivered: u64,
+    /// Length of `transactions`, which sled only gives by walking it
+    kept: usize,
+}
+
+impl Sled {
+    /// Use an already opened database, shared with the other stores
+    pub fn with_db(db: &sled::Db, max_count: usize) -> Result<Self, Error> {
+        let meta = db.open_tree("recent_transactions_meta").context(Database)?;
+        let delivered = meta
+            .get(DELIVERED_KEY)
+            .context(Database)?
+            .map(|value| index(&value))
+            .unwrap_or_default();
+
+        let transactions = db.open_tree("recent_transactions").context(Database)?;
+        let kept = transactions.len();
+
+        Ok(Self {
+            max_count,
+            transactions,
+            meta,
+            delivered,
+            kept,
+        })
+    }
+
+    fn decode(
+        entries: impl Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>>,
+    ) -> Result<Vec<FullTransaction>, Error> {
+        entries
+            .map(|entry| {
+                let (_, value) = entry.context(Database)?;
+                bincode::deserialize(&value).context(Decode)
+            })
+            .collect()
+    }
+}
+
+fn index(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes.try_into().expect("indexes are eight bytes"))
+}
+
+#[async_trait]
+impl TransactionLog for Sled {
+    async fn append(&mut self, full: FullTransaction) -> Result<usize, Error> {
+        let key = self.delivered.to_be_bytes();
+        let delivered = (self.delivered + 1).to_be_bytes();
+        let value = bincode::serialize(&full).context(Encode)?;
+
+        // the count might have been lowered since the last run
+        let excess = (self.kept + 1).saturating_sub(self.max_count);
+        let oldest = self
+            .transactions
+            .iter()
+            .keys()
+            .take(excess)
+            .collect::<Result<Vec<_>, _>>()
+            .context(Database)?;
+
+        (&self.transactions, &self.meta)
+            .transaction(|(tx_transactions, tx_meta)| {
+                for key in &oldest {
+                    tx_transactions.remove(key)?;
+                }
+                if self.max_count > 0 {
+                    tx_transactions.insert(&key[..], value.as_slice())?;
+                }
+                tx_meta.insert(DELIVERED_KEY, &delivered[..])?;
+
+                Ok::<_, sled::transaction::ConflictableTransactionError<()>>(())
+            })
+            .map_err(|err| match err {
+                TransactionError::Abort(()) => unreachable!("transaction never aborts"),
+                TransactionError::Storage(err) => err,
+            })
+            .context(Database)?;
+
+        self.delivered += 1;
+        self.kept -= oldest.len();
+        if self.max_count > 0 {
+            self.kept += 1;
+        }
+
+        Ok(oldest.len() + (self.max_count == 0) as usize)
+    }
+
+    async fn latest(&self, limit: usize) -> Result<Vec<FullTransaction>, Error> {
+        let mut latest = Self::decode(self.transactions.iter().rev().take(limit))?;
+        latest.reverse();
+
+        Ok(latest)
+    }
+
+    async fn indexed(&self) -> Result<(usize, Vec<FullTransaction>), Error> {
+        let first = match self.transactions.first().context(Database)? {
+            None => self.delivered,
+            Some((key, _)) => index(&key),
+        };
+
+        Ok((first as usize, Self::decode(self.transactions.iter())?))
+    }
+
+    async fn prune(&mut self, oldest: DateTime<Utc>) -> Result<usize, Error> {
+        let mut pruned = 0;
+        for entry in self.transactions.iter() {
+            let (key, value) = entry.context(Database)?;
+            let full: FullTransaction = bincode::deserialize(&value).context(Decode)?;
+            if full.timestamp >= oldest {
+                break;
+            }
+
+            self.transactions.remove(key).context(Database)?;
+            pruned += 1;
+        }
+        self.kept -= pruned;
+
+        Ok(pruned)
+    }
+
+    fn kept(&self) -> usize {
+        self.kept
+    }
+
+    fn delivered(&self) -> u64 {
+        self.delivered
+    }
+}
@@ -0,0 +1,244 @@
+//! Transactions delivered lately, kept as configured by
+//! [`config::ConfigRecentTransactions`]
+
+use std::time::Duration;
+
+use snafu::ResultExt;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time,
+};
+use tracing::warn;
+
+use super::config;
+use crate::FullTransaction;
+
+pub mod log;
+
+pub use log::TransactionLog;
+
+/// How many transactions are returned when no limit is asked
+pub const DEFAULT_LIMIT: usize = 10;
+/// How often transactions too old are dropped
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("log: {}", source))]
+    Log { source: log::Error },
+    #[snafu(display("gone on send"))]
+    GoneOnSend,
+    #[snafu(display("gone on recv"))]
+    GoneOnRecv,
+}
+
+#[derive(Debug)]
+enum Commands {
+    Put {
+        full: Box<FullTransaction>,
+        resp: oneshot::Sender<Result<(), log::Error>>,
+    },
+    GetAll {
+        resp: oneshot::Sender<Result<Vec<FullTransaction>, log::Error>>,
+    },
+    GetLatest {
+        limit: usize,
+        resp: oneshot::Sender<Result<Vec<FullTransaction>, log::Error>>,
+    },
+    GetIndexed {
+        resp: oneshot::Sender<Result<(usize, Vec<FullTransaction>), log::Error>>,
+    },
+    GetStats {
+        resp: oneshot::Sender<Stats>,
+    },
+}
+
+/// How full the recent transactions are, and how many were let go
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct Stats {
+    /// How many transactions are kept
+    pub kept: usize,
+    /// Most transactions kept at once, the configured `max_count`
+    pub capacity: usize,
+    /// How many transactions were delivered, kept or not
+    pub delivered: u64,
+    /// Dropped to make room for newer ones, since the start
+    pub dropped: u64,
+    /// Dropped for being older than `max_age`, since the start
+    pub expired: u64,
+}
+
+#[derive(Clone)]
+pub struct RecentTransactions {
+    agent: mpsc::Sender<Commands>,
+}
+
+/// Tokio agent owning the recent transactions.
+/// The only way to interacte with it is to use [`RecentTransactions`].
+struct RecentTransactionsHandler {
+    max_count: usize,
+    max_age: Option<chrono::Duration>,
+    log: Box<dyn TransactionLog>,
+    dropped: u64,
+    expired: u64,
+}
+
+impl RecentTransactions {
+    /// Keep the transactions in memory
+    pub fn new(config: config::ConfigRecentTransactions) -> Self {
+        let log = log::Memory::new(config.max_count);
+
+        Self::with_log(Box::new(log), config)
+    }
+
+    /// Keep the transactions in the given `log`, built with the `max_count` of the `config`
+    pub fn with_log(
+        log: Box<dyn TransactionLog>,
+        config: config::ConfigRecentTransactions,
+    ) -> Self {
+        Self {
+            agent: RecentTransactionsHandler {
+                max_count: config.max_count,
+                max_age: config
+                    .max_age
+                    .map(|max_age| chrono::Duration::seconds(max_age as i64)),
+                log,
+                dropped: 0,
+                expired: 0,
+            }
+            .spawn(),
+        }
+    }
+
+    /// Add a new transaction
+    pub async fn put(&self, full: FullTransaction) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Put {
+                full: Box::new(full),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?.context(Log)
+    }
+
+    /// Return the recently seen transactions
+    pub async fn get_all(&self) -> Result<Vec<FullTransaction>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetAll { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?.context(Log)
+    }
+
+    /// Return up to `limit` of the recently seen transactions, the newest ones
+    pub async fn get_latest(&self, limit: usize) -> Result<Vec<FullTransaction>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetLatest { limit, resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?.context(Log)
+    }
+
+    /// Return the recently seen transactions, along with the index of the first one
+    ///
+    /// Transactions are indexed from zero, in order of delivery.
+    pub async fn get_indexed(&self) -> Result<(usize, Vec<FullTransaction>), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetIndexed { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?.context(Log)
+    }
+
+    /// Return how full the recent transactions are, without reading them
+    pub async fn get_stats(&self) -> Result<Stats, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetStats { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+}
+
+impl RecentTransactionsHandler {
+    fn spawn(mut self) -> mpsc::Sender<Commands> {
+        let (tx, mut rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            let mut prune = time::interval(PRUNE_INTERVAL);
+
+            loop {
+                let cmd = tokio::select! {
+                    cmd = rx.recv() => match cmd {
+                        None => break,
+                        Some(cmd) => cmd,
+                    },
+                    _ = prune.tick() => {
+                        if let Err(err) = self.prune().await {
+                            warn!("prune recent transactions: {}", err);
+                        }
+                        continue;
+                    }
+                };
+
+                match cmd {
+                    Commands::Put { full, resp } => {
+                        let appended = self.log.append(*full).await.map(|dropped| {
+                            self.dropped += dropped as u64;
+                        });
+                        let _ = resp.send(appended);
+                    }
+                    Commands::GetAll { resp } => {
+                        let _ = resp.send(self.log.indexed().await.map(|(_, all)| all));
+                    }
+                    Commands::GetLatest { limit, resp } => {
+                        let _ = resp.send(self.log.latest(limit).await);
+                    }
+                    Commands::GetIndexed { resp } => {
+                        let _ = resp.send(self.log.indexed().await);
+                    }
+                    Commands::GetStats { resp } => {
+                        let _ = resp.send(self.stats());
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+
+    /// Drop the transactions older than the `max_age`
+    async fn prune(&mut self) -> Result<(), log::Error> {
+        if let Some(max_age) = self.max_age {
+            self.expired += self.log.prune(chrono::Utc::now() - max_age).await? as u64;
+        }
+
+        Ok(())
+    }
+
+    fn stats(&self) -> Stats {
+        Stats {
+            kept: self.log.kept(),
+            capacity: self.max_count,
+            delivered: self.log.delivered(),
+            dropped: self.dropped,
+            expired: self.expired,
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Transfers delivered but refused, so that their users can see why
+//!
+//! Only the latest ones are kept, but every rejection is counted.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use drop::crypto::sign;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{Amount, AssetId, Output};
+
+/// How many rejections to remember, the oldest being forgotten first
+const TRACKED_MAX_SIZE: usize = 10_000;
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("gone on send"))]
+    GoneOnSend,
+    #[snafu(display("gone on recv"))]
+    GoneOnRecv,
+}
+
+/// Why a transfer was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    InvalidSignature,
+    InsufficientBalance,
+    SequenceAlreadyUsed,
+    SequenceGap,
+    BalanceOverflow,
+    TransferOverflow,
+    InvalidMemo,
+    TooManyOutputs,
+    FeeTooLow,
+    UnexpectedFee,
+    UnknownAsset,
+    /// None of the above, such as the storage failing
+    Other,
+}
+
+impl Reason {
+    pub const ALL: [Reason; 12] = [
+        Reason::InvalidSignature,
+        Reason::InsufficientBalance,
+        Reason::SequenceAlreadyUsed,
+        Reason::SequenceGap,
+        Reason::BalanceOverflow,
+        Reason::TransferOverflow,
+        Reason::InvalidMemo,
+        Reason::TooManyOutputs,
+        Reason::FeeTooLow,
+        Reason::UnexpectedFee,
+        Reason::UnknownAsset,
+        Reason::Other,
+    ];
+
+    /// Snake case name, as used in the metrics
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reason::InvalidSignature => "invalid_signature",
+            Reason::InsufficientBalance => "insufficient_balance",
+            Reason::SequenceAlreadyUsed => "sequence_already_used",
+            Reason::SequenceGap => "sequence_gap",
+            Reason::BalanceOverflow => "balance_overflow",
+            Reason::TransferOverflow => "transfer_overflow",
+            Reason::InvalidMemo => "invalid_memo",
+            Reason::TooManyOutputs => "too_many_outputs",
+            Reason::FeeTooLow => "fee_too_low",
+            Reason::UnexpectedFee => "unexpected_fee",
+            Reason::UnknownAsset => "unknown_asset",
+            Reason::Other => "other",
+        }
+    }
+}
+
+/// A refused transfer
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    pub sender: sign::PublicKey,
+    pub sequence: sieve::Sequence,
+    /// Every recipient with its amount, as signed
+    pub outputs: Vec<Output>,
+    pub fee: Amount,
+    /// Registered one sent, the network's own if `None`
+    pub asset: Option<AssetId>,
+    pub reason: Reason,
+    /// As logged by the node, such as "insufficient balance: have 1, need 2"
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Rejection {
+    fn concerns(&self, account: &sign::PublicKey) -> bool {
+        self.sender == *account
+            || self
+                .outputs
+                .iter()
+                .any(|output| output.recipient == *account)
+    }
+}
+
+#[derive(Debug)]
+enum Commands {
+    Put {
+        rejection: Box<Rejection>,
+        resp: oneshot::Sender<()>,
+    },
+    Get {
+        account: Box<sign::PublicKey>,
+        resp: oneshot::Sender<Vec<Rejection>>,
+    },
+    GetCounts {
+        resp: oneshot::Sender<Vec<(Reason, u64)>>,
+    },
+}
+
+#[derive(Clone)]
+pub struct RejectedTransactions {
+    agent: mpsc::Sender<Commands>,
+}
+
+/// Tokio agent owning the rejections.
+/// The only way to interacte with it is to use [`RejectedTransactions`].
+struct RejectedTransactionsHandler {
+    /// Oldest first
+    rejections: VecDeque<Rejection>,
+    /// Since the start, in the order of [`Reason::ALL`]
+    counts: [u64; Reason::ALL.len()],
+}
+
+impl RejectedTransactions {
+    pub fn new() -> Self {
+        Self {
+            agent: RejectedTransactionsHandler {
+                rejections: VecDeque::new(),
+                counts: [0; Reason::ALL.len()],
+            }
+            .spawn(),
+        }
+    }
+
+    /// Remember `rejection`, forgetting the oldest one if full
+    pub async fn put(&self, rejection: Rejection) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Put {
+                rejection: Box::new(rejection),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// Remembered rejections sent or received by `account`, newest first
+    pub async fn get(&self, account: sign::PublicKey) -> Result<Vec<Rejection>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Get {
+                account: Box::new(account),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// How many were rejected for each reason since the start
+    pub async fn get_counts(&self) -> Result<Vec<(Reason, u64)>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::GetCounts { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+}
+
+impl RejectedTransactionsHandler {
+    fn spawn(mut self) -> mpsc::Sender<Commands> {
+        let (tx, mut rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            while let Some(cmd) = rx.recv().await {
+                match cmd {
+                    Commands::Put { rejection, resp } => {
+                        if let Some(index) = Reason::ALL.iter().position(|r| *r == rejection.reason)
+                        {
+                            self.counts[index] += 1;
+                        }
+
+                        if self.rejections.len() == TRACKED_MAX_SIZE {
+                            self.rejections.pop_front();
+                        }
+                        self.rejections.push_back(*rejection);
+
+                        let _ = resp.send(());
+                    }
+                    Commands::Get { account, resp } => {
+                        let _ = resp.send(
+                            self.rejections
+                                .iter()
+                                .rev()
+                                .filter(|rejection| rejection.concerns(&account))
+                                .cloned()
+                                .collect(),
+                        );
+                    }
+                    Commands::GetCounts { resp } => {
+                        let _ = resp.send(
+                            Reason::ALL
+                                .iter()
+                                .copied()
+                                .zip(self.counts.iter().copied())
+                                .collect(),
+                        );
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+}
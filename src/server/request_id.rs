@@ -0,0 +1,85 @@
+//! Tag each call with an ID, found in the logs and returned to the client
+//!
+//! The ID is taken from the [`REQUEST_ID_METADATA_KEY`] metadata when the
+//! client gives a valid one, otherwise it is generated. The call runs in a
+//! `request` span carrying it, and the response, failed or not, has it in the
+//! same metadata.
+
+use std::task::{Context, Poll};
+
+use futures::future::{BoxFuture, FutureExt};
+use http::HeaderValue;
+use rand::{rngs::OsRng, RngCore};
+use tonic::{codegen::Service, transport::NamedService};
+use tracing::{info_span, Instrument};
+
+use crate::{REQUEST_ID_MAX_LEN, REQUEST_ID_METADATA_KEY};
+
+/// Wrap a gRPC service to tag its calls
+#[derive(Debug, Clone)]
+pub struct RequestIds<S> {
+    inner: S,
+}
+
+impl<S> RequestIds<S> {
+    /// Tag the calls of `inner`
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S: NamedService> NamedService for RequestIds<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S, B, R> Service<http::Request<B>> for RequestIds<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<R>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        let id = request
+            .headers()
+            .get(REQUEST_ID_METADATA_KEY)
+            .filter(|id| is_valid(id))
+            .cloned()
+            .unwrap_or_else(generate);
+        request
+            .headers_mut()
+            .insert(REQUEST_ID_METADATA_KEY, id.clone());
+
+        let span = info_span!(
+            "request",
+            id = id.to_str().expect("request ID is visible ASCII")
+        );
+        let response = span.in_scope(|| self.inner.call(request));
+
+        async move {
+            let mut response = response.await?;
+            response.headers_mut().insert(REQUEST_ID_METADATA_KEY, id);
+            Ok(response)
+        }
+        .instrument(span)
+        .boxed()
+    }
+}
+
+fn is_valid(id: &HeaderValue) -> bool {
+    !id.is_empty()
+        && id.len() <= REQUEST_ID_MAX_LEN
+        && id.as_bytes().iter().all(u8::is_ascii_graphic)
+}
+
+/// Random ID, hex encoded
+fn generate() -> HeaderValue {
+    let id = format!("{:016x}", OsRng.next_u64());
+    HeaderValue::from_str(&id).expect("hex is a valid header")
+}
@@ -0,0 +1,424 @@
+//! Subset of the [Rosetta Data API](https://www.rosetta-api.org/docs/data_api_introduction.html)
+//!
+//! AT2 has no blocks, so each delivered transaction is exposed as its own
+//! block, indexed by order of delivery on this node; block zero is the empty
+//! genesis. As only the recent transactions are kept, older blocks are gone.
+
+use hyper::{Body, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{json::json_response, rpc};
+use crate::{address::Address, FullTransaction};
+
+const BLOCKCHAIN: &str = "at2";
+const ROSETTA_VERSION: &str = "1.4.10";
+const OPERATION_TRANSFER: &str = "TRANSFER";
+const STATUS_SUCCESS: &str = "SUCCESS";
+
+/// Paths served by this module
+pub const PATHS: &[&str] = &[
+    "/network/list",
+    "/network/options",
+    "/network/status",
+    "/account/balance",
+    "/block",
+];
+
+#[derive(Clone, Copy)]
+enum ErrorKind {
+    InvalidRequest = 1,
+    UnknownNetwork = 2,
+    InvalidAddress = 3,
+    BlockNotFound = 4,
+    Internal = 5,
+}
+
+impl ErrorKind {
+    const ALL: &'static [Self] = &[
+        Self::InvalidRequest,
+        Self::UnknownNetwork,
+        Self::InvalidAddress,
+        Self::BlockNotFound,
+        Self::Internal,
+    ];
+
+    fn message(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest => "invalid request",
+            Self::UnknownNetwork => "unknown network",
+            Self::InvalidAddress => "invalid address",
+            Self::BlockNotFound => "block not found",
+            Self::Internal => "internal error",
+        }
+    }
+
+    fn retriable(&self) -> bool {
+        matches!(self, Self::BlockNotFound | Self::Internal)
+    }
+
+    fn to_error(self) -> RosettaError {
+        RosettaError {
+            code: self as u32,
+            message: self.message(),
+            retriable: self.retriable(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RosettaError {
+    code: u32,
+    message: &'static str,
+    retriable: bool,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct NetworkIdentifier {
+    blockchain: String,
+    network: String,
+}
+
+#[derive(Serialize)]
+struct BlockIdentifier {
+    index: u64,
+    hash: String,
+}
+
+#[derive(Deserialize)]
+struct PartialBlockIdentifier {
+    index: Option<u64>,
+    hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AccountIdentifier {
+    address: String,
+}
+
+#[derive(Serialize)]
+struct Currency {
+    symbol: &'static str,
+    decimals: u32,
+}
+
+#[derive(Serialize)]
+struct Amount {
+    value: String,
+    currency: Currency,
+}
+
+#[derive(Serialize)]
+struct OperationIdentifier {
+    index: u64,
+}
+
+#[derive(Serialize)]
+struct Operation {
+    operation_identifier: OperationIdentifier,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    status: &'static str,
+    account: AccountIdentifier,
+    amount: Amount,
+}
+
+#[derive(Serialize)]
+struct TransactionIdentifier {
+    hash: String,
+}
+
+#[derive(Serialize)]
+struct Transaction {
+    transaction_identifier: TransactionIdentifier,
+    operations: Vec<Operation>,
+}
+
+#[derive(Serialize)]
+struct Block {
+    block_identifier: BlockIdentifier,
+    parent_block_identifier: BlockIdentifier,
+    timestamp: i64,
+    transactions: Vec<Transaction>,
+}
+
+#[derive(Deserialize)]
+struct MetadataRequest {}
+
+#[derive(Deserialize)]
+struct NetworkRequest {
+    network_identifier: NetworkIdentifier,
+}
+
+#[derive(Deserialize)]
+struct AccountBalanceRequest {
+    network_identifier: NetworkIdentifier,
+    account_identifier: AccountIdentifier,
+}
+
+#[derive(Deserialize)]
+struct BlockRequest {
+    network_identifier: NetworkIdentifier,
+    block_identifier: PartialBlockIdentifier,
+}
+
+#[derive(Serialize)]
+struct NetworkListResponse {
+    network_identifiers: Vec<NetworkIdentifier>,
+}
+
+#[derive(Serialize)]
+struct Version {
+    rosetta_version: &'static str,
+    node_version: &'static str,
+}
+
+#[derive(Serialize)]
+struct OperationStatus {
+    status: &'static str,
+    successful: bool,
+}
+
+#[derive(Serialize)]
+struct Allow {
+    operation_statuses: Vec<OperationStatus>,
+    operation_types: Vec<&'static str>,
+    errors: Vec<RosettaError>,
+    historical_balance_lookup: bool,
+    mempool_coins: bool,
+}
+
+#[derive(Serialize)]
+struct NetworkOptionsResponse {
+    version: Version,
+    allow: Allow,
+}
+
+#[derive(Serialize)]
+struct NetworkStatusResponse {
+    current_block_identifier: BlockIdentifier,
+    current_block_timestamp: i64,
+    genesis_block_identifier: BlockIdentifier,
+    peers: Vec<()>,
+}
+
+#[derive(Serialize)]
+struct AccountBalanceResponse {
+    block_identifier: BlockIdentifier,
+    balances: Vec<Amount>,
+}
+
+#[derive(Serialize)]
+struct BlockResponse {
+    block: Block,
+}
+
+fn network() -> NetworkIdentifier {
+    NetworkIdentifier {
+        blockchain: BLOCKCHAIN.to_owned(),
+        network: crate::address::DEFAULT_PREFIX.to_owned(),
+    }
+}
+
+fn currency() -> Currency {
+    Currency {
+        symbol: "AT2",
+        decimals: 0,
+    }
+}
+
+fn block_identifier(index: u64) -> BlockIdentifier {
+    BlockIdentifier {
+        index,
+        hash: format!("{:064x}", index),
+    }
+}
+
+fn operation(index: u64, account: &Address, value: String) -> Operation {
+    Operation {
+        operation_identifier: OperationIdentifier { index },
+        kind: OPERATION_TRANSFER,
+        status: STATUS_SUCCESS,
+        account: AccountIdentifier {
+            address: account.to_string(),
+        },
+        amount: Amount {
+            value,
+            currency: currency(),
+        },
+    }
+}
+
+fn block(index: u64, tx: &FullTransaction) -> Block {
+    let identifier = block_identifier(index);
+
+    Block {
+        transactions: vec![Transaction {
+            transaction_identifier: TransactionIdentifier {
+                hash: identifier.hash.clone(),
+            },
+            operations: vec![
+                operation(0, &Address::from(tx.sender), format!("-{}", tx.amount)),
+                operation(1, &Address::from(tx.recipient), tx.amount.to_string()),
+            ],
+        }],
+        block_identifier: identifier,
+        parent_block_identifier: block_identifier(index - 1),
+        timestamp: tx.timestamp.timestamp_millis(),
+    }
+}
+
+/// Serve the request for `path`, one of [`PATHS`]
+pub async fn route(service: rpc::Service, path: &str, body: &[u8]) -> Response<Body> {
+    let reply = match path {
+        "/network/list" => match decode::<MetadataRequest>(body) {
+            Ok(_) => encode(&NetworkListResponse {
+                network_identifiers: vec![network()],
+            }),
+            Err(err) => Err(err),
+        },
+        "/network/options" => match decode::<NetworkRequest>(body) {
+            Ok(_) => encode(&NetworkOptionsResponse {
+                version: Version {
+                    rosetta_version: ROSETTA_VERSION,
+                    node_version: env!("CARGO_PKG_VERSION"),
+                },
+                allow: Allow {
+                    operation_statuses: vec![OperationStatus {
+                        status: STATUS_SUCCESS,
+                        successful: true,
+                    }],
+                    operation_types: vec![OPERATION_TRANSFER],
+                    errors: ErrorKind::ALL.iter().map(|kind| kind.to_error()).collect(),
+                    historical_balance_lookup: false,
+                    mempool_coins: false,
+                },
+            }),
+            Err(err) => Err(err),
+        },
+        "/network/status" => network_status(service, body).await,
+        "/account/balance" => account_balance(service, body).await,
+        "/block" => get_block(service, body).await,
+        _ => Err(ErrorKind::InvalidRequest),
+    };
+
+    match reply {
+        Ok(reply) => reply,
+        Err(kind) => {
+            let body = serde_json::to_vec(&kind.to_error()).unwrap_or_default();
+
+            json_response(StatusCode::INTERNAL_SERVER_ERROR, body)
+        }
+    }
+}
+
+async fn network_status(service: rpc::Service, body: &[u8]) -> Result<Response<Body>, ErrorKind> {
+    let request = decode::<NetworkRequest>(body)?;
+    check_network(&request.network_identifier)?;
+
+    let (first, recents) = service
+        .recent_transactions()
+        .get_indexed()
+        .await
+        .map_err(|_| ErrorKind::Internal)?;
+    let current = (first + recents.len()) as u64;
+
+    encode(&NetworkStatusResponse {
+        current_block_identifier: block_identifier(current),
+        current_block_timestamp: recents
+            .last()
+            .map(|tx| tx.timestamp.timestamp_millis())
+            .unwrap_or_default(),
+        genesis_block_identifier: block_identifier(0),
+        peers: vec![],
+    })
+}
+
+async fn account_balance(service: rpc::Service, body: &[u8]) -> Result<Response<Body>, ErrorKind> {
+    let request = decode::<AccountBalanceRequest>(body)?;
+    check_network(&request.network_identifier)?;
+
+    let address = Address::parse_with_prefix(
+        &request.network_identifier.network,
+        &request.account_identifier.address,
+    )
+    .map_err(|_| ErrorKind::InvalidAddress)?;
+
+    let (first, recents) = service
+        .recent_transactions()
+        .get_indexed()
+        .await
+        .map_err(|_| ErrorKind::Internal)?;
+    let balance = service
+        .accounts()
+        .get_balance(Box::new(address.into()))
+        .await
+        .map_err(|_| ErrorKind::Internal)?;
+
+    encode(&AccountBalanceResponse {
+        block_identifier: block_identifier((first + recents.len()) as u64),
+        balances: vec![Amount {
+            value: balance.to_string(),
+            currency: currency(),
+        }],
+    })
+}
+
+async fn get_block(service: rpc::Service, body: &[u8]) -> Result<Response<Body>, ErrorKind> {
+    let request = decode::<BlockRequest>(body)?;
+    check_network(&request.network_identifier)?;
+
+    let index = match (
+        request.block_identifier.index,
+        request.block_identifier.hash,
+    ) {
+        (Some(index), _) => index,
+        (None, Some(hash)) => {
+            u64::from_str_radix(&hash, 16).map_err(|_| ErrorKind::BlockNotFound)?
+        }
+        (None, None) => return Err(ErrorKind::InvalidRequest),
+    };
+
+    if index == 0 {
+        return encode(&BlockResponse {
+            block: Block {
+                block_identifier: block_identifier(0),
+                parent_block_identifier: block_identifier(0),
+                timestamp: 0,
+                transactions: vec![],
+            },
+        });
+    }
+
+    let (first, recents) = service
+        .recent_transactions()
+        .get_indexed()
+        .await
+        .map_err(|_| ErrorKind::Internal)?;
+
+    let tx = (index as usize - 1)
+        .checked_sub(first)
+        .and_then(|offset| recents.get(offset))
+        .ok_or(ErrorKind::BlockNotFound)?;
+
+    encode(&BlockResponse {
+        block: block(index, tx),
+    })
+}
+
+fn check_network(network_identifier: &NetworkIdentifier) -> Result<(), ErrorKind> {
+    if *network_identifier == network() {
+        Ok(())
+    } else {
+        Err(ErrorKind::UnknownNetwork)
+    }
+}
+
+fn decode<T: DeserializeOwned>(body: &[u8]) -> Result<T, ErrorKind> {
+    serde_json::from_slice(body).map_err(|_| ErrorKind::InvalidRequest)
+}
+
+fn encode<T: Serialize>(reply: &T) -> Result<Response<Body>, ErrorKind> {
+    let body = serde_json::to_vec(reply).map_err(|_| ErrorKind::Internal)?;
+
+    Ok(json_response(StatusCode::OK, body))
+}
@@ -0,0 +1,2805 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    num::NonZeroUsize,
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use contagion::{Contagion, ContagionConfig, ContagionMessage};
+use drop::{
+    crypto::{
+        key::exchange::{self, Exchanger},
+        sign,
+    },
+    net::{ConnectorExt, ResolveConnector, TcpConnector, TcpListener},
+    system::{AllSampler, Handle, NetworkSender, PoissonSampler, System, SystemManager},
+};
+use futures::{future, Stream, StreamExt};
+use hex::{FromHex, ToHex};
+use murmur::MurmurConfig;
+use sieve::SieveConfig;
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use tokio::{
+    net,
+    sync::{broadcast, mpsc},
+};
+use tonic::{metadata::MetadataValue, Response};
+use tracing::{debug, info, info_span, warn, Instrument, Level};
+
+use super::{
+    accounts::{self, Accounts},
+    allowlist::AllowlistListener,
+    challenges::{self, Challenges},
+    config,
+    connector::{Addresses, FallbackConnector},
+    events::{self, Events},
+    evidence::{self, Evidences},
+    history::{self, History},
+    peer_statuses::{self, PeerStatuses},
+    rate_limit::{self, RateLimit},
+    recent_transactions::{self, RecentTransactions},
+    rejected_transactions::{self, Reason, RejectedTransactions, Rejection},
+    submissions::{self, Claim, Submissions},
+    sync,
+    transaction_statuses::{self, TransactionStatuses},
+    validation::{self, Rules},
+};
+use crate::{
+    address::{self, Address},
+    auth,
+    client::Client,
+    codec::{self, Codec, Encoding},
+    is_valid_memo, proto, proto_convert, version, Amount, Asset, BalanceAttestation,
+    BalanceStatement, BridgeDeposit, Fees, FullTransaction, LogLevel, Mint, Operation, Output,
+    ReceiptStatement, SignedOperation, SignedTransaction, ThinTransaction, LOG_LEVEL_MAX_SKEW,
+    OUTPUTS_MAX_LEN,
+};
+
+/// How many delivered transactions a subscriber can lag behind
+const DELIVERED_CAPACITY: usize = 64;
+/// How long a peer has to attest a balance
+const ATTESTATION_TIMEOUT: Duration = Duration::from_secs(2);
+/// How many accounts `GetBalances` reads at once
+const BALANCES_MAX_LEN: usize = 1_000;
+/// How many times a broadcast is tried before giving up
+const BROADCAST_ATTEMPTS: usize = 4;
+/// How long to wait before trying a broadcast again, doubled each time
+const BROADCAST_BACKOFF: Duration = Duration::from_millis(50);
+
+#[derive(Snafu, Debug)]
+pub enum ProtoError {
+    #[snafu(display("deserialize: {}", source))]
+    Deserialize { source: codec::Error },
+    #[snafu(display("serialize: {}", source))]
+    Serialize { source: codec::Error },
+    #[snafu(display("encoding: {}", source))]
+    UnknownEncoding { source: codec::Error },
+    #[snafu(display("address: {}", source))]
+    InvalidAddress { source: address::Error },
+    #[snafu(display("typed: {}", source))]
+    Convert { source: proto_convert::Error },
+    #[snafu(display("address and key differ"))]
+    ConflictingKeys,
+    #[snafu(display("size: {}", source))]
+    InvalidSize { source: validation::Error },
+}
+
+#[derive(Snafu, Debug)]
+pub enum ProcessTransactionError {
+    #[snafu(display("handle by acounts: {}", source))]
+    ProcessTxForAccounts { source: accounts::Error },
+    #[snafu(display("handle by recent transactions: {}", source))]
+    ProcessTxForRecent { source: recent_transactions::Error },
+    #[snafu(display("handle by history: {}", source))]
+    ProcessTxForHistory { source: history::Error },
+    #[snafu(display("handle by statuses: {}", source))]
+    ProcessTxForStatuses { source: transaction_statuses::Error },
+    #[snafu(display("handle by rejections: {}", source))]
+    ProcessTxForRejections {
+        source: rejected_transactions::Error,
+    },
+    #[snafu(display("handle by evidence: {}", source))]
+    ProcessTxForEvidence { source: evidence::Error },
+    #[snafu(display("invalid memo"))]
+    InvalidMemo,
+    #[snafu(display("too many outputs"))]
+    TooManyOutputs,
+    #[snafu(display("fee of {} below the minimum of {}", fee, minimum))]
+    FeeTooLow { fee: Amount, minimum: Amount },
+    #[snafu(display("fee paid to a network without fees"))]
+    UnexpectedFee,
+    #[snafu(display("invalid signature"))]
+    InvalidSignature,
+    #[snafu(display("mint by unknown admin"))]
+    UnknownAdmin,
+    #[snafu(display("deposit without a bridge"))]
+    NoBridge,
+    #[snafu(display("deposit by unknown bridge validator"))]
+    UnknownValidator,
+}
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("new service: {}", source))]
+    ServiceNew { source: drop::net::ListenerError },
+    #[snafu(display("new service: events: {}", source))]
+    ServiceEvents { source: events::Error },
+    #[snafu(display("new service: bridge: invalid key: {}", key))]
+    ServiceBridgeKey { key: String },
+    #[snafu(display("new service: genesis: invalid key: {}", key))]
+    ServiceGenesisKey { key: String },
+    #[snafu(display("new service: fees: invalid key: {}", key))]
+    ServiceFeesKey { key: String },
+    #[snafu(display("new service: admins: invalid key: {}", key))]
+    ServiceAdminKey { key: String },
+    #[snafu(display(
+        "new service: protocol: {} of {} is larger than its sample of {}",
+        name,
+        threshold,
+        sample
+    ))]
+    ServiceThreshold {
+        name: &'static str,
+        threshold: usize,
+        sample: usize,
+    },
+    #[snafu(display("new service: protocol: {} of {} isn't below {}", name, value, max))]
+    ServiceFraction {
+        name: &'static str,
+        value: f64,
+        max: f64,
+    },
+    #[snafu(display("new service: database: {}", source))]
+    ServiceDatabase { source: sled::Error },
+    #[snafu(display("new service: storage: {}", source))]
+    ServiceStorage { source: accounts::store::Error },
+    #[snafu(display("new service: history: {}", source))]
+    ServiceHistory { source: history::Error },
+    #[snafu(display("new service: recent transactions: {}", source))]
+    ServiceRecentTransactions {
+        source: recent_transactions::log::Error,
+    },
+    #[snafu(display("new service: persistent recent transactions without storage_path"))]
+    ServiceNoStorage,
+    #[snafu(display("new service: sync: {}", source))]
+    ServiceSync { source: sync::Error },
+    #[snafu(display("new service: checkpoint: {}", source))]
+    ServiceCheckpoint { source: accounts::Error },
+    #[snafu(display("service: process transaction: {}", source))]
+    ProcessTransaction { source: ProcessTransactionError },
+}
+
+/// Transactions streamed to a caller, as subscribed to or exported
+pub struct Subscription(mpsc::Receiver<Result<proto::ProcessedTransaction, tonic::Status>>);
+
+impl Stream for Subscription {
+    type Item = Result<proto::ProcessedTransaction, tonic::Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+#[derive(Clone)]
+pub struct Service {
+    handle: contagion::ContagionHandle<
+        Operation,
+        NetworkSender<ContagionMessage<Operation>>,
+        contagion::Fixed,
+    >,
+    accounts: Accounts,
+    recent_transactions: RecentTransactions,
+    history: History,
+    statuses: TransactionStatuses,
+    /// Transfers refused lately, with why
+    rejections: RejectedTransactions,
+    /// Transfers submitted lately, not to broadcast retries again
+    submissions: Submissions,
+    evidences: Evidences,
+    events: Option<Events>,
+    /// Epoch of the latest checkpoint published to the events
+    published_checkpoint: Arc<AtomicU64>,
+    delivered: broadcast::Sender<FullTransaction>,
+    /// Delivered transactions waiting to be applied
+    queued: Arc<AtomicUsize>,
+    bridge: Option<Bridge>,
+    rate_limit: Option<RateLimit>,
+    fees: Option<FeePolicy>,
+    rules: Rules,
+    /// Given to callers reading accounts, if they have to prove owning them
+    challenges: Option<Challenges>,
+    admins: Vec<sign::PublicKey>,
+    /// Signing balance statements
+    keypair: Arc<sign::KeyPair>,
+    chain_id: String,
+    node: String,
+    peers: Vec<String>,
+    nodes: Vec<config::Node>,
+    network_key: exchange::PublicKey,
+    /// With every default resolved
+    protocol: config::ConfigProtocol,
+    peer_statuses: PeerStatuses,
+    quorum: usize,
+    /// Logs can't be changed at runtime if absent
+    set_log_level: Option<SetLogLevel>,
+    /// Accepting writes if absent
+    follower: Option<Follower>,
+}
+
+/// Node only serving reads, see [`Service::as_follower`]
+#[derive(Clone)]
+struct Follower {
+    /// RPC of a node accepting writes, if known
+    validator: Option<String>,
+}
+
+/// Log from `level` on, only for `target` if given, the other targets going
+/// back to how the node started
+pub type SetLogLevel = Arc<dyn Fn(Level, Option<&str>) -> Result<(), String> + Send + Sync>;
+
+/// Parsed version of [`config::ConfigBridge`]
+#[derive(Clone)]
+struct Bridge {
+    lock: sign::PublicKey,
+    validators: Vec<sign::PublicKey>,
+    threshold: usize,
+}
+
+impl Bridge {
+    fn new(config: config::ConfigBridge) -> Result<Self, Error> {
+        let parse = |key: String| {
+            sign::PublicKey::from_hex(&key)
+                .ok()
+                .context(ServiceBridgeKey { key })
+        };
+
+        Ok(Self {
+            lock: parse(config.lock)?,
+            validators: config
+                .validators
+                .into_iter()
+                .map(parse)
+                .collect::<Result<_, _>>()?,
+            threshold: config.threshold,
+        })
+    }
+}
+
+/// Parsed version of [`config::ConfigFees`]
+#[derive(Clone)]
+struct FeePolicy {
+    account: sign::PublicKey,
+    fees: Fees,
+}
+
+impl FeePolicy {
+    fn new(config: config::ConfigFees) -> Result<Self, Error> {
+        Ok(Self {
+            account: sign::PublicKey::from_hex(&config.account)
+                .ok()
+                .context(ServiceFeesKey {
+                    key: config.account,
+                })?,
+            fees: Fees {
+                flat: config.flat.into(),
+                basis_points: config.basis_points,
+            },
+        })
+    }
+}
+
+/// How to run a [`Service`], mostly taken from [`config::Config`]
+pub struct Options {
+    /// Network accepted in submitted documents
+    pub chain_id: String,
+    /// Address given to the peers, the listening one if absent
+    pub advertised_addr: Option<String>,
+    /// Peers of the node
+    pub network: Vec<config::Node>,
+    pub protocol: config::ConfigProtocol,
+    pub recent_transactions: config::ConfigRecentTransactions,
+    pub checkpoints: config::ConfigCheckpoints,
+    /// Catch up with the peers before delivering, if present
+    pub sync: Option<config::ConfigSync>,
+    pub events: Option<config::ConfigEvents>,
+    pub bridge: Option<config::ConfigBridge>,
+    pub rate_limit: Option<config::ConfigRateLimit>,
+    pub fees: Option<config::ConfigFees>,
+    pub validation: config::ConfigValidation,
+    /// Directory where to keep the accounts, in memory if absent
+    pub storage_path: Option<PathBuf>,
+    /// Hex encoded public keys allowed to mint asset
+    pub admins: Vec<String>,
+    /// Hex encoded public keys with their starting balance
+    pub genesis: BTreeMap<String, u64>,
+    /// Whether reading an account needs proving owning it
+    pub authenticated_reads: bool,
+    /// Whether to accept nodes outside of `network`
+    pub open_network: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            chain_id: crate::interchange::DEFAULT_CHAIN_ID.to_owned(),
+            advertised_addr: None,
+            network: Vec::new(),
+            protocol: Default::default(),
+            recent_transactions: Default::default(),
+            checkpoints: Default::default(),
+            sync: None,
+            events: None,
+            bridge: None,
+            rate_limit: None,
+            fees: None,
+            validation: Default::default(),
+            storage_path: None,
+            admins: Vec::new(),
+            genesis: BTreeMap::new(),
+            authenticated_reads: false,
+            open_network: false,
+        }
+    }
+}
+
+impl Service {
+    pub async fn new(
+        listener_addr: impl net::ToSocketAddrs + fmt::Display,
+        network_keypair: exchange::KeyPair,
+        sign_keypair: sign::KeyPair,
+        options: Options,
+    ) -> Result<Self, Error> {
+        let Options {
+            chain_id,
+            advertised_addr,
+            network,
+            protocol,
+            recent_transactions,
+            checkpoints,
+            sync,
+            events,
+            bridge,
+            rate_limit,
+            fees,
+            validation,
+            storage_path,
+            admins,
+            genesis,
+            authenticated_reads,
+            open_network,
+        } = options;
+
+        let network_size = network.len();
+        let node = advertised_addr.unwrap_or_else(|| listener_addr.to_string());
+        let peers = network.iter().map(|node| node.address.clone()).collect();
+        let nodes = network.clone();
+        let network_key = network_keypair.public().to_owned();
+
+        let bridge = bridge.map(Bridge::new).transpose()?;
+        let fees = fees.map(FeePolicy::new).transpose()?;
+
+        let byzantine_fraction = protocol.byzantine_fraction.unwrap_or_default();
+        ensure_fraction("byzantine_fraction", byzantine_fraction, 0.5)?;
+        let (sample_size, tolerance) = match protocol.sampling {
+            config::ConfigSampling::All => (network_size, 0.0),
+            config::ConfigSampling::Poisson {
+                expected,
+                tolerance,
+            } => {
+                ensure_fraction("tolerance", tolerance, 1.0)?;
+                (expected, tolerance)
+            }
+        };
+        // enough for the smallest sample expected, once its faulty peers removed
+        let threshold = |sample: usize| {
+            let smallest = (sample as f64 * (1.0 - tolerance)).floor();
+            (smallest - (smallest * byzantine_fraction).floor()) as usize
+        };
+
+        let gossip_size = protocol.gossip_size.unwrap_or(sample_size);
+        let echo_sample_size = protocol.echo_sample_size.unwrap_or(sample_size);
+        let echo_threshold = protocol
+            .echo_threshold
+            .unwrap_or_else(|| threshold(echo_sample_size));
+        let ready_sample_size = protocol.ready_sample_size.unwrap_or(sample_size);
+        let ready_threshold = protocol
+            .ready_threshold
+            .unwrap_or_else(|| threshold(ready_sample_size));
+        ensure!(
+            echo_threshold <= echo_sample_size,
+            ServiceThreshold {
+                name: "echo_threshold",
+                threshold: echo_threshold,
+                sample: echo_sample_size,
+            }
+        );
+        ensure!(
+            ready_threshold <= ready_sample_size,
+            ServiceThreshold {
+                name: "ready_threshold",
+                threshold: ready_threshold,
+                sample: ready_sample_size,
+            }
+        );
+        let protocol = config::ConfigProtocol {
+            gossip_size: Some(gossip_size),
+            echo_sample_size: Some(echo_sample_size),
+            echo_threshold: Some(echo_threshold),
+            ready_sample_size: Some(ready_sample_size),
+            ready_threshold: Some(ready_threshold),
+            ..protocol
+        };
+
+        let admins = admins
+            .into_iter()
+            .map(|key| {
+                sign::PublicKey::from_hex(&key)
+                    .ok()
+                    .context(ServiceAdminKey { key })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let genesis = genesis
+            .into_iter()
+            .map(|(key, balance)| {
+                sign::PublicKey::from_hex(&key)
+                    .ok()
+                    .context(ServiceGenesisKey { key })
+                    .map(|user| (user, balance.into()))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let (accounts, history, recent_transactions) = match storage_path {
+            None => {
+                ensure!(!recent_transactions.persistent, ServiceNoStorage);
+
+                (
+                    Accounts::with_checkpoint_interval(
+                        Box::new(accounts::store::Memory::default()),
+                        genesis,
+                        checkpoints.interval,
+                    ),
+                    History::new(),
+                    RecentTransactions::new(recent_transactions),
+                )
+            }
+            Some(path) => {
+                let db = sled::open(path).context(ServiceDatabase)?;
+
+                let recent = if recent_transactions.persistent {
+                    let log =
+                        recent_transactions::log::Sled::with_db(&db, recent_transactions.max_count)
+                            .context(ServiceRecentTransactions)?;
+                    RecentTransactions::with_log(Box::new(log), recent_transactions)
+                } else {
+                    RecentTransactions::new(recent_transactions)
+                };
+
+                (
+                    Accounts::with_checkpoint_interval(
+                        Box::new(accounts::store::Sled::with_db(&db).context(ServiceStorage)?),
+                        genesis,
+                        checkpoints.interval,
+                    ),
+                    History::open(&db).context(ServiceHistory)?,
+                    recent,
+                )
+            }
+        };
+
+        // before delivering anything, which would count as known accounts
+        if let Some(config) = sync {
+            sync::catch_up(
+                &network,
+                config.quorum.get(),
+                &accounts,
+                &recent_transactions,
+            )
+            .await
+            .context(ServiceSync)?;
+        }
+
+        let events = match events {
+            None => None,
+            Some(config) => Some(Events::connect(config).await.context(ServiceEvents)?),
+        };
+        // only the checkpoints taken from now on are published
+        let published_checkpoint = match &events {
+            None => 0,
+            Some(_) => accounts
+                .get_checkpoint(None)
+                .await
+                .context(ServiceCheckpoint)?
+                .map_or(0, |checkpoint| checkpoint.epoch),
+        };
+
+        let exchanger = Exchanger::new(network_keypair);
+
+        let listener = TcpListener::new(listener_addr, exchanger.clone())
+            .await
+            .context(ServiceNew)?;
+        let listener = if open_network {
+            AllowlistListener::open(listener)
+        } else {
+            AllowlistListener::new(
+                listener,
+                network.iter().map(|node| node.public_key.to_owned()),
+            )
+        };
+
+        let connector =
+            FallbackConnector::new(ResolveConnector::new(TcpConnector::new(exchanger))).retry();
+        // TODO readd connections if dropped, needs support from drop, see README
+        let mut system = System::new_with_connector_zipped(
+            &connector,
+            network
+                .into_iter()
+                .map(|node| (node.public_key, Addresses::of(&node))),
+        )
+        .await;
+
+        let listener_errors = system.add_listener(listener).await;
+        tokio::spawn(async move {
+            listener_errors
+                .for_each(|err| {
+                    warn!("listener error: {}", err);
+                    future::ready(())
+                })
+                .await
+        });
+
+        let manager = SystemManager::new(system);
+
+        let contagion = Contagion::new(
+            contagion::Fixed::new_local(),
+            ContagionConfig {
+                sieve: SieveConfig {
+                    sieve_sample_size: echo_sample_size,
+                    echo_threshold,
+                    murmur: MurmurConfig {
+                        murmur_gossip_size: gossip_size,
+                        batch_size: protocol.batch_size,
+                        batch_delay: protocol.batch_delay,
+                        ..Default::default()
+                    },
+                },
+                contagion_sample_size: ready_sample_size,
+                ready_threshold,
+            },
+        );
+
+        let workers = protocol
+            .processor_workers
+            .map_or_else(num_cpus::get, NonZeroUsize::get);
+        let mut handle = match protocol.sampling {
+            config::ConfigSampling::All => {
+                manager.run(contagion, AllSampler::default(), workers).await
+            }
+            config::ConfigSampling::Poisson { .. } => {
+                manager
+                    .run(contagion, PoissonSampler::default(), workers)
+                    .await
+            }
+        };
+
+        let handle_errors = handle.errors();
+        tokio::spawn(async move {
+            if let Some(stream) = handle_errors {
+                stream
+                    .for_each(|err| {
+                        warn!("handle error: {}", err);
+                        future::ready(())
+                    })
+                    .await
+            }
+        });
+
+        let service = Self {
+            handle: handle.processor_handle(),
+            accounts,
+            recent_transactions,
+            history,
+            statuses: TransactionStatuses::new(),
+            rejections: RejectedTransactions::new(),
+            submissions: Submissions::new(),
+            evidences: Evidences::new(),
+            events,
+            published_checkpoint: Arc::new(AtomicU64::new(published_checkpoint)),
+            delivered: broadcast::channel(DELIVERED_CAPACITY).0,
+            queued: Default::default(),
+            bridge,
+            rate_limit: rate_limit.map(RateLimit::new),
+            fees,
+            rules: Rules::new(validation),
+            challenges: authenticated_reads.then(Challenges::new),
+            admins,
+            keypair: Arc::new(sign_keypair),
+            chain_id,
+            node,
+            peers,
+            nodes,
+            network_key,
+            protocol: protocol.clone(),
+            peer_statuses: PeerStatuses::new(),
+            quorum: echo_threshold.max(ready_threshold),
+            set_log_level: None,
+            follower: None,
+        };
+        service.spawn(protocol.delivery_capacity.max(1));
+
+        Ok(service)
+    }
+
+    /// Let the admins change the logs with `SetLogLevel`
+    pub fn with_set_log_level(self, set_log_level: SetLogLevel) -> Self {
+        Self {
+            set_log_level: Some(set_log_level),
+            ..self
+        }
+    }
+
+    /// Refuse every write, pointing callers to the `validator` RPC instead
+    ///
+    /// The node still delivers and applies the operations of the others. If
+    /// no `validator` is given, the first peer with an RPC address is.
+    pub fn as_follower(self, validator: Option<String>) -> Self {
+        let validator = validator.or_else(|| self.nodes.iter().find_map(|node| node.rpc_uri()));
+
+        Self {
+            follower: Some(Follower { validator }),
+            ..self
+        }
+    }
+
+    pub fn accounts(&self) -> &Accounts {
+        &self.accounts
+    }
+
+    pub fn recent_transactions(&self) -> &RecentTransactions {
+        &self.recent_transactions
+    }
+
+    pub fn rejected_transactions(&self) -> &RejectedTransactions {
+        &self.rejections
+    }
+
+    /// Network this node belongs to
+    pub fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+
+    /// Broadcast a transaction signed by its sender, once checked
+    pub async fn submit(&self, transaction: SignedTransaction) -> Result<(), tonic::Status> {
+        let span = info_span!(
+            "submit",
+            sender = %fingerprint(&transaction.sender),
+            sequence = transaction.sequence
+        );
+
+        async move {
+            self.check_writable()?;
+            self.rules
+                .check(&transaction)
+                .map_err(|err| tonic::Status::invalid_argument(err.to_string()))?;
+            if !transaction.verify() {
+                return Err(tonic::Status::unauthenticated("invalid signature"));
+            }
+            if transaction
+                .thin()
+                .total()
+                .and_then(|total| total.checked_add(transaction.fee))
+                .is_none()
+            {
+                return Err(accounts::Error::TransferOverflow.into());
+            }
+            self.check_fee(&transaction.thin())
+                .map_err(|err| tonic::Status::failed_precondition(err.to_string()))?;
+            if let Some(asset) = &transaction.asset {
+                // refused upfront rather than once delivered
+                self.accounts
+                    .get_asset_balance(Box::new(transaction.sender), asset.clone())
+                    .await?;
+            }
+
+            // such as a client retrying after a timeout
+            match self
+                .submissions
+                .claim(
+                    transaction.sender,
+                    transaction.sequence,
+                    &transaction.thin(),
+                )
+                .await?
+            {
+                Claim::New => {}
+                Claim::Duplicate => {
+                    debug!("already submitted");
+                    return Ok(());
+                }
+                Claim::Conflict => {
+                    // precisely refused if the other one was delivered
+                    self.check_sequence(&transaction.sender, transaction.sequence)
+                        .await?;
+                    return Err(tonic::Status::already_exists(
+                        "another transaction was submitted with this sequence",
+                    ));
+                }
+            }
+
+            let submitted = self.submit_new(&transaction).await;
+            if submitted.is_err() {
+                self.submissions
+                    .release(transaction.sender, transaction.sequence)
+                    .await?;
+            }
+
+            submitted
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Broadcast a checked transaction, submitted for the first time
+    async fn submit_new(&self, transaction: &SignedTransaction) -> Result<(), tonic::Status> {
+        self.check_sender(&transaction.sender, transaction.sequence)
+            .await?;
+
+        self.statuses
+            .put(
+                transaction.sender,
+                transaction.sequence,
+                transaction_statuses::Status::Pending,
+            )
+            .await?;
+
+        self.broadcast(sieve::Payload::new(
+            transaction.sender,
+            transaction.sequence,
+            Operation::Transfer(transaction.thin()),
+            transaction.signature,
+        ))
+        .await?;
+        debug!("broadcasted");
+
+        Ok(())
+    }
+
+    /// Refuse writing to a follower, carrying the validator to write to
+    fn check_writable(&self) -> Result<(), tonic::Status> {
+        let follower = match &self.follower {
+            None => return Ok(()),
+            Some(follower) => follower,
+        };
+
+        let validator = follower.validator.as_deref().unwrap_or_default();
+        let mut status = if validator.is_empty() {
+            tonic::Status::failed_precondition("read-only follower")
+        } else {
+            tonic::Status::failed_precondition(format!(
+                "read-only follower, write to {}",
+                validator
+            ))
+        };
+        // empty if unknown, still marking the refusal
+        status.metadata_mut().insert(
+            crate::VALIDATOR_METADATA_KEY,
+            MetadataValue::from_str(validator).unwrap_or_else(|_| MetadataValue::from_static("")),
+        );
+
+        Err(status)
+    }
+
+    /// Refuse an operation whose sequence was already used
+    ///
+    /// Only the applied sequences are known; the others might still be fine
+    /// once the pending ones are delivered.
+    async fn check_sequence(
+        &self,
+        sender: &sign::PublicKey,
+        sequence: sieve::Sequence,
+    ) -> Result<(), tonic::Status> {
+        let last = self.accounts.get_last_sequence(Box::new(*sender)).await?;
+        if sequence <= last {
+            return Err(accounts::Error::SequenceAlreadyUsed { sequence, last }.into());
+        }
+
+        Ok(())
+    }
+
+    /// Refuse an operation of a rate limited `sender`, or reusing a sequence,
+    /// before it is marked pending
+    async fn check_sender(
+        &self,
+        sender: &sign::PublicKey,
+        sequence: sieve::Sequence,
+    ) -> Result<(), tonic::Status> {
+        self.limit(rate_limit::Key::Sender(*sender)).await?;
+        self.check_sequence(sender, sequence).await
+    }
+
+    /// Refuse a fee below the minimum, or any fee if transfers are free
+    fn check_fee(&self, thin: &ThinTransaction) -> Result<(), ProcessTransactionError> {
+        match &self.fees {
+            None => ensure!(thin.fee == 0, UnexpectedFee),
+            Some(policy) => {
+                // an overflowing total is refused by the accounts
+                let minimum = policy.fees.minimum(thin.total().unwrap_or_default());
+                ensure!(
+                    thin.fee >= minimum,
+                    FeeTooLow {
+                        fee: thin.fee,
+                        minimum
+                    }
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Refuse if `key` submitted too many transactions lately
+    async fn limit(&self, key: rate_limit::Key) -> Result<(), tonic::Status> {
+        match &self.rate_limit {
+            Some(rate_limit) if !rate_limit.take(key).await? => {
+                Err(tonic::Status::resource_exhausted("rate limited"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Broadcast `payload`, trying again a few times as the channel to
+    /// contagion might only be busy for a while
+    async fn broadcast(&self, payload: sieve::Payload<Operation>) -> Result<(), tonic::Status> {
+        self.check_writable()?;
+
+        let mut delay = BROADCAST_BACKOFF;
+        let mut attempt = 1;
+
+        async move {
+            loop {
+                match self.handle.clone().broadcast(&payload).await {
+                    Ok(()) => return Ok(()),
+                    Err(err) if attempt < BROADCAST_ATTEMPTS => {
+                        debug!("broadcast: attempt {}: {}", attempt, err);
+                        tokio::time::sleep(delay).await;
+                        delay *= 2;
+                        attempt += 1;
+                    }
+                    Err(err) => {
+                        warn!("broadcast: giving up: {}", err);
+                        return Err(tonic::Status::unavailable("broadcasting failed"));
+                    }
+                }
+            }
+        }
+        .instrument(info_span!("broadcast"))
+        .await
+    }
+
+    /// Refuse reading `account` unless the caller proved owning it, if required
+    async fn authorize<T>(
+        &self,
+        request: &tonic::Request<T>,
+        account: &sign::PublicKey,
+    ) -> Result<(), tonic::Status> {
+        let challenges = match &self.challenges {
+            None => return Ok(()),
+            Some(challenges) => challenges,
+        };
+
+        let proof = auth::Proof::from_metadata(request.metadata())
+            .map_err(|err| tonic::Status::unauthenticated(err.to_string()))?;
+        if proof.account != *account {
+            return Err(tonic::Status::permission_denied("not the account's owner"));
+        }
+        if !challenges.check(proof.account, proof.nonce).await? {
+            return Err(tonic::Status::unauthenticated("unknown or expired nonce"));
+        }
+
+        Ok(())
+    }
+
+    /// Address of this node in the network
+    pub fn node(&self) -> &str {
+        &self.node
+    }
+
+    /// Addresses of the configured nodes
+    pub fn peers(&self) -> &[String] {
+        &self.peers
+    }
+
+    /// Peers of this node, as configured
+    pub fn nodes(&self) -> &[config::Node] {
+        &self.nodes
+    }
+
+    /// How the peers answered the health checks
+    pub fn peer_statuses(&self) -> &PeerStatuses {
+        &self.peer_statuses
+    }
+
+    /// How many peers have to take part for a transaction to be delivered
+    pub fn quorum(&self) -> usize {
+        self.quorum
+    }
+
+    /// Ask the peers with an `rpc` address to attest the `statement`, keeping
+    /// the valid attestations of the ones agreeing
+    async fn peer_attestations(&self, statement: &BalanceStatement) -> Vec<BalanceAttestation> {
+        let expected = bincode::serialize(statement).ok();
+
+        let proofs = future::join_all(self.nodes.iter().filter_map(config::Node::rpc_uri).map(
+            |uri| async move {
+                let mut client = Client::builder()
+                    .overall_timeout(ATTESTATION_TIMEOUT)
+                    .build(uri.parse().ok()?)
+                    .ok()?;
+
+                client
+                    .get_balance_proof(&statement.account, false)
+                    .await
+                    .map_err(|err| debug!("attestation by {}: {}", uri, err))
+                    .ok()
+            },
+        ))
+        .await;
+
+        proofs
+            .into_iter()
+            .flatten()
+            .filter(|proof| bincode::serialize(&proof.statement).ok() == expected)
+            .filter_map(|proof| proof.attestations.into_iter().next())
+            .filter(|attestation| attestation.verify(statement))
+            .collect()
+    }
+
+    /// Sign the `statement` of an accepted transfer
+    fn receipt(&self, statement: ReceiptStatement) -> Result<proto::Receipt, ProtoError> {
+        let signature = self.keypair.sign(&statement).expect("sign failed");
+        let (amount, amount_high) = proto_convert::proto_amount(statement.amount);
+
+        Ok(proto::Receipt {
+            sender: Some(proto_convert::proto_public_key(&statement.sender)),
+            sequence: statement.sequence,
+            recipient: Some(proto_convert::proto_public_key(&statement.recipient)),
+            amount,
+            amount_high,
+            received_at: statement.received_at,
+            node: Some(proto_convert::proto_public_key(&self.keypair.public())),
+            signature: Some(proto_convert::proto_signature(&signature).context(Convert)?),
+            asset: statement.asset.unwrap_or_default(),
+        })
+    }
+
+    /// Receive every transaction delivered from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<FullTransaction> {
+        self.delivered.subscribe()
+    }
+
+    /// How many delivered transactions are waiting to be applied
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Apply delivered transactions in a worker queuing up to `capacity` of them
+    ///
+    /// A single worker applies them in the order of delivery, as a transfer
+    /// can spend what another sender just sent. When its queue is full,
+    /// delivery waits for it.
+    fn spawn(&self, capacity: usize) {
+        let (worker, mut rx) = mpsc::channel::<sieve::Payload<Operation>>(capacity);
+        let mut service = self.clone();
+
+        tokio::spawn(async move {
+            while let Some(payload) = rx.recv().await {
+                service.queued.fetch_sub(1, Ordering::Relaxed);
+
+                let span = info_span!(
+                    "deliver",
+                    sender = %fingerprint(payload.sender()),
+                    sequence = payload.sequence()
+                );
+
+                async {
+                    match service
+                        .process_payload(&payload)
+                        .await
+                        .context(ProcessTransaction)
+                    {
+                        Err(err) => warn!("{}", err),
+                        Ok(()) => debug!("delivered"),
+                    }
+                    service.publish_checkpoint().await;
+                }
+                .instrument(span)
+                .await
+            }
+        });
+
+        let mut service = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                match service.handle.deliver().await {
+                    Err(contagion::ContagionError::Channel) => break,
+                    Err(err) => {
+                        warn!("deliver batch: {}", err);
+                        continue;
+                    }
+                    Ok(batch) => {
+                        for payload in batch.iter() {
+                            service.queued.fetch_add(1, Ordering::Relaxed);
+                            if worker.send(payload.clone()).await.is_err() {
+                                warn!("deliver batch: worker gone");
+                                return;
+                            }
+                        }
+                    }
+                };
+            }
+        });
+    }
+
+    async fn process_payload(
+        &mut self,
+        msg: &sieve::Payload<Operation>,
+    ) -> Result<(), ProcessTransactionError> {
+        let sender = Box::new(msg.sender().to_owned());
+
+        // peers might not have checked it
+        if msg.signature().verify(msg.payload(), &sender).is_err() {
+            let err = ProcessTransactionError::InvalidSignature;
+            if let Operation::Transfer(thin) = msg.payload() {
+                self.reject(*sender, msg.sequence(), thin, &err).await?;
+            }
+            return Err(err);
+        }
+
+        let signed = SignedOperation {
+            operation: msg.payload().clone(),
+            signature: msg.signature().clone(),
+        };
+        if self
+            .evidences
+            .put(*sender, msg.sequence(), signed)
+            .await
+            .context(ProcessTxForEvidence)?
+        {
+            warn!("sender signed conflicting operations");
+        }
+
+        match msg.payload() {
+            Operation::Transfer(thin) => self.process_transfer(sender, msg.sequence(), thin).await,
+            Operation::RegisterName(name) => self
+                .accounts
+                .register_name(sender, msg.sequence(), name.to_owned())
+                .await
+                .context(ProcessTxForAccounts),
+            Operation::Mint(mint) => self.process_mint(sender, msg.sequence(), mint).await,
+            Operation::SetSequenceWindow(size) => self
+                .accounts
+                .set_window(sender, msg.sequence(), *size)
+                .await
+                .context(ProcessTxForAccounts),
+            Operation::RegisterAsset(asset) => {
+                self.process_register_asset(sender, msg.sequence(), asset)
+                    .await
+            }
+            Operation::BridgeDeposit(deposit) => {
+                self.process_bridge_deposit(sender, msg.sequence(), deposit)
+                    .await
+            }
+        }
+    }
+
+    async fn process_mint(
+        &mut self,
+        admin: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        mint: &Mint,
+    ) -> Result<(), ProcessTransactionError> {
+        // every node has to know the admin, otherwise they diverge
+        let applied = if self.admins.contains(&admin) {
+            self.accounts
+                .mint(
+                    admin.clone(),
+                    sequence,
+                    Box::new(mint.recipient),
+                    mint.amount,
+                    mint.asset.clone(),
+                )
+                .await
+                .context(ProcessTxForAccounts)
+        } else {
+            UnknownAdmin.fail()
+        };
+
+        let status = match &applied {
+            Ok(()) => transaction_statuses::Status::Delivered,
+            Err(err) => transaction_statuses::Status::Rejected(err.to_string()),
+        };
+        self.statuses
+            .put(*admin, sequence, status)
+            .await
+            .context(ProcessTxForStatuses)?;
+
+        applied
+    }
+
+    async fn process_register_asset(
+        &mut self,
+        admin: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        asset: &Asset,
+    ) -> Result<(), ProcessTransactionError> {
+        // as for minting, every node has to know the admin
+        let applied = if self.admins.contains(&admin) {
+            self.accounts
+                .register_asset(admin.clone(), sequence, Box::new(asset.clone()))
+                .await
+                .context(ProcessTxForAccounts)
+        } else {
+            UnknownAdmin.fail()
+        };
+
+        let status = match &applied {
+            Ok(()) => transaction_statuses::Status::Delivered,
+            Err(err) => transaction_statuses::Status::Rejected(err.to_string()),
+        };
+        self.statuses
+            .put(*admin, sequence, status)
+            .await
+            .context(ProcessTxForStatuses)?;
+
+        applied
+    }
+
+    async fn process_bridge_deposit(
+        &mut self,
+        validator: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        deposit: &BridgeDeposit,
+    ) -> Result<(), ProcessTransactionError> {
+        // as for minting, every node has to know the validators and threshold
+        let applied = match &self.bridge {
+            None => NoBridge.fail(),
+            Some(bridge) if !bridge.validators.contains(&validator) => UnknownValidator.fail(),
+            Some(bridge) => self
+                .accounts
+                .bridge_deposit(
+                    validator.clone(),
+                    sequence,
+                    Box::new(deposit.clone()),
+                    bridge.threshold,
+                )
+                .await
+                .context(ProcessTxForAccounts),
+        };
+        if let Ok(true) = applied {
+            info!(user = %fingerprint(&deposit.user), "minted bridge deposit");
+        }
+
+        let status = match &applied {
+            Ok(_) => transaction_statuses::Status::Delivered,
+            Err(err) => transaction_statuses::Status::Rejected(err.to_string()),
+        };
+        self.statuses
+            .put(*validator, sequence, status)
+            .await
+            .context(ProcessTxForStatuses)?;
+
+        applied.map(|_| ())
+    }
+
+    async fn process_transfer(
+        &mut self,
+        sender: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        thin: &ThinTransaction,
+    ) -> Result<(), ProcessTransactionError> {
+        let applied = self.apply_transfer(sender.clone(), sequence, thin).await;
+
+        // its effects were already seen before the node restarted
+        if let Err(ProcessTransactionError::ProcessTxForAccounts {
+            source: accounts::Error::AlreadyApplied { .. },
+        }) = applied
+        {
+            debug!(sequence, "skipping transfer applied before");
+            return self
+                .statuses
+                .put(*sender, sequence, transaction_statuses::Status::Delivered)
+                .await
+                .context(ProcessTxForStatuses);
+        }
+
+        let status = match &applied {
+            Ok(()) => transaction_statuses::Status::Delivered,
+            Err(err) => transaction_statuses::Status::Rejected(err.to_string()),
+        };
+        self.statuses
+            .put(*sender, sequence, status)
+            .await
+            .context(ProcessTxForStatuses)?;
+        if let Err(err) = &applied {
+            self.reject(*sender, sequence, thin, err).await?;
+        }
+        applied?;
+
+        // one per output, each recipient seeing its own
+        for full in FullTransaction::all_with_thin(*sender, sequence, thin.to_owned()) {
+            if let Some(events) = &self.events {
+                if let Err(err) = events.transaction(&full).await {
+                    warn!("{}", err);
+                }
+            }
+
+            self.recent_transactions
+                .put(full.clone())
+                .await
+                .context(ProcessTxForRecent)?;
+            self.history
+                .put(full.clone())
+                .await
+                .context(ProcessTxForHistory)?;
+
+            // no subscriber is not an error
+            let _ = self.delivered.send(full);
+        }
+
+        Ok(())
+    }
+
+    /// Remember why the transfer was refused
+    async fn reject(
+        &self,
+        sender: sign::PublicKey,
+        sequence: sieve::Sequence,
+        thin: &ThinTransaction,
+        err: &ProcessTransactionError,
+    ) -> Result<(), ProcessTransactionError> {
+        let rejection = Rejection {
+            sender,
+            sequence,
+            outputs: thin.all_outputs(),
+            fee: thin.fee,
+            asset: thin.asset.clone(),
+            reason: rejection_reason(err),
+            message: err.to_string(),
+            timestamp: chrono::Utc::now(),
+        };
+
+        if let Some(events) = &self.events {
+            if let Err(err) = events.rejection(&rejection).await {
+                warn!("{}", err);
+            }
+        }
+
+        self.rejections
+            .put(rejection)
+            .await
+            .context(ProcessTxForRejections)
+    }
+
+    /// Publish the latest checkpoint to the events, if not yet done
+    async fn publish_checkpoint(&self) {
+        let events = match &self.events {
+            None => return,
+            Some(events) => events,
+        };
+
+        let checkpoint = match self.accounts.get_checkpoint(None).await {
+            Err(err) => {
+                warn!("get checkpoint: {}", err);
+                return;
+            }
+            Ok(None) => return,
+            Ok(Some(checkpoint)) => checkpoint,
+        };
+        // workers race to publish it, only the first one does
+        if self
+            .published_checkpoint
+            .fetch_max(checkpoint.epoch, Ordering::Relaxed)
+            < checkpoint.epoch
+        {
+            if let Err(err) = events.checkpoint(&checkpoint).await {
+                warn!("{}", err);
+            }
+        }
+    }
+
+    /// Check the transfer and apply it to the accounts
+    async fn apply_transfer(
+        &self,
+        sender: Box<sign::PublicKey>,
+        sequence: sieve::Sequence,
+        thin: &ThinTransaction,
+    ) -> Result<(), ProcessTransactionError> {
+        // peers might not have checked it
+        if let Some(memo) = &thin.memo {
+            ensure!(is_valid_memo(memo), InvalidMemo);
+        }
+        ensure!(thin.outputs.len() <= OUTPUTS_MAX_LEN, TooManyOutputs);
+        self.check_fee(thin)?;
+
+        let mut outputs = thin
+            .all_outputs()
+            .into_iter()
+            .map(|output| (output.recipient, output.amount))
+            .collect::<Vec<_>>();
+        // paid along the outputs, so that all or none of them are
+        if let Some(policy) = self.fees.as_ref().filter(|_| thin.fee > 0) {
+            outputs.push((policy.account, thin.fee));
+        }
+
+        self.accounts
+            .transfer_asset(sender, sequence, thin.asset.clone(), outputs)
+            .await
+            .context(ProcessTxForAccounts)
+    }
+}
+
+impl From<ProtoError> for tonic::Status {
+    fn from(err: ProtoError) -> Self {
+        Self::invalid_argument(err.to_string())
+    }
+}
+impl From<accounts::Error> for tonic::Status {
+    fn from(err: accounts::Error) -> Self {
+        use proto::account_error::Kind;
+        use tonic::Code;
+
+        let (code, error) = match &err {
+            accounts::Error::InsufficientBalance { have, need } => {
+                let (have, have_high) = proto_convert::proto_amount(*have);
+                let (need, need_high) = proto_convert::proto_amount(*need);
+
+                (
+                    Code::FailedPrecondition,
+                    proto::AccountError {
+                        kind: Kind::InsufficientBalance as i32,
+                        have,
+                        need,
+                        have_high,
+                        need_high,
+                        ..Default::default()
+                    },
+                )
+            }
+            accounts::Error::SequenceAlreadyUsed { sequence, last } => (
+                Code::AlreadyExists,
+                proto::AccountError {
+                    kind: Kind::SequenceAlreadyUsed as i32,
+                    sequence: *sequence,
+                    last_sequence: *last,
+                    ..Default::default()
+                },
+            ),
+            accounts::Error::SequenceGap { sequence, last } => (
+                Code::FailedPrecondition,
+                proto::AccountError {
+                    kind: Kind::SequenceGap as i32,
+                    sequence: *sequence,
+                    last_sequence: *last,
+                    ..Default::default()
+                },
+            ),
+            accounts::Error::UnknownAccount { .. } => (
+                Code::NotFound,
+                proto::AccountError {
+                    kind: Kind::UnknownAccount as i32,
+                    ..Default::default()
+                },
+            ),
+            accounts::Error::BalanceOverflow => (
+                Code::OutOfRange,
+                proto::AccountError {
+                    kind: Kind::BalanceOverflow as i32,
+                    ..Default::default()
+                },
+            ),
+            accounts::Error::NameTaken { .. } => (
+                Code::AlreadyExists,
+                proto::AccountError {
+                    kind: Kind::NameTaken as i32,
+                    ..Default::default()
+                },
+            ),
+            accounts::Error::UnknownAsset { .. } => (
+                Code::NotFound,
+                proto::AccountError {
+                    kind: Kind::UnknownAsset as i32,
+                    ..Default::default()
+                },
+            ),
+            accounts::Error::AssetTaken { .. } => (
+                Code::AlreadyExists,
+                proto::AccountError {
+                    kind: Kind::AssetTaken as i32,
+                    ..Default::default()
+                },
+            ),
+            accounts::Error::InvalidAsset { .. } => return Self::invalid_argument(err.to_string()),
+            accounts::Error::AlreadyApplied { .. } => return Self::already_exists(err.to_string()),
+            accounts::Error::InvalidWindow { .. } => {
+                return Self::invalid_argument(err.to_string())
+            }
+            accounts::Error::TransferOverflow => (
+                Code::InvalidArgument,
+                proto::AccountError {
+                    kind: Kind::TransferOverflow as i32,
+                    ..Default::default()
+                },
+            ),
+            accounts::Error::Bridge { .. } => return Self::failed_precondition(err.to_string()),
+            accounts::Error::Storage { .. }
+            | accounts::Error::GoneOnSend
+            | accounts::Error::GoneOnRecv => return Self::internal(err.to_string()),
+        };
+
+        proto_convert::account_status(code, err.to_string(), error)
+    }
+}
+impl From<recent_transactions::Error> for tonic::Status {
+    fn from(err: recent_transactions::Error) -> Self {
+        Self::invalid_argument(err.to_string())
+    }
+}
+impl From<rejected_transactions::Error> for tonic::Status {
+    fn from(err: rejected_transactions::Error) -> Self {
+        Self::internal(err.to_string())
+    }
+}
+impl From<history::Error> for tonic::Status {
+    fn from(err: history::Error) -> Self {
+        Self::internal(err.to_string())
+    }
+}
+impl From<evidence::Error> for tonic::Status {
+    fn from(err: evidence::Error) -> Self {
+        Self::internal(err.to_string())
+    }
+}
+impl From<peer_statuses::Error> for tonic::Status {
+    fn from(err: peer_statuses::Error) -> Self {
+        Self::internal(err.to_string())
+    }
+}
+impl From<challenges::Error> for tonic::Status {
+    fn from(err: challenges::Error) -> Self {
+        Self::internal(err.to_string())
+    }
+}
+impl From<rate_limit::Error> for tonic::Status {
+    fn from(err: rate_limit::Error) -> Self {
+        Self::internal(err.to_string())
+    }
+}
+
+impl From<transaction_statuses::Error> for tonic::Status {
+    fn from(err: transaction_statuses::Error) -> Self {
+        Self::internal(err.to_string())
+    }
+}
+
+impl From<submissions::Error> for tonic::Status {
+    fn from(err: submissions::Error) -> Self {
+        Self::internal(err.to_string())
+    }
+}
+
+/// Refuse a `value` out of `[0, max)`
+fn ensure_fraction(name: &'static str, value: f64, max: f64) -> Result<(), Error> {
+    ensure!(
+        (0.0..max).contains(&value),
+        ServiceFraction { name, value, max }
+    );
+
+    Ok(())
+}
+
+fn fingerprint(key: &sign::PublicKey) -> String {
+    super::fingerprint(&key.to_string()).to_owned()
+}
+
+/// Return the [`Encoding`] announced by the client, defaulting to bincode
+fn encoding<T>(request: &tonic::Request<T>) -> Result<Encoding, ProtoError> {
+    match request.metadata().get(codec::METADATA_KEY) {
+        None => Ok(Encoding::default()),
+        Some(value) => value
+            .to_str()
+            .unwrap_or_default()
+            .parse()
+            .context(UnknownEncoding),
+    }
+}
+
+/// Read a public key given as a typed key, as encoded bytes or as an [`Address`]
+///
+/// If more than one is given, they have to match.
+fn public_key(
+    encoding: Encoding,
+    typed: Option<&proto::PublicKey>,
+    bytes: &[u8],
+    address: &str,
+) -> Result<Box<sign::PublicKey>, ProtoError> {
+    let mut keys = Vec::with_capacity(3);
+
+    if let Some(typed) = typed {
+        keys.push(proto_convert::public_key(typed).context(Convert)?);
+    }
+    if !address.is_empty() {
+        keys.push(
+            Address::parse_with_prefix(address::DEFAULT_PREFIX, address)
+                .context(InvalidAddress)?
+                .into(),
+        );
+    }
+    if !bytes.is_empty() || keys.is_empty() {
+        let from_bytes: Box<sign::PublicKey> = encoding.decode(bytes).context(Deserialize)?;
+        validation::exact_size(encoding, "public key", &from_bytes, bytes).context(InvalidSize)?;
+        keys.push(*from_bytes);
+    }
+
+    let key = keys[0];
+    ensure!(keys.iter().all(|other| *other == key), ConflictingKeys);
+
+    Ok(Box::new(key))
+}
+
+/// Whether `target` is a module path, such as `contagion` or `at2_node::server`
+fn is_valid_log_target(target: &str) -> bool {
+    target
+        .split("::")
+        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+/// Read a signature given as a typed signature or as encoded bytes, preferring
+/// the former
+fn signature(
+    encoding: Encoding,
+    typed: Option<&proto::Signature>,
+    bytes: &[u8],
+) -> Result<sign::Signature, ProtoError> {
+    match typed {
+        Some(typed) => proto_convert::signature(typed).context(Convert),
+        None => {
+            let signature = encoding.decode(bytes).context(Deserialize)?;
+            validation::exact_size(encoding, "signature", &signature, bytes)
+                .context(InvalidSize)?;
+
+            Ok(signature)
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl proto::at2_server::At2 for Service {
+    async fn send_asset(
+        &self,
+        request: tonic::Request<proto::SendAssetRequest>,
+    ) -> Result<tonic::Response<proto::SendAssetReply>, tonic::Status> {
+        if let Some(addr) = request.remote_addr() {
+            self.limit(rate_limit::Key::Peer(addr.ip())).await?;
+        }
+
+        let encoding = encoding(&request)?;
+        let message = request.into_inner();
+        let received_at = chrono::Utc::now().timestamp_millis() as u64;
+
+        let transaction = SignedTransaction {
+            sender: *public_key(
+                encoding,
+                message.typed_sender.as_ref(),
+                &message.sender,
+                &message.sender_address,
+            )?,
+            sequence: message.sequence,
+            recipient: *public_key(
+                encoding,
+                message.typed_recipient.as_ref(),
+                &message.recipient,
+                &message.recipient_address,
+            )?,
+            amount: proto_convert::amount(message.amount, message.amount_high),
+            signature: signature(
+                encoding,
+                message.typed_signature.as_ref(),
+                &message.signature,
+            )?,
+            memo: Some(message.memo).filter(|memo| !memo.is_empty()),
+            fee: proto_convert::amount(message.fee, message.fee_high),
+            asset: Some(message.asset).filter(|asset| !asset.is_empty()),
+            outputs: message
+                .outputs
+                .iter()
+                .map(|output| {
+                    Ok(Output {
+                        recipient: *public_key(
+                            encoding,
+                            output.recipient.as_ref(),
+                            &[],
+                            &output.recipient_address,
+                        )?,
+                        amount: proto_convert::amount(output.amount, output.amount_high),
+                    })
+                })
+                .collect::<Result<_, ProtoError>>()?,
+        };
+        let statement = ReceiptStatement {
+            sender: transaction.sender,
+            sequence: transaction.sequence,
+            recipient: transaction.recipient,
+            amount: transaction.amount,
+            received_at,
+            asset: transaction.asset.clone(),
+        };
+
+        self.submit(transaction).await?;
+
+        let receipt = if message.with_receipt {
+            Some(self.receipt(statement)?)
+        } else {
+            None
+        };
+
+        Ok(Response::new(proto::SendAssetReply { receipt }))
+    }
+
+    async fn get_last_sequence(
+        &self,
+        request: tonic::Request<proto::GetLastSequenceRequest>,
+    ) -> Result<tonic::Response<proto::GetLastSequenceReply>, tonic::Status> {
+        let encoding = encoding(&request)?;
+        let message = request.get_ref();
+
+        let sender = public_key(
+            encoding,
+            message.typed_sender.as_ref(),
+            &message.sender,
+            &message.sender_address,
+        )?;
+        self.authorize(&request, &sender).await?;
+
+        let versioned = self.accounts.get_versioned(sender).await?;
+        if versioned.version <= message.if_version_newer_than {
+            return Ok(Response::new(proto::GetLastSequenceReply {
+                version: versioned.version,
+                not_modified: true,
+                ..Default::default()
+            }));
+        }
+
+        Ok(Response::new(proto::GetLastSequenceReply {
+            sequence: versioned.last_sequence,
+            version: versioned.version,
+            not_modified: false,
+        }))
+    }
+
+    async fn get_balance(
+        &self,
+        request: tonic::Request<proto::GetBalanceRequest>,
+    ) -> Result<tonic::Response<proto::GetBalanceReply>, tonic::Status> {
+        let encoding = encoding(&request)?;
+        let message = request.get_ref();
+
+        let sender = public_key(
+            encoding,
+            message.typed_sender.as_ref(),
+            &message.sender,
+            &message.sender_address,
+        )?;
+        self.authorize(&request, &sender).await?;
+
+        if !message.asset.is_empty() {
+            let balance = self
+                .accounts
+                .get_asset_balance(sender, message.asset.clone())
+                .await?;
+            let (amount, amount_high) = proto_convert::proto_amount(balance);
+
+            return Ok(Response::new(proto::GetBalanceReply {
+                amount,
+                amount_high,
+                ..Default::default()
+            }));
+        }
+
+        let versioned = self.accounts.get_versioned(sender).await?;
+        if versioned.version <= message.if_version_newer_than {
+            return Ok(Response::new(proto::GetBalanceReply {
+                version: versioned.version,
+                not_modified: true,
+                ..Default::default()
+            }));
+        }
+
+        let (amount, amount_high) = proto_convert::proto_amount(versioned.balance);
+
+        Ok(Response::new(proto::GetBalanceReply {
+            amount,
+            amount_high,
+            version: versioned.version,
+            not_modified: false,
+        }))
+    }
+
+    async fn get_balances(
+        &self,
+        request: tonic::Request<proto::GetBalancesRequest>,
+    ) -> Result<tonic::Response<proto::GetBalancesReply>, tonic::Status> {
+        use proto::get_balances_reply::Balance;
+
+        let accounts = request
+            .get_ref()
+            .accounts
+            .iter()
+            .map(|account| proto_convert::public_key(account).context(Convert))
+            .collect::<Result<Vec<_>, _>>()?;
+        if accounts.len() > BALANCES_MAX_LEN {
+            return Err(tonic::Status::invalid_argument("too many accounts"));
+        }
+
+        let mut balances = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            self.authorize(&request, &account).await?;
+
+            let (balance, last_sequence) = self
+                .accounts
+                .get_balance_and_sequence(Box::new(account))
+                .await?;
+            let (balance, balance_high) = proto_convert::proto_amount(balance);
+            balances.push(Balance {
+                account: Some(proto_convert::proto_public_key(&account)),
+                balance,
+                balance_high,
+                last_sequence,
+            });
+        }
+
+        Ok(Response::new(proto::GetBalancesReply { balances }))
+    }
+
+    async fn get_balance_proof(
+        &self,
+        request: tonic::Request<proto::GetBalanceProofRequest>,
+    ) -> Result<tonic::Response<proto::GetBalanceProofReply>, tonic::Status> {
+        use proto::get_balance_proof_reply::Attestation;
+
+        let encoding = encoding(&request)?;
+        let message = request.get_ref();
+
+        let account = public_key(
+            encoding,
+            message.account.as_ref(),
+            &[],
+            &message.account_address,
+        )?;
+        self.authorize(&request, &account).await?;
+        // peers would ask the node to prove owning the account
+        if message.aggregate && self.challenges.is_some() {
+            return Err(tonic::Status::failed_precondition(
+                "reads are authenticated, ask each node for its attestation",
+            ));
+        }
+
+        let (balance, last_sequence) = self
+            .accounts
+            .get_balance_and_sequence(account.clone())
+            .await?;
+
+        let statement = BalanceStatement {
+            account: *account,
+            balance,
+            last_sequence,
+            epoch: crate::current_epoch(),
+        };
+        let mut attestations = vec![BalanceAttestation {
+            node: self.keypair.public(),
+            signature: self.keypair.sign(&statement).expect("sign failed"),
+        }];
+        if message.aggregate {
+            attestations.extend(self.peer_attestations(&statement).await);
+        }
+
+        let (balance, balance_high) = proto_convert::proto_amount(balance);
+
+        Ok(Response::new(proto::GetBalanceProofReply {
+            account: Some(proto_convert::proto_public_key(&statement.account)),
+            balance,
+            balance_high,
+            last_sequence,
+            epoch: statement.epoch,
+            attestations: attestations
+                .iter()
+                .map(|attestation| {
+                    Ok(Attestation {
+                        node: Some(proto_convert::proto_public_key(&attestation.node)),
+                        signature: Some(
+                            proto_convert::proto_signature(&attestation.signature)
+                                .context(Convert)?,
+                        ),
+                    })
+                })
+                .collect::<Result<_, ProtoError>>()?,
+        }))
+    }
+
+    async fn get_latest_transactions(
+        &self,
+        request: tonic::Request<proto::GetLatestTransactionsRequest>,
+    ) -> Result<tonic::Response<proto::GetLatestTransactionsReply>, tonic::Status> {
+        if self.challenges.is_some() {
+            return Err(tonic::Status::permission_denied("reads are authenticated"));
+        }
+        let encoding = encoding(&request)?;
+        let limit = match request.get_ref().limit as usize {
+            0 => recent_transactions::DEFAULT_LIMIT,
+            limit => limit,
+        };
+
+        Ok(Response::new(proto::GetLatestTransactionsReply {
+            transactions: self
+                .recent_transactions
+                .get_latest(limit)
+                .await?
+                .iter()
+                .map(|tx| processed_transaction(encoding, tx))
+                .collect::<Result<_, ProtoError>>()?,
+        }))
+    }
+
+    async fn get_account_history(
+        &self,
+        request: tonic::Request<proto::GetAccountHistoryRequest>,
+    ) -> Result<tonic::Response<proto::GetAccountHistoryReply>, tonic::Status> {
+        let encoding = encoding(&request)?;
+        let message = request.get_ref();
+
+        let account = public_key(
+            encoding,
+            message.typed_account.as_ref(),
+            &message.account,
+            &message.account_address,
+        )?;
+        self.authorize(&request, &account).await?;
+
+        let limit = match message.limit as usize {
+            0 => history::PAGE_MAX_SIZE,
+            limit => limit,
+        };
+        let page = self
+            .history
+            .page(
+                account,
+                Some(message.cursor).filter(|cursor| *cursor != 0),
+                limit,
+            )
+            .await?;
+
+        Ok(Response::new(proto::GetAccountHistoryReply {
+            transactions: page
+                .transactions
+                .iter()
+                .map(|tx| processed_transaction(encoding, tx))
+                .collect::<Result<_, ProtoError>>()?,
+            next_cursor: page.next.unwrap_or_default(),
+        }))
+    }
+
+    async fn get_rejected_transactions(
+        &self,
+        request: tonic::Request<proto::GetRejectedTransactionsRequest>,
+    ) -> Result<tonic::Response<proto::GetRejectedTransactionsReply>, tonic::Status> {
+        let account = public_key(
+            Encoding::default(),
+            request.get_ref().account.as_ref(),
+            &[],
+            "",
+        )?;
+        self.authorize(&request, &account).await?;
+
+        let rejections = self.rejections.get(*account).await?;
+
+        Ok(Response::new(proto::GetRejectedTransactionsReply {
+            transactions: rejections.iter().map(rejected_transaction).collect(),
+        }))
+    }
+
+    type ExportHistoryStream = Subscription;
+
+    async fn export_history(
+        &self,
+        request: tonic::Request<proto::ExportHistoryRequest>,
+    ) -> Result<tonic::Response<Self::ExportHistoryStream>, tonic::Status> {
+        if self.challenges.is_some() {
+            return Err(tonic::Status::permission_denied("reads are authenticated"));
+        }
+        if let Some(addr) = request.remote_addr() {
+            self.limit(rate_limit::Key::Peer(addr.ip())).await?;
+        };
+
+        let encoding = encoding(&request)?;
+        let history = self.history.clone();
+        let (tx, rx) = mpsc::channel(DELIVERED_CAPACITY);
+
+        tokio::spawn(async move {
+            let mut after = None;
+
+            loop {
+                let page = match history.since(after, history::PAGE_MAX_SIZE).await {
+                    Ok(page) => page,
+                    Err(err) => {
+                        let _ = tx.send(Err(err.into())).await;
+                        break;
+                    }
+                };
+
+                for full in &page.transactions {
+                    let reply = processed_transaction(encoding, full).map_err(Into::into);
+
+                    // caller is gone
+                    if tx.send(reply).await.is_err() {
+                        return;
+                    }
+                }
+
+                match page.next {
+                    Some(next) => after = Some(next),
+                    None => break,
+                }
+            }
+        });
+
+        Ok(Response::new(Subscription(rx)))
+    }
+
+    async fn list_accounts(
+        &self,
+        request: tonic::Request<proto::ListAccountsRequest>,
+    ) -> Result<tonic::Response<proto::ListAccountsReply>, tonic::Status> {
+        use proto::get_balances_reply::Balance;
+
+        if self.challenges.is_some() {
+            return Err(tonic::Status::permission_denied("reads are authenticated"));
+        }
+        if let Some(addr) = request.remote_addr() {
+            self.limit(rate_limit::Key::Peer(addr.ip())).await?;
+        }
+        let message = request.into_inner();
+
+        let cursor = message
+            .cursor
+            .as_ref()
+            .map(|cursor| public_key(Encoding::default(), Some(cursor), &[], ""))
+            .transpose()?;
+        let limit = match message.limit as usize {
+            0 => accounts::PAGE_MAX_SIZE,
+            limit => limit,
+        };
+        let page = self.accounts.list(cursor, limit).await?;
+
+        Ok(Response::new(proto::ListAccountsReply {
+            accounts: page
+                .accounts
+                .iter()
+                .map(|(account, balance, last_sequence)| {
+                    let (balance, balance_high) = proto_convert::proto_amount(*balance);
+
+                    Balance {
+                        account: Some(proto_convert::proto_public_key(account)),
+                        balance,
+                        last_sequence: *last_sequence,
+                        balance_high,
+                    }
+                })
+                .collect(),
+            next_cursor: page.next.as_ref().map(proto_convert::proto_public_key),
+            total: page.total,
+        }))
+    }
+
+    async fn bridge_deposit(
+        &self,
+        request: tonic::Request<proto::BridgeDepositRequest>,
+    ) -> Result<tonic::Response<proto::BridgeDepositReply>, tonic::Status> {
+        self.check_writable()?;
+        if let Some(addr) = request.remote_addr() {
+            self.limit(rate_limit::Key::Peer(addr.ip())).await?;
+        }
+        let bridge = self
+            .bridge
+            .as_ref()
+            .ok_or_else(|| tonic::Status::failed_precondition("no bridge configured"))?;
+
+        let encoding = encoding(&request)?;
+        let message = request.into_inner();
+
+        let validator = public_key(
+            encoding,
+            message.typed_validator.as_ref(),
+            &message.validator,
+            "",
+        )?;
+        if !bridge.validators.contains(&validator) {
+            return Err(tonic::Status::permission_denied("unknown bridge validator"));
+        }
+
+        let operation = Operation::BridgeDeposit(BridgeDeposit {
+            source_network: message.source_network,
+            source_sequence: message.source_sequence,
+            user: *public_key(encoding, message.typed_user.as_ref(), &message.user, "")?,
+            amount: proto_convert::amount(message.amount, message.amount_high),
+        });
+        let signature = signature(
+            encoding,
+            message.typed_signature.as_ref(),
+            &message.signature,
+        )?;
+        if signature.verify(&operation, &validator).is_err() {
+            return Err(tonic::Status::unauthenticated("invalid signature"));
+        }
+        self.check_sender(&validator, message.sequence).await?;
+
+        self.statuses
+            .put(
+                *validator,
+                message.sequence,
+                transaction_statuses::Status::Pending,
+            )
+            .await?;
+
+        // counted on delivery, so that every node mints it
+        self.broadcast(sieve::Payload::new(
+            *validator,
+            message.sequence,
+            operation,
+            signature,
+        ))
+        .await?;
+
+        Ok(Response::new(proto::BridgeDepositReply { minted: false }))
+    }
+
+    async fn get_transaction_status(
+        &self,
+        request: tonic::Request<proto::GetTransactionStatusRequest>,
+    ) -> Result<tonic::Response<proto::GetTransactionStatusReply>, tonic::Status> {
+        use proto::get_transaction_status_reply::Status;
+
+        let encoding = encoding(&request)?;
+        let message = request.get_ref();
+
+        let sender = public_key(
+            encoding,
+            message.typed_sender.as_ref(),
+            &message.sender,
+            &message.sender_address,
+        )?;
+        self.authorize(&request, &sender).await?;
+
+        let (status, reason) = match self.statuses.get(*sender, message.sequence).await? {
+            None => (Status::Unknown, String::new()),
+            Some(transaction_statuses::Status::Pending) => (Status::Pending, String::new()),
+            Some(transaction_statuses::Status::Delivered) => (Status::Delivered, String::new()),
+            Some(transaction_statuses::Status::Rejected(reason)) => (Status::Rejected, reason),
+        };
+
+        Ok(Response::new(proto::GetTransactionStatusReply {
+            status: status as i32,
+            reason,
+        }))
+    }
+
+    type SubscribeTransactionsStream = Subscription;
+
+    async fn subscribe_transactions(
+        &self,
+        request: tonic::Request<proto::SubscribeTransactionsRequest>,
+    ) -> Result<tonic::Response<Self::SubscribeTransactionsStream>, tonic::Status> {
+        if self.challenges.is_some() {
+            return Err(tonic::Status::permission_denied("reads are authenticated"));
+        }
+        let encoding = encoding(&request)?;
+        let mut delivered = self.subscribe();
+        let (tx, rx) = mpsc::channel(DELIVERED_CAPACITY);
+
+        tokio::spawn(async move {
+            loop {
+                let reply = match delivered.recv().await {
+                    Ok(full) => processed_transaction(encoding, &full).map_err(Into::into),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("subscription lagged, skipped {} transactions", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                // subscriber is gone
+                if tx.send(reply).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(Subscription(rx)))
+    }
+
+    async fn register_name(
+        &self,
+        request: tonic::Request<proto::RegisterNameRequest>,
+    ) -> Result<tonic::Response<proto::RegisterNameReply>, tonic::Status> {
+        self.check_writable()?;
+        if let Some(addr) = request.remote_addr() {
+            self.limit(rate_limit::Key::Peer(addr.ip())).await?;
+        }
+        let encoding = encoding(&request)?;
+        let message = request.into_inner();
+
+        if !crate::is_valid_name(&message.name) {
+            return Err(tonic::Status::invalid_argument("invalid name"));
+        }
+
+        let sender = public_key(
+            encoding,
+            message.typed_sender.as_ref(),
+            &message.sender,
+            &message.sender_address,
+        )?;
+        let operation = Operation::RegisterName(message.name);
+        let signature = signature(
+            encoding,
+            message.typed_signature.as_ref(),
+            &message.signature,
+        )?;
+        if signature.verify(&operation, &sender).is_err() {
+            return Err(tonic::Status::unauthenticated("invalid signature"));
+        }
+        self.check_sender(&sender, message.sequence).await?;
+
+        self.broadcast(sieve::Payload::new(
+            *sender,
+            message.sequence,
+            operation,
+            signature,
+        ))
+        .await?;
+
+        Ok(Response::new(proto::RegisterNameReply {}))
+    }
+
+    async fn set_sequence_window(
+        &self,
+        request: tonic::Request<proto::SetSequenceWindowRequest>,
+    ) -> Result<tonic::Response<proto::SetSequenceWindowReply>, tonic::Status> {
+        self.check_writable()?;
+        if let Some(addr) = request.remote_addr() {
+            self.limit(rate_limit::Key::Peer(addr.ip())).await?;
+        }
+        let message = request.into_inner();
+
+        if !(1..=crate::SEQUENCE_WINDOW_MAX_SIZE).contains(&message.size) {
+            return Err(accounts::Error::InvalidWindow { size: message.size }.into());
+        }
+
+        let sender = public_key(Encoding::default(), message.sender.as_ref(), &[], "")?;
+        let operation = Operation::SetSequenceWindow(message.size);
+        let signature = signature(Encoding::default(), message.signature.as_ref(), &[])?;
+        if signature.verify(&operation, &sender).is_err() {
+            return Err(tonic::Status::unauthenticated("invalid signature"));
+        }
+        self.check_sender(&sender, message.sequence).await?;
+
+        self.broadcast(sieve::Payload::new(
+            *sender,
+            message.sequence,
+            operation,
+            signature,
+        ))
+        .await?;
+
+        Ok(Response::new(proto::SetSequenceWindowReply {}))
+    }
+
+    async fn resolve_name(
+        &self,
+        request: tonic::Request<proto::ResolveNameRequest>,
+    ) -> Result<tonic::Response<proto::ResolveNameReply>, tonic::Status> {
+        let encoding = encoding(&request)?;
+
+        let public_key = self
+            .accounts
+            .resolve_name(request.into_inner().name)
+            .await?
+            .ok_or_else(|| tonic::Status::not_found("no such name"))?;
+
+        Ok(Response::new(proto::ResolveNameReply {
+            public_key: encoding.encode(&public_key).context(Serialize)?,
+            typed_public_key: Some(proto_convert::proto_public_key(&public_key)),
+        }))
+    }
+
+    async fn get_bridge_withdrawals(
+        &self,
+        request: tonic::Request<proto::GetBridgeWithdrawalsRequest>,
+    ) -> Result<tonic::Response<proto::GetBridgeWithdrawalsReply>, tonic::Status> {
+        // every user's withdrawals, as the history of the lock
+        if self.challenges.is_some() {
+            return Err(tonic::Status::permission_denied("reads are authenticated"));
+        }
+        let bridge = self
+            .bridge
+            .as_ref()
+            .ok_or_else(|| tonic::Status::failed_precondition("no bridge configured"))?;
+
+        let encoding = encoding(&request)?;
+        let message = request.get_ref();
+
+        let limit = match message.limit as usize {
+            0 => history::PAGE_MAX_SIZE,
+            limit => limit,
+        };
+        // the whole history of the lock, not to miss older withdrawals
+        let page = self
+            .history
+            .page(
+                Box::new(bridge.lock),
+                Some(message.cursor).filter(|cursor| *cursor != 0),
+                limit,
+            )
+            .await?;
+
+        Ok(Response::new(proto::GetBridgeWithdrawalsReply {
+            transactions: page
+                .transactions
+                .iter()
+                .filter(|tx| tx.recipient == bridge.lock)
+                .map(|tx| processed_transaction(encoding, tx))
+                .collect::<Result<_, ProtoError>>()?,
+            next_cursor: page.next.unwrap_or_default(),
+        }))
+    }
+
+    async fn mint(
+        &self,
+        request: tonic::Request<proto::MintRequest>,
+    ) -> Result<tonic::Response<proto::MintReply>, tonic::Status> {
+        self.check_writable()?;
+        if let Some(addr) = request.remote_addr() {
+            self.limit(rate_limit::Key::Peer(addr.ip())).await?;
+        }
+        let encoding = encoding(&request)?;
+        let message = request.into_inner();
+
+        let admin = public_key(encoding, message.typed_admin.as_ref(), &message.admin, "")?;
+        if !self.admins.contains(&admin) {
+            return Err(tonic::Status::permission_denied("unknown admin"));
+        }
+
+        let operation = Operation::Mint(Mint {
+            recipient: *public_key(
+                encoding,
+                message.typed_recipient.as_ref(),
+                &message.recipient,
+                &message.recipient_address,
+            )?,
+            amount: proto_convert::amount(message.amount, message.amount_high),
+            asset: Some(message.asset).filter(|asset| !asset.is_empty()),
+        });
+        let signature = signature(
+            encoding,
+            message.typed_signature.as_ref(),
+            &message.signature,
+        )?;
+        if signature.verify(&operation, &admin).is_err() {
+            return Err(tonic::Status::unauthenticated("invalid signature"));
+        }
+        self.check_sender(&admin, message.sequence).await?;
+
+        self.statuses
+            .put(
+                *admin,
+                message.sequence,
+                transaction_statuses::Status::Pending,
+            )
+            .await?;
+
+        self.broadcast(sieve::Payload::new(
+            *admin,
+            message.sequence,
+            operation,
+            signature,
+        ))
+        .await?;
+        debug!(
+            admin = %fingerprint(&admin),
+            sequence = message.sequence,
+            "broadcasted"
+        );
+
+        Ok(Response::new(proto::MintReply {}))
+    }
+
+    async fn register_asset(
+        &self,
+        request: tonic::Request<proto::RegisterAssetRequest>,
+    ) -> Result<tonic::Response<proto::RegisterAssetReply>, tonic::Status> {
+        self.check_writable()?;
+        if let Some(addr) = request.remote_addr() {
+            self.limit(rate_limit::Key::Peer(addr.ip())).await?;
+        }
+        let message = request.into_inner();
+
+        let admin = public_key(Encoding::default(), message.admin.as_ref(), &[], "")?;
+        if !self.admins.contains(&admin) {
+            return Err(tonic::Status::permission_denied("unknown admin"));
+        }
+
+        let asset = message.asset.unwrap_or_default();
+        if !crate::is_valid_asset_id(&asset.id) {
+            return Err(accounts::Error::InvalidAsset { asset: asset.id }.into());
+        }
+        let operation = Operation::RegisterAsset(Asset {
+            id: asset.id,
+            name: asset.name,
+        });
+        let signature = signature(Encoding::default(), message.signature.as_ref(), &[])?;
+        if signature.verify(&operation, &admin).is_err() {
+            return Err(tonic::Status::unauthenticated("invalid signature"));
+        }
+        self.check_sender(&admin, message.sequence).await?;
+
+        self.statuses
+            .put(
+                *admin,
+                message.sequence,
+                transaction_statuses::Status::Pending,
+            )
+            .await?;
+
+        self.broadcast(sieve::Payload::new(
+            *admin,
+            message.sequence,
+            operation,
+            signature,
+        ))
+        .await?;
+
+        Ok(Response::new(proto::RegisterAssetReply {}))
+    }
+
+    async fn get_assets(
+        &self,
+        _: tonic::Request<proto::GetAssetsRequest>,
+    ) -> Result<tonic::Response<proto::GetAssetsReply>, tonic::Status> {
+        let assets = self.accounts.get_assets().await?;
+
+        Ok(Response::new(proto::GetAssetsReply {
+            assets: assets
+                .into_iter()
+                .map(|asset| proto::Asset {
+                    id: asset.id,
+                    name: asset.name,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_node_info(
+        &self,
+        _: tonic::Request<proto::GetNodeInfoRequest>,
+    ) -> Result<tonic::Response<proto::GetNodeInfoReply>, tonic::Status> {
+        Ok(Response::new(proto::GetNodeInfoReply {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            min_protocol_version: version::MIN_PROTOCOL_VERSION,
+            protocol_version: version::PROTOCOL_VERSION,
+            chain_id: self.chain_id.clone(),
+            follower: self.follower.is_some(),
+            fees: self.fees.as_ref().map(|policy| {
+                let (flat, flat_high) = proto_convert::proto_amount(policy.fees.flat);
+
+                proto::Fees {
+                    account: Some(proto_convert::proto_public_key(&policy.account)),
+                    flat,
+                    basis_points: policy.fees.basis_points,
+                    flat_high,
+                }
+            }),
+        }))
+    }
+
+    async fn get_evidence(
+        &self,
+        request: tonic::Request<proto::GetEvidenceRequest>,
+    ) -> Result<tonic::Response<proto::GetEvidenceReply>, tonic::Status> {
+        use proto::get_evidence_reply::{Evidence, SignedOperation};
+
+        if self.challenges.is_some() {
+            return Err(tonic::Status::permission_denied("reads are authenticated"));
+        }
+        let encoding = encoding(&request)?;
+        let signed = |signed: &crate::SignedOperation| {
+            Ok::<_, ProtoError>(SignedOperation {
+                operation: encoding.encode(&signed.operation).context(Serialize)?,
+                signature: encoding.encode(&signed.signature).context(Serialize)?,
+                typed_signature: Some(
+                    proto_convert::proto_signature(&signed.signature).context(Convert)?,
+                ),
+            })
+        };
+
+        let evidence = self
+            .evidences
+            .get_all()
+            .await?
+            .iter()
+            .map(|evidence| {
+                Ok(Evidence {
+                    timestamp: evidence.timestamp.to_rfc3339(),
+                    sender: encoding.encode(&evidence.sender).context(Serialize)?,
+                    sequence: evidence.sequence,
+                    first: Some(signed(&evidence.first)?),
+                    second: Some(signed(&evidence.second)?),
+                    typed_sender: Some(proto_convert::proto_public_key(&evidence.sender)),
+                })
+            })
+            .collect::<Result<_, ProtoError>>()?;
+
+        Ok(Response::new(proto::GetEvidenceReply { evidence }))
+    }
+
+    async fn get_checkpoint(
+        &self,
+        request: tonic::Request<proto::GetCheckpointRequest>,
+    ) -> Result<tonic::Response<proto::GetCheckpointReply>, tonic::Status> {
+        let epoch = Some(request.get_ref().epoch).filter(|epoch| *epoch != 0);
+
+        let checkpoint = self
+            .accounts
+            .get_checkpoint(epoch)
+            .await?
+            .ok_or_else(|| tonic::Status::not_found("no such checkpoint"))?;
+
+        Ok(Response::new(proto::GetCheckpointReply {
+            epoch: checkpoint.epoch,
+            applied: checkpoint.applied,
+            hash: checkpoint.hash.to_vec(),
+            timestamp: checkpoint.timestamp.to_rfc3339(),
+        }))
+    }
+
+    async fn get_state_digest(
+        &self,
+        _: tonic::Request<proto::GetStateDigestRequest>,
+    ) -> Result<tonic::Response<proto::GetStateDigestReply>, tonic::Status> {
+        let (digest, applied) = self.accounts.get_state_digest().await?;
+
+        Ok(Response::new(proto::GetStateDigestReply {
+            digest: digest.to_bytes().to_vec(),
+            applied,
+        }))
+    }
+
+    async fn get_snapshot(
+        &self,
+        request: tonic::Request<proto::GetSnapshotRequest>,
+    ) -> Result<tonic::Response<proto::GetSnapshotReply>, tonic::Status> {
+        use proto::{get_balances_reply::Balance, get_snapshot_reply::AssetBalance};
+
+        if self.challenges.is_some() {
+            return Err(tonic::Status::permission_denied("reads are authenticated"));
+        }
+        if let Some(addr) = request.remote_addr() {
+            self.limit(rate_limit::Key::Peer(addr.ip())).await?;
+        }
+        let encoding = encoding(&request)?;
+
+        let snapshot = self.accounts.get_snapshot().await?;
+
+        Ok(Response::new(proto::GetSnapshotReply {
+            accounts: snapshot
+                .accounts
+                .iter()
+                .map(|(account, balance, last_sequence)| {
+                    let (balance, balance_high) = proto_convert::proto_amount(*balance);
+
+                    Balance {
+                        account: Some(proto_convert::proto_public_key(account)),
+                        balance,
+                        last_sequence: *last_sequence,
+                        balance_high,
+                    }
+                })
+                .collect(),
+            hash: snapshot.hash.to_vec(),
+            recent_transactions: self
+                .recent_transactions
+                .get_all()
+                .await?
+                .iter()
+                .map(|tx| processed_transaction(encoding, tx))
+                .collect::<Result<_, ProtoError>>()?,
+            assets: snapshot
+                .assets
+                .into_iter()
+                .map(|asset| proto::Asset {
+                    id: asset.id,
+                    name: asset.name,
+                })
+                .collect(),
+            asset_balances: snapshot
+                .asset_balances
+                .into_iter()
+                .map(|(asset, account, balance)| {
+                    let (balance, balance_high) = proto_convert::proto_amount(balance);
+
+                    AssetBalance {
+                        asset,
+                        account: Some(proto_convert::proto_public_key(&account)),
+                        balance,
+                        balance_high,
+                    }
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_challenge(
+        &self,
+        request: tonic::Request<proto::GetChallengeRequest>,
+    ) -> Result<tonic::Response<proto::GetChallengeReply>, tonic::Status> {
+        let challenges = self
+            .challenges
+            .as_ref()
+            .ok_or_else(|| tonic::Status::failed_precondition("reads are not authenticated"))?;
+        if let Some(addr) = request.remote_addr() {
+            self.limit(rate_limit::Key::Peer(addr.ip())).await?;
+        }
+
+        let account = public_key(
+            Encoding::default(),
+            request.get_ref().account.as_ref(),
+            &[],
+            "",
+        )?;
+
+        Ok(Response::new(proto::GetChallengeReply {
+            nonce: challenges.issue(*account).await?,
+            lifetime: challenges::LIFETIME.as_secs(),
+        }))
+    }
+
+    async fn set_log_level(
+        &self,
+        request: tonic::Request<proto::SetLogLevelRequest>,
+    ) -> Result<tonic::Response<proto::SetLogLevelReply>, tonic::Status> {
+        let message = request.into_inner();
+
+        let admin = public_key(Encoding::default(), message.admin.as_ref(), &[], "")?;
+        if *admin != self.keypair.public() && !self.admins.contains(&admin) {
+            return Err(tonic::Status::permission_denied("unknown admin"));
+        }
+
+        let signature = signature(Encoding::default(), message.signature.as_ref(), &[])?;
+        let change = LogLevel {
+            level: message.level,
+            target: message.target,
+            timestamp: message.timestamp,
+        };
+        if signature.verify(&change, &admin).is_err() {
+            return Err(tonic::Status::unauthenticated("invalid signature"));
+        }
+        let now = chrono::Utc::now().timestamp() as u64;
+        if now.max(change.timestamp) - now.min(change.timestamp) > LOG_LEVEL_MAX_SKEW.as_secs() {
+            return Err(tonic::Status::unauthenticated(
+                "signed too far from the node's time",
+            ));
+        }
+
+        let level = change
+            .level
+            .parse::<Level>()
+            .map_err(|_| tonic::Status::invalid_argument("unknown level"))?;
+        let target = Some(change.target.as_str()).filter(|target| !target.is_empty());
+        if matches!(target, Some(target) if !is_valid_log_target(target)) {
+            return Err(tonic::Status::invalid_argument("invalid target"));
+        }
+
+        let set_log_level = self
+            .set_log_level
+            .as_ref()
+            .ok_or_else(|| tonic::Status::failed_precondition("logs can't be changed"))?;
+        set_log_level(level, target).map_err(tonic::Status::internal)?;
+        info!(
+            admin = %fingerprint(&admin),
+            level = %level,
+            target = target.unwrap_or("*"),
+            "log level changed"
+        );
+
+        Ok(Response::new(proto::SetLogLevelReply {}))
+    }
+
+    async fn get_network_info(
+        &self,
+        _: tonic::Request<proto::GetNetworkInfoRequest>,
+    ) -> Result<tonic::Response<proto::GetNetworkInfoReply>, tonic::Status> {
+        use proto::get_network_info_reply::{Broadcast, Peer, PeerStatus};
+
+        let mut statuses = self.peer_statuses.get_all().await?;
+
+        let peers = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let status = statuses.remove(&node.address).unwrap_or_default();
+
+                Peer {
+                    address: node.address.clone(),
+                    network_public_key: node.public_key.encode_hex(),
+                    rpc: node.rpc.clone().unwrap_or_default(),
+                    status: match status.answering {
+                        None => PeerStatus::Unknown,
+                        Some(true) => PeerStatus::Answering,
+                        Some(false) => PeerStatus::Unreachable,
+                    } as i32,
+                    last_seen: status
+                        .last_seen
+                        .map(|last_seen| last_seen.to_rfc3339())
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        let protocol = &self.protocol;
+        let resolved = |size: Option<usize>| size.unwrap_or_default() as u32;
+
+        Ok(Response::new(proto::GetNetworkInfoReply {
+            node: self.node.clone(),
+            network_public_key: self.network_key.encode_hex(),
+            peers,
+            broadcast: Some(Broadcast {
+                batch_size: protocol.batch_size as u32,
+                batch_delay: protocol.batch_delay,
+                gossip_size: resolved(protocol.gossip_size),
+                echo_sample_size: resolved(protocol.echo_sample_size),
+                echo_threshold: resolved(protocol.echo_threshold),
+                ready_sample_size: resolved(protocol.ready_sample_size),
+                ready_threshold: resolved(protocol.ready_threshold),
+            }),
+        }))
+    }
+
+    async fn get_peer_stats(
+        &self,
+        _: tonic::Request<proto::GetPeerStatsRequest>,
+    ) -> Result<tonic::Response<proto::GetPeerStatsReply>, tonic::Status> {
+        use proto::get_peer_stats_reply::Peer;
+
+        let mut statuses = self.peer_statuses.get_all().await?;
+        let micros = |rtt: Option<Duration>| rtt.map_or(0, |rtt| rtt.as_micros() as u64);
+
+        let peers = self
+            .nodes
+            .iter()
+            .filter(|node| node.rpc.is_some())
+            .map(|node| {
+                let stats = statuses
+                    .remove(&node.address)
+                    .map(|status| status.stats)
+                    .unwrap_or_default();
+
+                Peer {
+                    address: node.address.clone(),
+                    messages_sent: stats.messages_sent,
+                    messages_received: stats.messages_received,
+                    bytes_sent: stats.bytes_sent,
+                    bytes_received: stats.bytes_received,
+                    rtt_last: micros(stats.rtt_last),
+                    rtt_min: micros(stats.rtt_min),
+                    rtt_mean: micros(stats.rtt_mean()),
+                    rtt_max: micros(stats.rtt_max),
+                }
+            })
+            .collect();
+
+        Ok(Response::new(proto::GetPeerStatsReply { peers }))
+    }
+}
+
+/// Classify why a transfer was refused
+fn rejection_reason(err: &ProcessTransactionError) -> Reason {
+    match err {
+        ProcessTransactionError::ProcessTxForAccounts { source } => match source {
+            accounts::Error::InsufficientBalance { .. } => Reason::InsufficientBalance,
+            accounts::Error::SequenceAlreadyUsed { .. } => Reason::SequenceAlreadyUsed,
+            accounts::Error::SequenceGap { .. } => Reason::SequenceGap,
+            accounts::Error::BalanceOverflow => Reason::BalanceOverflow,
+            accounts::Error::TransferOverflow => Reason::TransferOverflow,
+            accounts::Error::UnknownAsset { .. } => Reason::UnknownAsset,
+            _ => Reason::Other,
+        },
+        ProcessTransactionError::InvalidSignature => Reason::InvalidSignature,
+        ProcessTransactionError::InvalidMemo => Reason::InvalidMemo,
+        ProcessTransactionError::TooManyOutputs => Reason::TooManyOutputs,
+        ProcessTransactionError::FeeTooLow { .. } => Reason::FeeTooLow,
+        ProcessTransactionError::UnexpectedFee => Reason::UnexpectedFee,
+        _ => Reason::Other,
+    }
+}
+
+fn rejected_transaction(rejection: &Rejection) -> proto::RejectedTransaction {
+    use proto::rejected_transaction::Reason as ProtoReason;
+
+    let reason = match rejection.reason {
+        Reason::InvalidSignature => ProtoReason::InvalidSignature,
+        Reason::InsufficientBalance => ProtoReason::InsufficientBalance,
+        Reason::SequenceAlreadyUsed => ProtoReason::SequenceAlreadyUsed,
+        Reason::SequenceGap => ProtoReason::SequenceGap,
+        Reason::BalanceOverflow => ProtoReason::BalanceOverflow,
+        Reason::TransferOverflow => ProtoReason::TransferOverflow,
+        Reason::InvalidMemo => ProtoReason::InvalidMemo,
+        Reason::TooManyOutputs => ProtoReason::TooManyOutputs,
+        Reason::FeeTooLow => ProtoReason::FeeTooLow,
+        Reason::UnexpectedFee => ProtoReason::UnexpectedFee,
+        Reason::UnknownAsset => ProtoReason::UnknownAsset,
+        Reason::Other => ProtoReason::Other,
+    };
+    let (fee, fee_high) = proto_convert::proto_amount(rejection.fee);
+
+    proto::RejectedTransaction {
+        timestamp: rejection.timestamp.to_rfc3339(),
+        sender: Some(proto_convert::proto_public_key(&rejection.sender)),
+        sequence: rejection.sequence,
+        outputs: rejection
+            .outputs
+            .iter()
+            .map(|output| {
+                let (amount, amount_high) = proto_convert::proto_amount(output.amount);
+
+                proto::TransferOutput {
+                    recipient: Some(proto_convert::proto_public_key(&output.recipient)),
+                    amount,
+                    amount_high,
+                    ..Default::default()
+                }
+            })
+            .collect(),
+        fee,
+        fee_high,
+        reason: reason as i32,
+        message: rejection.message.clone(),
+        asset: rejection.asset.clone().unwrap_or_default(),
+    }
+}
+
+fn processed_transaction(
+    encoding: Encoding,
+    tx: &FullTransaction,
+) -> Result<proto::ProcessedTransaction, ProtoError> {
+    let (amount, amount_high) = proto_convert::proto_amount(tx.amount);
+    let (fee, fee_high) = proto_convert::proto_amount(tx.fee);
+
+    Ok(proto::ProcessedTransaction {
+        timestamp: tx.timestamp.to_rfc3339(),
+        sender: encoding.encode(&tx.sender).context(Serialize)?,
+        recipient: encoding.encode(&tx.recipient).context(Serialize)?,
+        amount,
+        sequence: tx.sequence,
+        memo: tx.memo.clone().unwrap_or_default(),
+        fee,
+        typed_sender: Some(proto_convert::proto_public_key(&tx.sender)),
+        typed_recipient: Some(proto_convert::proto_public_key(&tx.recipient)),
+        amount_high,
+        fee_high,
+        asset: tx.asset.clone().unwrap_or_default(),
+    })
+}
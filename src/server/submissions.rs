@@ -0,0 +1,177 @@
+//! Transactions submitted lately, so that a retried submission isn't
+//! broadcasted twice
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use drop::crypto::sign;
+use sha2::{Digest, Sha256};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+
+use crate::ThinTransaction;
+
+/// How long a submission is remembered
+pub const LIFETIME: Duration = Duration::from_secs(60);
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("gone on send"))]
+    GoneOnSend,
+    #[snafu(display("gone on recv"))]
+    GoneOnRecv,
+}
+
+/// How a submission compares to the remembered ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    /// First one for its sender and sequence, now remembered
+    New,
+    /// Same as a remembered one
+    Duplicate,
+    /// Differs from the remembered one with its sender and sequence
+    Conflict,
+}
+
+type Key = (sign::PublicKey, sieve::Sequence);
+type Hash = [u8; 32];
+
+#[derive(Debug)]
+enum Commands {
+    Claim {
+        key: Box<Key>,
+        hash: Hash,
+        resp: oneshot::Sender<Claim>,
+    },
+    Release {
+        key: Box<Key>,
+        resp: oneshot::Sender<()>,
+    },
+}
+
+#[derive(Clone)]
+pub struct Submissions {
+    agent: mpsc::Sender<Commands>,
+}
+
+/// Tokio agent owning the submissions.
+/// The only way to interacte with it is to use [`Submissions`].
+struct SubmissionsHandler {
+    /// What was submitted, and when it expires
+    hashes: HashMap<Key, (Hash, Instant)>,
+    /// Keys in order of submission
+    order: VecDeque<(Key, Instant)>,
+}
+
+impl Submissions {
+    pub fn new() -> Self {
+        Self {
+            agent: SubmissionsHandler {
+                hashes: HashMap::new(),
+                order: VecDeque::new(),
+            }
+            .spawn(),
+        }
+    }
+
+    /// Remember the transfer of `sender` at `sequence`, unless one already is
+    pub async fn claim(
+        &self,
+        sender: sign::PublicKey,
+        sequence: sieve::Sequence,
+        thin: &ThinTransaction,
+    ) -> Result<Claim, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Claim {
+                key: Box::new((sender, sequence)),
+                hash: hash(thin),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// Forget the claim of `sender` at `sequence`, such as when it couldn't be
+    /// broadcasted, so that it can be submitted again
+    pub async fn release(
+        &self,
+        sender: sign::PublicKey,
+        sequence: sieve::Sequence,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Release {
+                key: Box::new((sender, sequence)),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+}
+
+impl SubmissionsHandler {
+    fn spawn(mut self) -> mpsc::Sender<Commands> {
+        let (tx, mut rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            while let Some(cmd) = rx.recv().await {
+                self.expire(Instant::now());
+
+                match cmd {
+                    Commands::Claim { key, hash, resp } => {
+                        let claim = match self.hashes.get(&*key) {
+                            Some((claimed, _)) if *claimed == hash => Claim::Duplicate,
+                            Some(_) => Claim::Conflict,
+                            None => {
+                                let expiry = Instant::now() + LIFETIME;
+                                self.hashes.insert(*key, (hash, expiry));
+                                self.order.push_back((*key, expiry));
+                                Claim::New
+                            }
+                        };
+
+                        let _ = resp.send(claim);
+                    }
+                    Commands::Release { key, resp } => {
+                        // its place in the order is skipped once expired
+                        self.hashes.remove(&*key);
+
+                        let _ = resp.send(());
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+
+    /// Forget the submissions expired at `now`
+    fn expire(&mut self, now: Instant) {
+        while matches!(self.order.front(), Some((_, expiry)) if *expiry <= now) {
+            let (key, expiry) = self.order.pop_front().expect("front is some");
+
+            // unless released then claimed again since
+            if matches!(self.hashes.get(&key), Some((_, claimed)) if *claimed == expiry) {
+                self.hashes.remove(&key);
+            }
+        }
+    }
+}
+
+/// SHA-256 of what the sender signed
+fn hash(thin: &ThinTransaction) -> Hash {
+    let encoded = bincode::serialize(thin).expect("transactions are serializable");
+
+    Sha256::digest(&encoded).into()
+}
@@ -0,0 +1,135 @@
+//! Copy the accounts of the peers when starting without any
+//!
+//! Each peer with an `rpc` address is asked for a snapshot of its accounts and
+//! assets, checked against the hash it announces. Once enough peers agree on the same
+//! hash, one of their snapshots is restored, along with its recent
+//! transactions. Transactions delivered in the meantime are only seen by the
+//! peers, as contagion doesn't replay past batches.
+
+use std::{collections::HashMap, time::Duration};
+
+use futures::future;
+use snafu::{ResultExt, Snafu};
+use tracing::{debug, info, warn};
+
+use super::{
+    accounts::{self, Accounts},
+    config,
+    recent_transactions::{self, RecentTransactions},
+};
+use crate::client::{self, Client};
+
+/// How many times the peers are asked before giving up
+const ROUNDS: usize = 5;
+/// How long to wait between two rounds
+const ROUND_DELAY: Duration = Duration::from_secs(1);
+/// How long a peer has to answer
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("accounts: {}", source))]
+    Accounts { source: accounts::Error },
+    #[snafu(display("recent transactions: {}", source))]
+    Recent { source: recent_transactions::Error },
+    #[snafu(display("no {} peers agreed on the accounts", quorum))]
+    NoQuorum { quorum: usize },
+}
+
+/// Restore the accounts agreed on by `quorum` of the `nodes`, unless some
+/// accounts were already modified
+pub async fn catch_up(
+    nodes: &[config::Node],
+    quorum: usize,
+    accounts: &Accounts,
+    recent_transactions: &RecentTransactions,
+) -> Result<(), Error> {
+    if !accounts
+        .get_snapshot()
+        .await
+        .context(Accounts)?
+        .accounts
+        .is_empty()
+    {
+        debug!("sync: accounts already known, not asking the peers");
+        return Ok(());
+    }
+
+    for round in 0..ROUNDS {
+        if round > 0 {
+            tokio::time::sleep(ROUND_DELAY).await;
+        }
+
+        if let Some(snapshot) = agreed(nodes, quorum).await? {
+            info!(
+                "sync: restoring {} accounts, {} assets and {} recent transactions",
+                snapshot.accounts.len(),
+                snapshot.assets.len(),
+                snapshot.recent_transactions.len()
+            );
+
+            accounts
+                .restore(
+                    accounts::Snapshot::new(
+                        snapshot.accounts,
+                        snapshot.assets,
+                        snapshot.asset_balances,
+                    )
+                    .context(Accounts)?,
+                )
+                .await
+                .context(Accounts)?;
+            for full in snapshot.recent_transactions {
+                recent_transactions.put(full).await.context(Recent)?;
+            }
+
+            return Ok(());
+        }
+
+        debug!("sync: round {}: no {} peers agreed", round, quorum);
+    }
+
+    NoQuorum { quorum }.fail()
+}
+
+/// Snapshot given by at least `quorum` peers, checked against its hash
+async fn agreed(nodes: &[config::Node], quorum: usize) -> Result<Option<client::Snapshot>, Error> {
+    let snapshots = future::join_all(nodes.iter().filter_map(config::Node::rpc_uri).map(
+        |uri| async move {
+            let mut client = Client::builder()
+                .overall_timeout(PEER_TIMEOUT)
+                .build(uri.parse().ok()?)
+                .ok()?;
+
+            client
+                .get_snapshot()
+                .await
+                .map_err(|err| debug!("sync: snapshot of {}: {}", uri, err))
+                .ok()
+        },
+    ))
+    .await;
+
+    let mut by_hash = HashMap::<_, Vec<_>>::new();
+    for snapshot in snapshots.into_iter().flatten() {
+        let hash = accounts::Snapshot::new(
+            snapshot.accounts.clone(),
+            snapshot.assets.clone(),
+            snapshot.asset_balances.clone(),
+        )
+        .context(Accounts)?
+        .hash;
+        if hash[..] != snapshot.hash[..] {
+            warn!("sync: a peer sent accounts not matching their hash");
+            continue;
+        }
+
+        by_hash.entry(hash).or_default().push(snapshot);
+    }
+
+    Ok(by_hash
+        .into_iter()
+        .map(|(_, snapshots)| snapshots)
+        .find(|snapshots| snapshots.len() >= quorum)
+        .and_then(|snapshots| snapshots.into_iter().next()))
+}
@@ -0,0 +1,151 @@
+use std::collections::{HashMap, VecDeque};
+
+use drop::crypto::sign;
+use tokio::sync::{mpsc, oneshot};
+
+/// How many transactions to remember, the oldest being forgotten first
+const TRACKED_MAX_SIZE: usize = 10_000;
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("gone on send"))]
+    GoneOnSend,
+    #[snafu(display("gone on recv"))]
+    GoneOnRecv,
+}
+
+/// What happened to a transaction, as seen by this node
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    /// Broadcasted, not yet delivered
+    Pending,
+    /// Delivered and applied to the accounts
+    Delivered,
+    /// Delivered but refused, with the reason
+    Rejected(String),
+}
+
+type Key = (sign::PublicKey, sieve::Sequence);
+
+#[derive(Debug)]
+enum Commands {
+    Put {
+        key: Box<Key>,
+        status: Status,
+        resp: oneshot::Sender<()>,
+    },
+    Get {
+        key: Box<Key>,
+        resp: oneshot::Sender<Option<Status>>,
+    },
+}
+
+#[derive(Clone)]
+pub struct TransactionStatuses {
+    agent: mpsc::Sender<Commands>,
+}
+
+/// Tokio agent owning the statuses.
+/// The only way to interacte with it is to use [`TransactionStatuses`].
+struct TransactionStatusesHandler {
+    statuses: HashMap<Key, Status>,
+    /// Keys in order of insertion
+    order: VecDeque<Key>,
+}
+
+impl TransactionStatuses {
+    pub fn new() -> Self {
+        Self {
+            agent: TransactionStatusesHandler::new().spawn(),
+        }
+    }
+
+    /// Record the status of the transaction of `sender` at `sequence`
+    ///
+    /// A delivered transaction stays so, as a conflicting one with the same
+    /// sequence can only be rejected afterwards.
+    pub async fn put(
+        &self,
+        sender: sign::PublicKey,
+        sequence: sieve::Sequence,
+        status: Status,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Put {
+                key: Box::new((sender, sequence)),
+                status,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// Return the status of the transaction, if still remembered
+    pub async fn get(
+        &self,
+        sender: sign::PublicKey,
+        sequence: sieve::Sequence,
+    ) -> Result<Option<Status>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Get {
+                key: Box::new((sender, sequence)),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+}
+
+impl TransactionStatusesHandler {
+    fn new() -> Self {
+        Self {
+            statuses: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn spawn(mut self) -> mpsc::Sender<Commands> {
+        let (tx, mut rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            while let Some(cmd) = rx.recv().await {
+                match cmd {
+                    Commands::Put { key, status, resp } => {
+                        self.put(*key, status);
+                        let _ = resp.send(());
+                    }
+                    Commands::Get { key, resp } => {
+                        let _ = resp.send(self.statuses.get(&key).cloned());
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+
+    fn put(&mut self, key: Key, status: Status) {
+        match self.statuses.get(&key) {
+            Some(Status::Delivered) => return,
+            Some(_) => {}
+            None => {
+                if self.order.len() == TRACKED_MAX_SIZE {
+                    if let Some(oldest) = self.order.pop_front() {
+                        self.statuses.remove(&oldest);
+                    }
+                }
+                self.order.push_back(key);
+            }
+        }
+
+        self.statuses.insert(key, status);
+    }
+}
@@ -0,0 +1,96 @@
+//! Checks of the submitted transfers, before they are broadcasted
+//!
+//! Each refusal names what is wrong, to be returned as an `InvalidArgument`
+//! rather than failing further down. Delivered transfers aren't checked
+//! against the configurable rules, as the nodes might differ on them.
+
+use serde::Serialize;
+use snafu::{ensure, ResultExt, Snafu};
+
+use super::config::ConfigValidation;
+use crate::{
+    codec::{self, Codec, Encoding},
+    is_valid_memo, SignedTransaction, MEMO_MAX_LEN, OUTPUTS_MAX_LEN,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{}: expected {} bytes, got {}", field, expected, size))]
+    Size {
+        field: &'static str,
+        expected: usize,
+        size: usize,
+    },
+    #[snafu(display("{}: {}", field, source))]
+    Encode {
+        field: &'static str,
+        source: codec::Error,
+    },
+    #[snafu(display("invalid memo: empty or longer than {} bytes", MEMO_MAX_LEN))]
+    InvalidMemo,
+    #[snafu(display("too many outputs: {}, at most {}", count, OUTPUTS_MAX_LEN))]
+    TooManyOutputs { count: usize },
+    #[snafu(display("zero amount"))]
+    ZeroAmount,
+    #[snafu(display("transfer to the sender"))]
+    SelfTransfer,
+}
+
+/// What a transfer has to follow, as configured
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    config: ConfigValidation,
+}
+
+impl Rules {
+    pub fn new(config: ConfigValidation) -> Self {
+        Self { config }
+    }
+
+    /// Refuse a `transaction` breaking the rules
+    pub fn check(&self, transaction: &SignedTransaction) -> Result<(), Error> {
+        if let Some(memo) = &transaction.memo {
+            ensure!(is_valid_memo(memo), InvalidMemo);
+        }
+        ensure!(
+            transaction.outputs.len() <= OUTPUTS_MAX_LEN,
+            TooManyOutputs {
+                count: transaction.outputs.len()
+            }
+        );
+
+        for output in transaction.thin().all_outputs() {
+            ensure!(
+                self.config.allow_zero_amount || output.amount != 0,
+                ZeroAmount
+            );
+            ensure!(
+                self.config.allow_self_transfer || output.recipient != transaction.sender,
+                SelfTransfer
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Refuse the `bytes` `value` was decoded from unless encoding it gives them
+/// back, such as when trailing bytes were ignored
+pub fn exact_size<T: Serialize + ?Sized>(
+    encoding: Encoding,
+    field: &'static str,
+    value: &T,
+    bytes: &[u8],
+) -> Result<(), Error> {
+    let expected = encoding.encode(value).context(Encode { field })?.len();
+    ensure!(
+        bytes.len() == expected,
+        Size {
+            field,
+            expected,
+            size: bytes.len()
+        }
+    );
+
+    Ok(())
+}
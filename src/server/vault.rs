@@ -0,0 +1,91 @@
+//! Fetch the node's keys from a HashiCorp Vault KV v2 secret
+//!
+//! The node cannot swap its keys while running, so a rotated secret is
+//! reported as an error, for the supervisor to restart the node.
+
+use std::{env, time::Duration};
+
+use serde::Deserialize;
+use serde_json::Value;
+use snafu::{ensure, ResultExt, Snafu};
+
+use super::config::{ConfigKeys, ConfigVault};
+
+const TOKEN_HEADER: &str = "X-Vault-Token";
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("token not found in ${}", name))]
+    NoToken { name: String },
+    #[snafu(display("request secret: {}", source))]
+    Request { source: reqwest::Error },
+    #[snafu(display("decode secret: {}", source))]
+    Decode { source: serde_json::Error },
+    #[snafu(display("secret rotated, restart to use it"))]
+    Rotated,
+}
+
+/// Body of a KV v2 read
+#[derive(Deserialize)]
+struct Secret {
+    data: SecretData,
+}
+
+#[derive(Deserialize)]
+struct SecretData {
+    data: Value,
+}
+
+async fn fetch_raw(config: &ConfigVault) -> Result<Value, Error> {
+    let token = env::var(&config.token_env).ok().context(NoToken {
+        name: &config.token_env,
+    })?;
+
+    let secret = reqwest::Client::new()
+        .get(format!(
+            "{}/v1/{}",
+            config.address.trim_end_matches('/'),
+            config.path.trim_start_matches('/')
+        ))
+        .header(TOKEN_HEADER, token)
+        .send()
+        .await
+        .context(Request)?
+        .error_for_status()
+        .context(Request)?
+        .json::<Secret>()
+        .await
+        .context(Request)?;
+
+    Ok(secret.data.data)
+}
+
+/// Secret as fetched, to [`watch`] for rotations
+pub struct Fetched {
+    pub keys: ConfigKeys,
+    pub raw: Value,
+}
+
+/// Fetch the keys stored in the secret
+pub async fn fetch(config: &ConfigVault) -> Result<Fetched, Error> {
+    let raw = fetch_raw(config).await?;
+
+    Ok(Fetched {
+        keys: serde_json::from_value(raw.clone()).context(Decode)?,
+        raw,
+    })
+}
+
+/// Poll the secret, failing once it changed from the `fetched` one
+pub async fn watch(config: ConfigVault, fetched: Value) -> Result<(), Error> {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.refresh));
+
+    loop {
+        interval.tick().await;
+
+        match fetch_raw(&config).await {
+            Ok(current) => ensure!(current == fetched, Rotated),
+            Err(err) => tracing::warn!("vault: {}", err),
+        }
+    }
+}
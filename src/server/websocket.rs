@@ -0,0 +1,211 @@
+//! [JSON-RPC](super::jsonrpc) over WebSocket, for browsers without gRPC-web
+//!
+//! Each text message is a call or a batch, with the methods of the HTTP
+//! endpoint, replied to by a text message. Outside of batches, two more
+//! methods are available:
+//! - `at2_subscribe`, returning a subscription id, after which every
+//!   delivered transaction is sent as an `at2_subscription` notification,
+//!   with `subscription` and `result`, the same event as the one streamed by
+//!   the [`json`](super::json) gateway
+//! - `at2_unsubscribe`, taking the `subscription` to stop
+
+use std::{collections::HashMap, future::Future, io, net::SocketAddr};
+
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use snafu::{ResultExt, Snafu};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::warn;
+
+use super::{
+    events::Event,
+    jsonrpc::{self, RpcError, RpcRequest, RpcResponse},
+    rpc,
+};
+
+/// How many messages can wait to be sent to a client
+const OUTGOING_CAPACITY: usize = 64;
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("bind: {}", source))]
+    Bind { source: io::Error },
+}
+
+#[derive(Deserialize)]
+struct UnsubscribeParams {
+    subscription: u64,
+}
+
+/// Serve JSON-RPC over WebSocket for `service` on `addr`, until `shutdown` resolves
+pub async fn serve(
+    addr: SocketAddr,
+    service: rpc::Service,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), Error> {
+    let listener = TcpListener::bind(addr).await.context(Bind)?;
+    futures::pin_mut!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => return Ok(()),
+            accepted = listener.accept() => match accepted {
+                Err(err) => warn!("websocket: accept: {}", err),
+                Ok((stream, _)) => {
+                    tokio::spawn(connection(service.clone(), stream));
+                }
+            },
+        }
+    }
+}
+
+/// Handle a client until it goes away
+async fn connection(service: rpc::Service, stream: TcpStream) {
+    let socket = match tokio_tungstenite::accept_async(stream).await {
+        Err(err) => {
+            warn!("websocket: handshake: {}", err);
+            return;
+        }
+        Ok(socket) => socket,
+    };
+    let (mut sink, mut incoming) = socket.split();
+
+    let (outgoing, mut to_send) = mpsc::channel(OUTGOING_CAPACITY);
+    let writer = tokio::spawn(async move {
+        while let Some(text) = to_send.recv().await {
+            if sink.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut subscriptions = Subscriptions {
+        service: service.clone(),
+        outgoing: outgoing.clone(),
+        next: 0,
+        running: HashMap::new(),
+    };
+
+    while let Some(Ok(message)) = incoming.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let reply = match serde_json::from_str::<Value>(&text) {
+            Err(err) => Some(serde_json::to_value(RpcResponse::new(
+                Value::Null,
+                Err(RpcError::new(jsonrpc::PARSE_ERROR, err)),
+            ))),
+            Ok(call) => match subscriptions.call(&call) {
+                Some(reply) => reply.map(serde_json::to_value),
+                None => jsonrpc::dispatch(&service, call).await,
+            },
+        };
+
+        let reply = match reply {
+            None => continue,
+            Some(reply) => reply.map(|reply| reply.to_string()).unwrap_or_default(),
+        };
+        if outgoing.send(reply).await.is_err() {
+            break;
+        }
+    }
+
+    subscriptions.running.values().for_each(JoinHandle::abort);
+    drop(subscriptions);
+    drop(outgoing);
+    let _ = writer.await;
+}
+
+/// Subscriptions of a single client
+struct Subscriptions {
+    service: rpc::Service,
+    outgoing: mpsc::Sender<String>,
+    next: u64,
+    running: HashMap<u64, JoinHandle<()>>,
+}
+
+impl Subscriptions {
+    /// Handle `call` if it is about subscriptions, returning `None` otherwise
+    ///
+    /// The inner `None` is for notifications, which aren't replied to.
+    fn call(&mut self, call: &Value) -> Option<Option<RpcResponse>> {
+        let request = match call.get("method").and_then(Value::as_str) {
+            Some("at2_subscribe") | Some("at2_unsubscribe") => {
+                serde_json::from_value::<RpcRequest>(call.clone()).ok()?
+            }
+            _ => return None,
+        };
+        if request.jsonrpc != jsonrpc::VERSION {
+            return None;
+        }
+
+        let ret = match request.method.as_str() {
+            "at2_subscribe" => Ok(Value::from(self.subscribe())),
+            _ => match serde_json::from_value::<UnsubscribeParams>(request.params) {
+                Err(err) => Err(RpcError::new(jsonrpc::INVALID_PARAMS, err)),
+                Ok(params) => Ok(Value::from(self.unsubscribe(params.subscription))),
+            },
+        };
+
+        Some(request.id.map(|id| RpcResponse::new(id, ret)))
+    }
+
+    fn subscribe(&mut self) -> u64 {
+        let subscription = self.next;
+        self.next += 1;
+
+        let mut delivered = self.service.subscribe();
+        let outgoing = self.outgoing.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let tx = match delivered.recv().await {
+                    Ok(tx) => tx,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "websocket: subscription lagged, skipped {} transactions",
+                            skipped
+                        );
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                let notification = json!({
+                    "jsonrpc": jsonrpc::VERSION,
+                    "method": "at2_subscription",
+                    "params": {
+                        "subscription": subscription,
+                        "result": Event::from(&tx),
+                    },
+                });
+                if outgoing.send(notification.to_string()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.running.insert(subscription, task);
+
+        subscription
+    }
+
+    /// Stop the subscription, returning whether it was running
+    fn unsubscribe(&mut self, subscription: u64) -> bool {
+        match self.running.remove(&subscription) {
+            None => false,
+            Some(task) => {
+                task.abort();
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,395 @@
+//! Run an AT2 network inside the current process, for testing
+//!
+//! Every node lives on the current tokio runtime and listens on localhost,
+//! so real consensus can be exercised without spawning the binaries.
+
+use std::{collections::BTreeMap, convert::TryInto, io, iter::repeat_with, net::SocketAddr};
+
+use drop::crypto::{key::exchange, sign};
+use futures::future;
+use snafu::{ResultExt, Snafu};
+use tokio::{net::TcpStream, task::JoinHandle};
+use tonic::transport::Server;
+use tracing::warn;
+
+use crate::{
+    client::{self, Client},
+    interchange,
+    proto::at2_server::At2Server,
+    server::{config, json, jsonrpc, request_id::RequestIds, rpc, websocket},
+    Fees,
+};
+
+/// Error generated when starting a [`Network`]
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// Finding a free local port
+    #[snafu(display("bind: {}", source))]
+    Bind {
+        /// Source of the error
+        source: io::Error,
+    },
+    /// Starting a node
+    #[snafu(display("start node: {}", source))]
+    StartNode {
+        /// Source of the error
+        source: rpc::Error,
+    },
+    /// Waiting for a node to accept connections
+    #[snafu(display("connect node: {}", source))]
+    ConnectNode {
+        /// Source of the error
+        source: io::Error,
+    },
+    /// Creating a client
+    #[snafu(display("client: {}", source))]
+    NewClient {
+        /// Source of the error
+        source: client::Error,
+    },
+}
+
+/// Nodes running in this process, stopped when dropped
+pub struct Network {
+    clients: Vec<Client>,
+    rpc_addresses: Vec<SocketAddr>,
+    gateways: Vec<Gateways>,
+    servers: Vec<JoinHandle<()>>,
+}
+
+/// Where a node serves its gateways, see [`Builder::gateways`]
+#[derive(Debug, Clone, Copy)]
+pub struct Gateways {
+    /// JSON gateway, along the Rosetta endpoints
+    pub json: SocketAddr,
+    /// JSON-RPC endpoint
+    pub jsonrpc: SocketAddr,
+    /// JSON-RPC over WebSocket
+    pub websocket: SocketAddr,
+}
+
+/// Configure a [`Network`] before starting it
+pub struct Builder {
+    size: usize,
+    chain_id: String,
+    protocol: config::ConfigProtocol,
+    admins: Vec<String>,
+    fees: Option<config::ConfigFees>,
+    authenticated_reads: bool,
+    genesis: BTreeMap<String, u64>,
+    followers: usize,
+    gateways: bool,
+    events: Option<config::ConfigEvents>,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            size: 3,
+            chain_id: interchange::DEFAULT_CHAIN_ID.to_owned(),
+            protocol: Default::default(),
+            admins: vec![],
+            fees: None,
+            authenticated_reads: false,
+            genesis: BTreeMap::new(),
+            followers: 0,
+            gateways: false,
+            events: None,
+        }
+    }
+}
+
+impl Builder {
+    /// Number of nodes, three by default
+    pub fn size(self, size: usize) -> Self {
+        Self { size, ..self }
+    }
+
+    /// Network the nodes belong to, [`interchange::DEFAULT_CHAIN_ID`] by default
+    pub fn chain_id(self, chain_id: impl Into<String>) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+            ..self
+        }
+    }
+
+    /// Tuning of the broadcast, shared by every node
+    pub fn protocol(self, protocol: config::ConfigProtocol) -> Self {
+        Self { protocol, ..self }
+    }
+
+    /// Allow `admin` to mint asset, on every node
+    pub fn admin(mut self, admin: &sign::PublicKey) -> Self {
+        self.admins.push(admin.to_string());
+        self
+    }
+
+    /// Charge `fees` on every transfer, paid to `account`
+    pub fn fees(self, account: &sign::PublicKey, fees: Fees) -> Self {
+        Self {
+            fees: Some(config::ConfigFees {
+                account: account.to_string(),
+                flat: fees
+                    .flat
+                    .try_into()
+                    .expect("flat fee is too large for the configuration"),
+                basis_points: fees.basis_points,
+            }),
+            ..self
+        }
+    }
+
+    /// Require callers to prove owning the accounts they read, on every node
+    pub fn authenticated_reads(self) -> Self {
+        Self {
+            authenticated_reads: true,
+            ..self
+        }
+    }
+
+    /// Give `account` a starting `balance`, on every node
+    pub fn genesis(mut self, account: &sign::PublicKey, balance: u64) -> Self {
+        self.genesis.insert(account.to_string(), balance);
+        self
+    }
+
+    /// Make the last `followers` nodes refuse writes, pointing to the first node
+    pub fn followers(self, followers: usize) -> Self {
+        Self { followers, ..self }
+    }
+
+    /// Also serve the JSON, JSON-RPC and WebSocket gateways on every node
+    pub fn gateways(self) -> Self {
+        Self {
+            gateways: true,
+            ..self
+        }
+    }
+
+    /// Publish the events of every node to `subject`, on the NATS server at `nats_url`
+    pub fn events(self, nats_url: impl Into<String>, subject: impl Into<String>) -> Self {
+        Self {
+            events: Some(config::ConfigEvents {
+                nats_url: nats_url.into(),
+                subject: subject.into(),
+            }),
+            ..self
+        }
+    }
+
+    /// Start the connected nodes, returning once each accepts clients
+    pub async fn start(self) -> Result<Network, Error> {
+        let Self {
+            size,
+            chain_id,
+            protocol,
+            admins,
+            fees,
+            authenticated_reads,
+            genesis,
+            followers,
+            gateways,
+            events,
+        } = self;
+
+        let keypairs = repeat_with(exchange::KeyPair::random)
+            .take(size)
+            .collect::<Vec<_>>();
+        let addresses = repeat_with(|| Ok((free_address()?, free_address()?)))
+            .take(size)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let nodes = keypairs
+            .iter()
+            .zip(&addresses)
+            .map(|(keypair, (node, rpc))| config::Node {
+                address: node.to_string(),
+                fallback_addresses: Vec::new(),
+                public_key: keypair.public().to_owned(),
+                rpc: Some(rpc.to_string()),
+            })
+            .collect::<Vec<_>>();
+
+        let services = future::try_join_all(keypairs.into_iter().zip(&addresses).enumerate().map(
+            |(pos, (keypair, (node, _)))| {
+                let peers = nodes
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != pos)
+                    .map(|(_, peer)| peer.clone())
+                    .collect();
+
+                rpc::Service::new(
+                    *node,
+                    keypair,
+                    sign::KeyPair::random(),
+                    rpc::Options {
+                        chain_id: chain_id.clone(),
+                        network: peers,
+                        protocol: protocol.clone(),
+                        fees: fees.clone(),
+                        admins: admins.clone(),
+                        genesis: genesis.clone(),
+                        authenticated_reads,
+                        events: events.clone(),
+                        ..Default::default()
+                    },
+                )
+            },
+        ))
+        .await
+        .context(StartNode)?;
+
+        let gateways = if gateways {
+            repeat_with(|| {
+                Ok(Gateways {
+                    json: free_address()?,
+                    jsonrpc: free_address()?,
+                    websocket: free_address()?,
+                })
+            })
+            .take(size)
+            .collect::<Result<Vec<_>, _>>()?
+        } else {
+            Vec::new()
+        };
+
+        let mut servers = Vec::new();
+        for (pos, (service, (_, rpc))) in services.into_iter().zip(&addresses).enumerate() {
+            // the first peer of the others is the first node
+            let service = if pos + followers >= size {
+                service.as_follower(None)
+            } else {
+                service
+            };
+
+            if let Some(gateways) = gateways.get(pos) {
+                servers.extend(serve_gateways(gateways, &service));
+            }
+
+            let server = Server::builder()
+                .add_service(RequestIds::new(At2Server::new(service)))
+                .serve(*rpc);
+            servers.push(tokio::spawn(async move {
+                if let Err(err) = server.await {
+                    warn!("testkit: rpc: {}", err);
+                }
+            }));
+        }
+
+        for gateway in &gateways {
+            for addr in &[gateway.json, gateway.jsonrpc, gateway.websocket] {
+                wait_until_connect(addr).await?;
+            }
+        }
+
+        let mut clients = Vec::with_capacity(size);
+        for (_, rpc) in &addresses {
+            wait_until_connect(rpc).await?;
+
+            clients.push(
+                Client::new(
+                    format!("http://{}", rpc)
+                        .parse()
+                        .expect("socket address is a valid uri"),
+                )
+                .context(NewClient)?,
+            );
+        }
+
+        Ok(Network {
+            clients,
+            rpc_addresses: addresses.into_iter().map(|(_, rpc)| rpc).collect(),
+            gateways,
+            servers,
+        })
+    }
+}
+
+impl Network {
+    /// Configure a network, to be started with [`Builder::start`]
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Start `size` connected nodes, returning once each accepts clients
+    pub async fn start(size: usize) -> Result<Self, Error> {
+        Self::builder().size(size).start().await
+    }
+
+    /// Clients connected to each node, in order
+    pub fn clients(&self) -> &[Client] {
+        &self.clients
+    }
+
+    /// Where each node serves its RPC, in order
+    pub fn rpc_addresses(&self) -> &[SocketAddr] {
+        &self.rpc_addresses
+    }
+
+    /// Where each node serves its gateways, in order, empty unless asked with
+    /// [`Builder::gateways`]
+    pub fn gateways(&self) -> &[Gateways] {
+        &self.gateways
+    }
+}
+
+impl Drop for Network {
+    fn drop(&mut self) {
+        self.servers.iter().for_each(JoinHandle::abort);
+    }
+}
+
+/// Serve the gateways of `service` until aborted
+fn serve_gateways(gateways: &Gateways, service: &rpc::Service) -> Vec<JoinHandle<()>> {
+    let json = json::serve(
+        gateways.json,
+        service.clone(),
+        config::DEFAULT_MAX_BODY_SIZE,
+        future::pending(),
+    );
+    let jsonrpc = jsonrpc::serve(
+        gateways.jsonrpc,
+        service.clone(),
+        config::DEFAULT_MAX_BODY_SIZE,
+        future::pending(),
+    );
+    let websocket = websocket::serve(gateways.websocket, service.clone(), future::pending());
+
+    vec![
+        tokio::spawn(async move {
+            if let Err(err) = json.await {
+                warn!("testkit: json: {}", err);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(err) = jsonrpc.await {
+                warn!("testkit: jsonrpc: {}", err);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(err) = websocket.await {
+                warn!("testkit: websocket: {}", err);
+            }
+        }),
+    ]
+}
+
+/// Reserve a local port, hoping that no one takes it before we do
+fn free_address() -> Result<SocketAddr, Error> {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .context(Bind)
+}
+
+async fn wait_until_connect(addr: &SocketAddr) -> Result<(), Error> {
+    loop {
+        match TcpStream::connect(addr).await {
+            Ok(_) => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                tokio::task::yield_now().await
+            }
+            Err(err) => return Err(err).context(ConnectNode),
+        }
+    }
+}
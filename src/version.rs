@@ -0,0 +1,48 @@
+//! Versioning of the protocol spoken between nodes and clients
+//!
+//! A node speaks every protocol version from [`MIN_PROTOCOL_VERSION`] to
+//! [`PROTOCOL_VERSION`]. Two parties interoperate on the highest version they
+//! both speak, which lets a network be upgraded one node at a time.
+
+/// Latest version of the protocol
+///
+/// 2. transfers carry an optional memo, changing what is signed
+/// 3. transfers carry further outputs, changing what is signed
+/// 4. transfers carry a fee, changing what is signed
+/// 5. amounts are 128 bits, changing what is signed
+/// 6. senders can set a sequence window, which older nodes can't decode
+/// 7. transfers and mints carry an asset, changing what is signed
+/// 8. bridge deposits are broadcasted operations, changing what is signed
+/// 9. receipts state the asset, and snapshots carry the assets, changing what
+///    is signed and hashed
+pub const PROTOCOL_VERSION: u32 = 9;
+/// Oldest version of the protocol still spoken
+pub const MIN_PROTOCOL_VERSION: u32 = 9;
+
+/// Versions spoken by a party
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolRange {
+    /// Oldest spoken version
+    pub min: u32,
+    /// Latest spoken version
+    pub max: u32,
+}
+
+impl ProtocolRange {
+    /// Versions spoken by this crate
+    pub const CURRENT: Self = Self {
+        min: MIN_PROTOCOL_VERSION,
+        max: PROTOCOL_VERSION,
+    };
+
+    /// Highest version spoken by both, if any
+    pub fn negotiate(&self, other: &Self) -> Option<u32> {
+        let common = self.max.min(other.max);
+
+        if common >= self.min.max(other.min) {
+            Some(common)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! Bindings for JavaScript, as built by `wasm-pack build -- --features wasm`
+//!
+//! Keys are given and returned as `Uint8Array`s of their raw bytes, amounts
+//! as `BigInt`s, and calls to the node return `Promise`s.
+//!
+//! ```js
+//! import { Client, KeyPair } from "at2-node";
+//!
+//! const client = new Client("http://127.0.0.1:3002");
+//! const keypair = new KeyPair();
+//! await client.sendAsset(keypair, 1, recipient, 10n);
+//! const balance = await client.getBalance(keypair.publicKey());
+//! ```
+
+use futures::StreamExt;
+use hex::{FromHex, ToHex};
+use js_sys::{BigInt, Function, Promise};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+
+use crate::{api::At2Api, client, interchange, proto, proto_convert, Amount, FullTransaction};
+use drop::crypto::sign;
+
+fn js_error(err: impl std::fmt::Display) -> JsValue {
+    js_sys::Error::new(&err.to_string()).into()
+}
+
+fn public_key(bytes: &[u8]) -> Result<sign::PublicKey, JsValue> {
+    proto_convert::public_key(&proto::PublicKey {
+        bytes: bytes.to_vec(),
+    })
+    .map_err(js_error)
+}
+
+/// Read an amount, refusing negative or too large ones
+fn amount(value: &BigInt) -> Result<Amount, JsValue> {
+    String::from(value.to_string(10).map_err(JsValue::from)?)
+        .parse()
+        .map_err(js_error)
+}
+
+fn big_int(amount: Amount) -> BigInt {
+    BigInt::new(&JsValue::from_str(&amount.to_string())).expect("amount is a valid BigInt")
+}
+
+/// Signing keys of a user
+#[wasm_bindgen]
+pub struct KeyPair {
+    inner: sign::KeyPair,
+}
+
+#[wasm_bindgen]
+impl KeyPair {
+    /// Generate new random keys
+    #[wasm_bindgen(constructor)]
+    pub fn random() -> Self {
+        Self {
+            inner: sign::KeyPair::random(),
+        }
+    }
+
+    /// Restore the keys from the bytes of [`KeyPair::private_key`]
+    #[wasm_bindgen(js_name = fromPrivateKey)]
+    pub fn from_private_key(bytes: &[u8]) -> Result<KeyPair, JsValue> {
+        let private = sign::PrivateKey::from_hex(hex::encode(bytes))
+            .map_err(|_| js_error("invalid private key"))?;
+
+        Ok(Self {
+            inner: sign::KeyPair::from(private),
+        })
+    }
+
+    /// Bytes of the private key, to be kept secret
+    #[wasm_bindgen(js_name = privateKey)]
+    pub fn private_key(&self) -> Vec<u8> {
+        let encoded: String = self.inner.private().encode_hex();
+
+        hex::decode(encoded).expect("hex encoded key")
+    }
+
+    /// Bytes of the public key, identifying the user
+    #[wasm_bindgen(js_name = publicKey)]
+    pub fn public_key(&self) -> Vec<u8> {
+        proto_convert::proto_public_key(&self.inner.public()).bytes
+    }
+
+    /// Sign a transfer of `amount` to `recipient`, to be sent later
+    #[wasm_bindgen(js_name = signTransfer)]
+    pub fn sign_transfer(
+        &self,
+        sequence: u32,
+        recipient: &[u8],
+        amount: &BigInt,
+        memo: Option<String>,
+    ) -> Result<SignedTransfer, JsValue> {
+        Ok(SignedTransfer {
+            inner: crate::SignedTransaction::with_memo(
+                &self.inner,
+                sequence,
+                public_key(recipient)?,
+                self::amount(amount)?,
+                memo,
+            ),
+        })
+    }
+}
+
+impl Default for KeyPair {
+    fn default() -> Self {
+        Self::random()
+    }
+}
+
+/// Transfer signed by its sender, see [`KeyPair::sign_transfer`]
+#[wasm_bindgen]
+pub struct SignedTransfer {
+    inner: crate::SignedTransaction,
+}
+
+#[wasm_bindgen]
+impl SignedTransfer {
+    /// Write it as a signed transactions file for `chain_id`, see [`interchange`]
+    #[wasm_bindgen(js_name = toDocument)]
+    pub fn to_document(&self, chain_id: &str) -> Result<String, JsValue> {
+        let mut document = Vec::new();
+        interchange::Document::new(chain_id, vec![self.inner.clone()])
+            .to_writer(&mut document)
+            .map_err(js_error)?;
+
+        String::from_utf8(document).map_err(js_error)
+    }
+}
+
+/// Transaction processed by the node
+#[wasm_bindgen]
+pub struct Transaction {
+    inner: FullTransaction,
+}
+
+#[wasm_bindgen]
+impl Transaction {
+    /// When the node processed it, in RFC 3339
+    #[wasm_bindgen(getter)]
+    pub fn timestamp(&self) -> String {
+        self.inner.timestamp.to_rfc3339()
+    }
+
+    /// Account paying it
+    #[wasm_bindgen(getter)]
+    pub fn sender(&self) -> Vec<u8> {
+        proto_convert::proto_public_key(&self.inner.sender).bytes
+    }
+
+    /// Sequence of the sender it used
+    #[wasm_bindgen(getter)]
+    pub fn sequence(&self) -> u32 {
+        self.inner.sequence
+    }
+
+    /// Account paid
+    #[wasm_bindgen(getter)]
+    pub fn recipient(&self) -> Vec<u8> {
+        proto_convert::proto_public_key(&self.inner.recipient).bytes
+    }
+
+    /// Amount paid to the recipient, as a `BigInt`
+    #[wasm_bindgen(getter)]
+    pub fn amount(&self) -> BigInt {
+        big_int(self.inner.amount)
+    }
+
+    /// Free text attached by the sender
+    #[wasm_bindgen(getter)]
+    pub fn memo(&self) -> Option<String> {
+        self.inner.memo.clone()
+    }
+
+    /// Fee paid to the network, as a `BigInt`
+    #[wasm_bindgen(getter)]
+    pub fn fee(&self) -> BigInt {
+        big_int(self.inner.fee)
+    }
+}
+
+/// Connection to a node, through grpc-web
+#[wasm_bindgen]
+pub struct Client {
+    inner: client::Client,
+}
+
+#[wasm_bindgen]
+impl Client {
+    /// Connect to the node at `url`, on the first call
+    #[wasm_bindgen(constructor)]
+    pub fn new(url: &str) -> Result<Client, JsValue> {
+        Ok(Self {
+            inner: client::Client::new(url.parse().map_err(js_error)?).map_err(js_error)?,
+        })
+    }
+
+    /// Send `amount` to `recipient`, resolving once the node broadcasted it
+    #[wasm_bindgen(js_name = sendAsset)]
+    pub fn send_asset(
+        &self,
+        keypair: &KeyPair,
+        sequence: u32,
+        recipient: &[u8],
+        amount: &BigInt,
+    ) -> Result<Promise, JsValue> {
+        let transfer = keypair.sign_transfer(sequence, recipient, amount, None)?;
+
+        Ok(self.send_signed(&transfer))
+    }
+
+    /// Send a transfer signed beforehand
+    #[wasm_bindgen(js_name = sendSigned)]
+    pub fn send_signed(&self, transfer: &SignedTransfer) -> Promise {
+        let mut inner = self.inner.clone();
+        let transaction = transfer.inner.clone();
+
+        future_to_promise(async move {
+            inner.send_signed(&transaction).await.map_err(js_error)?;
+
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Resolve to the balance of `account`, as a `BigInt`
+    #[wasm_bindgen(js_name = getBalance)]
+    pub fn get_balance(&self, account: &[u8]) -> Result<Promise, JsValue> {
+        let mut inner = self.inner.clone();
+        let account = public_key(account)?;
+
+        Ok(future_to_promise(async move {
+            let balance = inner.get_balance(&account).await.map_err(js_error)?;
+
+            Ok(big_int(balance).into())
+        }))
+    }
+
+    /// Resolve to the last sequence used by `account`
+    #[wasm_bindgen(js_name = getLastSequence)]
+    pub fn get_last_sequence(&self, account: &[u8]) -> Result<Promise, JsValue> {
+        let mut inner = self.inner.clone();
+        let account = public_key(account)?;
+
+        Ok(future_to_promise(async move {
+            let sequence = inner.get_last_sequence(&account).await.map_err(js_error)?;
+
+            Ok(sequence.into())
+        }))
+    }
+
+    /// Call `callback` with each [`Transaction`] processed from now on
+    ///
+    /// The promise rejects once the connection is lost.
+    #[wasm_bindgen(js_name = subscribeTransactions)]
+    pub fn subscribe_transactions(&self, callback: Function) -> Promise {
+        let mut inner = self.inner.clone();
+
+        future_to_promise(async move {
+            let transactions = inner.subscribe_transactions().await.map_err(js_error)?;
+            futures::pin_mut!(transactions);
+
+            while let Some(transaction) = transactions.next().await {
+                let transaction = Transaction {
+                    inner: transaction.map_err(js_error)?,
+                };
+                callback.call1(&JsValue::NULL, &transaction.into())?;
+            }
+
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+}
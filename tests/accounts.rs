@@ -0,0 +1,571 @@
+#![cfg(feature = "server")]
+
+use std::time::Duration;
+
+use at2_node::{
+    server::accounts::{self, store, Accounts},
+    Amount, Asset, BridgeDeposit,
+};
+use drop::crypto::sign;
+use proptest::prelude::*;
+
+const USERS: usize = 4;
+/// Balance of an account never seen before
+const INITIAL_BALANCE: Amount = 10;
+
+#[derive(Debug, Clone)]
+struct Transfer {
+    sender: usize,
+    receiver: usize,
+    amount: Amount,
+    /// Added to the next valid sequence of the sender
+    sequence_offset: i64,
+}
+
+fn transfer() -> impl Strategy<Value = Transfer> {
+    (0..USERS, 0..USERS, 0..2 * INITIAL_BALANCE, -1i64..=1).prop_map(
+        |(sender, receiver, amount, sequence_offset)| Transfer {
+            sender,
+            receiver,
+            amount,
+            sequence_offset,
+        },
+    )
+}
+
+/// What the accounts should contain
+#[derive(Clone, Copy)]
+struct Model {
+    last_sequence: sieve::Sequence,
+    balance: Amount,
+}
+
+proptest! {
+    #[test]
+    fn accounts_invariants(transfers in prop::collection::vec(transfer(), 1..64)) {
+        let runtime = tokio::runtime::Runtime::new().expect("start runtime");
+
+        runtime.block_on(async {
+            let accounts = Accounts::new(Default::default());
+            let users = (0..USERS)
+                .map(|_| sign::KeyPair::random().public())
+                .collect::<Vec<_>>();
+            let mut model = vec![
+                Model {
+                    last_sequence: sieve::Sequence::MIN,
+                    balance: INITIAL_BALANCE,
+                };
+                USERS
+            ];
+
+            for tx in transfers {
+                let sequence = (model[tx.sender].last_sequence as i64 + 1 + tx.sequence_offset)
+                    .max(0) as sieve::Sequence;
+                let valid = sequence == model[tx.sender].last_sequence + 1
+                    && tx.amount <= model[tx.sender].balance;
+
+                let ret = accounts
+                    .transfer(
+                        Box::new(users[tx.sender]),
+                        sequence,
+                        Box::new(users[tx.receiver]),
+                        tx.amount,
+                    )
+                    .await;
+                prop_assert_eq!(ret.is_ok(), valid, "{:?}", tx);
+
+                if valid {
+                    model[tx.sender].last_sequence = sequence;
+                    model[tx.sender].balance -= tx.amount;
+                    model[tx.receiver].balance += tx.amount;
+                }
+
+                let mut supply = 0;
+                for (user, expected) in users.iter().zip(&model) {
+                    let balance = accounts
+                        .get_balance(Box::new(*user))
+                        .await
+                        .expect("get balance");
+                    let last_sequence = accounts
+                        .get_last_sequence(Box::new(*user))
+                        .await
+                        .expect("get last sequence");
+
+                    prop_assert_eq!(balance, expected.balance);
+                    prop_assert_eq!(last_sequence, expected.last_sequence);
+                    supply += balance;
+                }
+                prop_assert_eq!(supply, INITIAL_BALANCE * USERS as Amount);
+            }
+
+            Ok(())
+        })?;
+    }
+}
+
+#[tokio::test]
+async fn sled_accounts_survive_restart() {
+    const AMOUNT: Amount = 3;
+
+    let path = std::env::temp_dir().join(format!("at2-accounts-{}", std::process::id()));
+    let sender = sign::KeyPair::random().public();
+    let recipient = sign::KeyPair::random().public();
+
+    let accounts = Accounts::with_store(
+        Box::new(store::Sled::open(&path).expect("open store")),
+        Default::default(),
+    );
+    accounts
+        .transfer(Box::new(sender), 1, Box::new(recipient), AMOUNT)
+        .await
+        .expect("transfer");
+    drop(accounts);
+
+    let accounts = Accounts::with_store(Box::new(reopen(&path).await), Default::default());
+
+    assert_eq!(
+        accounts.get_last_sequence(Box::new(sender)).await.unwrap(),
+        1
+    );
+    assert_eq!(
+        accounts.get_balance(Box::new(recipient)).await.unwrap(),
+        INITIAL_BALANCE + AMOUNT
+    );
+
+    // delivered again, only a conflicting transfer is an error
+    assert!(matches!(
+        accounts
+            .transfer(Box::new(sender), 1, Box::new(recipient), AMOUNT)
+            .await,
+        Err(accounts::Error::AlreadyApplied { sequence: 1 })
+    ));
+    assert!(matches!(
+        accounts
+            .transfer(Box::new(sender), 1, Box::new(recipient), AMOUNT + 1)
+            .await,
+        Err(accounts::Error::SequenceAlreadyUsed { .. })
+    ));
+    assert_eq!(
+        accounts.get_balance(Box::new(recipient)).await.unwrap(),
+        INITIAL_BALANCE + AMOUNT
+    );
+
+    drop(accounts);
+    let _ = std::fs::remove_dir_all(path);
+}
+
+/// Open the store again once the previous agent is gone
+async fn reopen(path: &std::path::Path) -> store::Sled {
+    // the database is only closed once the agent noticed that it is gone
+    let mut reopened = store::Sled::open(path);
+    for _ in 0..100 {
+        if reopened.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        reopened = store::Sled::open(path);
+    }
+
+    reopened.expect("reopen store")
+}
+
+#[tokio::test]
+async fn bridge_attestations_survive_restart() {
+    const AMOUNT: Amount = 5;
+    const THRESHOLD: usize = 2;
+
+    let path = std::env::temp_dir().join(format!("at2-bridge-{}", std::process::id()));
+    let validators = [
+        sign::KeyPair::random().public(),
+        sign::KeyPair::random().public(),
+    ];
+    let deposit = BridgeDeposit {
+        source_network: "other".to_owned(),
+        source_sequence: 1,
+        user: sign::KeyPair::random().public(),
+        amount: AMOUNT,
+    };
+
+    let accounts = Accounts::with_store(
+        Box::new(store::Sled::open(&path).expect("open store")),
+        Default::default(),
+    );
+    let minted = accounts
+        .bridge_deposit(
+            Box::new(validators[0]),
+            1,
+            Box::new(deposit.clone()),
+            THRESHOLD,
+        )
+        .await
+        .expect("first attestation");
+    assert!(!minted);
+    drop(accounts);
+
+    let accounts = Accounts::with_store(Box::new(reopen(&path).await), Default::default());
+
+    // the same validator again doesn't count twice
+    let minted = accounts
+        .bridge_deposit(
+            Box::new(validators[0]),
+            2,
+            Box::new(deposit.clone()),
+            THRESHOLD,
+        )
+        .await
+        .expect("repeated attestation");
+    assert!(!minted);
+
+    let conflicting = BridgeDeposit {
+        amount: AMOUNT + 1,
+        ..deposit.clone()
+    };
+    assert!(matches!(
+        accounts
+            .bridge_deposit(Box::new(validators[1]), 1, Box::new(conflicting), THRESHOLD)
+            .await,
+        Err(accounts::Error::Bridge { .. })
+    ));
+
+    let minted = accounts
+        .bridge_deposit(
+            Box::new(validators[1]),
+            1,
+            Box::new(deposit.clone()),
+            THRESHOLD,
+        )
+        .await
+        .expect("second attestation");
+    assert!(minted);
+    assert_eq!(
+        accounts.get_balance(Box::new(deposit.user)).await.unwrap(),
+        INITIAL_BALANCE + AMOUNT
+    );
+
+    drop(accounts);
+    let _ = std::fs::remove_dir_all(path);
+}
+
+#[tokio::test]
+async fn genesis_sets_starting_balance() {
+    const GENESIS_BALANCE: Amount = 1_000;
+
+    let rich = sign::KeyPair::random().public();
+    let other = sign::KeyPair::random().public();
+
+    let accounts = Accounts::new(vec![(rich, GENESIS_BALANCE)].into_iter().collect());
+
+    accounts
+        .transfer(Box::new(rich), 1, Box::new(other), GENESIS_BALANCE)
+        .await
+        .expect("transfer");
+
+    assert_eq!(accounts.get_balance(Box::new(rich)).await.unwrap(), 0);
+    assert_eq!(
+        accounts.get_balance(Box::new(other)).await.unwrap(),
+        INITIAL_BALANCE + GENESIS_BALANCE
+    );
+}
+
+#[tokio::test]
+async fn versions_follow_the_changes() {
+    let sender = sign::KeyPair::random().public();
+    let recipient = sign::KeyPair::random().public();
+    let other = sign::KeyPair::random().public();
+
+    let accounts = Accounts::new(Default::default());
+    let version = |user| {
+        let accounts = accounts.clone();
+        async move {
+            accounts
+                .get_versioned(Box::new(user))
+                .await
+                .expect("get versioned")
+                .version
+        }
+    };
+
+    assert_eq!(version(sender).await, 1);
+
+    for sequence in 1..=2 {
+        accounts
+            .transfer(Box::new(sender), sequence, Box::new(recipient), 1)
+            .await
+            .expect("transfer");
+    }
+
+    assert_eq!(version(sender).await, 3);
+    assert_eq!(version(recipient).await, 3);
+    assert_eq!(version(other).await, 1);
+}
+
+#[tokio::test]
+async fn window_accepts_sequences_out_of_order() {
+    let sender = sign::KeyPair::random().public();
+    let recipient = sign::KeyPair::random().public();
+
+    let accounts = Accounts::new(Default::default());
+    let transfer = |sequence| accounts.transfer(Box::new(sender), sequence, Box::new(recipient), 1);
+
+    assert!(matches!(
+        transfer(2).await,
+        Err(accounts::Error::SequenceGap { .. })
+    ));
+
+    accounts
+        .set_window(Box::new(sender), 1, 3)
+        .await
+        .expect("set window");
+
+    transfer(4).await.expect("transfer within the window");
+    transfer(3).await.expect("transfer within the window");
+    assert!(matches!(
+        transfer(5).await,
+        Err(accounts::Error::SequenceGap { .. })
+    ));
+    assert!(matches!(
+        transfer(4).await,
+        Err(accounts::Error::AlreadyApplied { .. })
+    ));
+    assert_eq!(
+        accounts.get_last_sequence(Box::new(sender)).await.unwrap(),
+        1
+    );
+
+    transfer(2).await.expect("transfer within the window");
+    assert_eq!(
+        accounts.get_last_sequence(Box::new(sender)).await.unwrap(),
+        4
+    );
+    assert_eq!(
+        accounts.get_balance(Box::new(sender)).await.unwrap(),
+        INITIAL_BALANCE - 3
+    );
+}
+
+#[tokio::test]
+async fn multi_transfer_is_atomic() {
+    let sender = sign::KeyPair::random().public();
+    let first = sign::KeyPair::random().public();
+    let second = sign::KeyPair::random().public();
+
+    let accounts = Accounts::new(Default::default());
+
+    assert!(accounts
+        .transfer_multi(
+            Box::new(sender),
+            1,
+            vec![(first, INITIAL_BALANCE), (second, 1)]
+        )
+        .await
+        .is_err());
+    assert_eq!(
+        accounts.get_balance(Box::new(first)).await.unwrap(),
+        INITIAL_BALANCE
+    );
+
+    accounts
+        .transfer_multi(
+            Box::new(sender),
+            1,
+            vec![(first, 4), (second, 3), (first, 2)],
+        )
+        .await
+        .expect("transfer");
+
+    assert_eq!(accounts.get_balance(Box::new(sender)).await.unwrap(), 1);
+    assert_eq!(
+        accounts.get_balance(Box::new(first)).await.unwrap(),
+        INITIAL_BALANCE + 6
+    );
+    assert_eq!(
+        accounts.get_balance(Box::new(second)).await.unwrap(),
+        INITIAL_BALANCE + 3
+    );
+}
+
+#[tokio::test]
+async fn checkpoints_match_across_orders() {
+    let first = sign::KeyPair::random().public();
+    let second = sign::KeyPair::random().public();
+
+    let checkpointed = || {
+        Accounts::with_checkpoint_interval(
+            Box::new(store::Memory::default()),
+            Default::default(),
+            2,
+        )
+    };
+    let accounts = checkpointed();
+    let reordered = checkpointed();
+
+    // contagion only orders the transfers of each sender
+    for (sender, receiver) in [(first, second), (second, first)] {
+        accounts
+            .transfer(Box::new(sender), 1, Box::new(receiver), 1)
+            .await
+            .expect("transfer");
+    }
+    for (sender, receiver) in [(second, first), (first, second)] {
+        reordered
+            .transfer(Box::new(sender), 1, Box::new(receiver), 1)
+            .await
+            .expect("transfer");
+    }
+
+    let checkpoint = accounts
+        .get_checkpoint(None)
+        .await
+        .unwrap()
+        .expect("checkpoint taken");
+    assert_eq!((checkpoint.epoch, checkpoint.applied), (1, 2));
+    assert_eq!(
+        reordered
+            .get_checkpoint(Some(1))
+            .await
+            .unwrap()
+            .unwrap()
+            .hash,
+        checkpoint.hash
+    );
+
+    accounts
+        .transfer(Box::new(first), 2, Box::new(second), 1)
+        .await
+        .expect("transfer");
+    assert_eq!(
+        accounts.get_checkpoint(None).await.unwrap().unwrap().epoch,
+        1
+    );
+    assert!(accounts.get_checkpoint(Some(2)).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn state_digest_follows_the_accounts() {
+    let first = sign::KeyPair::random().public();
+    let second = sign::KeyPair::random().public();
+
+    let accounts = Accounts::new(Default::default());
+    let reordered = Accounts::new(Default::default());
+
+    // computed now, then updated along the transfers
+    let (empty, _) = accounts.get_state_digest().await.expect("state digest");
+    for (sender, receiver) in [(first, second), (second, first), (first, second)] {
+        let sequence = accounts
+            .get_last_sequence(Box::new(sender))
+            .await
+            .expect("last sequence")
+            + 1;
+        accounts
+            .transfer(Box::new(sender), sequence, Box::new(receiver), 1)
+            .await
+            .expect("transfer");
+    }
+    // computed once every transfer is applied
+    for (sender, sequence, receiver) in [(second, 1, first), (first, 1, second), (first, 2, second)]
+    {
+        reordered
+            .transfer(Box::new(sender), sequence, Box::new(receiver), 1)
+            .await
+            .expect("transfer");
+    }
+
+    let digest = accounts.get_state_digest().await.expect("state digest");
+    assert_ne!(digest.0, empty);
+    assert_eq!(digest.1, 3);
+    assert_eq!(
+        reordered.get_state_digest().await.expect("state digest"),
+        digest
+    );
+}
+
+#[tokio::test]
+async fn snapshots_carry_the_assets() {
+    let admin = sign::KeyPair::random().public();
+    let user = sign::KeyPair::random().public();
+    let asset = Asset {
+        id: "EUR".to_owned(),
+        name: "Euro".to_owned(),
+    };
+
+    let accounts = Accounts::new(Default::default());
+
+    // computed now, then updated along the operations
+    let (empty, _) = accounts.get_state_digest().await.expect("state digest");
+    accounts
+        .register_asset(Box::new(admin), 1, Box::new(asset.clone()))
+        .await
+        .expect("register asset");
+    accounts
+        .mint(
+            Box::new(admin),
+            2,
+            Box::new(admin),
+            5,
+            Some(asset.id.clone()),
+        )
+        .await
+        .expect("mint");
+    accounts
+        .transfer_asset(Box::new(admin), 3, Some(asset.id.clone()), vec![(user, 2)])
+        .await
+        .expect("transfer asset");
+
+    let snapshot = accounts.get_snapshot().await.expect("snapshot");
+    assert_eq!(snapshot.assets.len(), 1);
+    assert_eq!(snapshot.asset_balances.len(), 2);
+
+    let restored = Accounts::new(Default::default());
+    restored.restore(snapshot.clone()).await.expect("restore");
+
+    assert_eq!(
+        restored
+            .get_asset_balance(Box::new(user), asset.id.clone())
+            .await
+            .expect("asset balance"),
+        2
+    );
+    assert_eq!(
+        restored.get_snapshot().await.expect("snapshot").hash,
+        snapshot.hash
+    );
+
+    // computed at once from the restored store
+    let (digest, _) = accounts.get_state_digest().await.expect("state digest");
+    assert_ne!(digest, empty);
+    assert_eq!(
+        restored.get_state_digest().await.expect("state digest").0,
+        digest
+    );
+}
+
+#[tokio::test]
+async fn overflows_are_refused() {
+    let rich = sign::KeyPair::random().public();
+    let other = sign::KeyPair::random().public();
+
+    let accounts = Accounts::new(vec![(rich, Amount::MAX)].into_iter().collect());
+
+    assert!(matches!(
+        accounts
+            .transfer(Box::new(other), 1, Box::new(rich), 1)
+            .await,
+        Err(accounts::Error::BalanceOverflow)
+    ));
+    assert!(matches!(
+        accounts
+            .transfer_multi(Box::new(rich), 1, vec![(other, Amount::MAX), (other, 1)])
+            .await,
+        Err(accounts::Error::TransferOverflow)
+    ));
+
+    // nothing was applied
+    assert_eq!(
+        accounts.get_balance(Box::new(rich)).await.unwrap(),
+        Amount::MAX
+    );
+    assert_eq!(
+        accounts.get_balance(Box::new(other)).await.unwrap(),
+        INITIAL_BALANCE
+    );
+}
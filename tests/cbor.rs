@@ -0,0 +1,96 @@
+#![cfg(all(feature = "testkit", feature = "cbor"))]
+
+use at2_node::{
+    api::At2Api,
+    codec::{self, Codec, Encoding},
+    proto::{self, at2_client::At2Client},
+    testkit::Network,
+    Amount,
+};
+use drop::crypto::sign;
+
+mod wait;
+
+#[tokio::test]
+async fn cbor_and_bincode_clients_agree() {
+    const AMOUNT: Amount = 5;
+
+    let network = Network::start(3).await.expect("start network");
+    let mut cbor = network.clients()[0].clone().with_encoding(Encoding::Cbor);
+    let mut bincode = network.clients()[1]
+        .clone()
+        .with_encoding(Encoding::Bincode);
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    let initial = bincode.get_balance(&recipient).await.expect("get balance");
+    assert_eq!(
+        cbor.get_balance(&recipient).await.expect("get balance"),
+        initial
+    );
+
+    cbor.send_asset(&sender, 1, recipient, AMOUNT)
+        .await
+        .expect("send asset");
+    wait::delivered(&bincode, &sender.public(), 1).await;
+    wait::delivered(&cbor, &sender.public(), 1).await;
+
+    for mut client in vec![cbor, bincode] {
+        assert_eq!(
+            client.get_balance(&recipient).await.expect("get balance"),
+            initial + AMOUNT
+        );
+
+        let transactions = client
+            .get_latest_transactions()
+            .await
+            .expect("get latest transactions");
+        let transaction = transactions
+            .iter()
+            .find(|tx| tx.sender == sender.public())
+            .expect("sent transaction");
+        assert_eq!(transaction.sequence, 1);
+        assert_eq!(transaction.recipient, recipient);
+        assert_eq!(transaction.amount, AMOUNT);
+    }
+}
+
+#[tokio::test]
+async fn unknown_encoding_is_rejected() {
+    let network = Network::start(1).await.expect("start network");
+    let channel =
+        tonic::transport::Endpoint::from_shared(format!("http://{}", network.rpc_addresses()[0]))
+            .expect("endpoint")
+            .connect()
+            .await
+            .expect("connect");
+    let mut client = At2Client::new(channel);
+
+    let sender = Encoding::Cbor
+        .encode(&sign::KeyPair::random().public())
+        .expect("encode");
+    let request = |encoding: &str| {
+        let mut request = tonic::Request::new(proto::GetBalanceRequest {
+            sender: sender.clone(),
+            ..Default::default()
+        });
+        request
+            .metadata_mut()
+            .insert(codec::METADATA_KEY, encoding.parse().expect("metadata"));
+
+        request
+    };
+
+    client
+        .get_balance(request(Encoding::Cbor.name()))
+        .await
+        .expect("get balance");
+
+    // what a node built without the feature answers to CBOR
+    let status = client
+        .get_balance(request("json"))
+        .await
+        .expect_err("unknown encoding");
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
@@ -1,23 +1,39 @@
+#![cfg(not(target_family = "wasm"))]
+
 #[cfg(all(test, not(all(feature = "server", feature = "client"))))]
 compile_error!("tests need both server and client features");
 
 use std::{
-    env, io,
+    collections::HashMap,
+    env, fs, io,
     io::{BufRead, BufReader},
-    iter::{repeat_with, Extend},
+    iter::{self, repeat_with, Extend},
     net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicU16, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU16, Ordering},
+        mpsc, Arc,
     },
     time::{Duration, Instant},
 };
 
 use duct::cmd;
 use futures::future::join_all;
-use tokio::{net::TcpStream, task::yield_now};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+    task::yield_now,
+};
+use tonic_health::proto::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
 use url::Url;
 
+mod process;
+
 const CLIENT_BIN: &str = env!("CARGO_BIN_EXE_client");
 const SERVER_BIN: &str = env!("CARGO_BIN_EXE_server");
 
@@ -38,25 +54,65 @@ fn next_test_ip4() -> SocketAddr {
 struct Server {
     handle: Arc<duct::ReaderHandle>,
     reader: Option<std::thread::JoinHandle<()>>,
+    logs: mpsc::Receiver<String>,
+}
+
+impl Server {
+    fn terminate(&self) {
+        process::terminate(&self.handle);
+    }
+
+    /// Wait for the node to exit, once terminated
+    async fn wait_stopped(&self) {
+        let timeout = Instant::now() + TIMEOUT;
+        while let Ok(None) = self.handle.try_wait() {
+            assert!(Instant::now() < timeout, "server still running");
+            tokio::time::sleep(TICK).await;
+        }
+    }
+
+    /// Wait for the node to log a line containing `needle`
+    async fn wait_for_log(&self, needle: &str) {
+        let timeout = Instant::now() + TIMEOUT;
+        loop {
+            match self.logs.try_recv() {
+                Ok(line) if line.contains(needle) => return,
+                Ok(_) => {}
+                Err(mpsc::TryRecvError::Empty) => {
+                    assert!(Instant::now() < timeout, "nothing logged about {}", needle);
+                    tokio::time::sleep(TICK).await;
+                }
+                Err(mpsc::TryRecvError::Disconnected) => panic!("server output closed"),
+            }
+        }
+    }
+
+    /// Freeze the node, keeping its connections open
+    #[cfg(unix)]
+    fn pause(&self) {
+        process::pause(&self.handle);
+    }
+
+    #[cfg(unix)]
+    fn resume(&self) {
+        process::resume(&self.handle);
+    }
 }
 
 impl Drop for Server {
     fn drop(&mut self) {
         use std::thread;
 
-        use nix::{
-            sys::signal::{self, Signal},
-            unistd::Pid,
-        };
-
-        self.handle.pids().iter().for_each(|pid| {
-            let _ = signal::kill(Pid::from_raw(*pid as i32), Signal::SIGTERM);
-        });
+        // a paused node would ignore the termination
+        #[cfg(unix)]
+        self.resume();
+        self.terminate();
 
         let timeout = Instant::now() + TIMEOUT;
         while Instant::now() < timeout {
-            if let Ok(None) = self.handle.try_wait() {
-                thread::sleep(TICK);
+            match self.handle.try_wait() {
+                Ok(None) => thread::sleep(TICK),
+                _ => break,
             }
         }
 
@@ -95,7 +151,7 @@ fn gen_config(node: &SocketAddr, rpc: &SocketAddr) -> (ServerConfig, NodeConfig)
 }
 
 fn start_server(server_config: ServerConfig) -> Server {
-    let handle = cmd!(SERVER_BIN, "run")
+    let handle = process::stoppable(cmd!(SERVER_BIN, "run"))
         .stdin_bytes(server_config)
         .stderr_to_stdout()
         .reader()
@@ -103,6 +159,7 @@ fn start_server(server_config: ServerConfig) -> Server {
         .expect("run server");
 
     let to_read = handle.clone();
+    let (logs, received) = mpsc::channel();
     Server {
         handle,
         reader: Some(std::thread::spawn(move || {
@@ -110,9 +167,11 @@ fn start_server(server_config: ServerConfig) -> Server {
             let mut line = String::new();
             while reader.read_line(&mut line).is_ok() {
                 print!("{}", line);
+                let _ = logs.send(line.clone());
                 line.clear();
             }
         })),
+        logs: received,
     }
 }
 
@@ -159,36 +218,97 @@ async fn server_started_twice_fails() {
     assert_eq!(exit, Some(io::ErrorKind::Other));
 }
 
+#[tokio::test]
+async fn server_stops_when_terminated() {
+    let (node, rpc) = (next_test_ip4(), next_test_ip4());
+
+    let (server_config, _) = gen_config(&node, &rpc);
+
+    let server = start_server(server_config);
+    join_all(vec![
+        wait_until_connect(&server, &node),
+        wait_until_connect(&server, &rpc),
+    ])
+    .await;
+
+    server.terminate();
+
+    server.wait_stopped().await;
+    assert!(
+        matches!(server.handle.try_wait(), Ok(Some(_))),
+        "server failed to stop"
+    );
+}
+
 async fn start_network(size: usize) -> (Vec<Server>, Url) {
-    let addresses = repeat_with(|| (next_test_ip4(), next_test_ip4()))
+    let (servers, rpcs) = start_network_rpcs(size).await;
+
+    (
+        servers,
+        rpcs.into_iter().next().expect("zero sized network"),
+    )
+}
+
+async fn start_network_rpcs(size: usize) -> (Vec<Server>, Vec<Url>) {
+    let addresses = network_addresses(size);
+    let configs = network_configs(&addresses, |_, peer| addresses[peer].0);
+
+    start_servers(&configs, &addresses).await
+}
+
+/// Node and RPC addresses of `size` nodes
+fn network_addresses(size: usize) -> Vec<(SocketAddr, SocketAddr)> {
+    repeat_with(|| (next_test_ip4(), next_test_ip4()))
         .take(size)
-        .collect::<Vec<_>>();
+        .collect()
+}
 
-    let (mut server_configs, node_configs): (Vec<_>, Vec<_>) = addresses
+/// Config of each node, knowing every other one, the `peer`th one being
+/// reached by the `server`th one at `peer_address(server, peer)`
+fn network_configs(
+    addresses: &[(SocketAddr, SocketAddr)],
+    peer_address: impl Fn(usize, usize) -> SocketAddr,
+) -> Vec<ServerConfig> {
+    let (server_configs, node_configs): (Vec<_>, Vec<_>) = addresses
         .iter()
         .map(|(node, rpc)| gen_config(node, rpc))
         .unzip();
 
     server_configs
-        .iter_mut()
+        .into_iter()
         .enumerate()
-        .for_each(|(server_pos, server_config)| {
-            server_config.extend(
-                node_configs
-                    .iter()
-                    .enumerate()
-                    .filter(|(node_pos, _)| *node_pos != server_pos)
-                    .map(|(_, node_config)| node_config)
-                    .flatten(),
-            )
-        });
+        .map(|(server_pos, mut server_config)| {
+            for (node_pos, node_config) in node_configs.iter().enumerate() {
+                if node_pos == server_pos {
+                    continue;
+                }
+
+                let node_config = String::from_utf8(node_config.clone()).expect("utf-8 config");
+                server_config.extend(
+                    node_config
+                        .replace(
+                            &format!("\"{}\"", addresses[node_pos].0),
+                            &format!("\"{}\"", peer_address(server_pos, node_pos)),
+                        )
+                        .into_bytes(),
+                );
+            }
+
+            server_config
+        })
+        .collect()
+}
 
-    let servers: Vec<_> = server_configs
+async fn start_servers(
+    configs: &[ServerConfig],
+    addresses: &[(SocketAddr, SocketAddr)],
+) -> (Vec<Server>, Vec<Url>) {
+    let servers: Vec<_> = configs
         .iter()
         .map(|server_config| start_server(server_config.clone()))
         .collect();
 
-    join_all(servers.iter().zip(&addresses).flat_map(|(server, addrs)| {
+    join_all(servers.iter().zip(addresses).flat_map(|(server, addrs)| {
         vec![
             wait_until_connect(server, &addrs.0),
             wait_until_connect(server, &addrs.1),
@@ -196,15 +316,12 @@ async fn start_network(size: usize) -> (Vec<Server>, Url) {
     }))
     .await;
 
-    let rpc = addresses
+    let rpcs = addresses
         .iter()
-        .map(|(_, rpc)| rpc)
-        .copied()
-        .next()
-        .map(|addr| Url::parse(&format!("http://{}", addr)).expect("format as URL"))
-        .expect("zero sized network");
+        .map(|(_, rpc)| Url::parse(&format!("http://{}", rpc)).expect("format as URL"))
+        .collect();
 
-    (servers, rpc)
+    (servers, rpcs)
 }
 
 #[tokio::test]
@@ -212,6 +329,88 @@ async fn can_run_network() {
     start_network(3).await;
 }
 
+#[tokio::test]
+async fn unknown_peer_is_dropped() {
+    let addresses = network_addresses(2);
+
+    // only the second node knows the first one
+    let (known, known_node) = gen_config(&addresses[0].0, &addresses[0].1);
+    let (mut unknown, _) = gen_config(&addresses[1].0, &addresses[1].1);
+    unknown.extend(known_node);
+
+    let (servers, _) = start_servers(&[known, unknown], &addresses).await;
+
+    servers[0].wait_for_log("dropping unknown peer").await;
+}
+
+/// PEM file of [`tests/tls`](tls/generate.sh)
+fn tls_file(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("tls")
+        .join(name)
+}
+
+#[tokio::test]
+async fn rpc_is_served_over_mtls() {
+    let (node, rpc) = (next_test_ip4(), next_test_ip4());
+
+    // literal strings, as windows paths hold backslashes
+    let (mut server_config, _) = gen_config(&node, &rpc);
+    server_config.extend(
+        format!(
+            "\n[tls]\ncert = '{}'\nkey = '{}'\nclient_ca = '{}'\n",
+            tls_file("node.pem").display(),
+            tls_file("node-key.pem").display(),
+            tls_file("ca.pem").display(),
+        )
+        .into_bytes(),
+    );
+    let server = start_server(server_config);
+    join_all(vec![
+        wait_until_connect(&server, &node),
+        wait_until_connect(&server, &rpc),
+    ])
+    .await;
+
+    // the certificate is for a name, as rustls doesn't check IP addresses
+    let https = format!("https://localhost:{}", rpc.port());
+    let client = cmd!(CLIENT_BIN, "config", "new", &https)
+        .read()
+        .expect("create client");
+    let trusting = format!(
+        "{}\n[tls]\nca = '{}'\n",
+        client,
+        tls_file("ca.pem").display()
+    );
+    let identity = format!(
+        "identity = {{ cert = '{}', key = '{}' }}\n",
+        tls_file("client.pem").display(),
+        tls_file("client-key.pem").display(),
+    );
+    let balance = |config: String| {
+        cmd!(CLIENT_BIN, "get-balance")
+            .stdin_bytes(config)
+            .stderr_null()
+            .read()
+    };
+
+    assert!(balance(format!("{}{}", trusting, identity)).is_ok());
+    assert!(
+        balance(trusting).is_err(),
+        "client without identity accepted"
+    );
+    assert!(
+        balance(format!("{}\n[tls]\n{}", client, identity)).is_err(),
+        "node trusted without its authority"
+    );
+
+    let plaintext = cmd!(CLIENT_BIN, "config", "new", &format!("http://{}", rpc))
+        .read()
+        .expect("create client");
+    assert!(balance(plaintext).is_err(), "plaintext client accepted");
+}
+
 #[tokio::test]
 async fn client_without_servers_fails() {
     let (_, rpc) = start_network(2).await;
@@ -227,6 +426,41 @@ async fn client_without_servers_fails() {
         .expect_err("send asset");
 }
 
+/// Health of the node as a whole, serving its RPC at `rpc`
+async fn health(rpc: &Url) -> ServingStatus {
+    let channel = tonic::transport::Endpoint::from_shared(rpc.to_string())
+        .expect("endpoint")
+        .connect()
+        .await
+        .expect("connect");
+    let reply = HealthClient::new(channel)
+        .check(HealthCheckRequest {
+            service: String::new(),
+        })
+        .await
+        .expect("check health");
+
+    ServingStatus::from_i32(reply.get_ref().status).expect("known status")
+}
+
+#[tokio::test]
+async fn node_serves_once_peers_answer() {
+    let addresses = network_addresses(3);
+    let configs = network_configs(&addresses, |_, peer| addresses[peer].0);
+
+    let (_first, rpcs) = start_servers(&configs[..1], &addresses[..1]).await;
+    assert_eq!(health(&rpcs[0]).await, ServingStatus::NotServing);
+
+    let _others = start_servers(&configs[1..], &addresses[1..]).await;
+
+    // peers are asked every few seconds
+    let timeout = Instant::now() + 3 * TIMEOUT;
+    while health(&rpcs[0]).await != ServingStatus::Serving {
+        assert!(Instant::now() < timeout, "node still not serving");
+        tokio::time::sleep(TICK).await;
+    }
+}
+
 fn get_balance(config: String) -> usize {
     cmd!(CLIENT_BIN, "get-balance")
         .stdin_bytes(config)
@@ -270,6 +504,13 @@ fn transfer(
     .expect("send asset");
 }
 
+/// Same client `config`, asking the node at `to` instead of the one at `from`
+fn on_node(config: &str, from: &Url, to: &Url) -> String {
+    let authority = |url: &Url| format!("{}:{}", url.host_str().unwrap(), url.port().unwrap());
+
+    config.replace(&authority(from), &authority(to))
+}
+
 fn get_last_sequence(config: String) -> sieve::Sequence {
     cmd!(CLIENT_BIN, "get-last-sequence")
         .stdin_bytes(config)
@@ -332,3 +573,469 @@ async fn can_send_asset() {
 
     assert_eq!(get_balance(sender) + AMOUNT, get_balance(receiver) - AMOUNT);
 }
+
+#[tokio::test]
+async fn signed_transactions_are_imported_in_order() {
+    const AMOUNT: usize = 4;
+
+    let (_servers, rpc) = start_network(3).await;
+    let files = env::temp_dir().join(format!("at2-import-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&files);
+    fs::create_dir_all(&files).expect("create transactions directory");
+
+    let sender = cmd!(CLIENT_BIN, "config", "new", &rpc.to_string())
+        .read()
+        .expect("create sender");
+    let receiver = cmd!(CLIENT_BIN, "config", "new", &rpc.to_string())
+        .read()
+        .expect("create receiver");
+    let recipient = cmd!(CLIENT_BIN, "config", "get-public-key")
+        .stdin_bytes(receiver.clone())
+        .read()
+        .expect("get public key");
+
+    let initial = get_balance(receiver.clone());
+
+    let signed = [1, 2]
+        .iter()
+        .map(|sequence| {
+            let path = files.join(format!("{}.json", sequence));
+            cmd!(
+                CLIENT_BIN,
+                "sign-asset",
+                sequence.to_string(),
+                &recipient,
+                AMOUNT.to_string()
+            )
+            .stdin_bytes(sender.clone())
+            .stdout_path(&path)
+            .run()
+            .expect("sign asset");
+
+            path
+        })
+        .collect::<Vec<_>>();
+    let import = |documents: &[PathBuf]| {
+        cmd(
+            CLIENT_BIN,
+            iter::once(PathBuf::from("import")).chain(documents.to_vec()),
+        )
+        .stdin_bytes(sender.clone())
+        .stderr_null()
+        .run()
+    };
+
+    // nothing is sent until then
+    assert_eq!(get_last_sequence(sender.clone()), 0);
+
+    import(&signed[1..]).expect_err("import without the first transaction");
+    assert_eq!(get_last_sequence(sender.clone()), 0);
+
+    import(&signed).expect("import transactions");
+    assert_eq!(get_last_sequence(sender.clone()), 2);
+    assert_eq!(get_balance(receiver.clone()), initial + 2 * AMOUNT);
+
+    // restarting an import skips what was processed
+    import(&signed).expect("import transactions again");
+    assert_eq!(get_last_sequence(sender), 2);
+    assert_eq!(get_balance(receiver), initial + 2 * AMOUNT);
+
+    let _ = fs::remove_dir_all(&files);
+}
+
+// freezing a process needs SIGSTOP
+#[cfg(unix)]
+#[tokio::test]
+async fn paused_node_catches_up() {
+    const AMOUNT: usize = 3;
+
+    let (servers, rpcs) = start_network_rpcs(3).await;
+
+    let sender = cmd!(CLIENT_BIN, "config", "new", &rpcs[0].to_string())
+        .read()
+        .expect("create sender");
+    let receiver = cmd!(CLIENT_BIN, "config", "new", &rpcs[0].to_string())
+        .read()
+        .expect("create receiver");
+
+    let initial = get_balance(receiver.clone());
+
+    servers[2].pause();
+    transfer(sender.clone(), 1, receiver.clone(), AMOUNT);
+    tokio::time::sleep(TICK).await;
+    servers[2].resume();
+
+    // same keys, asking the node which was paused
+    wait_for_sequence(on_node(&sender, &rpcs[0], &rpcs[2]), 1).await;
+    assert_eq!(
+        get_balance(on_node(&receiver, &rpcs[0], &rpcs[2])),
+        initial + AMOUNT
+    );
+}
+
+/// Same server `config`, keeping its accounts in `path`
+fn with_storage(config: ServerConfig, path: &Path) -> ServerConfig {
+    // a literal string, as windows paths hold backslashes
+    let mut with = format!("storage_path = '{}'\n", path.display()).into_bytes();
+    with.extend(config);
+
+    with
+}
+
+#[tokio::test]
+async fn restarted_node_catches_up() {
+    const AMOUNT: usize = 3;
+
+    let storage = env::temp_dir().join(format!("at2-restarted-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&storage);
+
+    let addresses = network_addresses(3);
+    let configs = network_configs(&addresses, |_, peer| addresses[peer].0)
+        .into_iter()
+        .enumerate()
+        .map(|(pos, config)| with_storage(config, &storage.join(pos.to_string())))
+        .collect::<Vec<_>>();
+    let (mut servers, rpcs) = start_servers(&configs, &addresses).await;
+
+    let sender = cmd!(CLIENT_BIN, "config", "new", &rpcs[0].to_string())
+        .read()
+        .expect("create sender");
+    let receiver = cmd!(CLIENT_BIN, "config", "new", &rpcs[0].to_string())
+        .read()
+        .expect("create receiver");
+
+    let initial = get_balance(receiver.clone());
+
+    transfer(sender.clone(), 1, receiver.clone(), AMOUNT);
+    wait_for_sequence(on_node(&sender, &rpcs[0], &rpcs[2]), 1).await;
+
+    servers[2].terminate();
+    servers[2].wait_stopped().await;
+    servers[2] = start_server(configs[2].clone());
+    join_all(vec![
+        wait_until_connect(&servers[2], &addresses[2].0),
+        wait_until_connect(&servers[2], &addresses[2].1),
+    ])
+    .await;
+
+    // the restarted node kept the first one and gets the next one
+    transfer(sender.clone(), 2, receiver.clone(), AMOUNT);
+    for rpc in &rpcs {
+        wait_for_sequence(on_node(&sender, &rpcs[0], rpc), 2).await;
+        assert_eq!(
+            get_balance(on_node(&receiver, &rpcs[0], rpc)),
+            initial + 2 * AMOUNT
+        );
+    }
+
+    drop(servers);
+    let _ = fs::remove_dir_all(&storage);
+}
+
+#[tokio::test]
+async fn node_without_storage_syncs_from_peers() {
+    const AMOUNT: usize = 3;
+
+    let storage = env::temp_dir().join(format!("at2-synced-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&storage);
+
+    let addresses = network_addresses(3);
+    let mut configs = network_configs(&addresses, |_, peer| addresses[peer].0)
+        .into_iter()
+        .enumerate()
+        .map(|(pos, config)| with_storage(config, &storage.join(pos.to_string())))
+        .collect::<Vec<_>>();
+    let (mut servers, rpcs) = start_servers(&configs, &addresses).await;
+
+    let sender = cmd!(CLIENT_BIN, "config", "new", &rpcs[0].to_string())
+        .read()
+        .expect("create sender");
+    let receiver = cmd!(CLIENT_BIN, "config", "new", &rpcs[0].to_string())
+        .read()
+        .expect("create receiver");
+
+    let initial = get_balance(receiver.clone());
+
+    transfer(sender.clone(), 1, receiver.clone(), AMOUNT);
+    wait_for_sequence(on_node(&sender, &rpcs[0], &rpcs[2]), 1).await;
+
+    // as if the node was lost, the transfer isn't delivered to it again
+    servers[2].terminate();
+    servers[2].wait_stopped().await;
+    fs::remove_dir_all(storage.join("2")).expect("remove storage");
+    configs[2].extend(b"\n[sync]\nquorum = 2\n");
+    servers[2] = start_server(configs[2].clone());
+    join_all(vec![
+        wait_until_connect(&servers[2], &addresses[2].0),
+        wait_until_connect(&servers[2], &addresses[2].1),
+    ])
+    .await;
+
+    assert_eq!(get_last_sequence(on_node(&sender, &rpcs[0], &rpcs[2])), 1);
+    assert_eq!(
+        get_balance(on_node(&receiver, &rpcs[0], &rpcs[2])),
+        initial + AMOUNT
+    );
+
+    transfer(sender.clone(), 2, receiver.clone(), AMOUNT);
+    for rpc in &rpcs {
+        wait_for_sequence(on_node(&sender, &rpcs[0], rpc), 2).await;
+        assert_eq!(
+            get_balance(on_node(&receiver, &rpcs[0], rpc)),
+            initial + 2 * AMOUNT
+        );
+    }
+
+    drop(servers);
+    let _ = fs::remove_dir_all(&storage);
+}
+
+/// Forward connections to `to`, holding back what is sent while `cut`
+///
+/// Connections are kept open, as drop doesn't dial its peers again.
+async fn proxy(to: SocketAddr, cut: Arc<AtomicBool>) -> SocketAddr {
+    let listener = TcpListener::bind(next_test_ip4())
+        .await
+        .expect("bind proxy");
+    let address = listener.local_addr().expect("proxy address");
+
+    tokio::spawn(async move {
+        while let Ok((inbound, _)) = listener.accept().await {
+            // not yet listening, the node tries again
+            let outbound = match TcpStream::connect(to).await {
+                Ok(outbound) => outbound,
+                Err(_) => continue,
+            };
+
+            let (inbound_read, inbound_write) = inbound.into_split();
+            let (outbound_read, outbound_write) = outbound.into_split();
+            tokio::spawn(forward(inbound_read, outbound_write, cut.clone()));
+            tokio::spawn(forward(outbound_read, inbound_write, cut.clone()));
+        }
+    });
+
+    address
+}
+
+async fn forward(mut from: OwnedReadHalf, mut to: OwnedWriteHalf, cut: Arc<AtomicBool>) {
+    let mut buffer = [0; 4096];
+
+    loop {
+        let read = match from.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(read) => read,
+        };
+
+        while cut.load(Ordering::Relaxed) {
+            tokio::time::sleep(TICK).await;
+        }
+
+        if to.write_all(&buffer[..read]).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[tokio::test]
+async fn partitioned_node_catches_up() {
+    const AMOUNT: usize = 3;
+    const ISOLATED: usize = 2;
+
+    let addresses = network_addresses(3);
+
+    // every link goes through a proxy, the ones of the isolated node sharing a cut
+    let cut = Arc::new(AtomicBool::new(false));
+    let mut proxies = HashMap::new();
+    for server in 0..addresses.len() {
+        for peer in (0..addresses.len()).filter(|peer| *peer != server) {
+            let link = if server == ISOLATED || peer == ISOLATED {
+                cut.clone()
+            } else {
+                Default::default()
+            };
+            proxies.insert((server, peer), proxy(addresses[peer].0, link).await);
+        }
+    }
+
+    let configs = network_configs(&addresses, |server, peer| proxies[&(server, peer)]);
+    let (_servers, rpcs) = start_servers(&configs, &addresses).await;
+
+    let sender = cmd!(CLIENT_BIN, "config", "new", &rpcs[0].to_string())
+        .read()
+        .expect("create sender");
+    let isolated_sender = cmd!(CLIENT_BIN, "config", "new", &rpcs[ISOLATED].to_string())
+        .read()
+        .expect("create isolated sender");
+    let receiver = cmd!(CLIENT_BIN, "config", "new", &rpcs[0].to_string())
+        .read()
+        .expect("create receiver");
+
+    let initial = get_balance(receiver.clone());
+
+    // both sides of the partition keep accepting transfers
+    cut.store(true, Ordering::Relaxed);
+    transfer(sender.clone(), 1, receiver.clone(), AMOUNT);
+    transfer(isolated_sender.clone(), 1, receiver.clone(), AMOUNT);
+    tokio::time::sleep(TICK).await;
+    cut.store(false, Ordering::Relaxed);
+
+    for rpc in &rpcs {
+        wait_for_sequence(on_node(&sender, &rpcs[0], rpc), 1).await;
+        wait_for_sequence(on_node(&isolated_sender, &rpcs[ISOLATED], rpc), 1).await;
+        assert_eq!(
+            get_balance(on_node(&receiver, &rpcs[0], rpc)),
+            initial + 2 * AMOUNT
+        );
+    }
+}
+
+#[test]
+fn address_is_prefixed() {
+    let address = cmd!(CLIENT_BIN, "config", "new", "http://localhost")
+        .pipe(cmd!(CLIENT_BIN, "config", "get-address"))
+        .read()
+        .expect("get address");
+
+    assert!(address.starts_with("at21"));
+}
+
+#[test]
+fn genesis_is_added_to_config() {
+    let public_key = cmd!(CLIENT_BIN, "config", "new", "http://localhost")
+        .pipe(cmd!(CLIENT_BIN, "config", "get-public-key"))
+        .read()
+        .expect("get public key");
+
+    let config = cmd!(SERVER_BIN, "config", "new", "127.0.0.1:0", "127.0.0.1:0")
+        .pipe(cmd!(
+            SERVER_BIN,
+            "config",
+            "add-genesis",
+            &public_key,
+            "1000"
+        ))
+        .read()
+        .expect("add genesis");
+
+    assert!(config.contains(&format!("{} = 1000", public_key)));
+}
+
+#[test]
+fn added_node_is_valid() {
+    let (server_config, _) = gen_config(&next_test_ip4(), &next_test_ip4());
+    let (peer_config, _) = gen_config(&next_test_ip4(), &next_test_ip4());
+
+    let identity = cmd!(SERVER_BIN, "config", "show-identity")
+        .stdin_bytes(peer_config)
+        .read()
+        .expect("show identity");
+    let network_key = identity
+        .lines()
+        .find_map(|line| line.strip_prefix("network key: "))
+        .expect("network key shown");
+
+    let peer = next_test_ip4().to_string();
+    let config = cmd!(SERVER_BIN, "config", "add-node", &peer, network_key)
+        .stdin_bytes(server_config)
+        .stdout_capture()
+        .run()
+        .expect("add node")
+        .stdout;
+    assert!(String::from_utf8_lossy(&config).contains(&peer));
+
+    cmd!(SERVER_BIN, "config", "validate")
+        .stdin_bytes(config)
+        .stdout_null()
+        .run()
+        .expect("validate config");
+}
+
+#[test]
+fn config_is_migrated_unless_newer() {
+    let (server_config, _) = gen_config(&next_test_ip4(), &next_test_ip4());
+    let server_config = String::from_utf8(server_config).expect("utf-8 config");
+    assert!(server_config.starts_with("version = 1\n"));
+
+    let unversioned = server_config.replacen("version = 1\n", "", 1);
+    let migrated = cmd!(SERVER_BIN, "config", "migrate")
+        .stdin_bytes(unversioned)
+        .read()
+        .expect("migrate config");
+    assert_eq!(migrated.trim_end(), server_config.trim_end());
+
+    let newer = server_config.replacen("version = 1\n", "version = 99\n", 1);
+    let refused = cmd!(SERVER_BIN, "config", "validate")
+        .stdin_bytes(newer)
+        .stdout_null()
+        .stderr_capture()
+        .unchecked()
+        .run()
+        .expect("run validate");
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("version 99 is newer"));
+}
+
+#[test]
+fn sync_is_refused_with_authenticated_reads() {
+    let (server_config, _) = gen_config(&next_test_ip4(), &next_test_ip4());
+    let server_config = String::from_utf8(server_config).expect("utf-8 config");
+    let config = format!(
+        "{}\n[sync]\nquorum = 1\n",
+        server_config.replacen(
+            "version = 1\n",
+            "version = 1\nauthenticated_reads = true\n",
+            1
+        )
+    );
+
+    let refused = cmd!(SERVER_BIN, "config", "validate")
+        .stdin_bytes(config)
+        .stdout_null()
+        .stderr_capture()
+        .unchecked()
+        .run()
+        .expect("run validate");
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("peers refuse snapshots"));
+}
+
+#[test]
+fn encrypted_keys_are_decrypted() {
+    const PASSPHRASE: &str = "correct horse battery staple";
+
+    let (server_config, _) = gen_config(&next_test_ip4(), &next_test_ip4());
+    let show_identity = |config: Vec<u8>, passphrase: &str| {
+        cmd!(SERVER_BIN, "config", "show-identity")
+            .env("AT2_KEYS_PASSPHRASE", passphrase)
+            .stdin_bytes(config)
+            .stderr_null()
+            .read()
+    };
+
+    let encrypted = cmd!(SERVER_BIN, "config", "encrypt")
+        .env("AT2_KEYS_PASSPHRASE", PASSPHRASE)
+        .stdin_bytes(server_config.clone())
+        .stdout_capture()
+        .run()
+        .expect("encrypt keys")
+        .stdout;
+    assert!(show_identity(encrypted.clone(), "wrong").is_err());
+
+    let decrypted = cmd!(SERVER_BIN, "config", "decrypt")
+        .env("AT2_KEYS_PASSPHRASE", PASSPHRASE)
+        .stdin_bytes(encrypted.clone())
+        .stdout_capture()
+        .run()
+        .expect("decrypt keys")
+        .stdout;
+
+    let identity = show_identity(server_config, "").expect("show identity");
+    assert_eq!(
+        show_identity(encrypted, PASSPHRASE).expect("show encrypted identity"),
+        identity
+    );
+    assert_eq!(
+        show_identity(decrypted, "").expect("show decrypted identity"),
+        identity
+    );
+}
@@ -0,0 +1,139 @@
+#![cfg(feature = "testkit")]
+
+//! Transfers sent through different nodes of a network, only using the client
+
+use at2_node::{
+    api::At2Api,
+    client::{Client, TransactionStatus},
+    testkit::Network,
+    Amount,
+};
+use drop::crypto::sign;
+
+mod wait;
+
+/// Starting balance of an account never seen before
+const INITIAL_BALANCE: Amount = 10;
+
+/// Transactions of `account` seen by `client`, in a node independent order
+async fn history(
+    client: &Client,
+    account: &sign::PublicKey,
+) -> Vec<(String, sieve::Sequence, String, Amount)> {
+    let mut client = client.clone();
+    let mut history = Vec::new();
+
+    let mut cursor = None;
+    loop {
+        let page = client
+            .get_account_history(account, cursor, 10)
+            .await
+            .expect("get account history");
+        history.extend(page.transactions.into_iter().map(|tx| {
+            (
+                tx.sender.to_string(),
+                tx.sequence,
+                tx.recipient.to_string(),
+                tx.amount,
+            )
+        }));
+
+        match page.next {
+            None => break,
+            next => cursor = next,
+        }
+    }
+
+    // nodes might deliver the transactions of different senders in another order
+    history.sort();
+    history
+}
+
+#[tokio::test]
+async fn transfers_across_nodes_converge() {
+    const SEEDED: u64 = 1_000;
+    const MINTED: Amount = 500;
+
+    let admin = sign::KeyPair::random();
+    let rich = sign::KeyPair::random();
+    let minted = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    let network = Network::builder()
+        .admin(&admin.public())
+        .genesis(&rich.public(), SEEDED)
+        .start()
+        .await
+        .expect("start network");
+    let clients = network.clients();
+
+    clients[0]
+        .clone()
+        .mint(&admin, 1, minted.public(), MINTED)
+        .await
+        .expect("mint");
+    for client in clients {
+        wait::delivered(client, &admin.public(), 1).await;
+    }
+
+    // each sender goes through another node
+    for sequence in 1..=2 {
+        clients[1]
+            .clone()
+            .send_asset(&rich, sequence, recipient, 100)
+            .await
+            .expect("send asset");
+    }
+    clients[2]
+        .clone()
+        .send_asset(&minted, 1, recipient, 200)
+        .await
+        .expect("send asset");
+    clients[2]
+        .clone()
+        .send_asset(&minted, 2, rich.public(), 50)
+        .await
+        .expect("send asset");
+
+    for client in clients {
+        wait::delivered(client, &rich.public(), 2).await;
+        wait::delivered(client, &minted.public(), 2).await;
+    }
+
+    let expected = [
+        (rich.public(), Amount::from(SEEDED) - 2 * 100 + 50),
+        (minted.public(), INITIAL_BALANCE + MINTED - 200 - 50),
+        (recipient, INITIAL_BALANCE + 2 * 100 + 200),
+    ];
+    for client in clients {
+        let mut client = client.clone();
+
+        for (account, balance) in &expected {
+            assert_eq!(
+                client.get_balance(account).await.expect("get balance"),
+                *balance
+            );
+        }
+        assert_eq!(
+            client
+                .get_transaction_status(&minted.public(), 2)
+                .await
+                .expect("get transaction status"),
+            TransactionStatus::Delivered
+        );
+    }
+
+    // histories are written right after the accounts
+    let mut histories = Vec::with_capacity(clients.len());
+    for client in clients {
+        histories.push(
+            wait::until(|| async move {
+                let history = history(client, &recipient).await;
+                Some(history).filter(|history| history.len() >= 3)
+            })
+            .await,
+        );
+    }
+    assert_eq!(histories[0].len(), 3);
+    assert!(histories.iter().all(|history| *history == histories[0]));
+}
@@ -0,0 +1,122 @@
+#![cfg(feature = "testkit")]
+
+use std::net::SocketAddr;
+
+use at2_node::{api::At2Api, testkit::Network, Amount};
+use drop::crypto::sign;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+mod wait;
+
+const SUBJECT: &str = "at2.events";
+
+/// Sent by a NATS server to each new client
+const INFO: &str = concat!(
+    r#"INFO {"server_id":"test","version":"2.0.0","proto":1,"max_payload":1048576}"#,
+    "\r\n"
+);
+
+/// Message published on a subject
+type Published = (String, Vec<u8>);
+
+/// Listen as a NATS server would, forwarding what clients publish
+async fn nats_server() -> (SocketAddr, mpsc::UnboundedReceiver<Published>) {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind nats server");
+    let addr = listener.local_addr().expect("nats server address");
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve_nats(stream, tx.clone()));
+        }
+    });
+
+    (addr, rx)
+}
+
+/// Handshake, answer pings and forward publications, ignoring the rest
+async fn serve_nats(stream: TcpStream, published: mpsc::UnboundedSender<Published>) {
+    let (read, mut write) = stream.into_split();
+    let mut read = BufReader::new(read);
+
+    if write.write_all(INFO.as_bytes()).await.is_err() {
+        return;
+    }
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match read.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["PING"] => {
+                if write.write_all(b"PONG\r\n").await.is_err() {
+                    return;
+                }
+            }
+            // with an optional reply subject before the size
+            ["PUB", subject, .., size] => {
+                let size = size.parse::<usize>().expect("payload size");
+                let mut payload = vec![0; size + 2];
+                if read.read_exact(&mut payload).await.is_err() {
+                    return;
+                }
+                payload.truncate(size);
+
+                let _ = published.send((subject.to_string(), payload));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn delivered_transaction_is_published() {
+    const AMOUNT: Amount = 7;
+
+    let (nats, mut published) = nats_server().await;
+    let network = Network::builder()
+        .size(1)
+        .events(format!("nats://{}", nats), SUBJECT)
+        .start()
+        .await
+        .expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    client
+        .send_asset(&sender, 1, recipient, AMOUNT)
+        .await
+        .expect("send asset");
+
+    // checkpoints are published as well
+    let event = loop {
+        let (subject, payload) = tokio::time::timeout(wait::TIMEOUT, published.recv())
+            .await
+            .expect("timeout expired")
+            .expect("nats server stopped");
+        assert_eq!(subject, SUBJECT);
+
+        let event = serde_json::from_slice::<Value>(&payload).expect("decode event");
+        if event["type"] == "transaction" {
+            break event;
+        }
+    };
+
+    assert_eq!(event["sender"], sender.public().to_string());
+    assert_eq!(event["recipient"], recipient.to_string());
+    assert_eq!(event["amount"].as_u64(), Some(AMOUNT as u64));
+    assert!(event["timestamp"].is_string());
+}
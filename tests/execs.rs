@@ -1,3 +1,6 @@
+// the execs are shell scripts, found by their executable bit
+#![cfg(unix)]
+
 use std::{env, fs, path::PathBuf};
 
 use duct::cmd;
@@ -0,0 +1,232 @@
+#![cfg(feature = "testkit")]
+
+use std::net::SocketAddr;
+
+use at2_node::{
+    address::{self, Address},
+    api::At2Api,
+    interchange,
+    testkit::Network,
+    Amount, SignedTransaction,
+};
+use drop::crypto::sign;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+mod wait;
+
+async fn post(addr: SocketAddr, path: &str, body: impl Into<reqwest::Body>) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("http://{}{}", addr, path))
+        .header("content-type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .expect("send request")
+}
+
+async fn call(addr: SocketAddr, path: &str, request: Value) -> Value {
+    let response = post(addr, path, request.to_string()).await;
+    assert_eq!(response.status(), StatusCode::OK, "{}", path);
+
+    response.json().await.expect("decode reply")
+}
+
+/// Bytes as the JSON messages carry them
+fn encoded(value: &impl serde::Serialize) -> String {
+    base64::encode(bincode::serialize(value).expect("encode"))
+}
+
+/// Next server-sent event, failing past [`wait::TIMEOUT`]
+async fn next_event(events: &mut reqwest::Response, buffer: &mut String) -> Value {
+    loop {
+        if let Some(end) = buffer.find("\n\n") {
+            let event = buffer[..end].to_owned();
+            buffer.replace_range(..end + 2, "");
+
+            let data = event.strip_prefix("data: ").expect("data field");
+            return serde_json::from_str(data).expect("decode event");
+        }
+
+        let chunk = tokio::time::timeout(wait::TIMEOUT, events.chunk())
+            .await
+            .expect("timeout expired")
+            .expect("receive event")
+            .expect("events closed");
+        buffer.push_str(std::str::from_utf8(&chunk).expect("utf-8 event"));
+    }
+}
+
+#[tokio::test]
+async fn sent_asset_is_streamed_and_read_back() {
+    const AMOUNT: Amount = 6;
+
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let addr = network.gateways()[0].json;
+
+    let mut events = reqwest::get(format!("http://{}/v1/events", addr))
+        .await
+        .expect("subscribe events");
+    assert_eq!(events.headers()["content-type"], "text/event-stream");
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    let reply = call(
+        addr,
+        "/v1/get_balance",
+        json!({"sender": encoded(&recipient)}),
+    )
+    .await;
+    let initial = reply["amount"].as_u64().expect("amount");
+
+    let transaction = SignedTransaction::new(&sender, 1, recipient, AMOUNT);
+    let reply = call(
+        addr,
+        "/v1/send_asset",
+        json!({
+            "sender": encoded(&sender.public()),
+            "sequence": 1,
+            "recipient_address": Address::new(address::DEFAULT_PREFIX, recipient).to_string(),
+            "amount": AMOUNT,
+            "signature": encoded(&transaction.signature),
+        }),
+    )
+    .await;
+    assert_eq!(reply, json!({}));
+
+    let event = next_event(&mut events, &mut String::new()).await;
+    assert_eq!(event["type"], "transaction");
+    assert_eq!(event["sender"], sender.public().to_string());
+    assert_eq!(event["recipient"], recipient.to_string());
+    assert_eq!(event["amount"].as_u64(), Some(AMOUNT as u64));
+
+    wait::delivered(&network.clients()[0], &sender.public(), 1).await;
+    let reply = call(
+        addr,
+        "/v1/get_last_sequence",
+        json!({"sender": encoded(&sender.public())}),
+    )
+    .await;
+    assert_eq!(reply["sequence"], 1);
+
+    let reply = call(
+        addr,
+        "/v1/get_balance",
+        json!({
+            "sender_address": Address::new(address::DEFAULT_PREFIX, recipient).to_string(),
+        }),
+    )
+    .await;
+    assert_eq!(reply["amount"].as_u64(), Some(initial + AMOUNT as u64));
+
+    let reply = call(addr, "/v1/get_latest_transactions", json!({"limit": 1})).await;
+    let latest = &reply["transactions"][0];
+    assert_eq!(latest["sender"], encoded(&sender.public()));
+    assert_eq!(latest["recipient"], encoded(&recipient));
+    assert_eq!(latest["sequence"], 1);
+}
+
+#[tokio::test]
+async fn errors_map_to_http_statuses() {
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let addr = network.gateways()[0].json;
+
+    let response = post(addr, "/v1/unknown", "{}").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = reqwest::get(format!("http://{}/v1/get_balance", addr))
+        .await
+        .expect("send request");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = post(addr, "/v1/get_balance", "{").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let reply = response.json::<Value>().await.expect("decode reply");
+    assert!(reply["message"].is_string());
+
+    // signed for another amount
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    let transaction = SignedTransaction::new(&sender, 1, recipient, 1);
+    let response = post(
+        addr,
+        "/v1/send_asset",
+        json!({
+            "sender": encoded(&sender.public()),
+            "sequence": 1,
+            "recipient": encoded(&recipient),
+            "amount": 2,
+            "signature": encoded(&transaction.signature),
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn submitted_document_is_sent_in_order() {
+    const AMOUNT: Amount = 2;
+
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let addr = network.gateways()[0].json;
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    let initial = client.get_balance(&recipient).await.expect("get balance");
+
+    let document = |chain_id: &str| {
+        let mut written = Vec::new();
+        interchange::Document::new(
+            chain_id,
+            vec![
+                SignedTransaction::new(&sender, 1, recipient, AMOUNT),
+                SignedTransaction::new(&sender, 2, recipient, AMOUNT),
+            ],
+        )
+        .to_writer(&mut written)
+        .expect("write document");
+
+        written
+    };
+
+    let response = post(addr, "/v1/submit_transactions", document("other")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let mut outdated = serde_json::from_slice::<Value>(&document(interchange::DEFAULT_CHAIN_ID))
+        .expect("decode document");
+    assert_eq!(outdated["version"], interchange::VERSION);
+    outdated["version"] = json!(interchange::MIN_VERSION - 1);
+    let response = post(addr, "/v1/submit_transactions", outdated.to_string()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = post(
+        addr,
+        "/v1/submit_transactions",
+        document(interchange::DEFAULT_CHAIN_ID),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    wait::delivered(&client, &sender.public(), 2).await;
+    assert_eq!(
+        client.get_balance(&recipient).await.expect("get balance"),
+        initial + 2 * AMOUNT
+    );
+}
@@ -0,0 +1,212 @@
+#![cfg(feature = "testkit")]
+
+use std::net::SocketAddr;
+
+use at2_node::{
+    address::{self, Address},
+    api::At2Api,
+    testkit::Network,
+    Amount, SignedTransaction,
+};
+use drop::crypto::sign;
+use serde_json::{json, Value};
+
+mod wait;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+async fn post(addr: SocketAddr, body: impl Into<reqwest::Body>) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("http://{}", addr))
+        .header("content-type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .expect("send request")
+}
+
+async fn call(addr: SocketAddr, request: Value) -> Value {
+    post(addr, request.to_string())
+        .await
+        .json()
+        .await
+        .expect("decode reply")
+}
+
+/// Bytes as the JSON messages carry them
+fn encoded(value: &impl serde::Serialize) -> String {
+    base64::encode(bincode::serialize(value).expect("encode"))
+}
+
+fn error_code(reply: &Value) -> i64 {
+    reply["error"]["code"].as_i64().expect("error code")
+}
+
+#[tokio::test]
+async fn sent_asset_shows_in_balance() {
+    const AMOUNT: Amount = 3;
+
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let addr = network.gateways()[0].jsonrpc;
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    let recipient_address = Address::new(address::DEFAULT_PREFIX, recipient).to_string();
+    let initial = client.get_balance(&recipient).await.expect("get balance");
+
+    let transaction = SignedTransaction::new(&sender, 1, recipient, AMOUNT);
+    let reply = call(
+        addr,
+        json!({
+            "jsonrpc": "2.0",
+            "method": "at2_sendAsset",
+            "params": {
+                "sender": encoded(&sender.public()),
+                "sequence": 1,
+                "recipient_address": recipient_address,
+                "amount": AMOUNT,
+                "signature": encoded(&transaction.signature),
+            },
+            "id": 1,
+        }),
+    )
+    .await;
+    assert_eq!(reply, json!({"jsonrpc": "2.0", "result": {}, "id": 1}));
+
+    let balance = wait::until(|| {
+        let recipient_address = &recipient_address;
+
+        async move {
+            let reply = call(
+                addr,
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "at2_getBalance",
+                    "params": {"sender_address": recipient_address},
+                    "id": "balance",
+                }),
+            )
+            .await;
+            assert_eq!(reply["id"], "balance");
+
+            let balance = reply["result"]["amount"].as_u64().expect("amount");
+            Some(balance).filter(|balance| *balance != initial as u64)
+        }
+    })
+    .await;
+    assert_eq!(balance, (initial + AMOUNT) as u64);
+}
+
+#[tokio::test]
+async fn errors_carry_their_code() {
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let addr = network.gateways()[0].jsonrpc;
+
+    let reply = post(addr, "{")
+        .await
+        .json::<Value>()
+        .await
+        .expect("decode reply");
+    assert_eq!(error_code(&reply), PARSE_ERROR);
+    assert_eq!(reply["id"], Value::Null);
+
+    let reply = call(
+        addr,
+        json!({"jsonrpc": "1.0", "method": "at2_getBalance", "id": 1}),
+    )
+    .await;
+    assert_eq!(error_code(&reply), INVALID_REQUEST);
+
+    let reply = call(
+        addr,
+        json!({"jsonrpc": "2.0", "method": "at2_unknown", "id": 2}),
+    )
+    .await;
+    assert_eq!(error_code(&reply), METHOD_NOT_FOUND);
+    assert_eq!(reply["id"], 2);
+
+    let reply = call(
+        addr,
+        json!({
+            "jsonrpc": "2.0",
+            "method": "at2_getBalance",
+            "params": {"sender": 3},
+            "id": 3,
+        }),
+    )
+    .await;
+    assert_eq!(error_code(&reply), INVALID_PARAMS);
+
+    // signed for another amount
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    let transaction = SignedTransaction::new(&sender, 1, recipient, 1);
+    let reply = call(
+        addr,
+        json!({
+            "jsonrpc": "2.0",
+            "method": "at2_sendAsset",
+            "params": {
+                "sender": encoded(&sender.public()),
+                "sequence": 1,
+                "recipient": encoded(&recipient),
+                "amount": 2,
+                "signature": encoded(&transaction.signature),
+            },
+            "id": 4,
+        }),
+    )
+    .await;
+    assert_eq!(error_code(&reply), SERVER_ERROR);
+    assert_eq!(reply["error"]["message"], "invalid signature");
+}
+
+#[tokio::test]
+async fn batches_answer_every_call_but_notifications() {
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let addr = network.gateways()[0].jsonrpc;
+    let account = encoded(&sign::KeyPair::random().public());
+
+    let reply = call(
+        addr,
+        json!([
+            {"jsonrpc": "2.0", "method": "at2_getBalance", "params": {"sender": account}, "id": 1},
+            {"jsonrpc": "2.0", "method": "at2_getBalance", "params": {"sender": account}},
+            {"jsonrpc": "2.0", "method": "at2_unknown", "id": 2},
+        ]),
+    )
+    .await;
+    let replies = reply.as_array().expect("batch reply");
+    assert_eq!(replies.len(), 2);
+    assert_eq!(replies[0]["id"], 1);
+    assert!(replies[0]["result"]["amount"].is_u64());
+    assert_eq!(error_code(&replies[1]), METHOD_NOT_FOUND);
+
+    let only_notification = post(
+        addr,
+        json!({"jsonrpc": "2.0", "method": "at2_getBalance", "params": {"sender": account}})
+            .to_string(),
+    )
+    .await;
+    assert_eq!(only_notification.status(), reqwest::StatusCode::NO_CONTENT);
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "client")]
+
+use at2_node::{
+    address::Address,
+    api::{
+        mock::{MockNode, INITIAL_BALANCE},
+        At2Api,
+    },
+    client::Wallet,
+    proto::account_error::Kind,
+    proto_convert, Amount, Output, SignedTransaction, MEMO_MAX_LEN,
+};
+use drop::crypto::sign;
+
+#[tokio::test]
+async fn mock_node_transfers() {
+    const AMOUNT: Amount = 3;
+
+    let mut node = MockNode::new();
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    node.send_asset(&sender, 1, recipient, AMOUNT)
+        .await
+        .expect("send asset");
+    assert!(node
+        .send_asset(&sender, 1, recipient, AMOUNT)
+        .await
+        .is_err());
+
+    assert_eq!(node.get_last_sequence(&sender.public()).await.unwrap(), 1);
+    assert_eq!(
+        node.get_balance(&recipient).await.unwrap(),
+        INITIAL_BALANCE + AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn mock_node_keeps_memos() {
+    let mut node = MockNode::new();
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    assert!(node
+        .send_asset_with_memo(&sender, 1, recipient, 1, "x".repeat(MEMO_MAX_LEN + 1))
+        .await
+        .is_err());
+    node.send_asset_with_memo(&sender, 1, recipient, 1, "invoice 42".to_owned())
+        .await
+        .expect("send asset");
+
+    let transactions = node.get_latest_transactions().await.unwrap();
+    assert_eq!(transactions[0].memo.as_deref(), Some("invoice 42"));
+}
+
+#[tokio::test]
+async fn wallet_resyncs_its_sequence() {
+    let mut node = MockNode::new();
+    let keypair = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    // sent from elsewhere, unknown to the wallet
+    node.send_asset(&keypair, 1, recipient, 1)
+        .await
+        .expect("send asset");
+
+    let mut wallet = Wallet::new(node.clone(), sign::KeyPair::from(keypair.private()));
+    assert_eq!(wallet.transfer(recipient, 1).await.expect("transfer"), 2);
+
+    node.send_asset(&keypair, 3, recipient, 1)
+        .await
+        .expect("send asset");
+    assert!(wallet.transfer(recipient, 1).await.is_err());
+    assert_eq!(wallet.transfer(recipient, 1).await.expect("transfer"), 4);
+}
+
+#[tokio::test]
+async fn refusals_tell_the_account_error() {
+    let mut node = MockNode::new();
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    let err = node
+        .send_asset(&sender, 1, recipient, INITIAL_BALANCE + 1)
+        .await
+        .expect_err("overdraft accepted");
+    let error = err.account_error().expect("account error");
+    assert_eq!(error.kind(), Kind::InsufficientBalance);
+    assert_eq!(
+        (
+            proto_convert::amount(error.have, error.have_high),
+            proto_convert::amount(error.need, error.need_high)
+        ),
+        (INITIAL_BALANCE, INITIAL_BALANCE + 1)
+    );
+
+    let err = node
+        .send_asset(&sender, 3, recipient, 1)
+        .await
+        .expect_err("sequence gap accepted");
+    let error = err.account_error().expect("account error");
+    assert_eq!(error.kind(), Kind::SequenceGap);
+    assert_eq!((error.sequence, error.last_sequence), (3, 0));
+
+    let err = node
+        .send_signed(&SignedTransaction::with_outputs(
+            &sender,
+            1,
+            recipient,
+            Amount::MAX,
+            vec![Output {
+                recipient,
+                amount: 1,
+            }],
+            None,
+        ))
+        .await
+        .expect_err("overflowing transfer accepted");
+    let error = err.account_error().expect("account error");
+    assert_eq!(error.kind(), Kind::TransferOverflow);
+}
+
+#[test]
+fn readme_address_is_valid() {
+    let address = include_str!("../README.md")
+        .lines()
+        .find_map(|line| line.strip_prefix("recipient="))
+        .expect("example address in the README");
+
+    address.parse::<Address>().expect("parse address");
+}
@@ -0,0 +1,52 @@
+//! Stop the child processes of the tests, whatever the platform
+//!
+//! On unix, they are sent a SIGTERM and can be frozen. On windows, they are
+//! started in their own process group, so that a Ctrl-Break reaches them but
+//! not the tests, and can't be frozen.
+
+use duct::{Expression, ReaderHandle};
+
+/// Start `expression` so that [`terminate`] can reach it
+pub fn stoppable(expression: Expression) -> Expression {
+    #[cfg(windows)]
+    let expression = expression.before_spawn(|command| {
+        use std::os::windows::process::CommandExt;
+
+        command.creation_flags(winapi::um::winbase::CREATE_NEW_PROCESS_GROUP);
+        Ok(())
+    });
+
+    expression
+}
+
+/// Ask the processes of `handle` to shut down
+pub fn terminate(handle: &ReaderHandle) {
+    #[cfg(unix)]
+    signal(handle, nix::sys::signal::Signal::SIGTERM);
+
+    #[cfg(windows)]
+    handle.pids().iter().for_each(|pid| unsafe {
+        winapi::um::wincon::GenerateConsoleCtrlEvent(winapi::um::wincon::CTRL_BREAK_EVENT, *pid);
+    });
+}
+
+/// Freeze the processes of `handle`, keeping their connections open
+#[cfg(unix)]
+pub fn pause(handle: &ReaderHandle) {
+    signal(handle, nix::sys::signal::Signal::SIGSTOP);
+}
+
+/// Unfreeze the processes of `handle`
+#[cfg(unix)]
+pub fn resume(handle: &ReaderHandle) {
+    signal(handle, nix::sys::signal::Signal::SIGCONT);
+}
+
+#[cfg(unix)]
+fn signal(handle: &ReaderHandle, signal: nix::sys::signal::Signal) {
+    use nix::{sys::signal, unistd::Pid};
+
+    handle.pids().iter().for_each(|pid| {
+        let _ = signal::kill(Pid::from_raw(*pid as i32), signal);
+    });
+}
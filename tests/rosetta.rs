@@ -0,0 +1,227 @@
+#![cfg(feature = "testkit")]
+
+use std::net::SocketAddr;
+
+use at2_node::{
+    address::{self, Address},
+    api::At2Api,
+    testkit::Network,
+    Amount,
+};
+use drop::crypto::sign;
+use serde_json::{json, Value};
+
+mod wait;
+
+const UNKNOWN_NETWORK: u64 = 2;
+const INVALID_ADDRESS: u64 = 3;
+const BLOCK_NOT_FOUND: u64 = 4;
+
+async fn post(addr: SocketAddr, path: &str, request: Value) -> (reqwest::StatusCode, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("http://{}{}", addr, path))
+        .json(&request)
+        .send()
+        .await
+        .expect("send request");
+
+    (
+        response.status(),
+        response.json().await.expect("decode reply"),
+    )
+}
+
+fn network_identifier() -> Value {
+    json!({"blockchain": "at2", "network": address::DEFAULT_PREFIX})
+}
+
+async fn balance(addr: SocketAddr, account: &sign::PublicKey) -> (u64, String) {
+    let (status, reply) = post(
+        addr,
+        "/account/balance",
+        json!({
+            "network_identifier": network_identifier(),
+            "account_identifier": {
+                "address": Address::new(address::DEFAULT_PREFIX, *account).to_string(),
+            },
+        }),
+    )
+    .await;
+    assert!(status.is_success(), "account balance: {}", reply);
+
+    (
+        reply["block_identifier"]["index"]
+            .as_u64()
+            .expect("block index"),
+        reply["balances"][0]["value"]
+            .as_str()
+            .expect("balance")
+            .to_owned(),
+    )
+}
+
+#[tokio::test]
+async fn network_is_listed_with_its_options() {
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let addr = network.gateways()[0].json;
+
+    let (_, reply) = post(addr, "/network/list", json!({})).await;
+    assert_eq!(reply["network_identifiers"], json!([network_identifier()]));
+
+    let (_, reply) = post(
+        addr,
+        "/network/options",
+        json!({"network_identifier": network_identifier()}),
+    )
+    .await;
+    assert_eq!(reply["allow"]["operation_types"], json!(["TRANSFER"]));
+
+    let (status, reply) = post(
+        addr,
+        "/network/status",
+        json!({"network_identifier": network_identifier()}),
+    )
+    .await;
+    assert!(status.is_success());
+    assert_eq!(reply["genesis_block_identifier"]["index"], 0);
+    assert_eq!(reply["current_block_identifier"]["index"], 0);
+
+    let (status, reply) = post(
+        addr,
+        "/network/status",
+        json!({"network_identifier": {"blockchain": "at2", "network": "other"}}),
+    )
+    .await;
+    assert!(status.is_server_error());
+    assert_eq!(reply["code"], UNKNOWN_NETWORK);
+}
+
+#[tokio::test]
+async fn transfer_shows_as_a_block_and_in_balances() {
+    const AMOUNT: Amount = 4;
+
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let addr = network.gateways()[0].json;
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    let (index, initial) = balance(addr, &recipient).await;
+    assert_eq!(index, 0);
+    assert_eq!(
+        initial,
+        client
+            .get_balance(&recipient)
+            .await
+            .expect("get balance")
+            .to_string()
+    );
+
+    client
+        .send_asset(&sender, 1, recipient, AMOUNT)
+        .await
+        .expect("send asset");
+
+    // blocks are the recent transactions, kept once applied
+    let (_, received) = wait::until(|| async move {
+        Some(balance(addr, &recipient).await).filter(|(index, _)| *index == 1)
+    })
+    .await;
+    assert_eq!(
+        received.parse::<Amount>().expect("amount"),
+        initial.parse::<Amount>().expect("amount") + AMOUNT
+    );
+
+    let (_, reply) = post(
+        addr,
+        "/network/status",
+        json!({"network_identifier": network_identifier()}),
+    )
+    .await;
+    assert_eq!(reply["current_block_identifier"]["index"], 1);
+
+    let (status, reply) = post(
+        addr,
+        "/block",
+        json!({
+            "network_identifier": network_identifier(),
+            "block_identifier": {"index": 1},
+        }),
+    )
+    .await;
+    assert!(status.is_success());
+    let block = &reply["block"];
+    assert_eq!(block["parent_block_identifier"]["index"], 0);
+    let operations = &block["transactions"][0]["operations"];
+    assert_eq!(
+        operations[0]["account"]["address"],
+        Address::new(address::DEFAULT_PREFIX, sender.public()).to_string()
+    );
+    assert_eq!(operations[0]["amount"]["value"], format!("-{}", AMOUNT));
+    assert_eq!(
+        operations[1]["account"]["address"],
+        Address::new(address::DEFAULT_PREFIX, recipient).to_string()
+    );
+    assert_eq!(operations[1]["amount"]["value"], AMOUNT.to_string());
+
+    // found by its hash as well
+    let hash = block["block_identifier"]["hash"].clone();
+    let (_, by_hash) = post(
+        addr,
+        "/block",
+        json!({
+            "network_identifier": network_identifier(),
+            "block_identifier": {"hash": hash},
+        }),
+    )
+    .await;
+    assert_eq!(by_hash, reply);
+
+    let (status, reply) = post(
+        addr,
+        "/block",
+        json!({
+            "network_identifier": network_identifier(),
+            "block_identifier": {"index": 2},
+        }),
+    )
+    .await;
+    assert!(status.is_server_error());
+    assert_eq!(reply["code"], BLOCK_NOT_FOUND);
+    assert_eq!(reply["retriable"], true);
+}
+
+#[tokio::test]
+async fn invalid_address_is_refused() {
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let addr = network.gateways()[0].json;
+
+    let (status, reply) = post(
+        addr,
+        "/account/balance",
+        json!({
+            "network_identifier": network_identifier(),
+            "account_identifier": {"address": "at21notanaddress"},
+        }),
+    )
+    .await;
+    assert!(status.is_server_error());
+    assert_eq!(reply["code"], INVALID_ADDRESS);
+    assert_eq!(reply["retriable"], false);
+}
@@ -0,0 +1,1095 @@
+#![cfg(feature = "testkit")]
+
+use std::time::{Duration, Instant};
+
+use at2_node::{
+    api::At2Api,
+    client::{self, TransactionStatus},
+    proto,
+    server::config,
+    testkit::Network,
+    Amount, Asset, Fees, Operation, SignedOperation, SignedTransaction, ThinTransaction,
+};
+use drop::crypto::sign;
+use futures::StreamExt;
+
+mod wait;
+
+#[tokio::test]
+async fn can_send_asset_in_process() {
+    const AMOUNT: Amount = 3;
+
+    let network = Network::start(3).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    let initial = client.get_balance(&recipient).await.expect("get balance");
+    client
+        .send_asset(&sender, 1, recipient, AMOUNT)
+        .await
+        .expect("send asset");
+
+    wait::delivered(&client, &sender.public(), 1).await;
+
+    assert_eq!(
+        client.get_balance(&recipient).await.expect("get balance"),
+        initial + AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn balances_are_read_at_once() {
+    let network = Network::start(1).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    let accounts = [sender.public(), recipient];
+
+    client
+        .send_asset(&sender, 1, recipient, 1)
+        .await
+        .expect("send asset");
+
+    let balances = wait::until(|| {
+        let (mut client, accounts, sender) = (client.clone(), &accounts, sender.public());
+
+        async move {
+            let balances = client.get_balances(accounts).await.expect("get balances");
+            Some(balances).filter(|balances| balances[&sender].1 == 1)
+        }
+    })
+    .await;
+
+    for account in &accounts {
+        assert_eq!(
+            balances[account],
+            (
+                client.get_balance(account).await.expect("get balance"),
+                client
+                    .get_last_sequence(account)
+                    .await
+                    .expect("get last sequence")
+            )
+        );
+    }
+}
+
+#[tokio::test]
+async fn forged_transaction_is_refused() {
+    let network = Network::start(1).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let mut forged = SignedTransaction::new(&sender, 1, sign::KeyPair::random().public(), 1);
+    forged.amount += 1;
+
+    let err = client
+        .send_signed(&forged)
+        .await
+        .expect_err("forged transaction accepted");
+    assert!(
+        matches!(&err, client::Error::Rpc { source } if source.code() == tonic::Code::Unauthenticated),
+        "{}",
+        err
+    );
+    assert!(err.request_id().is_some(), "no request id returned");
+}
+
+#[tokio::test]
+async fn invalid_transfers_are_refused() {
+    let network = Network::start(1).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    for (recipient, amount) in [(recipient, 0), (sender.public(), 1)] {
+        let err = client
+            .send_asset(&sender, 1, recipient, amount)
+            .await
+            .expect_err("invalid transfer accepted");
+        assert!(
+            matches!(&err, client::Error::Rpc { source } if source.code() == tonic::Code::InvalidArgument),
+            "{}",
+            err
+        );
+    }
+}
+
+#[tokio::test]
+async fn used_sequence_is_refused_early() {
+    let network = Network::start(1).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    client
+        .send_asset(&sender, 1, recipient, 1)
+        .await
+        .expect("send asset");
+
+    wait::delivered(&client, &sender.public(), 1).await;
+
+    let err = client
+        .send_asset(&sender, 1, recipient, 2)
+        .await
+        .expect_err("used sequence accepted");
+    assert!(
+        matches!(&err, client::Error::Rpc { source } if source.code() == tonic::Code::AlreadyExists),
+        "{}",
+        err
+    );
+    let error = err.account_error().expect("account error");
+    assert_eq!(
+        error.kind(),
+        proto::account_error::Kind::SequenceAlreadyUsed
+    );
+    assert_eq!((error.sequence, error.last_sequence), (1, 1));
+}
+
+#[tokio::test]
+async fn retried_transaction_is_applied_once() {
+    const AMOUNT: Amount = 3;
+
+    let network = Network::start(1).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    let initial = client.get_balance(&recipient).await.expect("get balance");
+
+    let transaction = SignedTransaction::new(&sender, 1, recipient, AMOUNT);
+    for _ in 0..2 {
+        client.send_signed(&transaction).await.expect("send asset");
+    }
+
+    wait::delivered(&client, &sender.public(), 1).await;
+    client.send_signed(&transaction).await.expect("send asset");
+
+    assert_eq!(
+        client.get_balance(&recipient).await.expect("get balance"),
+        initial + AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn subscriber_receives_transactions() {
+    let network = Network::start(3).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    let mut transactions = Box::pin(
+        client
+            .subscribe_transactions()
+            .await
+            .expect("subscribe transactions"),
+    );
+    client
+        .send_asset(&sender, 1, recipient, 1)
+        .await
+        .expect("send asset");
+
+    let tx = tokio::time::timeout(wait::TIMEOUT, transactions.next())
+        .await
+        .expect("timeout expired")
+        .expect("stream ended")
+        .expect("receive transaction");
+    assert_eq!(tx.sender, sender.public());
+    assert_eq!(tx.recipient, recipient);
+}
+
+#[tokio::test]
+async fn transaction_status_follows_delivery() {
+    let network = Network::start(3).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    client
+        .send_asset(&sender, 1, recipient, 1)
+        .await
+        .expect("send asset");
+    client
+        .send_asset(&sender, 2, recipient, 1_000)
+        .await
+        .expect("send asset");
+
+    wait::until(|| {
+        let (mut client, sender) = (client.clone(), sender.public());
+
+        async move {
+            let first = client
+                .get_transaction_status(&sender, 1)
+                .await
+                .expect("get transaction status");
+            let second = client
+                .get_transaction_status(&sender, 2)
+                .await
+                .expect("get transaction status");
+
+            Some(()).filter(|_| {
+                first == TransactionStatus::Delivered
+                    && matches!(second, TransactionStatus::Rejected(_))
+            })
+        }
+    })
+    .await;
+
+    assert_eq!(
+        client
+            .get_transaction_status(&sender.public(), 3)
+            .await
+            .expect("get transaction status"),
+        TransactionStatus::Unknown
+    );
+}
+
+#[tokio::test]
+async fn rejected_transfer_is_kept_with_its_reason() {
+    use proto::rejected_transaction::Reason;
+
+    let network = Network::start(3).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    client
+        .send_asset(&sender, 1, recipient, 1_000)
+        .await
+        .expect("send asset");
+
+    let rejections = wait::until(|| {
+        let mut client = client.clone();
+
+        async move {
+            let rejections = client
+                .get_rejected_transactions(&recipient)
+                .await
+                .expect("get rejected transactions");
+            Some(rejections).filter(|rejections| !rejections.is_empty())
+        }
+    })
+    .await;
+
+    assert_eq!(rejections.len(), 1);
+    assert_eq!(rejections[0].sender, sender.public());
+    assert_eq!(rejections[0].sequence, 1);
+    assert_eq!(rejections[0].outputs[0].amount, 1_000);
+    assert_eq!(rejections[0].reason, Reason::InsufficientBalance);
+    assert!(client
+        .get_rejected_transactions(&sign::KeyPair::random().public())
+        .await
+        .expect("get rejected transactions")
+        .is_empty());
+}
+
+#[tokio::test]
+async fn builder_configures_every_node() {
+    let network = Network::builder()
+        .size(2)
+        .chain_id("at2-testkit")
+        .start()
+        .await
+        .expect("start network");
+
+    assert_eq!(network.rpc_addresses().len(), 2);
+    for client in network.clients() {
+        let info = client.clone().get_node_info().await.expect("get node info");
+        assert_eq!(info.chain_id, "at2-testkit");
+    }
+}
+
+#[tokio::test]
+async fn client_gives_up_after_overall_timeout() {
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("find free address");
+
+    let mut client = client::Client::builder()
+        .retries(u32::MAX, Duration::from_millis(10))
+        .overall_timeout(wait::TICK)
+        .build(
+            format!("http://{}", address)
+                .parse()
+                .expect("socket address is a valid uri"),
+        )
+        .expect("create client");
+
+    let started = Instant::now();
+    let ret = client.get_node_info().await;
+
+    assert!(matches!(ret, Err(client::Error::Timeout)), "{:?}", ret);
+    assert!(started.elapsed() < wait::TIMEOUT);
+}
+
+#[tokio::test]
+async fn multi_client_fails_over_unavailable_node() {
+    const AMOUNT: Amount = 2;
+
+    let network = Network::start(2).await.expect("start network");
+
+    let unavailable = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("find free address");
+    let uris = std::iter::once(&unavailable)
+        .chain(network.rpc_addresses())
+        .map(|address| {
+            format!("http://{}", address)
+                .parse()
+                .expect("socket address is a valid uri")
+        })
+        .collect();
+    let mut client = client::Client::builder()
+        .selection(client::Selection::LowestLatency)
+        .build_multi(uris)
+        .expect("create client");
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    let initial = client.get_balance(&recipient).await.expect("get balance");
+    client
+        .send_asset(&sender, 1, recipient, AMOUNT)
+        .await
+        .expect("send asset");
+
+    wait::delivered(&client, &sender.public(), 1).await;
+
+    assert_eq!(
+        client.get_balance(&recipient).await.expect("get balance"),
+        initial + AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn follower_refuses_writes_but_follows() {
+    let network = Network::builder()
+        .followers(1)
+        .start()
+        .await
+        .expect("start network");
+    let mut follower = network.clients()[2].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    assert!(
+        follower
+            .get_node_info()
+            .await
+            .expect("get node info")
+            .follower
+    );
+
+    let err = follower
+        .send_asset(&sender, 1, recipient, 1)
+        .await
+        .expect_err("write accepted by a follower");
+    assert_eq!(
+        err.validator(),
+        Some(format!("http://{}", network.rpc_addresses()[0]).as_str())
+    );
+
+    // writes go to the next node, reads still to the follower
+    let mut client = client::Client::new_multi(
+        [2, 0]
+            .iter()
+            .map(|pos| {
+                format!("http://{}", network.rpc_addresses()[*pos])
+                    .parse()
+                    .expect("valid uri")
+            })
+            .collect(),
+    )
+    .expect("new client");
+    client
+        .send_asset(&sender, 1, recipient, 1)
+        .await
+        .expect("send asset");
+
+    wait::delivered(&follower, &sender.public(), 1).await;
+}
+
+#[tokio::test]
+async fn tuned_connection_reaches_the_node() {
+    let network = Network::start(1).await.expect("start network");
+
+    let client = client::Client::builder()
+        .keepalive(Duration::from_secs(1), Duration::from_secs(1))
+        .window_sizes(1 << 20, 1 << 22)
+        .max_concurrent_streams(2)
+        .build(
+            format!("http://{}", network.rpc_addresses()[0])
+                .parse()
+                .expect("socket address is a valid uri"),
+        )
+        .expect("create client");
+
+    // more calls than streams, on clones sharing the connection
+    let balances = futures::future::try_join_all((0..4).map(|_| {
+        let mut client = client.clone();
+        async move { client.get_balance(&sign::KeyPair::random().public()).await }
+    }))
+    .await
+    .expect("get balances");
+    assert_eq!(balances.len(), 4);
+}
+
+#[tokio::test]
+async fn account_history_is_paginated() {
+    let network = Network::start(3).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    for sequence in 1..=3 {
+        client
+            .send_asset(&sender, sequence, recipient, 1)
+            .await
+            .expect("send asset");
+    }
+
+    let first = wait::until(|| {
+        let mut client = client.clone();
+
+        async move {
+            let page = client
+                .get_account_history(&recipient, None, 2)
+                .await
+                .expect("get account history");
+            Some(page).filter(|page| page.next.is_some())
+        }
+    })
+    .await;
+    let second = client
+        .get_account_history(&recipient, first.next, 2)
+        .await
+        .expect("get account history");
+
+    let sequences = first
+        .transactions
+        .iter()
+        .chain(&second.transactions)
+        .map(|tx| tx.sequence)
+        .collect::<Vec<_>>();
+    assert_eq!(sequences, vec![3, 2, 1]);
+    assert_eq!(second.next, None);
+}
+
+#[tokio::test]
+async fn history_is_exported_oldest_first() {
+    let network = Network::start(1).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    for sequence in 1..=3 {
+        client
+            .send_asset(&sender, sequence, recipient, sequence.into())
+            .await
+            .expect("send asset");
+    }
+
+    let transactions = wait::until(|| {
+        let (mut client, sender) = (client.clone(), sender.public());
+
+        async move {
+            let transactions = client
+                .export_history()
+                .await
+                .expect("export history")
+                .map(|tx| tx.expect("exported transaction"))
+                .filter(|tx| futures::future::ready(tx.sender == sender))
+                .collect::<Vec<_>>()
+                .await;
+            Some(transactions).filter(|transactions| transactions.len() == 3)
+        }
+    })
+    .await;
+
+    let amounts = transactions.iter().map(|tx| tx.amount).collect::<Vec<_>>();
+    assert_eq!(amounts, vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn memo_is_kept_in_history() {
+    let network = Network::start(3).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    client
+        .send_asset_with_memo(&sender, 1, recipient, 1, "invoice 42".to_owned())
+        .await
+        .expect("send asset");
+
+    let page = wait::until(|| {
+        let mut client = client.clone();
+
+        async move {
+            let page = client
+                .get_account_history(&recipient, None, 1)
+                .await
+                .expect("get account history");
+            Some(page).filter(|page| !page.transactions.is_empty())
+        }
+    })
+    .await;
+
+    assert_eq!(page.transactions[0].memo.as_deref(), Some("invoice 42"));
+}
+
+#[tokio::test]
+async fn accounts_are_listed_by_page() {
+    let network = Network::start(1).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    for sequence in 1..=2 {
+        client
+            .send_asset(&sender, sequence, sign::KeyPair::random().public(), 1)
+            .await
+            .expect("send asset");
+    }
+
+    wait::delivered(&client, &sender.public(), 2).await;
+
+    let mut listed = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = client
+            .list_accounts(cursor.as_ref(), 2)
+            .await
+            .expect("list accounts");
+        assert_eq!(page.total, 3);
+        assert!(page.accounts.len() <= 2);
+
+        listed.extend(page.accounts.into_iter().map(|(account, _, _)| account));
+        cursor = match page.next {
+            None => break,
+            next => next,
+        };
+    }
+
+    assert_eq!(listed.len(), 3);
+    assert!(listed.contains(&sender.public()));
+}
+
+#[tokio::test]
+async fn only_admins_can_mint() {
+    const AMOUNT: Amount = 1_000;
+
+    let admin = sign::KeyPair::random();
+    let network = Network::builder()
+        .admin(&admin.public())
+        .start()
+        .await
+        .expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let recipient = sign::KeyPair::random().public();
+    let initial = client.get_balance(&recipient).await.expect("get balance");
+
+    let err = client
+        .mint(&sign::KeyPair::random(), 1, recipient, AMOUNT)
+        .await
+        .expect_err("mint by unknown admin accepted");
+    assert!(
+        matches!(&err, client::Error::Rpc { source } if source.code() == tonic::Code::PermissionDenied),
+        "{}",
+        err
+    );
+
+    client
+        .mint(&admin, 1, recipient, AMOUNT)
+        .await
+        .expect("mint");
+
+    wait::delivered(&client, &admin.public(), 1).await;
+
+    assert_eq!(
+        client.get_balance(&recipient).await.expect("get balance"),
+        initial + AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn registered_asset_is_minted_and_sent() {
+    const MINTED: Amount = 10;
+    const SENT: Amount = 3;
+
+    let admin = sign::KeyPair::random();
+    let network = Network::builder()
+        .admin(&admin.public())
+        .start()
+        .await
+        .expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    let native = client.get_balance(&recipient).await.expect("get balance");
+
+    let asset = Asset {
+        id: "EUR".to_owned(),
+        name: "Euro".to_owned(),
+    };
+    client
+        .register_asset(&admin, 1, asset)
+        .await
+        .expect("register asset");
+    wait::delivered(&client, &admin.public(), 1).await;
+    let assets = client.get_assets().await.expect("get assets");
+    assert_eq!(
+        assets
+            .iter()
+            .map(|asset| asset.id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["EUR"]
+    );
+
+    client
+        .mint_asset(&admin, 2, sender.public(), MINTED, Some("EUR".to_owned()))
+        .await
+        .expect("mint asset");
+    wait::delivered(&client, &admin.public(), 2).await;
+
+    let transaction = SignedTransaction::sign(
+        &sender,
+        1,
+        ThinTransaction {
+            recipient,
+            amount: SENT,
+            memo: None,
+            outputs: Vec::new(),
+            fee: 0,
+            asset: Some("EUR".to_owned()),
+        },
+    );
+    client.send_signed(&transaction).await.expect("send asset");
+    wait::delivered(&client, &sender.public(), 1).await;
+
+    assert_eq!(
+        client
+            .get_asset_balance(&sender.public(), "EUR")
+            .await
+            .expect("get asset balance"),
+        MINTED - SENT
+    );
+    assert_eq!(
+        client
+            .get_asset_balance(&recipient, "EUR")
+            .await
+            .expect("get asset balance"),
+        SENT
+    );
+    assert_eq!(
+        client.get_balance(&recipient).await.expect("get balance"),
+        native
+    );
+}
+
+#[tokio::test]
+async fn only_admins_can_set_log_level() {
+    let admin = sign::KeyPair::random();
+    let network = Network::builder()
+        .admin(&admin.public())
+        .start()
+        .await
+        .expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let err = client
+        .set_log_level(&sign::KeyPair::random(), "debug", None)
+        .await
+        .expect_err("log level set by unknown admin");
+    assert!(
+        matches!(&err, client::Error::Rpc { source } if source.code() == tonic::Code::PermissionDenied),
+        "{}",
+        err
+    );
+
+    // in process nodes leave the logs to the test
+    let err = client
+        .set_log_level(&admin, "debug", Some("contagion"))
+        .await
+        .expect_err("log level set without logs");
+    assert!(
+        matches!(&err, client::Error::Rpc { source } if source.code() == tonic::Code::FailedPrecondition),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn network_info_lists_peers() {
+    let network = Network::start(3).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let info = client.get_network_info().await.expect("get network info");
+
+    assert_eq!(info.peers.len(), 2);
+    assert!(info.peers.iter().all(|peer| peer.answering.is_none()));
+    assert_eq!(info.broadcast.echo_threshold, 2);
+}
+
+#[tokio::test]
+async fn thresholds_leave_out_the_byzantine_fraction() {
+    let network = Network::builder()
+        .size(5)
+        .protocol(config::ConfigProtocol {
+            byzantine_fraction: Some(0.25),
+            ..Default::default()
+        })
+        .start()
+        .await
+        .expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let info = client.get_network_info().await.expect("get network info");
+
+    assert_eq!(info.broadcast.echo_sample_size, 4);
+    assert_eq!(info.broadcast.echo_threshold, 3);
+    assert_eq!(info.broadcast.ready_threshold, 3);
+}
+
+#[tokio::test]
+async fn receipt_is_signed_by_the_node() {
+    let network = Network::start(1).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    // the node's signing key, as it attests balances with it
+    let node = client
+        .get_balance_proof(&recipient, false)
+        .await
+        .expect("get balance proof")
+        .attestations[0]
+        .node;
+
+    let mut receipt = client
+        .send_asset_with_receipt(&sender, 1, recipient, 2)
+        .await
+        .expect("send asset with receipt");
+
+    let transaction = SignedTransaction::new(&sender, 1, recipient, 2);
+    assert!(client::verify_receipt(&receipt, &transaction, &[node]));
+    assert!(!client::verify_receipt(
+        &receipt,
+        &SignedTransaction::new(&sender, 2, recipient, 2),
+        &[node]
+    ));
+    assert!(!client::verify_receipt(
+        &receipt,
+        &transaction,
+        &[recipient]
+    ));
+
+    receipt.statement.received_at += 1;
+    assert!(!client::verify_receipt(&receipt, &transaction, &[node]));
+}
+
+#[tokio::test]
+async fn balance_proof_is_attested_by_peers() {
+    let network = Network::start(3).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let account = sign::KeyPair::random().public();
+    let balance = client.get_balance(&account).await.expect("get balance");
+
+    let mut proof = client
+        .get_balance_proof(&account, true)
+        .await
+        .expect("get balance proof");
+    assert_eq!(proof.statement.balance, balance);
+
+    let nodes = proof
+        .attestations
+        .iter()
+        .map(|attestation| attestation.node)
+        .collect::<Vec<_>>();
+    assert!(client::verify_balance_proof(&proof, &nodes, 3));
+    assert!(!client::verify_balance_proof(&proof, &nodes[..1], 2));
+
+    proof.statement.balance += 1;
+    assert!(!client::verify_balance_proof(&proof, &nodes, 1));
+}
+
+#[tokio::test]
+async fn latest_transactions_are_limited() {
+    let network = Network::start(3).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    for sequence in 1..=3 {
+        client
+            .send_asset(&sender, sequence, recipient, 1)
+            .await
+            .expect("send asset");
+    }
+
+    wait::delivered(&client, &sender.public(), 3).await;
+
+    let latest = client
+        .get_latest_transactions_up_to(2)
+        .await
+        .expect("get latest transactions");
+    assert_eq!(
+        latest.iter().map(|tx| tx.sequence).collect::<Vec<_>>(),
+        vec![2, 3]
+    );
+}
+
+#[tokio::test]
+async fn conflicting_transfers_are_kept_as_evidence() {
+    // without any echo to wait for, each node delivers what it was submitted
+    let network = Network::builder()
+        .size(2)
+        .protocol(config::ConfigProtocol {
+            echo_threshold: Some(0),
+            ready_threshold: Some(0),
+            ..Default::default()
+        })
+        .start()
+        .await
+        .expect("start network");
+    let (mut first, mut second) = (network.clients()[0].clone(), network.clients()[1].clone());
+
+    let sender = sign::KeyPair::random();
+    let recipients = [
+        sign::KeyPair::random().public(),
+        sign::KeyPair::random().public(),
+    ];
+
+    let (sent_first, sent_second) = tokio::join!(
+        first.send_asset(&sender, 1, recipients[0], 1),
+        second.send_asset(&sender, 1, recipients[1], 1),
+    );
+    sent_first.expect("send first asset");
+    sent_second.expect("send conflicting asset");
+
+    let evidence = wait::until(|| {
+        let mut client = first.clone();
+
+        async move { client.get_evidence().await.expect("get evidence").pop() }
+    })
+    .await;
+
+    assert!(evidence.verify());
+    assert_eq!(evidence.sender, sender.public());
+    assert_eq!(evidence.sequence, 1);
+
+    let recipient = |signed: &SignedOperation| match &signed.operation {
+        Operation::Transfer(thin) => thin.recipient,
+        other => panic!("not a transfer: {:?}", other),
+    };
+    let mut recorded = vec![recipient(&evidence.first), recipient(&evidence.second)];
+    let mut expected = recipients.to_vec();
+    recorded.sort_by_key(|key| key.to_string());
+    expected.sort_by_key(|key| key.to_string());
+    assert_eq!(recorded, expected);
+
+    // the sequence is still used once
+    let initial = first
+        .get_balance(&sign::KeyPair::random().public())
+        .await
+        .expect("get balance");
+    let mut received = 0;
+    for recipient in &recipients {
+        received += first.get_balance(recipient).await.expect("get balance") - initial;
+    }
+    assert_eq!(received, 1);
+}
+
+#[tokio::test]
+async fn authenticated_reads_need_the_account_key() {
+    let network = Network::builder()
+        .size(1)
+        .authenticated_reads()
+        .start()
+        .await
+        .expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let owner = sign::KeyPair::random();
+    let code = |err: client::Error| match err {
+        client::Error::Rpc { source } => source.code(),
+        err => panic!("{}", err),
+    };
+
+    let err = client
+        .get_balance(&owner.public())
+        .await
+        .expect_err("anonymous read accepted");
+    assert_eq!(code(err), tonic::Code::Unauthenticated);
+
+    client.authenticate(&owner);
+    client
+        .get_balance(&owner.public())
+        .await
+        .expect("get balance");
+    let err = client
+        .get_balance(&sign::KeyPair::random().public())
+        .await
+        .expect_err("read of another account accepted");
+    assert_eq!(code(err), tonic::Code::PermissionDenied);
+
+    client
+        .get_balance_proof(&owner.public(), false)
+        .await
+        .expect("get balance proof");
+    let err = client
+        .get_balance_proof(&sign::KeyPair::random().public(), false)
+        .await
+        .expect_err("proof of another account accepted");
+    assert_eq!(code(err), tonic::Code::PermissionDenied);
+
+    client
+        .get_transaction_status(&owner.public(), 1)
+        .await
+        .expect("get transaction status");
+    let err = client
+        .get_transaction_status(&sign::KeyPair::random().public(), 1)
+        .await
+        .expect_err("status of another account accepted");
+    assert_eq!(code(err), tonic::Code::PermissionDenied);
+
+    let err = client
+        .get_latest_transactions_up_to(0)
+        .await
+        .expect_err("latest transactions of every account accepted");
+    assert_eq!(code(err), tonic::Code::PermissionDenied);
+
+    let err = client
+        .get_evidence()
+        .await
+        .expect_err("evidences of every account accepted");
+    assert_eq!(code(err), tonic::Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn fees_go_to_the_fee_account() {
+    let fee_account = sign::KeyPair::random().public();
+    let network = Network::builder()
+        .size(1)
+        .fees(
+            &fee_account,
+            Fees {
+                flat: 1,
+                basis_points: 0,
+            },
+        )
+        .start()
+        .await
+        .expect("start network");
+    let mut client = network.clients()[0].clone();
+
+    let info = client.get_node_info().await.expect("get node info");
+    assert_eq!(info.fees.map(|fees| fees.minimum(5)), Some(1));
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    let initial = client.get_balance(&fee_account).await.expect("get balance");
+
+    let err = client
+        .send_asset(&sender, 1, recipient, 5)
+        .await
+        .expect_err("transfer without fee accepted");
+    assert!(
+        matches!(&err, client::Error::Rpc { source } if source.code() == tonic::Code::FailedPrecondition),
+        "{}",
+        err
+    );
+
+    client
+        .send_asset_with_fee(&sender, 1, recipient, 5, 1)
+        .await
+        .expect("send asset");
+
+    wait::delivered(&client, &sender.public(), 1).await;
+
+    assert_eq!(
+        client.get_balance(&fee_account).await.expect("get balance"),
+        initial + 1
+    );
+    let latest = client
+        .get_latest_transactions()
+        .await
+        .expect("get latest transactions");
+    let sent = latest.iter().find(|tx| tx.sender == sender.public());
+    assert_eq!(sent.map(|tx| tx.fee), Some(1));
+}
+
+#[tokio::test]
+async fn names_resolve_to_their_first_owner() {
+    let network = Network::start(3).await.expect("start network");
+    let mut client = network.clients()[0].clone();
+    let mut other = network.clients()[2].clone();
+
+    let owner = sign::KeyPair::random();
+    let late = sign::KeyPair::random();
+
+    let err = other
+        .resolve_name("alice".to_owned())
+        .await
+        .expect_err("unregistered name resolved");
+    assert!(
+        matches!(&err, client::Error::Rpc { source } if source.code() == tonic::Code::NotFound),
+        "{}",
+        err
+    );
+
+    client
+        .register_name(&owner, 1, "alice".to_owned())
+        .await
+        .expect("register name");
+    wait::delivered(&other, &owner.public(), 1).await;
+
+    assert_eq!(
+        other
+            .resolve_name("alice".to_owned())
+            .await
+            .expect("resolve name"),
+        owner.public()
+    );
+
+    // taken names are only refused once delivered, still using the sequence
+    client
+        .register_name(&late, 1, "alice".to_owned())
+        .await
+        .expect("register name");
+    wait::delivered(&other, &late.public(), 1).await;
+
+    assert_eq!(
+        other
+            .resolve_name("alice".to_owned())
+            .await
+            .expect("resolve name"),
+        owner.public()
+    );
+
+    let err = client
+        .register_name(&late, 2, "Alice".to_owned())
+        .await
+        .expect_err("invalid name accepted");
+    assert!(
+        matches!(&err, client::Error::Rpc { source } if source.code() == tonic::Code::InvalidArgument),
+        "{}",
+        err
+    );
+}
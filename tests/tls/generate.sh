@@ -0,0 +1,36 @@
+#!/bin/sh
+# Certificates used by tests/cli.rs, valid for thirty years
+#
+# A test authority signs the node's certificate, for localhost as rustls
+# doesn't check IP addresses, and the one of a client for mTLS.
+
+set -eu
+
+cd "$(dirname "$0")"
+
+days=10950
+
+openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 -out ca-key.pem
+openssl req -x509 -new -key ca-key.pem -days $days -subj '/CN=at2 test authority' \
+	-addext basicConstraints=critical,CA:TRUE \
+	-addext keyUsage=critical,keyCertSign,cRLSign \
+	-out ca.pem
+
+sign() {
+	name=$1
+	usage=$2
+	shift 2
+
+	openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 -out "$name-key.pem"
+	openssl req -new -key "$name-key.pem" -subj "/CN=at2 test $name" -out "$name.csr"
+	printf 'basicConstraints=critical,CA:FALSE\nkeyUsage=critical,digitalSignature,keyEncipherment\nextendedKeyUsage=%s\n%s' \
+		"$usage" "$*" >"$name.ext"
+	openssl x509 -req -in "$name.csr" -CA ca.pem -CAkey ca-key.pem -CAcreateserial \
+		-days $days -extfile "$name.ext" -out "$name.pem"
+	rm "$name.csr" "$name.ext"
+}
+
+sign node serverAuth 'subjectAltName=DNS:localhost'
+sign client clientAuth
+
+rm ca-key.pem ca.srl
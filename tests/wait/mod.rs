@@ -0,0 +1,54 @@
+//! Poll the nodes of the tests until they get somewhere, failing past a
+//! timeout
+//!
+//! Not every test uses every helper.
+#![allow(dead_code)]
+
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use at2_node::{api::At2Api, client::Client};
+use drop::crypto::sign;
+
+/// Between two polls
+pub const TICK: Duration = Duration::from_millis(100);
+/// How long to poll before failing
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Poll `get` until it returns something, panicking past [`TIMEOUT`]
+pub async fn until<T, F, Fut>(mut get: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<T>>,
+{
+    let timeout = Instant::now() + TIMEOUT;
+
+    loop {
+        if let Some(value) = get().await {
+            return value;
+        }
+
+        assert!(Instant::now() < timeout, "timeout expired");
+        tokio::time::sleep(TICK).await;
+    }
+}
+
+/// Wait until the node of `client` applied the operations of `sender` up to
+/// `sequence`
+pub async fn delivered(client: &Client, sender: &sign::PublicKey, sequence: sieve::Sequence) {
+    until(|| {
+        let mut client = client.clone();
+
+        async move {
+            let last = client
+                .get_last_sequence(sender)
+                .await
+                .expect("get last sequence");
+
+            Some(()).filter(|_| last >= sequence)
+        }
+    })
+    .await
+}
@@ -0,0 +1,67 @@
+//! Run the wasm [`Client`] through grpc-web against a running node
+//!
+//! Start a node first, then give its RPC address at build time, such as
+//! `AT2_RPC=http://127.0.0.1:3001 wasm-pack test --headless --firefox`.
+
+#![cfg(target_family = "wasm")]
+
+use at2_node::{api::At2Api, client::Client, Amount};
+use drop::crypto::sign;
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const DEFAULT_RPC: &str = "http://127.0.0.1:3001";
+/// How many times to poll the node before giving up
+const POLLS: usize = 1000;
+
+fn client() -> Client {
+    Client::new(
+        option_env!("AT2_RPC")
+            .unwrap_or(DEFAULT_RPC)
+            .parse()
+            .expect("valid rpc address"),
+    )
+    .expect("create client")
+}
+
+#[wasm_bindgen_test]
+async fn new_user_has_some_asset() {
+    let balance = client()
+        .get_balance(&sign::KeyPair::random().public())
+        .await
+        .expect("get balance");
+
+    assert!(balance > 0);
+}
+
+#[wasm_bindgen_test]
+async fn can_send_asset() {
+    const AMOUNT: Amount = 3;
+
+    let mut client = client();
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+
+    let initial = client.get_balance(&recipient).await.expect("get balance");
+    client
+        .send_asset(&sender, 1, recipient, AMOUNT)
+        .await
+        .expect("send asset");
+
+    for _ in 0..POLLS {
+        if client
+            .get_last_sequence(&sender.public())
+            .await
+            .expect("get last sequence")
+            == 1
+        {
+            break;
+        }
+    }
+
+    assert_eq!(
+        client.get_balance(&recipient).await.expect("get balance"),
+        initial + AMOUNT
+    );
+}
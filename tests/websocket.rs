@@ -0,0 +1,168 @@
+#![cfg(feature = "testkit")]
+
+use std::net::SocketAddr;
+
+use at2_node::{testkit::Network, Amount, SignedTransaction};
+use drop::crypto::sign;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+
+mod wait;
+
+type Socket = WebSocketStream<TcpStream>;
+
+async fn connect(addr: SocketAddr) -> Socket {
+    let stream = TcpStream::connect(addr).await.expect("connect");
+    let (socket, _) = tokio_tungstenite::client_async(format!("ws://{}/", addr), stream)
+        .await
+        .expect("handshake");
+
+    socket
+}
+
+async fn send(socket: &mut Socket, message: Value) {
+    socket
+        .send(Message::Text(message.to_string()))
+        .await
+        .expect("send message");
+}
+
+/// Next text message, failing past [`wait::TIMEOUT`]
+async fn receive(socket: &mut Socket) -> Value {
+    loop {
+        let message = tokio::time::timeout(wait::TIMEOUT, socket.next())
+            .await
+            .expect("timeout expired")
+            .expect("socket closed")
+            .expect("receive message");
+
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).expect("decode message");
+        }
+    }
+}
+
+/// Bytes as the JSON messages carry them
+fn encoded(value: &impl serde::Serialize) -> String {
+    base64::encode(bincode::serialize(value).expect("encode"))
+}
+
+#[tokio::test]
+async fn subscriber_is_notified_of_sent_transaction() {
+    const AMOUNT: Amount = 2;
+
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let mut socket = connect(network.gateways()[0].websocket).await;
+
+    send(
+        &mut socket,
+        json!({"jsonrpc": "2.0", "method": "at2_subscribe", "id": 1}),
+    )
+    .await;
+    let reply = receive(&mut socket).await;
+    assert_eq!(reply["id"], 1);
+    let subscription = reply["result"].as_u64().expect("subscription id");
+
+    let sender = sign::KeyPair::random();
+    let recipient = sign::KeyPair::random().public();
+    let transaction = SignedTransaction::new(&sender, 1, recipient, AMOUNT);
+    send(
+        &mut socket,
+        json!({
+            "jsonrpc": "2.0",
+            "method": "at2_sendAsset",
+            "params": {
+                "sender": encoded(&sender.public()),
+                "sequence": 1,
+                "recipient": encoded(&recipient),
+                "amount": AMOUNT,
+                "signature": encoded(&transaction.signature),
+            },
+            "id": 2,
+        }),
+    )
+    .await;
+
+    // the reply and the notification can come in any order
+    let mut replied = false;
+    let notification = loop {
+        let message = receive(&mut socket).await;
+        match message["method"].as_str() {
+            Some("at2_subscription") => break message,
+            _ => {
+                assert_eq!(message["id"], 2);
+                assert_eq!(message["result"], json!({}));
+                replied = true;
+            }
+        }
+    };
+
+    let params = &notification["params"];
+    assert_eq!(params["subscription"], subscription);
+    assert_eq!(params["result"]["type"], "transaction");
+    assert_eq!(params["result"]["sender"], sender.public().to_string());
+    assert_eq!(params["result"]["recipient"], recipient.to_string());
+    assert_eq!(params["result"]["amount"].as_u64(), Some(AMOUNT as u64));
+
+    if !replied {
+        let reply = receive(&mut socket).await;
+        assert_eq!(reply["id"], 2);
+    }
+
+    send(
+        &mut socket,
+        json!({
+            "jsonrpc": "2.0",
+            "method": "at2_unsubscribe",
+            "params": {"subscription": subscription},
+            "id": 3,
+        }),
+    )
+    .await;
+    assert_eq!(
+        receive(&mut socket).await,
+        json!({"jsonrpc": "2.0", "result": true, "id": 3})
+    );
+
+    send(
+        &mut socket,
+        json!({
+            "jsonrpc": "2.0",
+            "method": "at2_unsubscribe",
+            "params": {"subscription": subscription},
+            "id": 4,
+        }),
+    )
+    .await;
+    assert_eq!(
+        receive(&mut socket).await,
+        json!({"jsonrpc": "2.0", "result": false, "id": 4})
+    );
+}
+
+#[tokio::test]
+async fn invalid_message_is_a_parse_error() {
+    let network = Network::builder()
+        .size(1)
+        .gateways()
+        .start()
+        .await
+        .expect("start network");
+    let mut socket = connect(network.gateways()[0].websocket).await;
+
+    socket
+        .send(Message::Text("{".to_owned()))
+        .await
+        .expect("send message");
+    let reply = receive(&mut socket).await;
+
+    assert_eq!(reply["error"]["code"], -32700);
+    assert_eq!(reply["id"], Value::Null);
+}